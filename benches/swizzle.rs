@@ -0,0 +1,86 @@
+//! Throughput of swizzling and deswizzling with each of the [`SwizzleOptions`], to pick the
+//! fastest for a target. Run with `cargo bench --bench swizzle`.
+
+use bntx::tegra_swizzle::{self, AddressTable, BlockHeight, SurfaceParams, SwizzleOptions};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+/// Bytes per block of the formats benchmarked: R8, RGBA8 and BC1, and BC7
+const BYTES_PER_BLOCK: &[u32] = &[1, 4, 8, 16];
+
+/// Width and height in blocks of the surfaces benchmarked
+const SIZES: &[u32] = &[256, 1024];
+
+fn options() -> Vec<(String, SwizzleOptions)> {
+    let mut options = Vec::new();
+    for unchecked in [false, true] {
+        for gob_rows in [0, 1, 2, 4, 16] {
+            let name = format!("gob_rows={}{}", gob_rows, if unchecked { ",unchecked" } else { "" });
+            options.push((name, SwizzleOptions { gob_rows, unchecked }));
+        }
+    }
+
+    options
+}
+
+fn params(size: u32, bytes_per_block: u32) -> SurfaceParams {
+    let mut params = SurfaceParams::new(size, size, bytes_per_block);
+    params.block_height = BlockHeight::Sixteen;
+
+    params
+}
+
+fn bench(c: &mut Criterion, to_swizzle: bool) {
+    let mut group = c.benchmark_group(if to_swizzle { "swizzle" } else { "deswizzle" });
+
+    for &size in SIZES {
+        for &bytes_per_block in BYTES_PER_BLOCK {
+            let params = params(size, bytes_per_block);
+            let table = AddressTable::new(&params);
+            let deswizzled: Vec<u8> = (0..tegra_swizzle::deswizzled_mip_size(&params))
+                .map(|i| i as u8)
+                .collect();
+            let swizzled = table.swizzle(&deswizzled);
+
+            group.throughput(Throughput::Bytes(deswizzled.len() as u64));
+            for (name, options) in options() {
+                let id = BenchmarkId::new(name, format!("{}x{}x{}", size, size, bytes_per_block));
+                if to_swizzle {
+                    let mut out = vec![0; swizzled.len()];
+                    group.bench_function(id, |b| {
+                        b.iter(|| table.swizzle_into_with(&deswizzled, &mut out, &options))
+                    });
+                } else {
+                    let mut out = vec![0; deswizzled.len()];
+                    group.bench_function(id, |b| {
+                        b.iter(|| table.deswizzle_into_with(&swizzled, &mut out, &options))
+                    });
+                }
+            }
+        }
+    }
+
+    group.finish();
+}
+
+fn swizzle(c: &mut Criterion) {
+    bench(c, true);
+}
+
+fn deswizzle(c: &mut Criterion) {
+    bench(c, false);
+}
+
+/// Computing the table, which the free functions do on every call
+fn address_table(c: &mut Criterion) {
+    let mut group = c.benchmark_group("address_table");
+
+    for &size in SIZES {
+        let params = params(size, 4);
+        group.bench_function(format!("{}x{}", size, size), |b| b.iter(|| AddressTable::new(&params)));
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, swizzle, deswizzle, address_table);
+criterion_main!(benches);
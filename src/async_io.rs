@@ -0,0 +1,56 @@
+#[cfg(feature = "write")]
+use std::io;
+use std::path::Path;
+
+use binrw::prelude::*;
+use tokio::io::{AsyncRead, AsyncReadExt};
+#[cfg(feature = "write")]
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+#[cfg(all(feature = "zstd", feature = "write"))]
+use crate::compression;
+use crate::BntxFile;
+
+// BNTX parsing needs random access for its pointers, so the file is buffered in full and the
+// parse/layout work happens on the in-memory copy. Only the actual I/O is awaited. Compression
+// is handled the same way as the sync `open`/`save`.
+impl BntxFile {
+    pub async fn open_async<P: AsRef<Path>>(path: P) -> BinResult<Self> {
+        let data = tokio::fs::read(path.as_ref()).await?;
+
+        Self::from_bytes(data)
+    }
+
+    pub async fn read_async<R: AsyncRead + Unpin>(reader: &mut R) -> BinResult<Self> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await?;
+
+        Self::from_bytes(data)
+    }
+
+    #[cfg(feature = "write")]
+    pub async fn write_async<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> io::Result<()> {
+        let mut data = Vec::new();
+        self.write(&mut data)?;
+
+        writer.write_all(&data).await?;
+        writer.flush().await
+    }
+
+    #[cfg(feature = "write")]
+    pub async fn save_async<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut data = Vec::new();
+        self.write(&mut data)?;
+
+        #[cfg(feature = "zstd")]
+        {
+            if compression::saves_as_zstd(path.as_ref()) {
+                data = compression::compress_zstd(&data)?;
+            }
+        }
+
+        let mut file = tokio::fs::File::create(path.as_ref()).await?;
+        file.write_all(&data).await?;
+        file.flush().await
+    }
+}
@@ -0,0 +1,301 @@
+//! Converting whole directories of textures, or every file in an archive, at once
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+
+use crate::progress::NO_PROGRESS;
+use crate::{BntxFile, EncodeOptions, Error, PngColorSpace, Progress, SurfaceFormat, Texture};
+
+/// The file type textures are extracted to when converting BNTX files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// A decoded PNG of the base image
+    Png,
+    /// A DDS file with every mip level and array layer in the texture's original format
+    Dds,
+}
+
+/// Options for [`convert_dir`] and [`extract_archive`]
+#[derive(Debug, Clone)]
+pub struct ConvertOptions {
+    /// What textures extracted from BNTX files are saved as
+    pub image_format: ImageFormat,
+    /// The format textures created from PNG files are encoded in. DDS files keep their format.
+    pub texture_format: SurfaceFormat,
+    /// How many files to convert at once, or 0 to use every available core
+    pub threads: usize,
+    /// Writes a `.bntx.toml` sidecar next to each extracted texture, and rebuilds PNGs with one
+    /// next to them with its parameters rather than `texture_format`, so extracting, editing and
+    /// rebuilding a texture keeps everything but its pixels. See [`BntxFile::write_sidecar`].
+    pub sidecar: bool,
+    /// How colors are stored in extracted PNGs, and so how PNGs being converted are read.
+    /// With [`PngColorSpace::Srgb`], PNGs rebuilt into linear formats are converted back from
+    /// sRGB, so extracting and rebuilding a texture doesn't shift its colors.
+    pub png_color_space: PngColorSpace,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        ConvertOptions {
+            image_format: ImageFormat::Png,
+            texture_format: SurfaceFormat::R8G8B8A8_SRGB,
+            threads: 0,
+            sidecar: false,
+            png_color_space: PngColorSpace::Untagged,
+        }
+    }
+}
+
+/// The outcome of converting a single file
+#[derive(Debug)]
+pub struct FileReport {
+    pub input: PathBuf,
+    /// The files written, or why the conversion failed
+    pub result: Result<Vec<PathBuf>, Error>,
+}
+
+/// The outcome of extracting a single file from an archive
+#[derive(Debug)]
+pub struct ArchiveReport {
+    /// The position of the file's reader in the iterator given to [`extract_archive`]
+    pub index: usize,
+    /// The files written, or why the extraction failed
+    pub result: Result<Vec<PathBuf>, Error>,
+}
+
+/// Converts every file matching `input_glob`, writing the results to `output_dir`. PNG and DDS
+/// files are converted to a BNTX file named after the image, while each texture in a BNTX file
+/// is extracted to an image named after the texture. Files are converted in parallel, and one
+/// file failing doesn't stop the rest, so check each report's result.
+pub fn convert_dir<P: AsRef<Path>>(
+    input_glob: &str,
+    output_dir: P,
+    options: &ConvertOptions,
+) -> Result<Vec<FileReport>, Error> {
+    convert_dir_with_progress(input_glob, output_dir, options, NO_PROGRESS)
+}
+
+/// Like [`convert_dir`], reporting progress after each file whether or not it succeeded
+pub fn convert_dir_with_progress<P: AsRef<Path>>(
+    input_glob: &str,
+    output_dir: P,
+    options: &ConvertOptions,
+    progress: &dyn Progress,
+) -> Result<Vec<FileReport>, Error> {
+    let output_dir = output_dir.as_ref();
+    fs::create_dir_all(output_dir)?;
+
+    let inputs = glob::glob(input_glob)
+        .map_err(|err| Error::Io(io::Error::new(io::ErrorKind::InvalidInput, err)))?
+        .collect::<Vec<_>>();
+
+    let threads = thread_count(options.threads);
+
+    let next = AtomicUsize::new(0);
+    let reports = Mutex::new(Vec::with_capacity(inputs.len()));
+    thread::scope(|scope| {
+        for _ in 0..threads.min(inputs.len()) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(input) = inputs.get(i) else { break };
+
+                let report = match input {
+                    Ok(path) => FileReport {
+                        input: path.clone(),
+                        result: convert_file(path, output_dir, options),
+                    },
+                    Err(err) => FileReport {
+                        input: err.path().to_owned(),
+                        result: Err(Error::Io(io::Error::new(err.error().kind(), err.to_string()))),
+                    },
+                };
+
+                let mut reports = reports.lock().unwrap();
+                reports.push((i, report));
+                progress.update(reports.len() as u64, inputs.len() as u64);
+            });
+        }
+    });
+
+    let mut reports = reports.into_inner().unwrap();
+    reports.sort_by_key(|&(i, _)| i);
+
+    Ok(reports.into_iter().map(|(_, report)| report).collect())
+}
+
+/// Extracts every texture from many BNTX files at once, such as every file in a game's
+/// archives, as PNG or DDS files as set by `options.image_format`. The textures of each file go
+/// in a directory in `output_dir` named after the file's index and the name stored in it, such
+/// as `12_chara_0_mario_00`, so textures with the same name in different files don't clash.
+///
+/// Files are read from `readers` in parallel, a new one only once a thread is free to take it,
+/// so at most a couple of files per thread are in memory at once however many there are. One
+/// file failing doesn't stop the rest, so check each report's result. Reports are in the same
+/// order as `readers`.
+pub fn extract_archive<I, R, P>(
+    readers: I,
+    output_dir: P,
+    options: &ConvertOptions,
+) -> Result<Vec<ArchiveReport>, Error>
+where
+    I: IntoIterator<Item = R>,
+    R: Read + Send,
+    P: AsRef<Path>,
+{
+    let output_dir = output_dir.as_ref();
+    fs::create_dir_all(output_dir)?;
+
+    let threads = thread_count(options.threads);
+
+    let (sender, receiver) = mpsc::sync_channel::<(usize, R)>(threads);
+    let receiver = Mutex::new(receiver);
+    let reports = Mutex::new(Vec::new());
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                // the lock is released before extracting, so the other threads can take files
+                let next = receiver.lock().unwrap().recv();
+                let Ok((index, reader)) = next else { break };
+
+                let result = extract_reader(index, reader, output_dir, options);
+                reports.lock().unwrap().push(ArchiveReport { index, result });
+            });
+        }
+
+        // blocks while every thread is busy and the queue is full
+        for item in readers.into_iter().enumerate() {
+            if sender.send(item).is_err() {
+                break;
+            }
+        }
+        drop(sender);
+    });
+
+    let mut reports = reports.into_inner().unwrap();
+    reports.sort_by_key(|report| report.index);
+
+    Ok(reports)
+}
+
+/// The number of threads to use, resolving 0 to the number of cores
+fn thread_count(threads: usize) -> usize {
+    match threads {
+        0 => thread::available_parallelism().map_or(1, |threads| threads.get()),
+        threads => threads,
+    }
+}
+
+fn extract_reader<R: Read>(
+    index: usize,
+    reader: R,
+    output_dir: &Path,
+    options: &ConvertOptions,
+) -> Result<Vec<PathBuf>, Error> {
+    trace_span!("extract_reader", index);
+
+    let file = BntxFile::from_reader(reader)?;
+    let name = String::from(file.header.inner.file_name.clone());
+
+    let output_dir = output_dir.join(format!("{}_{}", index, safe_file_name(&name)?));
+    fs::create_dir_all(&output_dir)?;
+
+    extract_textures(&file, &output_dir, options)
+}
+
+fn convert_file(input: &Path, output_dir: &Path, options: &ConvertOptions) -> Result<Vec<PathBuf>, Error> {
+    trace_span!("convert_file", input = %input.display());
+
+    let extension = input.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+    let name = input.file_stem().and_then(|stem| stem.to_str()).unwrap_or("texture");
+    let sidecar = input.with_file_name(format!("{}.bntx.toml", name));
+
+    let texture = match extension.as_deref() {
+        Some("png") if options.sidecar && sidecar.exists() => {
+            let mut sidecar = BufReader::new(File::open(&sidecar)?);
+            let file = BntxFile::from_png_with_sidecar(image::open(input)?, &mut sidecar, options.png_color_space)?;
+
+            return save_file(&file, name, output_dir);
+        }
+        Some("png") => {
+            let image = options.png_color_space.decode(image::open(input)?, options.texture_format);
+            let mut texture = Texture::from_image(image, name)?;
+            if options.texture_format != texture.format {
+                // files are already converted in parallel, so each one is encoded on one thread
+                let encode_options = EncodeOptions { threads: 1 };
+                texture.convert_format_with_options(options.texture_format, &encode_options, NO_PROGRESS)?;
+            }
+            texture
+        }
+        Some("dds") => Texture::from_dds(&mut BufReader::new(File::open(input)?), name)?,
+        // sidecars are read along with their image
+        Some("toml") => return Ok(Vec::new()),
+        _ => return extract_textures(&BntxFile::open(input)?, output_dir, options),
+    };
+
+    let mut file = BntxFile::new(name);
+    file.push_texture(texture)?;
+
+    save_file(&file, name, output_dir)
+}
+
+fn save_file(file: &BntxFile, name: &str, output_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let output = output_dir.join(format!("{}.bntx", name));
+    file.save(&output)?;
+
+    Ok(vec![output])
+}
+
+/// Checks that a name read from a file, such as a texture name, is a single plain file name, so
+/// joining it to the output directory can't escape it
+fn safe_file_name(name: &str) -> Result<&str, Error> {
+    if name.contains(['/', '\\', ':', '\0']) || name.contains("..") {
+        return Err(Error::UnsafeName(name.to_owned()));
+    }
+
+    Ok(name)
+}
+
+fn extract_textures(file: &BntxFile, output_dir: &Path, options: &ConvertOptions) -> Result<Vec<PathBuf>, Error> {
+    // every name is checked first, so nothing is extracted from a file with an unsafe one
+    let names = file.textures()
+        .map(|texture| safe_file_name(texture.name()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut outputs = Vec::new();
+    for (texture, name) in file.textures().zip(names) {
+        let output = match options.image_format {
+            ImageFormat::Png => {
+                let output = output_dir.join(format!("{}.png", name));
+                let mut writer = BufWriter::new(File::create(&output)?);
+                texture.to_png(&mut writer, options.png_color_space)?;
+                writer.flush()?;
+                output
+            }
+            ImageFormat::Dds => {
+                let output = output_dir.join(format!("{}.dds", name));
+                let mut writer = BufWriter::new(File::create(&output)?);
+                texture.to_dds(&mut writer)?;
+                writer.flush()?;
+                output
+            }
+        };
+
+        outputs.push(output);
+
+        if options.sidecar {
+            let output = output_dir.join(format!("{}.bntx.toml", name));
+            let mut writer = BufWriter::new(File::create(&output)?);
+            file.write_sidecar(texture.name(), &mut writer)?;
+            writer.flush()?;
+            outputs.push(output);
+        }
+    }
+
+    Ok(outputs)
+}
@@ -0,0 +1,963 @@
+//! Encoders and decoders for single BC1-BC7 blocks. Every block covers 4x4 pixels, stored row
+//! by row.
+
+use std::array::from_fn;
+use std::convert::TryInto;
+
+/// The 16 pixels of a block as 8-bit RGBA
+pub(crate) type Block = [[u8; 4]; 16];
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        BitReader { data, pos: 0 }
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        let mut value = 0;
+        for i in 0..count {
+            let bit = (self.data[self.pos / 8] >> (self.pos % 8)) & 1;
+            value |= (bit as u32) << i;
+            self.pos += 1;
+        }
+
+        value
+    }
+}
+
+struct BitWriter {
+    data: [u8; 16],
+    pos: usize,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        BitWriter { data: [0; 16], pos: 0 }
+    }
+
+    fn write(&mut self, value: u32, count: u32) {
+        for i in 0..count {
+            if (value >> i) & 1 != 0 {
+                self.data[self.pos / 8] |= 1 << (self.pos % 8);
+            }
+            self.pos += 1;
+        }
+    }
+}
+
+fn distance<const N: usize>(a: &[f32; N], b: &[f32; N]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+/// Index of the palette entry closest to `point`, along with its squared distance
+fn nearest<const N: usize>(palette: &[[f32; N]], point: &[f32; N]) -> (usize, f32) {
+    palette.iter()
+        .map(|entry| distance(entry, point))
+        .enumerate()
+        .fold((0, f32::MAX), |best, (i, dist)| if dist < best.1 { (i, dist) } else { best })
+}
+
+/// Endpoints of the line through `points` along their axis of greatest variance
+fn principal_endpoints<const N: usize>(points: &[[f32; N]]) -> ([f32; N], [f32; N]) {
+    let count = points.len() as f32;
+    let mean: [f32; N] = from_fn(|c| points.iter().map(|p| p[c]).sum::<f32>() / count);
+
+    let mut covariance = [[0.0f32; N]; N];
+    for p in points {
+        for i in 0..N {
+            for j in 0..N {
+                covariance[i][j] += (p[i] - mean[i]) * (p[j] - mean[j]);
+            }
+        }
+    }
+
+    // power iteration, starting from the channel with the most variance
+    let start = (0..N)
+        .max_by(|&a, &b| covariance[a][a].total_cmp(&covariance[b][b]))
+        .unwrap_or(0);
+    let mut axis: [f32; N] = from_fn(|c| if c == start { 1.0 } else { 0.0 });
+    for _ in 0..8 {
+        let next: [f32; N] = from_fn(|i| (0..N).map(|j| covariance[i][j] * axis[j]).sum());
+        let len = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if len < 1e-6 {
+            break;
+        }
+        axis = next.map(|v| v / len);
+    }
+
+    let (min, max) = points.iter()
+        .map(|p| (0..N).map(|c| (p[c] - mean[c]) * axis[c]).sum::<f32>())
+        .fold((f32::MAX, f32::MIN), |(min, max), t| (min.min(t), max.max(t)));
+
+    (from_fn(|c| mean[c] + axis[c] * min), from_fn(|c| mean[c] + axis[c] * max))
+}
+
+/// Endpoints minimizing the squared error of `points` when interpolated with the given weights,
+/// each from 0 (first endpoint) to 1 (second endpoint)
+fn least_squares<const N: usize>(points: &[[f32; N]], weights: &[f32]) -> Option<([f32; N], [f32; N])> {
+    let (mut aa, mut ab, mut bb) = (0.0, 0.0, 0.0);
+    let (mut ax, mut bx) = ([0.0f32; N], [0.0f32; N]);
+    for (p, &w) in points.iter().zip(weights) {
+        let a = 1.0 - w;
+        aa += a * a;
+        ab += a * w;
+        bb += w * w;
+        for c in 0..N {
+            ax[c] += a * p[c];
+            bx[c] += w * p[c];
+        }
+    }
+
+    let det = aa * bb - ab * ab;
+    if det.abs() < 1e-6 {
+        return None;
+    }
+
+    Some((
+        from_fn(|c| (bb * ax[c] - ab * bx[c]) / det),
+        from_fn(|c| (aa * bx[c] - ab * ax[c]) / det),
+    ))
+}
+
+// ---- BC1-BC3 color ----
+
+fn unpack_565(color: u16) -> [u8; 4] {
+    let r = ((color >> 11) & 0x1f) as u8;
+    let g = ((color >> 5) & 0x3f) as u8;
+    let b = (color & 0x1f) as u8;
+
+    [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2), 255]
+}
+
+fn pack_565(color: [f32; 3]) -> u16 {
+    let quantize = |v: f32, max: f32| (v.clamp(0.0, 255.0) / 255.0 * max).round() as u16;
+
+    (quantize(color[0], 31.0) << 11) | (quantize(color[1], 63.0) << 5) | quantize(color[2], 31.0)
+}
+
+fn color_palette(c0: u16, c1: u16, four_color: bool) -> [[u8; 4]; 4] {
+    let (a, b) = (unpack_565(c0), unpack_565(c1));
+    let mix = |wa: u32, wb: u32| -> [u8; 4] {
+        let mut out = [255; 4];
+        for c in 0..3 {
+            out[c] = ((a[c] as u32 * wa + b[c] as u32 * wb) / (wa + wb)) as u8;
+        }
+        out
+    };
+
+    if four_color {
+        [a, b, mix(2, 1), mix(1, 2)]
+    } else {
+        [a, b, mix(1, 1), [0; 4]]
+    }
+}
+
+/// Decodes a color block. Only BC1 blocks can use the three color mode with transparency.
+fn decode_color(block: &[u8], out: &mut Block, allow_transparency: bool) {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let palette = color_palette(c0, c1, !allow_transparency || c0 > c1);
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+
+    for (i, pixel) in out.iter_mut().enumerate() {
+        *pixel = palette[((indices >> (i * 2)) & 3) as usize];
+    }
+}
+
+fn encode_color(pixels: &Block, allow_transparency: bool) -> [u8; 8] {
+    let transparent = |p: &[u8; 4]| allow_transparency && p[3] < 128;
+    let colors: Vec<[f32; 3]> = pixels.iter()
+        .filter(|p| !transparent(p))
+        .map(|p| [p[0] as f32, p[1] as f32, p[2] as f32])
+        .collect();
+    let has_transparency = colors.len() < pixels.len();
+
+    let (c0, c1) = if colors.is_empty() {
+        (0, 0)
+    } else {
+        let (lo, hi) = principal_endpoints(&colors);
+        (pack_565(hi), pack_565(lo))
+    };
+
+    // the endpoint order selects between the four color and three color + transparent modes
+    let (c0, c1) = if (has_transparency && c0 > c1) || (!has_transparency && c0 < c1) {
+        (c1, c0)
+    } else {
+        (c0, c1)
+    };
+
+    let four_color = !allow_transparency || c0 > c1;
+    let palette = color_palette(c0, c1, four_color)
+        .map(|c| [c[0] as f32, c[1] as f32, c[2] as f32]);
+    let choices = if four_color { 4 } else { 3 };
+
+    let mut indices = 0u32;
+    for (i, p) in pixels.iter().enumerate() {
+        let index = if transparent(p) {
+            3
+        } else {
+            nearest(&palette[..choices], &[p[0] as f32, p[1] as f32, p[2] as f32]).0
+        };
+        indices |= (index as u32) << (i * 2);
+    }
+
+    let mut block = [0; 8];
+    block[..2].copy_from_slice(&c0.to_le_bytes());
+    block[2..4].copy_from_slice(&c1.to_le_bytes());
+    block[4..].copy_from_slice(&indices.to_le_bytes());
+    block
+}
+
+// ---- BC3-BC5 alpha/channel ----
+
+/// The eight values a BC4 style block can select from, either unsigned or signed
+fn alpha_palette(a0: i32, a1: i32, signed: bool) -> [i32; 8] {
+    let (min, max) = if signed { (-127, 127) } else { (0, 255) };
+    let mut palette = [a0, a1, 0, 0, 0, 0, min, max];
+
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i as usize + 1] = (a0 * (7 - i) + a1 * i) / 7;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i as usize + 1] = (a0 * (5 - i) + a1 * i) / 5;
+        }
+    }
+
+    palette
+}
+
+fn decode_alpha(block: &[u8], signed: bool) -> [i32; 16] {
+    let endpoint = |b: u8| if signed { (b as i8).max(-127) as i32 } else { b as i32 };
+    let palette = alpha_palette(endpoint(block[0]), endpoint(block[1]), signed);
+
+    let mut bytes = [0; 8];
+    bytes[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bytes);
+
+    from_fn(|i| palette[((indices >> (i * 3)) & 7) as usize])
+}
+
+fn encode_alpha(values: &[i32; 16], signed: bool) -> [u8; 8] {
+    let max = *values.iter().max().unwrap();
+    let min = *values.iter().min().unwrap();
+    let palette = alpha_palette(max, min, signed).map(|v| [v as f32]);
+
+    let mut indices = 0u64;
+    for (i, &v) in values.iter().enumerate() {
+        indices |= (nearest(&palette, &[v as f32]).0 as u64) << (i * 3);
+    }
+
+    let mut block = [0; 8];
+    block[0] = max as u8;
+    block[1] = min as u8;
+    block[2..].copy_from_slice(&indices.to_le_bytes()[..6]);
+    block
+}
+
+pub(crate) fn decode_bc1(block: &[u8], out: &mut Block) {
+    decode_color(block, out, true);
+}
+
+pub(crate) fn decode_bc2(block: &[u8], out: &mut Block) {
+    decode_color(&block[8..], out, false);
+
+    let alpha = u64::from_le_bytes(block[..8].try_into().unwrap());
+    for (i, pixel) in out.iter_mut().enumerate() {
+        pixel[3] = ((alpha >> (i * 4)) & 0xf) as u8 * 17;
+    }
+}
+
+pub(crate) fn decode_bc3(block: &[u8], out: &mut Block) {
+    decode_color(&block[8..], out, false);
+
+    for (pixel, alpha) in out.iter_mut().zip(decode_alpha(&block[..8], false)) {
+        pixel[3] = alpha as u8;
+    }
+}
+
+/// Decodes a single channel block, as 0 to 255 or -127 to 127 if signed
+pub(crate) fn decode_bc4(block: &[u8], signed: bool) -> [i32; 16] {
+    decode_alpha(block, signed)
+}
+
+pub(crate) fn encode_bc1(pixels: &Block) -> [u8; 8] {
+    encode_color(pixels, true)
+}
+
+pub(crate) fn encode_bc2(pixels: &Block) -> [u8; 16] {
+    let mut alpha = 0u64;
+    for (i, pixel) in pixels.iter().enumerate() {
+        alpha |= ((pixel[3] as u64 * 15 + 127) / 255) << (i * 4);
+    }
+
+    let mut block = [0; 16];
+    block[..8].copy_from_slice(&alpha.to_le_bytes());
+    block[8..].copy_from_slice(&encode_color(pixels, false));
+    block
+}
+
+pub(crate) fn encode_bc3(pixels: &Block) -> [u8; 16] {
+    let mut block = [0; 16];
+    block[..8].copy_from_slice(&encode_alpha(&pixels.map(|p| p[3] as i32), false));
+    block[8..].copy_from_slice(&encode_color(pixels, false));
+    block
+}
+
+pub(crate) fn encode_bc4(values: &[i32; 16], signed: bool) -> [u8; 8] {
+    encode_alpha(values, signed)
+}
+
+// ---- BC6H and BC7 ----
+
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+fn weights(bits: u32) -> &'static [u32] {
+    match bits {
+        2 => &WEIGHTS_2,
+        3 => &WEIGHTS_3,
+        _ => &WEIGHTS_4,
+    }
+}
+
+fn interpolate(a: i32, b: i32, weight: u32) -> i32 {
+    (a * (64 - weight as i32) + b * weight as i32 + 32) >> 6
+}
+
+/// Subset of each pixel for the two subset partitions, one bit per pixel
+const PARTITIONS_2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80,
+    0xc800, 0xffec, 0xfe80, 0xe800, 0xffe8, 0xff00, 0xfff0, 0xf000,
+    0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c,
+    0xaaaa, 0xf0f0, 0x5a5a, 0x33cc, 0x3c3c, 0x55aa, 0x9696, 0xa55a,
+    0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c,
+    0x9336, 0x9cc6, 0x817e, 0xe718, 0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+/// Subset of each pixel for the three subset partitions, two bits per pixel
+const PARTITIONS_3: [u32; 64] = [
+    0xaa685050, 0x6a5a5040, 0x5a5a4200, 0x5450a0a8, 0xa5a50000, 0xa0a05050, 0x5555a0a0, 0x5a5a5050,
+    0xaa550000, 0xaa555500, 0xaaaa5500, 0x90909090, 0x94949494, 0xa4a4a4a4, 0xa9a59450, 0x2a0a4250,
+    0xa5945040, 0x0a425054, 0xa5a5a500, 0x55a0a0a0, 0xa8a85454, 0x6a6a4040, 0xa4a45000, 0x1a1a0500,
+    0x0050a4a4, 0xaaa59090, 0x14696914, 0x69691400, 0xa08585a0, 0xaa821414, 0x50a4a450, 0x6a5a0200,
+    0xa9a58000, 0x5090a0a8, 0xa8a09050, 0x24242424, 0x00aa5500, 0x24924924, 0x24499224, 0x50a50a50,
+    0x500aa550, 0xaaaa4444, 0x66660000, 0xa5a0a5a0, 0x50a050a0, 0x69286928, 0x44aaaa44, 0x66666600,
+    0xaa444444, 0x54a854a8, 0x95809580, 0x96969600, 0xa85454a8, 0x80959580, 0xaa141414, 0x96960000,
+    0xaaaa1414, 0xa05050a0, 0xa0a5a5a0, 0x96000000, 0x40804080, 0xa9a8a9a8, 0xaaaaaa44, 0x2a4a5254,
+];
+
+/// Anchor pixel of the second subset of each two subset partition
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+    15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2,
+    15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6,
+    6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Anchor pixels of the second and third subsets of each three subset partition
+const ANCHORS_3: [[u8; 64]; 2] = [
+    [
+        3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3,
+        3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5, 15, 15,
+        8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15,
+        3, 15, 5, 5, 5, 8, 5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+    ],
+    [
+        15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8,
+        15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6, 10, 15, 15, 10, 8,
+        15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8,
+        15, 3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+    ],
+];
+
+fn subset(subsets: u32, partition: usize, pixel: usize) -> usize {
+    match subsets {
+        2 => ((PARTITIONS_2[partition] >> pixel) & 1) as usize,
+        3 => ((PARTITIONS_3[partition] >> (pixel * 2)) & 3) as usize,
+        _ => 0,
+    }
+}
+
+/// Anchor pixels store their index with one less bit, since its top bit is always 0
+fn is_anchor(subsets: u32, partition: usize, pixel: usize) -> bool {
+    pixel == 0
+        || match subsets {
+            2 => pixel == ANCHORS_2[partition] as usize,
+            3 => pixel == ANCHORS_3[0][partition] as usize || pixel == ANCHORS_3[1][partition] as usize,
+            _ => false,
+        }
+}
+
+struct Bc7Mode {
+    subsets: u32,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: u32,
+    index2_bits: u32,
+}
+
+#[allow(clippy::too_many_arguments)]
+const fn bc7_mode(
+    subsets: u32,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: u32,
+    index2_bits: u32,
+) -> Bc7Mode {
+    Bc7Mode {
+        subsets,
+        partition_bits,
+        rotation_bits,
+        index_selection_bits,
+        color_bits,
+        alpha_bits,
+        endpoint_pbits,
+        shared_pbits,
+        index_bits,
+        index2_bits,
+    }
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    bc7_mode(3, 4, 0, 0, 4, 0, true, false, 3, 0),
+    bc7_mode(2, 6, 0, 0, 6, 0, false, true, 3, 0),
+    bc7_mode(3, 6, 0, 0, 5, 0, false, false, 2, 0),
+    bc7_mode(2, 6, 0, 0, 7, 0, true, false, 2, 0),
+    bc7_mode(1, 0, 2, 1, 5, 6, false, false, 2, 3),
+    bc7_mode(1, 0, 2, 0, 7, 8, false, false, 2, 2),
+    bc7_mode(1, 0, 0, 0, 7, 7, true, false, 4, 0),
+    bc7_mode(2, 6, 0, 0, 5, 5, true, false, 2, 0),
+];
+
+pub(crate) fn decode_bc7(block: &[u8], out: &mut Block) {
+    // blocks without a mode bit are reserved and decode to transparent black
+    if block[0] == 0 {
+        *out = [[0; 4]; 16];
+        return;
+    }
+
+    let mode_index = block[0].trailing_zeros();
+    let mode = &BC7_MODES[mode_index as usize];
+    let mut reader = BitReader::new(block);
+    reader.read(mode_index + 1);
+
+    let partition = reader.read(mode.partition_bits) as usize;
+    let rotation = reader.read(mode.rotation_bits);
+    let index_selection = reader.read(mode.index_selection_bits);
+
+    let endpoint_count = mode.subsets as usize * 2;
+    let mut endpoints = [[0u32; 4]; 6];
+    for c in 0..3 {
+        for endpoint in &mut endpoints[..endpoint_count] {
+            endpoint[c] = reader.read(mode.color_bits);
+        }
+    }
+    if mode.alpha_bits > 0 {
+        for endpoint in &mut endpoints[..endpoint_count] {
+            endpoint[3] = reader.read(mode.alpha_bits);
+        }
+    }
+
+    let has_pbits = mode.endpoint_pbits || mode.shared_pbits;
+    if mode.endpoint_pbits {
+        for endpoint in &mut endpoints[..endpoint_count] {
+            let pbit = reader.read(1);
+            *endpoint = endpoint.map(|v| (v << 1) | pbit);
+        }
+    } else if mode.shared_pbits {
+        for pair in endpoints[..endpoint_count].chunks_mut(2) {
+            let pbit = reader.read(1);
+            for endpoint in pair {
+                *endpoint = endpoint.map(|v| (v << 1) | pbit);
+            }
+        }
+    }
+
+    let expand = |v: u32, bits: u32| {
+        let v = v << (8 - bits);
+        (v | (v >> bits)) as i32
+    };
+    let color_bits = mode.color_bits + has_pbits as u32;
+    let alpha_bits = mode.alpha_bits + has_pbits as u32;
+    let endpoints: [[i32; 4]; 6] = endpoints.map(|e| [
+        expand(e[0], color_bits),
+        expand(e[1], color_bits),
+        expand(e[2], color_bits),
+        if mode.alpha_bits > 0 { expand(e[3], alpha_bits) } else { 255 },
+    ]);
+
+    let mut indices = [0; 16];
+    for (i, index) in indices.iter_mut().enumerate() {
+        *index = reader.read(mode.index_bits - is_anchor(mode.subsets, partition, i) as u32);
+    }
+    let mut indices2 = [0; 16];
+    if mode.index2_bits > 0 {
+        for (i, index) in indices2.iter_mut().enumerate() {
+            *index = reader.read(mode.index2_bits - (i == 0) as u32);
+        }
+    }
+
+    for (i, pixel) in out.iter_mut().enumerate() {
+        let s = subset(mode.subsets, partition, i);
+        let (e0, e1) = (endpoints[s * 2], endpoints[s * 2 + 1]);
+
+        let (color_weight, alpha_weight) = if mode.index2_bits == 0 {
+            let weight = weights(mode.index_bits)[indices[i] as usize];
+            (weight, weight)
+        } else if index_selection == 0 {
+            (
+                weights(mode.index_bits)[indices[i] as usize],
+                weights(mode.index2_bits)[indices2[i] as usize],
+            )
+        } else {
+            (
+                weights(mode.index2_bits)[indices2[i] as usize],
+                weights(mode.index_bits)[indices[i] as usize],
+            )
+        };
+
+        for c in 0..3 {
+            pixel[c] = interpolate(e0[c], e1[c], color_weight) as u8;
+        }
+        pixel[3] = interpolate(e0[3], e1[3], alpha_weight) as u8;
+
+        match rotation {
+            1 => pixel.swap(0, 3),
+            2 => pixel.swap(1, 3),
+            3 => pixel.swap(2, 3),
+            _ => (),
+        }
+    }
+}
+
+/// A block using BC7 mode 6: a single subset with 7-bit RGBA endpoints, a p-bit per endpoint and
+/// 4-bit indices
+struct Bc7Mode6 {
+    endpoints: [[u8; 4]; 2],
+    pbits: [u8; 2],
+    indices: [u8; 16],
+    error: f32,
+}
+
+impl Bc7Mode6 {
+    fn fit(points: &[[f32; 4]], lo: [f32; 4], hi: [f32; 4]) -> Self {
+        let mut best: Option<Bc7Mode6> = None;
+
+        for pbits in [[0, 0], [0, 1], [1, 0], [1, 1]] {
+            let quantize = |e: [f32; 4], pbit: u8| {
+                e.map(|v| ((v - pbit as f32) / 2.0).round().clamp(0.0, 127.0) as u8)
+            };
+            let endpoints = [quantize(lo, pbits[0]), quantize(hi, pbits[1])];
+            let unpacked: [[i32; 4]; 2] = from_fn(|e| endpoints[e].map(|v| ((v << 1) | pbits[e]) as i32));
+            let palette: [[f32; 4]; 16] = from_fn(|i| {
+                from_fn(|c| interpolate(unpacked[0][c], unpacked[1][c], WEIGHTS_4[i]) as f32)
+            });
+
+            let mut indices = [0; 16];
+            let mut error = 0.0;
+            for (index, point) in indices.iter_mut().zip(points) {
+                let (i, dist) = nearest(&palette, point);
+                *index = i as u8;
+                error += dist;
+            }
+
+            if best.as_ref().is_none_or(|best| error < best.error) {
+                best = Some(Bc7Mode6 { endpoints, pbits, indices, error });
+            }
+        }
+
+        best.unwrap()
+    }
+
+    fn pack(mut self) -> [u8; 16] {
+        if self.indices[0] >= 8 {
+            self.endpoints.swap(0, 1);
+            self.pbits.swap(0, 1);
+            self.indices = self.indices.map(|i| 15 - i);
+        }
+
+        let mut writer = BitWriter::new();
+        writer.write(1 << 6, 7);
+        for c in 0..4 {
+            for endpoint in &self.endpoints {
+                writer.write(endpoint[c] as u32, 7);
+            }
+        }
+        for pbit in self.pbits {
+            writer.write(pbit as u32, 1);
+        }
+        for (i, &index) in self.indices.iter().enumerate() {
+            writer.write(index as u32, if i == 0 { 3 } else { 4 });
+        }
+
+        writer.data
+    }
+}
+
+/// Encodes a block using only mode 6, which handles smooth color and alpha well
+pub(crate) fn encode_bc7(pixels: &Block) -> [u8; 16] {
+    let points = pixels.map(|p| p.map(|c| c as f32));
+    let (lo, hi) = principal_endpoints(&points);
+    let mut best = Bc7Mode6::fit(&points, lo, hi);
+
+    let weights = best.indices.map(|i| WEIGHTS_4[i as usize] as f32 / 64.0);
+    if let Some((lo, hi)) = least_squares(&points, &weights) {
+        let refined = Bc7Mode6::fit(&points, lo, hi);
+        if refined.error < best.error {
+            best = refined;
+        }
+    }
+
+    best.pack()
+}
+
+const R0: u8 = 0;
+const G0: u8 = 1;
+const B0: u8 = 2;
+const R1: u8 = 3;
+const G1: u8 = 4;
+const B1: u8 = 5;
+const R2: u8 = 6;
+const G2: u8 = 7;
+const B2: u8 = 8;
+const R3: u8 = 9;
+const G3: u8 = 10;
+const B3: u8 = 11;
+
+struct Bc6hMode {
+    value: u32,
+    regions: u32,
+    transformed: bool,
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    /// Where each bit of the header goes, as (endpoint component, first bit, last bit). Bits
+    /// are stored from the first bit to the last, which may be in descending order.
+    layout: &'static [(u8, u8, u8)],
+}
+
+const BC6H_MODES: [Bc6hMode; 14] = [
+    Bc6hMode {
+        value: 0x00, regions: 2, transformed: true, endpoint_bits: 10, delta_bits: [5, 5, 5],
+        layout: &[
+            (G2, 4, 4), (B2, 4, 4), (B3, 4, 4), (R0, 0, 9), (G0, 0, 9), (B0, 0, 9), (R1, 0, 4),
+            (G3, 4, 4), (G2, 0, 3), (G1, 0, 4), (B3, 0, 0), (G3, 0, 3), (B1, 0, 4), (B3, 1, 1),
+            (B2, 0, 3), (R2, 0, 4), (B3, 2, 2), (R3, 0, 4), (B3, 3, 3),
+        ],
+    },
+    Bc6hMode {
+        value: 0x01, regions: 2, transformed: true, endpoint_bits: 7, delta_bits: [6, 6, 6],
+        layout: &[
+            (G2, 5, 5), (G3, 4, 4), (G3, 5, 5), (R0, 0, 6), (B3, 0, 0), (B3, 1, 1), (B2, 4, 4),
+            (G0, 0, 6), (B2, 5, 5), (B3, 2, 2), (G2, 4, 4), (B0, 0, 6), (B3, 3, 3), (B3, 5, 5),
+            (B3, 4, 4), (R1, 0, 5), (G2, 0, 3), (G1, 0, 5), (G3, 0, 3), (B1, 0, 5), (B2, 0, 3),
+            (R2, 0, 5), (R3, 0, 5),
+        ],
+    },
+    Bc6hMode {
+        value: 0x02, regions: 2, transformed: true, endpoint_bits: 11, delta_bits: [5, 4, 4],
+        layout: &[
+            (R0, 0, 9), (G0, 0, 9), (B0, 0, 9), (R1, 0, 4), (R0, 10, 10), (G2, 0, 3), (G1, 0, 3),
+            (G0, 10, 10), (B3, 0, 0), (G3, 0, 3), (B1, 0, 3), (B0, 10, 10), (B3, 1, 1), (B2, 0, 3),
+            (R2, 0, 4), (B3, 2, 2), (R3, 0, 4), (B3, 3, 3),
+        ],
+    },
+    Bc6hMode {
+        value: 0x06, regions: 2, transformed: true, endpoint_bits: 11, delta_bits: [4, 5, 4],
+        layout: &[
+            (R0, 0, 9), (G0, 0, 9), (B0, 0, 9), (R1, 0, 3), (R0, 10, 10), (G3, 4, 4), (G2, 0, 3),
+            (G1, 0, 4), (G0, 10, 10), (G3, 0, 3), (B1, 0, 3), (B0, 10, 10), (B3, 1, 1), (B2, 0, 3),
+            (R2, 0, 3), (B3, 0, 0), (B3, 2, 2), (R3, 0, 3), (G2, 4, 4), (B3, 3, 3),
+        ],
+    },
+    Bc6hMode {
+        value: 0x0a, regions: 2, transformed: true, endpoint_bits: 11, delta_bits: [4, 4, 5],
+        layout: &[
+            (R0, 0, 9), (G0, 0, 9), (B0, 0, 9), (R1, 0, 3), (R0, 10, 10), (B2, 4, 4), (G2, 0, 3),
+            (G1, 0, 3), (G0, 10, 10), (B3, 0, 0), (G3, 0, 3), (B1, 0, 4), (B0, 10, 10), (B2, 0, 3),
+            (R2, 0, 3), (B3, 1, 1), (B3, 2, 2), (R3, 0, 3), (B3, 4, 4), (B3, 3, 3),
+        ],
+    },
+    Bc6hMode {
+        value: 0x0e, regions: 2, transformed: true, endpoint_bits: 9, delta_bits: [5, 5, 5],
+        layout: &[
+            (R0, 0, 8), (B2, 4, 4), (G0, 0, 8), (G2, 4, 4), (B0, 0, 8), (B3, 4, 4), (R1, 0, 4),
+            (G3, 4, 4), (G2, 0, 3), (G1, 0, 4), (B3, 0, 0), (G3, 0, 3), (B1, 0, 4), (B3, 1, 1),
+            (B2, 0, 3), (R2, 0, 4), (B3, 2, 2), (R3, 0, 4), (B3, 3, 3),
+        ],
+    },
+    Bc6hMode {
+        value: 0x12, regions: 2, transformed: true, endpoint_bits: 8, delta_bits: [6, 5, 5],
+        layout: &[
+            (R0, 0, 7), (G3, 4, 4), (B2, 4, 4), (G0, 0, 7), (B3, 2, 2), (G2, 4, 4), (B0, 0, 7),
+            (B3, 3, 3), (B3, 4, 4), (R1, 0, 5), (G2, 0, 3), (G1, 0, 4), (B3, 0, 0), (G3, 0, 3),
+            (B1, 0, 4), (B3, 1, 1), (B2, 0, 3), (R2, 0, 5), (R3, 0, 5),
+        ],
+    },
+    Bc6hMode {
+        value: 0x16, regions: 2, transformed: true, endpoint_bits: 8, delta_bits: [5, 6, 5],
+        layout: &[
+            (R0, 0, 7), (B3, 0, 0), (B2, 4, 4), (G0, 0, 7), (G2, 5, 5), (G2, 4, 4), (B0, 0, 7),
+            (G3, 5, 5), (B3, 4, 4), (R1, 0, 4), (G3, 4, 4), (G2, 0, 3), (G1, 0, 5), (G3, 0, 3),
+            (B1, 0, 4), (B3, 1, 1), (B2, 0, 3), (R2, 0, 4), (B3, 2, 2), (R3, 0, 4), (B3, 3, 3),
+        ],
+    },
+    Bc6hMode {
+        value: 0x1a, regions: 2, transformed: true, endpoint_bits: 8, delta_bits: [5, 5, 6],
+        layout: &[
+            (R0, 0, 7), (B3, 1, 1), (B2, 4, 4), (G0, 0, 7), (B2, 5, 5), (G2, 4, 4), (B0, 0, 7),
+            (B3, 5, 5), (B3, 4, 4), (R1, 0, 4), (G3, 4, 4), (G2, 0, 3), (G1, 0, 4), (B3, 0, 0),
+            (G3, 0, 3), (B1, 0, 5), (B2, 0, 3), (R2, 0, 4), (B3, 2, 2), (R3, 0, 4), (B3, 3, 3),
+        ],
+    },
+    Bc6hMode {
+        value: 0x1e, regions: 2, transformed: false, endpoint_bits: 6, delta_bits: [6, 6, 6],
+        layout: &[
+            (R0, 0, 5), (G3, 4, 4), (B3, 0, 0), (B3, 1, 1), (B2, 4, 4), (G0, 0, 5), (G2, 5, 5),
+            (B2, 5, 5), (B3, 2, 2), (G2, 4, 4), (B0, 0, 5), (G3, 5, 5), (B3, 3, 3), (B3, 5, 5),
+            (B3, 4, 4), (R1, 0, 5), (G2, 0, 3), (G1, 0, 5), (G3, 0, 3), (B1, 0, 5), (B2, 0, 3),
+            (R2, 0, 5), (R3, 0, 5),
+        ],
+    },
+    Bc6hMode {
+        value: 0x03, regions: 1, transformed: false, endpoint_bits: 10, delta_bits: [10, 10, 10],
+        layout: &[(R0, 0, 9), (G0, 0, 9), (B0, 0, 9), (R1, 0, 9), (G1, 0, 9), (B1, 0, 9)],
+    },
+    Bc6hMode {
+        value: 0x07, regions: 1, transformed: true, endpoint_bits: 11, delta_bits: [9, 9, 9],
+        layout: &[
+            (R0, 0, 9), (G0, 0, 9), (B0, 0, 9), (R1, 0, 8), (R0, 10, 10), (G1, 0, 8), (G0, 10, 10),
+            (B1, 0, 8), (B0, 10, 10),
+        ],
+    },
+    Bc6hMode {
+        value: 0x0b, regions: 1, transformed: true, endpoint_bits: 12, delta_bits: [8, 8, 8],
+        layout: &[
+            (R0, 0, 9), (G0, 0, 9), (B0, 0, 9), (R1, 0, 7), (R0, 11, 10), (G1, 0, 7), (G0, 11, 10),
+            (B1, 0, 7), (B0, 11, 10),
+        ],
+    },
+    Bc6hMode {
+        value: 0x0f, regions: 1, transformed: true, endpoint_bits: 16, delta_bits: [4, 4, 4],
+        layout: &[
+            (R0, 0, 9), (G0, 0, 9), (B0, 0, 9), (R1, 0, 3), (R0, 15, 10), (G1, 0, 3), (G0, 15, 10),
+            (B1, 0, 3), (B0, 15, 10),
+        ],
+    },
+];
+
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    (value << shift) >> shift
+}
+
+/// Expands a quantized endpoint to the 16-bit range interpolation happens in
+fn bc6h_unquantize(value: i32, bits: u32, signed: bool) -> i32 {
+    if !signed {
+        if bits >= 15 {
+            value
+        } else if value == 0 {
+            0
+        } else if value == (1 << bits) - 1 {
+            0xffff
+        } else {
+            ((value << 16) + 0x8000) >> bits
+        }
+    } else if bits >= 16 {
+        value
+    } else {
+        let magnitude = value.abs();
+        let unquantized = if magnitude == 0 {
+            0
+        } else if magnitude >= (1 << (bits - 1)) - 1 {
+            0x7fff
+        } else {
+            ((magnitude << 15) + 0x4000) >> (bits - 1)
+        };
+
+        if value < 0 { -unquantized } else { unquantized }
+    }
+}
+
+/// Scales an interpolated value to the bits of a half float
+fn bc6h_finish(value: i32, signed: bool) -> u16 {
+    if !signed {
+        ((value * 31) >> 6) as u16
+    } else if value < 0 {
+        ((((-value) * 31) >> 5) as u16) | 0x8000
+    } else {
+        ((value * 31) >> 5) as u16
+    }
+}
+
+/// Maps half float bits to the range endpoints are interpolated in, the inverse of `bc6h_finish`
+fn bc6h_start(half: u16, signed: bool) -> f32 {
+    let magnitude = (half & 0x7fff).min(0x7bff) as f32;
+    let negative = half & 0x8000 != 0;
+
+    match (signed, negative) {
+        (false, true) => 0.0,
+        (false, false) => magnitude * 64.0 / 31.0,
+        (true, false) => magnitude * 32.0 / 31.0,
+        (true, true) => -magnitude * 32.0 / 31.0,
+    }
+}
+
+/// Decodes a block to half float RGB pixels
+pub(crate) fn decode_bc6h(block: &[u8], signed: bool) -> [[u16; 3]; 16] {
+    let mut reader = BitReader::new(block);
+    let mut value = reader.read(2);
+    if value >= 2 {
+        value |= reader.read(3) << 2;
+    }
+
+    // reserved modes decode to black
+    let mode = match BC6H_MODES.iter().find(|mode| mode.value == value) {
+        Some(mode) => mode,
+        None => return [[0; 3]; 16],
+    };
+
+    let mut endpoints = [0i32; 12];
+    for &(field, first, last) in mode.layout {
+        let bits: Vec<u8> = if first <= last {
+            (first..=last).collect()
+        } else {
+            (last..=first).rev().collect()
+        };
+        for bit in bits {
+            endpoints[field as usize] |= (reader.read(1) as i32) << bit;
+        }
+    }
+    let partition = if mode.regions == 2 { reader.read(5) as usize } else { 0 };
+
+    let endpoint_count = mode.regions as usize * 2;
+    let bits = mode.endpoint_bits;
+    let mask = (1 << bits) - 1;
+    if signed {
+        for value in &mut endpoints[..3] {
+            *value = sign_extend(*value, bits);
+        }
+    }
+    for e in 1..endpoint_count {
+        for c in 0..3 {
+            let base = endpoints[c];
+            let value = &mut endpoints[e * 3 + c];
+            if mode.transformed {
+                let sum = base + sign_extend(*value, mode.delta_bits[c]);
+                *value = if signed { sign_extend(sum & mask, bits) } else { sum & mask };
+            } else if signed {
+                *value = sign_extend(*value, mode.delta_bits[c]);
+            }
+        }
+    }
+    let endpoints = endpoints.map(|v| bc6h_unquantize(v, bits, signed));
+
+    let index_bits = if mode.regions == 2 { 3 } else { 4 };
+    from_fn(|i| {
+        let index = reader.read(index_bits - is_anchor(mode.regions, partition, i) as u32);
+        let weight = weights(index_bits)[index as usize];
+        let region = subset(mode.regions, partition, i);
+
+        from_fn(|c| {
+            let (a, b) = (endpoints[region * 6 + c], endpoints[region * 6 + 3 + c]);
+            bc6h_finish(interpolate(a, b, weight), signed)
+        })
+    })
+}
+
+/// A block using BC6H mode 11: a single region with 10-bit endpoints and 4-bit indices
+struct Bc6hMode11 {
+    endpoints: [[i32; 3]; 2],
+    indices: [u8; 16],
+    error: f32,
+}
+
+impl Bc6hMode11 {
+    fn fit(points: &[[f32; 3]], lo: [f32; 3], hi: [f32; 3], signed: bool) -> Self {
+        let quantize = |v: f32| {
+            let (min, max, estimate) = if signed {
+                (-511, 511, v * 512.0 / 32768.0)
+            } else {
+                (0, 1023, v * 1024.0 / 65536.0)
+            };
+            let estimate = estimate as i32;
+
+            (estimate - 1..=estimate + 1)
+                .map(|q| q.clamp(min, max))
+                .min_by_key(|&q| (bc6h_unquantize(q, 10, signed) as f32 - v).abs() as i64)
+                .unwrap()
+        };
+
+        let endpoints = [lo.map(quantize), hi.map(quantize)];
+        let unquantized = endpoints.map(|e| e.map(|v| bc6h_unquantize(v, 10, signed)));
+        let palette: [[f32; 3]; 16] = from_fn(|i| {
+            from_fn(|c| interpolate(unquantized[0][c], unquantized[1][c], WEIGHTS_4[i]) as f32)
+        });
+
+        let mut indices = [0; 16];
+        let mut error = 0.0;
+        for (index, point) in indices.iter_mut().zip(points) {
+            let (i, dist) = nearest(&palette, point);
+            *index = i as u8;
+            error += dist;
+        }
+
+        Bc6hMode11 { endpoints, indices, error }
+    }
+
+    fn pack(mut self) -> [u8; 16] {
+        if self.indices[0] >= 8 {
+            self.endpoints.swap(0, 1);
+            self.indices = self.indices.map(|i| 15 - i);
+        }
+
+        let mut writer = BitWriter::new();
+        writer.write(0x03, 5);
+        for endpoint in &self.endpoints {
+            for &value in endpoint {
+                writer.write(value as u32 & 0x3ff, 10);
+            }
+        }
+        for (i, &index) in self.indices.iter().enumerate() {
+            writer.write(index as u32, if i == 0 { 3 } else { 4 });
+        }
+
+        writer.data
+    }
+}
+
+/// Encodes a block of half float RGB pixels using only mode 11
+pub(crate) fn encode_bc6h(pixels: &[[u16; 3]; 16], signed: bool) -> [u8; 16] {
+    let points = pixels.map(|p| p.map(|h| bc6h_start(h, signed)));
+    let (lo, hi) = principal_endpoints(&points);
+    let mut best = Bc6hMode11::fit(&points, lo, hi, signed);
+
+    let weights = best.indices.map(|i| WEIGHTS_4[i as usize] as f32 / 64.0);
+    if let Some((lo, hi)) = least_squares(&points, &weights) {
+        let refined = Bc6hMode11::fit(&points, lo, hi, signed);
+        if refined.error < best.error {
+            best = refined;
+        }
+    }
+
+    best.pack()
+}
+
@@ -0,0 +1,66 @@
+use binrw::io::{Read, Seek, SeekFrom};
+use binrw::BinResult;
+
+use crate::BntxFile;
+
+// Where the external file section lives in a BFRES header varies between versions, so rather
+// than following it the file is scanned for an aligned BNTX header, which is always stored
+// as-is in an external file.
+const SCAN_ALIGN: u64 = 8;
+const SCAN_CHUNK_SIZE: usize = 0x10000;
+
+impl BntxFile {
+    /// Reads the BNTX embedded in a BFRES model file's external files, where most games keep
+    /// their model textures. The BNTX is parsed directly from `reader` without copying it out.
+    pub fn from_bfres<R: Read + Seek>(reader: &mut R) -> BinResult<Self> {
+        let start = reader.stream_position()?;
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != b"FRES" {
+            return Err(binrw::Error::BadMagic { pos: start, found: Box::new(magic) });
+        }
+
+        let mut chunk = vec![0; SCAN_CHUNK_SIZE];
+        let mut chunk_start = start;
+        loop {
+            reader.seek(SeekFrom::Start(chunk_start))?;
+            let len = read_full(reader, &mut chunk)?;
+
+            let candidates = (0..len.saturating_sub(3))
+                .step_by(SCAN_ALIGN as usize)
+                .filter(|&i| &chunk[i..i + 4] == b"BNTX");
+            for i in candidates {
+                let offset = chunk_start + i as u64;
+
+                // a match that doesn't parse is just data that happens to contain the magic
+                if let Ok(file) = BntxFile::read_at(reader, offset) {
+                    return Ok(file);
+                }
+            }
+
+            if len < chunk.len() {
+                break;
+            }
+            chunk_start += len as u64;
+        }
+
+        Err(binrw::Error::AssertFail {
+            pos: start,
+            message: "no BNTX found in BFRES".into(),
+        })
+    }
+}
+
+/// Reads until `buf` is full or the end of the stream, returning the number of bytes read
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+
+    Ok(len)
+}
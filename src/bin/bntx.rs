@@ -0,0 +1,187 @@
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+
+use bntx::inspect::{inspect, InspectOptions};
+use bntx::{BntxFile, PngColorSpace};
+
+const USAGE: &str = "\
+usage: bntx inspect <file> [--hexdump <section>]... [--limit <bytes>]
+       bntx convert <file> <output> [--texture <name>] [--format <format>]
+
+commands:
+  inspect    print a file's header, textures, sections and relocation entries. A file of -
+             reads from stdin.
+  convert    write a texture as a PNG, or the whole file rebuilt as BNTX. A file of - reads
+             from stdin and an output of - writes to stdout.
+
+options:
+  --hexdump <section>  also dump a section's bytes with its fields labelled, such as BRTI or
+                       _RLT, or all for every section. Can be given more than once.
+  --limit <bytes>      the most bytes dumped from each section, 0x200 by default
+  --texture <name>     the texture written as a PNG, the first one by default
+  --format <format>    png or bntx, going by the output's extension by default or png when
+                       writing to stdout";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let result = match args.first().map(String::as_str) {
+        Some("inspect") => parse_inspect(&args[1..]).and_then(|(path, options)| run_inspect(&path, &options)),
+        Some("convert") => parse_convert(&args[1..]).and_then(|convert| run_convert(&convert)),
+        Some("-h" | "--help" | "help") => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Some(command) => Err(format!("unknown command `{}`", command)),
+        None => Err("missing command".to_owned()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse_inspect(args: &[String]) -> Result<(String, InspectOptions), String> {
+    let mut path = None;
+    let mut options = InspectOptions::default();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--hexdump" => {
+                let section = args.next().ok_or("--hexdump needs a section")?;
+                options.hexdump.push(section.clone());
+            }
+            "--limit" => {
+                let limit = args.next().ok_or("--limit needs a number of bytes")?;
+                options.hexdump_limit = parse_size(limit).ok_or_else(|| format!("invalid limit `{}`", limit))?;
+            }
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument `{}`", arg)),
+        }
+    }
+
+    Ok((path.ok_or("missing file")?, options))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Png,
+    Bntx,
+}
+
+struct Convert {
+    path: String,
+    output: String,
+    texture: Option<String>,
+    format: Format,
+}
+
+fn parse_convert(args: &[String]) -> Result<Convert, String> {
+    let mut paths = Vec::new();
+    let mut texture = None;
+    let mut format = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--texture" => texture = Some(args.next().ok_or("--texture needs a name")?.clone()),
+            "--format" => {
+                let name = args.next().ok_or("--format needs png or bntx")?;
+                format = Some(parse_format(name).ok_or_else(|| format!("unknown format `{}`", name))?);
+            }
+            _ if paths.len() < 2 && !arg.starts_with("--") => paths.push(arg.clone()),
+            _ => return Err(format!("unexpected argument `{}`", arg)),
+        }
+    }
+
+    let output = paths.pop().filter(|_| !paths.is_empty()).ok_or("missing output")?;
+    let format = match format {
+        Some(format) => format,
+        None if output == "-" => Format::Png,
+        None => std::path::Path::new(&output)
+            .extension()
+            .and_then(|ext| parse_format(ext.to_str()?))
+            .ok_or_else(|| format!("can't tell what format to write `{}` as, pass --format", output))?,
+    };
+
+    Ok(Convert { path: paths.remove(0), output, texture, format })
+}
+
+/// Parses a format name or the output file extension for it, so `.zs` means compressed BNTX
+fn parse_format(name: &str) -> Option<Format> {
+    match name.to_ascii_lowercase().as_str() {
+        "png" => Some(Format::Png),
+        "bntx" | "zs" => Some(Format::Bntx),
+        _ => None,
+    }
+}
+
+/// Parses a decimal or `0x` prefixed hexadecimal size
+fn parse_size(size: &str) -> Option<usize> {
+    match size.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => size.parse().ok(),
+    }
+}
+
+/// Reads the whole input file, or stdin if `path` is `-`
+fn read_input(path: &str) -> Result<Vec<u8>, String> {
+    let data = if path == "-" {
+        let mut data = Vec::new();
+        io::stdin().read_to_end(&mut data).map(|_| data)
+    } else {
+        std::fs::read(path)
+    };
+
+    data.map_err(|err| format!("couldn't read {}: {}", path, err))
+}
+
+fn run_inspect(path: &str, options: &InspectOptions) -> Result<(), String> {
+    let data = read_input(path)?;
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    inspect(&data, options, &mut out)
+        .and_then(|()| out.flush())
+        .map_err(|err| err.to_string())
+}
+
+fn run_convert(convert: &Convert) -> Result<(), String> {
+    let data = read_input(&convert.path)?;
+    let file = BntxFile::from_reader(&data[..]).map_err(|err| format!("couldn't parse {}: {}", convert.path, err))?;
+
+    // saved through the library so a .zs output is compressed
+    if convert.format == Format::Bntx && convert.output != "-" {
+        return file.save(&convert.output).map_err(|err| format!("couldn't write {}: {}", convert.output, err));
+    }
+
+    let written = match convert.format {
+        Format::Bntx => {
+            let mut data = Vec::new();
+            file.write(&mut data).map(|()| data).map_err(|err| err.to_string())?
+        }
+        Format::Png => {
+            let texture = match &convert.texture {
+                Some(name) => file.texture(name).ok_or_else(|| format!("no texture named `{}`", name))?,
+                None => file.textures().next().ok_or("the file has no textures")?,
+            };
+
+            let mut data = Vec::new();
+            texture.to_png(&mut data, PngColorSpace::default()).map_err(|err| err.to_string())?;
+            data
+        }
+    };
+
+    if convert.output == "-" {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        out.write_all(&written).and_then(|()| out.flush()).map_err(|err| err.to_string())
+    } else {
+        std::fs::write(&convert.output, written).map_err(|err| format!("couldn't write {}: {}", convert.output, err))
+    }
+}
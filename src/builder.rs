@@ -0,0 +1,625 @@
+use crate::tegra_swizzle::{AddressTable, SurfaceParams};
+use crate::{
+    BntxStr, Error, GammaConversion, GpuAccess, ImageData, SurfaceFormat, Texture, TextureFlags,
+    TextureKind, MAX_TEXTURE_SIZE,
+};
+
+/// Creates a [`Texture`] from an image, with control over how it's encoded
+#[derive(Debug, Clone)]
+pub struct TextureBuilder {
+    name: String,
+    swizzle: u16,
+    alpha: AlphaConversion,
+    gamma: GammaConversion,
+    size_alignment: SizeAlignment,
+    alignment: u32,
+    grayscale_as_r8: bool,
+    one_dimensional: bool,
+    color_space: ColorSpace,
+    normal_map: NormalMap,
+    transform: ImageTransform,
+}
+
+/// A conversion applied to the color channels of an image based on its alpha before encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlphaConversion {
+    /// Leave the pixels as they are
+    None,
+    /// Multiply each color channel by alpha, for engines that expect premultiplied textures
+    Premultiply,
+    /// Divide each color channel by alpha, undoing a previous premultiply
+    Unpremultiply,
+}
+
+/// Whether an image holds colors meant to be seen or data such as normals and masks, which
+/// decides between the sRGB and UNORM variant of the texture's format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Colors, stored as `R8G8B8A8_SRGB` so the GPU converts them to linear when sampling
+    Srgb,
+    /// Data, stored as `R8G8B8A8_UNORM` so the values are sampled as they are rather than
+    /// gamma-decoded, which would skew normals and lighting masks
+    Linear,
+}
+
+/// Changes to an image's orientation and channels, for images made with tools whose conventions
+/// differ from the game's, such as textures with their origin at the bottom left or stored as
+/// BGRA. Each is applied in the order listed, to every mip level given. None are by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ImageTransform {
+    /// Rotate the image 90 degrees clockwise, swapping its width and height
+    pub rotate_90: bool,
+    /// Flip the image upside down
+    pub flip_vertical: bool,
+    /// Swap the red and blue channels
+    pub swap_red_blue: bool,
+}
+
+impl ImageTransform {
+    fn apply(self, img: image::RgbaImage) -> image::RgbaImage {
+        let mut img = if self.rotate_90 { image::imageops::rotate90(&img) } else { img };
+        if self.flip_vertical {
+            image::imageops::flip_vertical_in_place(&mut img);
+        }
+        if self.swap_red_blue {
+            for pixel in img.pixels_mut() {
+                pixel.0.swap(0, 2);
+            }
+        }
+
+        img
+    }
+}
+
+/// Whether an image is a tangent space normal map, and how to store it if so. Each pixel of a
+/// normal map holds a unit vector, with X, Y and Z mapped from -1 to 1 onto red, green and blue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalMap {
+    /// The image isn't a normal map, and is stored as its color space says
+    None,
+    /// Stored as `R8G8B8A8_UNORM`, keeping all three components
+    Rgb,
+    /// Stored as two channel `BC5_UNORM`, keeping X and Y, at a quarter of the size. Blue and
+    /// alpha read as one, so shaders have to rebuild Z from X and Y, as most games' shaders do.
+    TwoChannel,
+}
+
+/// Name suffixes commonly used for textures holding data rather than colors: normal maps,
+/// parameter maps (metalness, glossiness and ambient occlusion), masks, roughness and height
+const LINEAR_SUFFIXES: &[&str] = &[
+    "_n", "_nor", "_nrm", "_normal", "_prm", "_mask", "_msk", "_rgh", "_rough", "_ao", "_hgt", "_height",
+];
+
+impl ColorSpace {
+    /// Guesses whether a texture holds data from its name, then from its pixels. Names ending
+    /// in a common suffix for data textures, such as `_nor` or `_prm`, ignoring case and any
+    /// trailing digits, are treated as data. Otherwise images that look like tangent space
+    /// normal maps, mostly blue with red and green centered around half, are too.
+    pub fn guess(name: &str, img: &image::DynamicImage) -> ColorSpace {
+        let name = name.to_ascii_lowercase();
+        let name = name.trim_end_matches(|c: char| c.is_ascii_digit());
+        if LINEAR_SUFFIXES.iter().any(|suffix| name.ends_with(suffix)) {
+            return ColorSpace::Linear;
+        }
+
+        if img.color().channel_count() >= 3 && looks_like_normal_map(&img.to_rgb8()) {
+            ColorSpace::Linear
+        } else {
+            ColorSpace::Srgb
+        }
+    }
+}
+
+/// Tangent space normals point away from the surface, so blue is never below half, and on
+/// average they point straight out, giving a flat lavender
+fn looks_like_normal_map(img: &image::RgbImage) -> bool {
+    let count = img.width() as u64 * img.height() as u64;
+    if count == 0 || img.pixels().any(|pixel| pixel[2] < 128) {
+        return false;
+    }
+
+    let mut sums = [0u64; 3];
+    for pixel in img.pixels() {
+        for (sum, &value) in sums.iter_mut().zip(&pixel.0) {
+            *sum += value as u64;
+        }
+    }
+
+    let [red, green, blue] = sums.map(|sum| sum / count);
+
+    red.abs_diff(128) <= 24 && green.abs_diff(128) <= 24 && blue >= 192
+}
+
+/// How to handle images whose dimensions aren't a multiple of a required size, such as the 4x4
+/// blocks of compressed formats or the sizes a game expects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeAlignment {
+    /// Use the image at its original size
+    None,
+    /// Fail with [`Error::UnalignedSize`] if either dimension isn't a multiple of the size
+    Reject(u32),
+    /// Extend the image to the next multiple with transparent pixels on the right and bottom
+    Pad(u32),
+    /// Scale the image up to the next multiple
+    Resize(u32),
+}
+
+impl SizeAlignment {
+    fn apply(self, img: image::RgbaImage) -> Result<image::RgbaImage, Error> {
+        let (width, height) = img.dimensions();
+        let multiple = match self {
+            SizeAlignment::None => return Ok(img),
+            SizeAlignment::Reject(multiple)
+                | SizeAlignment::Pad(multiple)
+                | SizeAlignment::Resize(multiple) => multiple.max(1),
+        };
+
+        if width % multiple == 0 && height % multiple == 0 {
+            return Ok(img);
+        }
+
+        let aligned_width = width.div_ceil(multiple) * multiple;
+        let aligned_height = height.div_ceil(multiple) * multiple;
+
+        Ok(match self {
+            SizeAlignment::Pad(_) => {
+                let mut padded = image::RgbaImage::new(aligned_width, aligned_height);
+                image::imageops::replace(&mut padded, &img, 0, 0);
+                padded
+            }
+            SizeAlignment::Resize(_) => image::imageops::resize(
+                &img,
+                aligned_width,
+                aligned_height,
+                image::imageops::FilterType::Triangle
+            ),
+            _ => return Err(Error::UnalignedSize { width, height, multiple }),
+        })
+    }
+}
+
+impl AlphaConversion {
+    fn apply(self, pixel: &mut [u8; 4]) {
+        let alpha = pixel[3] as u32;
+        for channel in &mut pixel[..3] {
+            let value = *channel as u32;
+            *channel = match self {
+                AlphaConversion::None => continue,
+                AlphaConversion::Premultiply => (value * alpha + 127) / 255,
+                AlphaConversion::Unpremultiply if alpha == 0 => 0,
+                AlphaConversion::Unpremultiply => ((value * 255 + alpha / 2) / alpha).min(255),
+            } as u8;
+        }
+    }
+}
+
+impl TextureBuilder {
+    pub fn new(name: &str) -> Self {
+        TextureBuilder {
+            name: name.to_owned(),
+            swizzle: 0,
+            alpha: AlphaConversion::None,
+            gamma: GammaConversion::None,
+            size_alignment: SizeAlignment::None,
+            alignment: crate::TEXTURE_ALIGN,
+            grayscale_as_r8: false,
+            one_dimensional: false,
+            color_space: ColorSpace::Srgb,
+            normal_map: NormalMap::None,
+            transform: ImageTransform::default(),
+        }
+    }
+
+    /// Sets the swizzle value stored in the texture and used when laying out its data. Most
+    /// textures use 0, which is the default.
+    pub fn swizzle(mut self, swizzle: u16) -> Self {
+        self.swizzle = swizzle;
+        self
+    }
+
+    /// Premultiplies or unpremultiplies the image's alpha before encoding it. Mismatched alpha
+    /// is a common cause of dark or bright halos around the edges of transparent UI elements.
+    pub fn alpha_conversion(mut self, alpha: AlphaConversion) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    /// Converts the image's colors between sRGB and linear before encoding it. Colors are
+    /// stored sRGB encoded unless [`color_space`](TextureBuilder::color_space) says otherwise,
+    /// so this is only needed when the source image isn't encoded the way it's stored.
+    pub fn gamma_conversion(mut self, gamma: GammaConversion) -> Self {
+        self.gamma = gamma;
+        self
+    }
+
+    /// Pads, resizes or rejects images whose dimensions aren't a multiple of a given size.
+    /// Images are used at their original size by default.
+    pub fn size_alignment(mut self, size_alignment: SizeAlignment) -> Self {
+        self.size_alignment = size_alignment;
+        self
+    }
+
+    /// Sets the alignment of the texture's data, in bytes. Each array layer is padded to a
+    /// multiple of it, and the data is placed at a multiple of it when the file is written.
+    /// Defaults to 512, but some games require 0x1000. Building fails if it isn't a power of two up to 0x10000.
+    pub fn alignment(mut self, alignment: u32) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    /// Stores single channel images, such as masks and heightmaps, as `R8_UNORM` with a channel
+    /// mapping that reads red as gray and alpha as one, instead of expanding them to RGBA8. This
+    /// makes the texture a quarter of the size. Images with any other channels are unaffected.
+    pub fn grayscale_as_r8(mut self, grayscale_as_r8: bool) -> Self {
+        self.grayscale_as_r8 = grayscale_as_r8;
+        self
+    }
+
+    /// Creates 1D textures, for lookup tables such as gradient ramps, instead of 2D ones. Images
+    /// must be a single pixel tall, and are decoded back as a row of pixels.
+    pub fn one_dimensional(mut self, one_dimensional: bool) -> Self {
+        self.one_dimensional = one_dimensional;
+        self
+    }
+
+    /// Whether the image holds colors, stored as `R8G8B8A8_SRGB`, or data such as normal maps
+    /// and masks, stored as `R8G8B8A8_UNORM`. Defaults to [`ColorSpace::Srgb`]. Use
+    /// [`ColorSpace::guess`] to pick based on the texture's name and pixels.
+    pub fn color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
+    /// Treats the image as a normal map. Every mip level is renormalized, since averaging
+    /// normals while scaling them down shortens them, which makes the surface look flatter and
+    /// darker from a distance. The color space is ignored, as normals are always stored as
+    /// UNORM. Defaults to [`NormalMap::None`].
+    pub fn normal_map(mut self, normal_map: NormalMap) -> Self {
+        self.normal_map = normal_map;
+        self
+    }
+
+    /// Rotates, flips or swaps the channels of each image before anything else is done to it,
+    /// so the size alignment applies to the transformed image
+    pub fn transform(mut self, transform: ImageTransform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn build(self, img: image::DynamicImage) -> Result<Texture, Error> {
+        self.build_array(vec![img])
+    }
+
+    /// Creates a texture array with one layer per image. Every image must be the same size.
+    /// For flipbook animations, pass the frames in order to get one layer per frame.
+    pub fn build_array(self, images: Vec<image::DynamicImage>) -> Result<Texture, Error> {
+        let grayscale = self.is_grayscale(&images);
+        let layers = images.into_iter()
+            .map(|img| self.prepare(img))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (width, height) = layers.first().ok_or(Error::EmptyArray)?.dimensions();
+        if let Some(layer) = layers.iter().find(|layer| layer.dimensions() != (width, height)) {
+            return Err(Error::MismatchedSize {
+                expected: (width, height),
+                found: layer.dimensions(),
+            });
+        }
+
+        self.build_layers(layers.into_iter().map(|layer| vec![layer]).collect(), grayscale)
+    }
+
+    /// Creates a texture array with one layer per frame of a sprite sheet, for flipbook
+    /// animations. Frames are read left to right, then top to bottom, and any partial frames at
+    /// the right and bottom edges of the sheet are skipped.
+    pub fn build_from_sprite_sheet(
+        self,
+        sheet: image::DynamicImage,
+        frame_width: u32,
+        frame_height: u32,
+    ) -> Result<Texture, Error> {
+        let columns = sheet.width().checked_div(frame_width).unwrap_or(0);
+        let rows = sheet.height().checked_div(frame_height).unwrap_or(0);
+
+        let frames = (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (column, row)))
+            .map(|(column, row)| {
+                sheet.crop_imm(column * frame_width, row * frame_height, frame_width, frame_height)
+            })
+            .collect();
+
+        self.build_array(frames)
+    }
+
+    /// Creates a texture from a base image and its mip levels, largest first. Each mip must be
+    /// half the size of the one before it (rounded down, but at least 1), and the size
+    /// alignment option only applies to the base image.
+    pub fn build_with_mips(
+        self,
+        base: image::DynamicImage,
+        mips: Vec<image::DynamicImage>,
+    ) -> Result<Texture, Error> {
+        let grayscale = self.is_grayscale(std::slice::from_ref(&base)) && self.is_grayscale(&mips);
+        let base = self.prepare(base)?;
+        let (width, height) = base.dimensions();
+
+        let max = 32 - width.max(height).max(1).leading_zeros();
+        let count = mips.len() as u32 + 1;
+        if count > max {
+            return Err(Error::TooManyMips { count, max });
+        }
+
+        let mut chain = vec![base];
+        for (level, mip) in (1..).zip(mips) {
+            let mut mip = self.transform.apply(mip.to_rgba8());
+            let expected = ((width >> level).max(1), (height >> level).max(1));
+            if mip.dimensions() != expected {
+                return Err(Error::MismatchedSize { expected, found: mip.dimensions() });
+            }
+
+            self.convert_colors(&mut mip);
+            chain.push(mip);
+        }
+
+        self.build_layers(vec![chain], grayscale)
+    }
+
+    /// Creates a texture with a full chain of mip levels, down to 1x1, scaled down from the
+    /// image after the size alignment option is applied
+    pub fn build_with_generated_mips(self, img: image::DynamicImage) -> Result<Texture, Error> {
+        let grayscale = self.is_grayscale(std::slice::from_ref(&img));
+        let base = self.prepare(img)?;
+        let (width, height) = base.dimensions();
+
+        let count = 32 - width.max(height).max(1).leading_zeros();
+        let mips: Vec<_> = (1..count)
+            .map(|level| {
+                let (mip_width, mip_height) = ((width >> level).max(1), (height >> level).max(1));
+                let filter = image::imageops::FilterType::Triangle;
+                let mut mip = image::imageops::resize(&base, mip_width, mip_height, filter);
+                if self.normal_map != NormalMap::None {
+                    renormalize(&mut mip);
+                }
+
+                mip
+            })
+            .collect();
+
+        let mut chain = vec![base];
+        chain.extend(mips);
+
+        self.build_layers(vec![chain], grayscale)
+    }
+
+    /// Creates a texture from separate grayscale images for its red, green, blue and alpha
+    /// channels, the inverse of [`Texture::split_channels`]. Missing color channels are filled
+    /// with 0 and a missing alpha channel with 255. Every image given must be the same size.
+    pub fn build_from_channels(self, channels: [Option<image::DynamicImage>; 4]) -> Result<Texture, Error> {
+        let channels = channels.map(|channel| channel.map(|img| img.to_luma8()));
+
+        let mut given = channels.iter().flatten();
+        let (width, height) = given.next().ok_or(Error::EmptyArray)?.dimensions();
+        if let Some(channel) = given.find(|channel| channel.dimensions() != (width, height)) {
+            return Err(Error::MismatchedSize {
+                expected: (width, height),
+                found: channel.dimensions(),
+            });
+        }
+
+        let img = image::RgbaImage::from_fn(width, height, |x, y| {
+            let mut pixel = [0, 0, 0, 255];
+            for (value, channel) in pixel.iter_mut().zip(&channels) {
+                if let Some(channel) = channel {
+                    *value = channel.get_pixel(x, y)[0];
+                }
+            }
+
+            image::Rgba(pixel)
+        });
+
+        self.build(image::DynamicImage::ImageRgba8(img))
+    }
+
+    /// Whether the images should be stored as `R8_UNORM`
+    fn is_grayscale(&self, images: &[image::DynamicImage]) -> bool {
+        self.grayscale_as_r8
+            && self.normal_map == NormalMap::None
+            && images.iter().all(|img| img.color().channel_count() == 1)
+    }
+
+    /// Swizzles array layers, each made up of same-sized mip chains, into a texture, converting
+    /// it to `R8_UNORM` if the images were `grayscale`
+    fn build_layers(self, layers: Vec<Vec<image::RgbaImage>>, grayscale: bool) -> Result<Texture, Error> {
+        let (width, height) = layers[0][0].dimensions();
+        trace_span!("build", name = %self.name, width, height, layers = layers.len());
+
+        if width == 0 || height == 0 || width > MAX_TEXTURE_SIZE || height > MAX_TEXTURE_SIZE {
+            return Err(Error::InvalidDimensions { width, height });
+        }
+        if self.one_dimensional && height != 1 {
+            return Err(Error::InvalidHeight { height });
+        }
+        if !crate::valid_alignment(self.alignment) {
+            return Err(Error::InvalidAlignment { alignment: self.alignment });
+        }
+
+        let dim = if self.one_dimensional { 1 } else { 2 };
+        let array_len = layers.len() as u32;
+        let mips_count = layers[0].len() as u16;
+        let params = SurfaceParams {
+            swizzle: self.swizzle as _,
+            ..SurfaceParams::new(width, height, 4)
+        };
+        let block_height = params.block_height;
+        let layer_alignment = crate::layer_alignment(self.alignment, array_len, block_height);
+        let tables: Vec<_> = (0..mips_count as u32).map(|mip| AddressTable::new(&params.mip(mip))).collect();
+
+        let mut data = Vec::new();
+        let mut mip_offsets = Vec::new();
+        for (i, layer) in layers.into_iter().enumerate() {
+            let layer_start = data.len();
+
+            for (img, table) in layer.into_iter().zip(&tables) {
+                if i == 0 {
+                    mip_offsets.push((data.len() - layer_start) as u64);
+                }
+
+                data.extend(table.swizzle(&img.into_raw()));
+            }
+
+            data.resize(data.len().next_multiple_of(layer_alignment), 0);
+        }
+
+        // the data's size and offsets are stored as 32-bit values
+        if data.len() as u64 > u32::MAX as u64 {
+            return Err(Error::TooLarge { size: data.len() as u64 });
+        }
+
+        let mut texture = Texture {
+            flags: TextureFlags::SPECIFY_TEXTURE_LAYOUT.bits(),
+            dim,
+            tile_mode: 0,
+            swizzle: self.swizzle,
+            mips_count,
+            num_multi_sample: 1,
+            format: match (self.normal_map, self.color_space) {
+                (NormalMap::None, ColorSpace::Srgb) => SurfaceFormat::R8G8B8A8_SRGB,
+                _ => SurfaceFormat::R8G8B8A8_UNORM,
+            },
+            unk2: GpuAccess::TEXTURE.bits(),
+            width,
+            height,
+            depth: 1,
+            array_len,
+            size_range: block_height.log2(),
+            unk4: [
+                65543,
+                0,
+                0,
+                0,
+                0,
+                0,
+            ],
+            image_size: data.len() as _,
+            align: self.alignment,
+            comp_sel: crate::preset::IDENTITY_COMP_SEL,
+            kind: TextureKind::for_texture(dim, array_len),
+            name: BntxStr::try_new(&self.name)?,
+            mip_offsets,
+            texture: ImageData(data),
+            source: None,
+            pitch_alignment: crate::LINEAR_PITCH_ALIGN,
+        };
+
+        if grayscale {
+            texture.convert_format(SurfaceFormat::R8_UNORM)?;
+            texture.comp_sel = crate::preset::GRAYSCALE_COMP_SEL;
+        }
+        if self.normal_map == NormalMap::TwoChannel {
+            texture.convert_format(SurfaceFormat::BC5_UNORM)?;
+            texture.comp_sel = crate::preset::NORMAL_MAP_COMP_SEL;
+        }
+
+        Ok(texture)
+    }
+
+    /// Packs same-sized images into a grid in a single texture, returning the region each image
+    /// ended up in, in the same order as `images`
+    pub fn build_atlas(self, images: Vec<image::DynamicImage>) -> Result<(Texture, Vec<AtlasRegion>), Error> {
+        let images: Vec<_> = images.into_iter().map(|img| img.to_rgba8()).collect();
+
+        let (width, height) = images.first().ok_or(Error::EmptyArray)?.dimensions();
+        if let Some(img) = images.iter().find(|img| img.dimensions() != (width, height)) {
+            return Err(Error::MismatchedSize {
+                expected: (width, height),
+                found: img.dimensions(),
+            });
+        }
+
+        let columns = (images.len() as f64).sqrt().ceil() as u32;
+        let rows = (images.len() as u32).div_ceil(columns);
+        let mut atlas = image::RgbaImage::new(columns * width, rows * height);
+
+        let mut regions = Vec::with_capacity(images.len());
+        for (i, img) in images.iter().enumerate() {
+            let x = (i as u32 % columns) * width;
+            let y = (i as u32 / columns) * height;
+            image::imageops::replace(&mut atlas, img, x as i64, y as i64);
+            regions.push((x, y));
+        }
+
+        let (atlas_width, atlas_height) = atlas.dimensions();
+        let resized = matches!(self.size_alignment, SizeAlignment::Resize(_));
+        let texture = self.build(image::DynamicImage::ImageRgba8(atlas))?;
+
+        // padding adds space after the grid, while resizing stretches the whole grid
+        let (uv_width, uv_height) = if resized {
+            (atlas_width as f32, atlas_height as f32)
+        } else {
+            (texture.width() as f32, texture.height() as f32)
+        };
+
+        let regions = regions.into_iter()
+            .map(|(x, y)| AtlasRegion {
+                x,
+                y,
+                width,
+                height,
+                uv_min: [x as f32 / uv_width, y as f32 / uv_height],
+                uv_max: [(x + width) as f32 / uv_width, (y + height) as f32 / uv_height],
+            })
+            .collect();
+
+        Ok((texture, regions))
+    }
+
+    /// Applies the transform, size, gamma and alpha options to an image
+    fn prepare(&self, img: image::DynamicImage) -> Result<image::RgbaImage, Error> {
+        let mut img = self.size_alignment.apply(self.transform.apply(img.to_rgba8()))?;
+        self.convert_colors(&mut img);
+
+        Ok(img)
+    }
+
+    /// Applies the gamma and alpha options to an image
+    fn convert_colors(&self, img: &mut image::RgbaImage) {
+        // gamma first, so premultiplying happens on the final values
+        self.gamma.apply(img);
+        for pixel in img.pixels_mut() {
+            self.alpha.apply(&mut pixel.0);
+        }
+
+        if self.normal_map != NormalMap::None {
+            renormalize(img);
+        }
+    }
+}
+
+/// Scales each pixel's normal back to unit length. Pixels with no direction at all, such as
+/// fully transparent black, are left as they are.
+fn renormalize(img: &mut image::RgbaImage) {
+    for pixel in img.pixels_mut() {
+        let [x, y, z] = [pixel[0], pixel[1], pixel[2]].map(|value| value as f32 / 255.0 * 2.0 - 1.0);
+        let length = (x * x + y * y + z * z).sqrt();
+        if length < 1e-3 {
+            continue;
+        }
+
+        for (channel, value) in pixel.0.iter_mut().zip([x, y, z]) {
+            *channel = (((value / length + 1.0) / 2.0 * 255.0).round()).clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+/// Where an image was placed in an atlas built by [`TextureBuilder::build_atlas`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasRegion {
+    /// Position and size in pixels within the packed grid, before any size alignment
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Texture coordinates of the top left and bottom right corners
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+}
@@ -0,0 +1,80 @@
+use crate::{Error, Texture};
+
+/// How much a channel differs between two textures, as found by [`compare`]. Differences are
+/// on a scale of 0 to 1, where one step of an 8-bit channel is 1/255.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ChannelStats {
+    /// The largest difference of any pixel
+    pub max_diff: f32,
+    /// The average difference over every pixel
+    pub mean_diff: f64,
+    /// The average squared difference over every pixel
+    pub mse: f64,
+    /// Peak signal-to-noise ratio in decibels, infinite if the channel is identical
+    pub psnr: f64,
+}
+
+/// How much two textures differ, as found by [`compare`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompareReport {
+    /// The largest difference of any channel of any pixel
+    pub max_diff: f32,
+    /// Peak signal-to-noise ratio in decibels over every channel, infinite if the textures are
+    /// identical. Around 40 dB and up is usually hard to tell apart by eye.
+    pub psnr: f64,
+    /// Red, green, blue and alpha, in that order
+    pub channels: [ChannelStats; 4],
+}
+
+impl ChannelStats {
+    fn from_sums(max_diff: f32, sum: f64, sum_squared: f64, count: usize) -> Self {
+        let count = count.max(1) as f64;
+        let mse = sum_squared / count;
+
+        ChannelStats { max_diff, mean_diff: sum / count, mse, psnr: psnr(mse) }
+    }
+}
+
+fn psnr(mse: f64) -> f64 {
+    if mse == 0.0 {
+        f64::INFINITY
+    } else {
+        -10.0 * mse.log10()
+    }
+}
+
+/// Compares the decoded pixels of the first mip level of two textures, such as to measure how
+/// much quality was lost by converting a texture to a compressed format. The textures can be in
+/// different formats, but must be the same size.
+pub fn compare(a: &Texture, b: &Texture) -> Result<CompareReport, Error> {
+    trace_span!("compare", a = a.name(), b = b.name());
+
+    if (a.width, a.height) != (b.width, b.height) {
+        return Err(Error::MismatchedSize { expected: (a.width, a.height), found: (b.width, b.height) });
+    }
+
+    let (a, b) = (a.to_float_image()?, b.to_float_image()?);
+
+    let mut max_diffs = [0.0f32; 4];
+    let mut sums = [0.0f64; 4];
+    let mut sums_squared = [0.0f64; 4];
+    for (a, b) in a.pixels().zip(b.pixels()) {
+        for channel in 0..4 {
+            let diff = (a[channel] - b[channel]).abs();
+            max_diffs[channel] = max_diffs[channel].max(diff);
+            sums[channel] += diff as f64;
+            sums_squared[channel] += (diff as f64).powi(2);
+        }
+    }
+
+    let count = a.pixels().len();
+    let channels = std::array::from_fn(|i| {
+        ChannelStats::from_sums(max_diffs[i], sums[i], sums_squared[i], count)
+    });
+
+    Ok(CompareReport {
+        max_diff: max_diffs.iter().copied().fold(0.0, f32::max),
+        psnr: psnr(sums_squared.iter().sum::<f64>() / (count.max(1) * 4) as f64),
+        channels,
+    })
+}
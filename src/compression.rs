@@ -0,0 +1,107 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+#[cfg(feature = "zstd")]
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// The compression level used when saving `.zs` files, zstd's default
+#[cfg(all(feature = "zstd", feature = "write"))]
+const ZSTD_LEVEL: i32 = 0;
+
+#[cfg(feature = "yaz0")]
+const YAZ0_MAGIC: [u8; 4] = *b"Yaz0";
+
+/// Decompresses the rest of `reader` if it starts with the magic of a supported compression
+/// format. Otherwise returns `None`, leaving the reader where it was.
+pub(crate) fn decompress<R: Read + Seek>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let start = reader.stream_position()?;
+
+    let mut magic = [0; 4];
+    let found = reader.read_exact(&mut magic).is_ok();
+    reader.seek(SeekFrom::Start(start))?;
+
+    if !found {
+        return Ok(None);
+    }
+
+    #[cfg(feature = "zstd")]
+    {
+        if magic == ZSTD_MAGIC {
+            return zstd::decode_all(reader).map(Some);
+        }
+    }
+
+    #[cfg(feature = "yaz0")]
+    {
+        if magic == YAZ0_MAGIC {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data)?;
+
+            return decompress_yaz0(&data).map(Some);
+        }
+    }
+
+    Ok(None)
+}
+
+/// Whether a file saved to `path` gets zstd compressed, going by its `.zs` extension
+#[cfg(all(feature = "zstd", feature = "write"))]
+pub(crate) fn saves_as_zstd(path: &std::path::Path) -> bool {
+    path.extension() == Some("zs".as_ref())
+}
+
+#[cfg(all(feature = "zstd", feature = "write"))]
+pub(crate) fn compress_zstd(data: &[u8]) -> io::Result<Vec<u8>> {
+    zstd::encode_all(data, ZSTD_LEVEL)
+}
+
+/// Decompresses Yaz0 data, including its 16 byte header
+#[cfg(feature = "yaz0")]
+pub(crate) fn decompress_yaz0(data: &[u8]) -> io::Result<Vec<u8>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_owned());
+
+    if data.len() < 16 || data[..4] != YAZ0_MAGIC {
+        return Err(invalid("not Yaz0 compressed"));
+    }
+
+    let size = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize;
+    let mut input = data[16..].iter().copied();
+    let mut next = || input.next().ok_or_else(|| invalid("Yaz0 data ends early"));
+
+    // the size is read from the header, so don't reserve more up front than the input could
+    // plausibly expand to and let the vector grow past that if it really does
+    let mut out = Vec::with_capacity(size.min(data.len() * 8));
+    while out.len() < size {
+        // each bit of the group header, from the most significant, says whether the next chunk
+        // is a literal byte or a back reference
+        let header = next()?;
+        for bit in (0..8).rev() {
+            if out.len() >= size {
+                break;
+            }
+
+            if header & (1 << bit) != 0 {
+                out.push(next()?);
+                continue;
+            }
+
+            let (b1, b2) = (next()? as usize, next()? as usize);
+            let distance = ((b1 & 0xf) << 8 | b2) + 1;
+            let len = match b1 >> 4 {
+                0 => next()? as usize + 0x12,
+                n => n + 2,
+            };
+
+            if distance > out.len() {
+                return Err(invalid("Yaz0 back reference before the start of the data"));
+            }
+
+            // copied a byte at a time, since the reference can overlap what it's producing
+            let start = out.len() - distance;
+            for i in 0..len.min(size - out.len()) {
+                out.push(out[start + i]);
+            }
+        }
+    }
+
+    Ok(out)
+}
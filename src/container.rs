@@ -0,0 +1,138 @@
+use std::borrow::Cow;
+
+use crate::tegra_swizzle::{self, SurfaceParams};
+use crate::{BntxFile, Error, Surface, SurfaceFormat, Texture};
+
+/// A swizzled texture as the Switch stores it, described without anything specific to the file
+/// it came from. Other Switch texture containers (nutexb, XTX, BFRES tooling) can implement this
+/// to share code with this crate, rather than converting to and from each other.
+///
+/// Only the dimensions, swizzle parameters and each mip level's swizzled data need to be
+/// provided; deswizzling is implemented on top of those.
+pub trait SwizzledSurface {
+    fn name(&self) -> &str;
+
+    fn width(&self) -> u32;
+
+    fn height(&self) -> u32;
+
+    fn depth(&self) -> u32 {
+        1
+    }
+
+    fn format(&self) -> SurfaceFormat;
+
+    /// Number of mip levels, at least 1
+    fn mip_count(&self) -> u32;
+
+    /// Number of array layers, at least 1
+    fn layer_count(&self) -> u32;
+
+    /// The swizzle parameters of the first mip level. Use [`SurfaceParams::mip`] for the
+    /// smaller mip levels.
+    fn swizzle_params(&self) -> Result<SurfaceParams, Error>;
+
+    /// The swizzled data of a single mip level of a single array layer, or `None` if the
+    /// texture doesn't have it
+    fn swizzled_mip(&self, layer: u32, mip: u32) -> Option<Cow<'_, [u8]>>;
+
+    /// Deswizzles a single mip level of a single array layer into rows of blocks
+    fn deswizzled_mip(&self, layer: u32, mip: u32) -> Result<Vec<u8>, Error> {
+        let params = self.swizzle_params()?.mip(mip);
+        let data = self.swizzled_mip(layer, mip)
+            .ok_or(Error::MissingMip { level: mip, count: self.mip_count() })?;
+
+        Ok(tegra_swizzle::deswizzle(&params, &data))
+    }
+
+    /// Deswizzles every mip level of every array layer
+    fn to_surface(&self) -> Result<Surface, Error> {
+        let mut data = Vec::new();
+        for layer in 0..self.layer_count() {
+            for mip in 0..self.mip_count() {
+                data.extend(self.deswizzled_mip(layer, mip)?);
+            }
+        }
+
+        Surface::new(self.format(), self.width(), self.height(), self.mip_count(), self.layer_count(), data)
+    }
+}
+
+/// A file holding any number of [`SwizzledSurface`]s, such as a BNTX file
+pub trait TextureContainer {
+    type Surface: SwizzledSurface;
+
+    fn surface_count(&self) -> usize;
+
+    fn surface(&self, index: usize) -> Option<&Self::Surface>;
+
+    fn surface_by_name(&self, name: &str) -> Option<&Self::Surface> {
+        (0..self.surface_count())
+            .filter_map(|i| self.surface(i))
+            .find(|surface| surface.name() == name)
+    }
+}
+
+impl SwizzledSurface for Texture {
+    fn name(&self) -> &str {
+        Texture::name(self)
+    }
+
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn depth(&self) -> u32 {
+        self.depth.max(1)
+    }
+
+    fn format(&self) -> SurfaceFormat {
+        self.format
+    }
+
+    fn mip_count(&self) -> u32 {
+        self.mip_levels()
+    }
+
+    fn layer_count(&self) -> u32 {
+        self.array_len.max(1)
+    }
+
+    fn swizzle_params(&self) -> Result<SurfaceParams, Error> {
+        self.layout()
+    }
+
+    fn swizzled_mip(&self, layer: u32, mip: u32) -> Option<Cow<'_, [u8]>> {
+        if layer >= self.layer_count() || mip >= self.mip_levels() {
+            return None;
+        }
+
+        let params = self.layout().ok()?.mip(mip);
+
+        Some(self.swizzled_surface(layer, mip, &params))
+    }
+
+    fn to_surface(&self) -> Result<Surface, Error> {
+        Texture::to_surface(self)
+    }
+}
+
+impl TextureContainer for BntxFile {
+    type Surface = Texture;
+
+    fn surface_count(&self) -> usize {
+        self.nx_header.textures.len()
+    }
+
+    fn surface(&self, index: usize) -> Option<&Texture> {
+        self.nx_header.textures.get(index)
+    }
+
+    fn surface_by_name(&self, name: &str) -> Option<&Texture> {
+        self.texture(name)
+    }
+}
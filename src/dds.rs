@@ -0,0 +1,149 @@
+use std::convert::TryInto;
+use std::io::{Read, Write};
+
+use crate::{tegra_swizzle, Error, Surface, SurfaceFormat, Texture};
+
+const DDS_MAGIC: [u8; 4] = *b"DDS ";
+const HEADER_SIZE: u32 = 124;
+const PIXEL_FORMAT_SIZE: u32 = 32;
+
+const DDSD_CAPS: u32 = 0x1;
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_PITCH: u32 = 0x8;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSD_LINEARSIZE: u32 = 0x80000;
+
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+
+const DDSCAPS_COMPLEX: u32 = 0x8;
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+const DDSCAPS_MIPMAP: u32 = 0x400000;
+
+const D3D10_RESOURCE_DIMENSION_TEXTURE2D: u32 = 3;
+
+/// Formats of DDS files written before the DX10 header extension, by their FourCC
+const FOURCC_FORMATS: &[(&[u8; 4], SurfaceFormat)] = &[
+    (b"DXT1", SurfaceFormat::BC1_UNORM),
+    (b"DXT3", SurfaceFormat::BC2_UNORM),
+    (b"DXT5", SurfaceFormat::BC3_UNORM),
+    (b"ATI1", SurfaceFormat::BC4_UNORM),
+    (b"BC4U", SurfaceFormat::BC4_UNORM),
+    (b"BC4S", SurfaceFormat::BC4_SNORM),
+    (b"ATI2", SurfaceFormat::BC5_UNORM),
+    (b"BC5U", SurfaceFormat::BC5_UNORM),
+    (b"BC5S", SurfaceFormat::BC5_SNORM),
+];
+
+fn read_u32s<R: Read, const N: usize>(reader: &mut R) -> Result<[u32; N], Error> {
+    let mut values = [0; N];
+    for value in &mut values {
+        let mut bytes = [0; 4];
+        reader.read_exact(&mut bytes)?;
+        *value = u32::from_le_bytes(bytes);
+    }
+
+    Ok(values)
+}
+
+impl Texture {
+    /// Writes every mip level and array layer of the texture as a DDS file with a DX10 header,
+    /// keeping the data in its original format
+    pub fn to_dds<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        let dxgi_format = self.format.to_dxgi_format()
+            .ok_or(Error::UnsupportedFormat(self.format))?;
+        let params = self.surface_params(self.format, self.size_range)?;
+
+        let base_size = tegra_swizzle::deswizzled_mip_size(&params) as u32;
+        let (size_flag, pitch_or_linear_size) = if self.format.is_compressed() {
+            (DDSD_LINEARSIZE, base_size)
+        } else {
+            (DDSD_PITCH, base_size / self.height.max(1))
+        };
+
+        let mips = self.mip_levels();
+        let caps = if mips > 1 {
+            DDSCAPS_TEXTURE | DDSCAPS_COMPLEX | DDSCAPS_MIPMAP
+        } else {
+            DDSCAPS_TEXTURE
+        };
+
+        let mut header = vec![
+            HEADER_SIZE,
+            DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT | DDSD_MIPMAPCOUNT | size_flag,
+            self.height,
+            self.width,
+            pitch_or_linear_size,
+            0,
+            mips,
+        ];
+        header.extend([0; 11]);
+        header.extend([PIXEL_FORMAT_SIZE, DDPF_FOURCC, u32::from_le_bytes(*b"DX10"), 0, 0, 0, 0, 0]);
+        header.extend([caps, 0, 0, 0, 0]);
+        header.extend([dxgi_format, D3D10_RESOURCE_DIMENSION_TEXTURE2D, 0, self.array_len.max(1), 0]);
+
+        writer.write_all(&DDS_MAGIC)?;
+        for value in header {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+
+        writer.write_all(self.to_surface()?.data())?;
+
+        Ok(())
+    }
+
+    /// Reads a 2D texture or texture array from a DDS file, keeping its format and mip levels.
+    /// Both DX10 headers and the FourCCs of older block compressed files are supported.
+    pub fn from_dds<R: Read>(reader: &mut R, name: &str) -> Result<Texture, Error> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != DDS_MAGIC {
+            return Err(Error::InvalidDds("missing DDS magic"));
+        }
+
+        let header: [u32; 31] = read_u32s(reader)?;
+        let [size, _, height, width, _, depth, mips] = header[..7].try_into().unwrap();
+        let [_, pixel_flags, four_cc, bit_count, r_mask, g_mask, b_mask, a_mask] =
+            header[18..26].try_into().unwrap();
+        if size != HEADER_SIZE {
+            return Err(Error::InvalidDds("invalid header size"));
+        }
+
+        let (format, array_len) = if pixel_flags & DDPF_FOURCC != 0 && four_cc.to_le_bytes() == *b"DX10" {
+            let [dxgi_format, dimension, _, array_len, _]: [u32; 5] = read_u32s(reader)?;
+            if dimension != D3D10_RESOURCE_DIMENSION_TEXTURE2D {
+                return Err(Error::InvalidDds("only 2D textures are supported"));
+            }
+
+            let format = SurfaceFormat::from_dxgi_format(dxgi_format)
+                .ok_or(Error::InvalidDds("unsupported DXGI format"))?;
+            (format, array_len.max(1))
+        } else if pixel_flags & DDPF_FOURCC != 0 {
+            let format = FOURCC_FORMATS.iter()
+                .find(|(fourcc, _)| **fourcc == four_cc.to_le_bytes())
+                .map(|&(_, format)| format)
+                .ok_or(Error::InvalidDds("unsupported FourCC"))?;
+            (format, 1)
+        } else if pixel_flags & DDPF_RGB != 0 && bit_count == 32 {
+            let format = match (r_mask, g_mask, b_mask, a_mask) {
+                (0xff, 0xff00, 0xff0000, 0xff000000) => SurfaceFormat::R8G8B8A8_UNORM,
+                (0xff0000, 0xff00, 0xff, 0xff000000) => SurfaceFormat::B8G8R8A8_UNORM,
+                _ => return Err(Error::InvalidDds("unsupported RGB masks")),
+            };
+            (format, 1)
+        } else {
+            return Err(Error::InvalidDds("unsupported pixel format"));
+        };
+
+        if depth > 1 {
+            return Err(Error::InvalidDepth { depth });
+        }
+
+        let mut surface = Surface::zeroed(format, width, height, mips, array_len)?;
+        reader.read_exact(surface.data_mut())?;
+
+        Texture::from_surface(name, &surface)
+    }
+}
@@ -0,0 +1,194 @@
+use std::convert::TryFrom;
+
+use binrw::prelude::*;
+
+use crate::{BntxStr, Error};
+#[cfg(feature = "write")]
+use crate::layout::Layout;
+
+/// The `_DIC` section, a radix tree mapping texture names to their index
+#[cfg_attr(feature = "write", binrw::binrw)]
+#[cfg_attr(not(feature = "write"), binrw::binread)]
+#[derive(Debug, Clone)]
+#[brw(magic = b"_DIC")]
+#[bw(import(layout: &Layout))]
+pub(crate) struct DictSection {
+    #[br(temp)]
+    #[bw(calc = (nodes.len() - 1) as u32)]
+    count: u32,
+
+    // the root node isn't counted
+    #[br(count = count + 1)]
+    #[bw(args(layout))]
+    nodes: Vec<DictNode>,
+}
+
+#[cfg_attr(feature = "write", binrw::binrw)]
+#[cfg_attr(not(feature = "write"), binrw::binread)]
+#[derive(Debug, Clone)]
+#[bw(import(layout: &Layout))]
+struct DictNode {
+    reference: u32,
+    left: u16,
+    right: u16,
+
+    #[br(parse_with = crate::read_ptr64)]
+    #[bw(map = |key: &BntxStr| layout.string_offset(&key.bytes) as u64)]
+    key: BntxStr,
+}
+
+#[cfg(feature = "write")]
+const DICT_HEADER_SIZE: usize = 8;
+#[cfg(feature = "write")]
+const DICT_NODE_SIZE: usize = 0x10;
+
+/// Bits are numbered starting from the least significant bit of the last character
+fn get_bit(name: &[u8], bit: u32) -> bool {
+    let byte = (bit / 8) as usize;
+
+    byte < name.len() && (name[name.len() - byte - 1] >> (bit % 8)) & 1 != 0
+}
+
+/// Index of the first bit that differs between two names, if any
+fn first_diff_bit(a: &[u8], b: &[u8]) -> Option<u32> {
+    (0..(a.len().max(b.len()) * 8) as u32)
+        .find(|&bit| get_bit(a, bit) != get_bit(b, bit))
+}
+
+impl DictSection {
+    /// Builds a dictionary with one entry per name, in order, failing if a name is empty or
+    /// appears more than once, since lookups could never find it
+    pub(crate) fn try_from_names<'a>(names: impl IntoIterator<Item = &'a [u8]>) -> Result<Self, Error> {
+        let mut dict = DictSection::empty();
+        for name in names {
+            dict.insert(name)?;
+        }
+
+        Ok(dict)
+    }
+
+    /// Like [`try_from_names`](DictSection::try_from_names), but leaving out empty and
+    /// duplicate names, for files read with them. Looking those up falls back to a linear
+    /// search.
+    pub(crate) fn from_names<'a>(names: impl IntoIterator<Item = &'a [u8]>) -> Self {
+        let mut dict = DictSection::empty();
+        for name in names {
+            let _ = dict.insert(name);
+        }
+
+        dict
+    }
+
+    fn empty() -> Self {
+        let root = DictNode {
+            reference: u32::MAX,
+            left: 0,
+            right: 0,
+            key: BntxStr::from(Vec::new()),
+        };
+
+        DictSection { nodes: vec![root] }
+    }
+
+    fn insert(&mut self, name: &[u8]) -> Result<(), Error> {
+        // the root's key is empty, so an empty name would be found as the root
+        if name.is_empty() {
+            return Err(Error::EmptyName);
+        }
+
+        let closest = &self.nodes[self.search(name)].key.bytes;
+        let bit = first_diff_bit(name, closest)
+            .ok_or_else(|| Error::DuplicateName(String::from_utf8_lossy(name).into_owned()))?;
+
+        // walk down again, stopping where the new node's bit fits in the tree
+        let mut prev = 0;
+        let mut index = self.nodes[0].left as usize;
+        let mut went_right = false;
+        while (self.nodes[prev].reference as i32) < (self.nodes[index].reference as i32)
+            && self.nodes[index].reference < bit
+        {
+            prev = index;
+            went_right = get_bit(name, self.nodes[index].reference);
+            index = if went_right {
+                self.nodes[index].right
+            } else {
+                self.nodes[index].left
+            } as usize;
+        }
+
+        let new_index = self.nodes.len() as u16;
+        let (left, right) = if get_bit(name, bit) {
+            (index as u16, new_index)
+        } else {
+            (new_index, index as u16)
+        };
+
+        self.nodes.push(DictNode {
+            reference: bit,
+            left,
+            right,
+            key: BntxStr::from(name.to_vec()),
+        });
+
+        if went_right {
+            self.nodes[prev].right = new_index;
+        } else {
+            self.nodes[prev].left = new_index;
+        }
+
+        Ok(())
+    }
+
+    /// Follows the tree as far as it goes for `name`, returning the index of the node reached
+    fn search(&self, name: &[u8]) -> usize {
+        let mut prev = 0;
+        let mut index = self.nodes[0].left as usize;
+
+        while (self.nodes[prev].reference as i32) < (self.nodes[index].reference as i32) {
+            prev = index;
+            index = if get_bit(name, self.nodes[index].reference) {
+                self.nodes[index].right
+            } else {
+                self.nodes[index].left
+            } as usize;
+        }
+
+        index
+    }
+
+    #[cfg(feature = "write")]
+    pub(crate) fn get_size(&self) -> usize {
+        DICT_HEADER_SIZE + (self.nodes.len() * DICT_NODE_SIZE)
+    }
+
+    /// Offsets of each node's key pointer, relative to the start of the section
+    #[cfg(feature = "write")]
+    pub(crate) fn key_pointer_offsets(&self) -> impl Iterator<Item = usize> {
+        (0..self.nodes.len()).map(|i| DICT_HEADER_SIZE + (i * DICT_NODE_SIZE) + 8)
+    }
+
+    /// Walks the tree looking for `name`, returning the index of the matching entry
+    pub(crate) fn find(&self, name: &str) -> Option<usize> {
+        let mut prev = self.nodes.first()?;
+        let mut index = prev.left as usize;
+        let mut node = self.nodes.get(index)?;
+
+        // the root's reference is -1, and references only increase going down the tree, so
+        // reaching a node with a lower reference means a back-edge was followed
+        while (prev.reference as i32) < (node.reference as i32) {
+            prev = node;
+            index = if get_bit(name.as_bytes(), node.reference) {
+                node.right
+            } else {
+                node.left
+            } as usize;
+            node = self.nodes.get(index)?;
+        }
+
+        if index != 0 && node.key.bytes == name.as_bytes() {
+            Some(index - 1)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use std::{error, fmt};
+
+use crate::{SurfaceFormat, TextureKind};
+
+/// An error encountered while converting a texture's data
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The data is in a format this crate can't decode or encode
+    UnsupportedFormat(SurfaceFormat),
+    /// The image's dimensions aren't a multiple of the size required by the builder
+    UnalignedSize { width: u32, height: u32, multiple: u32 },
+    /// No images were given for a texture array, atlas or set of channels
+    EmptyArray,
+    /// The images given for a texture array, atlas or set of channels aren't all the same size
+    MismatchedSize { expected: (u32, u32), found: (u32, u32) },
+    /// The image is empty or larger than the hardware's maximum texture size
+    InvalidDimensions { width: u32, height: u32 },
+    /// The depth is 0, or larger than 1 for a texture that isn't 3D
+    InvalidDepth { depth: u32 },
+    /// The height isn't 1 for a 1D texture
+    InvalidHeight { height: u32 },
+    /// The texture's kind doesn't agree with its number of dimensions, depth or array length,
+    /// such as a cube map without six layers
+    InconsistentKind { kind: TextureKind, dim: u8, depth: u32, array_len: u32 },
+    /// Raw texture data doesn't match the size the texture expects
+    MismatchedDataSize { expected: usize, found: usize },
+    /// No texture with the given name exists in the file
+    MissingTexture(String),
+    /// The file has no textures at all
+    NoTextures,
+    /// More mip levels were given than the image's size allows
+    TooManyMips { count: u32, max: u32 },
+    /// The texture has no mip level with the given index
+    MissingMip { level: u32, count: u32 },
+    /// A rectangle extends past the edges of the image or mip level it's in
+    InvalidRegion { x: u32, y: u32, width: u32, height: u32 },
+    /// A texture's data alignment is zero, not a power of two or larger than 0x10000
+    InvalidAlignment { alignment: u32 },
+    /// The texture's data is larger than a BNTX file can address
+    TooLarge { size: u64 },
+    /// The file being written is too large for one of its header's fields, such as its size
+    /// or the string section's 16-bit offset
+    FileTooLarge { field: &'static str, value: u64 },
+    /// The file isn't a DDS file, or uses a feature or format that isn't supported
+    InvalidDds(&'static str),
+    /// The metadata of a raw texture is missing a field or can't be parsed
+    InvalidMetadata(String),
+    /// A texture or file name is longer than the 65535 bytes a BNTX string can hold
+    NameTooLong { len: usize },
+    /// A texture's name is empty, so it couldn't be looked up in the file's dictionary
+    EmptyName,
+    /// Two textures in a file have the same name
+    DuplicateName(String),
+    /// A texture has header values known to hang or crash games, as found by
+    /// [`Texture::check_game_compat`](crate::Texture::check_game_compat)
+    GameCompat { texture: String, reason: String },
+    /// A name read from a file can't be used as an output file name, since it has a path
+    /// separator, `..`, a drive prefix or a NUL and could write outside the output directory
+    UnsafeName(String),
+    /// The decoded image couldn't be written
+    Image(image::ImageError),
+    /// A BNTX file couldn't be parsed
+    Read(binrw::Error),
+    /// A file couldn't be read or written
+    Io(std::io::Error),
+    /// The texture couldn't be encoded as WebP
+    #[cfg(feature = "webp")]
+    Webp(webp::WebPEncodingError),
+    /// The preview window couldn't be opened or drawn to
+    #[cfg(feature = "preview")]
+    Window(minifb::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnsupportedFormat(format) => write!(f, "unsupported surface format {}", format),
+            Error::UnalignedSize { width, height, multiple } => write!(
+                f, "image size {}x{} is not a multiple of {}", width, height, multiple
+            ),
+            Error::EmptyArray => write!(f, "no images were given"),
+            Error::MismatchedSize { expected, found } => write!(
+                f, "expected a {}x{} image, found {}x{}", expected.0, expected.1, found.0, found.1
+            ),
+            Error::InvalidDimensions { width, height } => write!(
+                f,
+                "image size {}x{} is outside the supported range of 1x1 to {max}x{max}",
+                width, height, max = crate::MAX_TEXTURE_SIZE
+            ),
+            Error::InvalidDepth { depth } => write!(
+                f, "depth {} is invalid, only 3D textures can have a depth other than 1", depth
+            ),
+            Error::InvalidHeight { height } => write!(
+                f, "height {} is invalid, 1D textures must have a height of 1", height
+            ),
+            Error::InconsistentKind { kind, dim, depth, array_len } => write!(
+                f,
+                "texture kind {:?} doesn't match {} dimensions, a depth of {} and {} array layers",
+                kind, dim, depth, array_len
+            ),
+            Error::MismatchedDataSize { expected, found } => write!(
+                f, "expected {} bytes of texture data, found {}", expected, found
+            ),
+            Error::MissingTexture(name) => write!(f, "no texture named {:?}", name),
+            Error::NoTextures => write!(f, "the file has no textures"),
+            Error::TooManyMips { count, max } => write!(
+                f, "{} mip levels were given but the image only has {}", count, max
+            ),
+            Error::MissingMip { level, count } => write!(
+                f, "mip level {} does not exist, the texture only has {}", level, count
+            ),
+            Error::InvalidRegion { x, y, width, height } => write!(
+                f, "region {}x{} at ({}, {}) is empty or extends past the image", width, height, x, y
+            ),
+            Error::InvalidAlignment { alignment } => write!(
+                f, "alignment {:#x} is not a power of two up to 0x10000", alignment
+            ),
+            Error::TooLarge { size } => write!(
+                f, "texture data is {} bytes, more than the {} a BNTX file can hold", size, u32::MAX
+            ),
+            Error::FileTooLarge { field, value } => write!(
+                f, "the file's {} of {:#x} doesn't fit in its header", field, value
+            ),
+            Error::InvalidDds(reason) => write!(f, "invalid DDS file: {}", reason),
+            Error::InvalidMetadata(reason) => write!(f, "invalid raw texture metadata: {}", reason),
+            Error::NameTooLong { len } => write!(
+                f, "name is {} bytes, more than the {} a BNTX string can hold", len, u16::MAX
+            ),
+            Error::EmptyName => write!(f, "texture names can't be empty"),
+            Error::DuplicateName(name) => write!(f, "more than one texture is named {:?}", name),
+            Error::GameCompat { texture, reason } => write!(
+                f, "texture {:?} could hang or crash games: {}", texture, reason
+            ),
+            Error::UnsafeName(name) => write!(f, "{:?} can't be used as a file name", name),
+            Error::Image(err) => write!(f, "failed to write image: {}", err),
+            Error::Read(err) => write!(f, "failed to read BNTX file: {}", err),
+            Error::Io(err) => write!(f, "{}", err),
+            #[cfg(feature = "webp")]
+            Error::Webp(err) => write!(f, "failed to encode WebP: {:?}", err),
+            #[cfg(feature = "preview")]
+            Error::Window(err) => write!(f, "failed to show preview window: {}", err),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Image(err) => Some(err),
+            Error::Read(err) => Some(err),
+            Error::Io(err) => Some(err),
+            #[cfg(feature = "preview")]
+            Error::Window(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(err: image::ImageError) -> Self {
+        Error::Image(err)
+    }
+}
+
+impl From<binrw::Error> for Error {
+    fn from(err: binrw::Error) -> Self {
+        Error::Read(err)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+#[cfg(feature = "preview")]
+impl From<minifb::Error> for Error {
+    fn from(err: minifb::Error) -> Self {
+        Error::Window(err)
+    }
+}
@@ -0,0 +1,219 @@
+//! Decoders for single ETC2 and EAC blocks. Like BCn blocks, every block covers 4x4 pixels and
+//! is decoded row by row, but the blocks themselves are big endian and index their pixels
+//! column by column.
+
+use std::convert::TryInto;
+
+use crate::bcn::Block;
+
+/// Intensity modifiers for the individual and differential modes
+const ETC_MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8], [5, 17], [9, 29], [13, 42], [18, 60], [24, 80], [33, 106], [47, 183],
+];
+
+/// Distances between paint colors in the T and H modes
+const ETC_DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+fn bits(block: u64, low: u32, count: u32) -> i32 {
+    ((block >> low) & ((1 << count) - 1)) as i32
+}
+
+fn extend(value: i32, count: u32) -> i32 {
+    (value << (8 - count)) | (value >> (2 * count - 8))
+}
+
+fn clamp(value: i32) -> u8 {
+    value.clamp(0, 255) as u8
+}
+
+fn offset(color: [i32; 3], amount: i32) -> [u8; 4] {
+    [clamp(color[0] + amount), clamp(color[1] + amount), clamp(color[2] + amount), 255]
+}
+
+/// The 2-bit index of the pixel at row-major position `i`
+fn etc_index(block: u64, i: usize) -> usize {
+    let bit = (i % 4) * 4 + i / 4;
+
+    ((block >> (bit + 15) & 2) | (block >> bit & 1)) as usize
+}
+
+/// Decodes the planar mode, which interpolates between three colors across the block
+fn decode_planar(block: u64, out: &mut Block) {
+    let red = [
+        bits(block, 57, 6),
+        bits(block, 32, 1) | bits(block, 34, 5) << 1,
+        bits(block, 13, 6),
+    ];
+    let green = [
+        bits(block, 49, 6) | bits(block, 56, 1) << 6,
+        bits(block, 25, 7),
+        bits(block, 6, 7),
+    ];
+    let blue = [
+        bits(block, 39, 3) | bits(block, 43, 2) << 3 | bits(block, 48, 1) << 5,
+        bits(block, 19, 6),
+        bits(block, 0, 6),
+    ];
+
+    let channels = [red.map(|v| extend(v, 6)), green.map(|v| extend(v, 7)), blue.map(|v| extend(v, 6))];
+    for (i, pixel) in out.iter_mut().enumerate() {
+        let (x, y) = ((i % 4) as i32, (i / 4) as i32);
+        for (value, [o, h, v]) in pixel.iter_mut().zip(channels) {
+            *value = clamp((x * (h - o) + y * (v - o) + 4 * o + 2) >> 2);
+        }
+        pixel[3] = 255;
+    }
+}
+
+/// Decodes the T and H modes, which pick from four paint colors made from two base colors
+fn decode_paint(block: u64, h_mode: bool, out: &mut Block, transparent: bool) {
+    let (c1, c2, distance) = if h_mode {
+        let c1 = [
+            bits(block, 59, 4),
+            bits(block, 52, 1) | bits(block, 56, 3) << 1,
+            bits(block, 47, 3) | bits(block, 51, 1) << 3,
+        ];
+        let c2 = [bits(block, 43, 4), bits(block, 39, 4), bits(block, 35, 4)];
+        let (c1, c2) = (c1.map(|v| extend(v, 4)), c2.map(|v| extend(v, 4)));
+        let order = (c1 >= c2) as i32;
+
+        (c1, c2, bits(block, 34, 1) << 2 | bits(block, 32, 1) << 1 | order)
+    } else {
+        let c1 = [bits(block, 56, 2) | bits(block, 59, 2) << 2, bits(block, 52, 4), bits(block, 48, 4)];
+        let c2 = [bits(block, 44, 4), bits(block, 40, 4), bits(block, 36, 4)];
+
+        (c1.map(|v| extend(v, 4)), c2.map(|v| extend(v, 4)), bits(block, 32, 1) | bits(block, 34, 2) << 1)
+    };
+
+    let d = ETC_DISTANCES[distance as usize];
+    let paint = if h_mode {
+        [offset(c1, d), offset(c1, -d), offset(c2, d), offset(c2, -d)]
+    } else {
+        [offset(c1, 0), offset(c2, d), offset(c2, 0), offset(c2, -d)]
+    };
+
+    for (i, pixel) in out.iter_mut().enumerate() {
+        let index = etc_index(block, i);
+        *pixel = if transparent && index == 2 { [0; 4] } else { paint[index] };
+    }
+}
+
+/// Decodes an 8 byte ETC2 RGB block. With `punchthrough`, the block is from a format with
+/// 1-bit alpha, which repurposes the differential bit as whether the block is fully opaque.
+fn decode_etc2_rgb(data: &[u8], out: &mut Block, punchthrough: bool) {
+    let block = u64::from_be_bytes(data[..8].try_into().unwrap());
+    let differential = punchthrough || bits(block, 33, 1) == 1;
+    let transparent = punchthrough && bits(block, 33, 1) == 0;
+
+    let (base1, base2) = if differential {
+        let base = [bits(block, 59, 5), bits(block, 51, 5), bits(block, 43, 5)];
+        let delta = [bits(block, 56, 3), bits(block, 48, 3), bits(block, 40, 3)]
+            .map(|d| (d << 29) >> 29);
+        let other: [i32; 3] = std::array::from_fn(|c| base[c] + delta[c]);
+
+        // a base color that overflows selects one of the modes added in ETC2
+        if !(0..32).contains(&other[0]) {
+            return decode_paint(block, false, out, transparent);
+        } else if !(0..32).contains(&other[1]) {
+            return decode_paint(block, true, out, transparent);
+        } else if !(0..32).contains(&other[2]) {
+            return decode_planar(block, out);
+        }
+
+        (base.map(|v| extend(v, 5)), other.map(|v| extend(v, 5)))
+    } else {
+        let base1 = [bits(block, 60, 4), bits(block, 52, 4), bits(block, 44, 4)];
+        let base2 = [bits(block, 56, 4), bits(block, 48, 4), bits(block, 40, 4)];
+
+        (base1.map(|v| extend(v, 4)), base2.map(|v| extend(v, 4)))
+    };
+
+    let flip = bits(block, 32, 1) == 1;
+    let tables = [ETC_MODIFIERS[bits(block, 37, 3) as usize], ETC_MODIFIERS[bits(block, 34, 3) as usize]];
+    for (i, pixel) in out.iter_mut().enumerate() {
+        let second = if flip { i / 4 >= 2 } else { i % 4 >= 2 };
+        let (base, [a, b]) = if second { (base2, tables[1]) } else { (base1, tables[0]) };
+
+        *pixel = match etc_index(block, i) {
+            0 if transparent => offset(base, 0),
+            2 if transparent => [0; 4],
+            0 => offset(base, a),
+            1 => offset(base, b),
+            2 => offset(base, -a),
+            _ => offset(base, -b),
+        };
+    }
+}
+
+/// Decodes the 16 11-bit values of an EAC block, or 8-bit values with `alpha`. Values are
+/// returned as read from the block, row by row.
+fn decode_eac(data: &[u8], alpha: bool, signed: bool) -> [i32; 16] {
+    let block = u64::from_be_bytes(data[..8].try_into().unwrap());
+    let base = if signed { (bits(block, 56, 8) as i8).max(-127) as i32 } else { bits(block, 56, 8) };
+    let multiplier = bits(block, 52, 4);
+    let table = EAC_MODIFIERS[bits(block, 48, 4) as usize];
+
+    std::array::from_fn(|i| {
+        let index = bits(block, 45 - ((i % 4) * 4 + i / 4) as u32 * 3, 3) as usize;
+        let modifier = table[index];
+
+        if alpha {
+            (base + modifier * multiplier).clamp(0, 255)
+        } else {
+            // 11-bit values are centered in each step of the base, and a multiplier of zero
+            // scales modifiers by 1/8 rather than dropping them
+            let step = if multiplier == 0 { modifier } else { modifier * multiplier * 8 };
+            if signed {
+                (base * 8 + step).clamp(-1023, 1023)
+            } else {
+                (base * 8 + 4 + step).clamp(0, 2047)
+            }
+        }
+    })
+}
+
+pub(crate) fn decode_etc2_rgb8(block: &[u8]) -> Block {
+    let mut pixels = [[0; 4]; 16];
+    decode_etc2_rgb(block, &mut pixels, false);
+    pixels
+}
+
+pub(crate) fn decode_etc2_rgb8a1(block: &[u8]) -> Block {
+    let mut pixels = [[0; 4]; 16];
+    decode_etc2_rgb(block, &mut pixels, true);
+    pixels
+}
+
+/// Decodes an ETC2 RGBA block, which is an EAC alpha block followed by an ETC2 RGB block
+pub(crate) fn decode_etc2_rgba8(block: &[u8]) -> Block {
+    let mut pixels = decode_etc2_rgb8(&block[8..]);
+    for (pixel, alpha) in pixels.iter_mut().zip(decode_eac(&block[..8], true, false)) {
+        pixel[3] = alpha as u8;
+    }
+
+    pixels
+}
+
+/// Decodes an EAC R11 block to values from 0 to 2047, or -1023 to 1023 if `signed`
+pub(crate) fn decode_eac_r11(block: &[u8], signed: bool) -> [i32; 16] {
+    decode_eac(block, false, signed)
+}
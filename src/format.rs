@@ -0,0 +1,745 @@
+use std::fmt;
+use binrw::BinRead;
+#[cfg(feature = "write")]
+use binrw::{io, BinResult, BinWrite, Endian};
+
+use crate::bcn::{self, Block};
+use crate::etc;
+use crate::Error;
+
+/// The pixel format of a texture's data
+#[allow(non_camel_case_types)]
+#[derive(BinRead, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SurfaceFormat {
+    #[br(magic = 0x0201u32)]
+    R8_UNORM,
+    #[br(magic = 0x0301u32)]
+    R4G4B4A4_UNORM,
+    #[br(magic = 0x0501u32)]
+    R5G5B5A1_UNORM,
+    #[br(magic = 0x0701u32)]
+    R5G6B5_UNORM,
+    #[br(magic = 0x0b01u32)]
+    R8G8B8A8_UNORM,
+    #[br(magic = 0x0b06u32)]
+    R8G8B8A8_SRGB,
+    #[br(magic = 0x0c01u32)]
+    B8G8R8A8_UNORM,
+    #[br(magic = 0x0c06u32)]
+    B8G8R8A8_SRGB,
+    #[br(magic = 0x0e01u32)]
+    R10G10B10A2_UNORM,
+    #[br(magic = 0x1505u32)]
+    R16G16B16A16_FLOAT,
+    #[br(magic = 0x1905u32)]
+    R32G32B32A32_FLOAT,
+    #[br(magic = 0x1a01u32)]
+    BC1_UNORM,
+    #[br(magic = 0x1a06u32)]
+    BC1_SRGB,
+    #[br(magic = 0x1b01u32)]
+    BC2_UNORM,
+    #[br(magic = 0x1b06u32)]
+    BC2_SRGB,
+    #[br(magic = 0x1c01u32)]
+    BC3_UNORM,
+    #[br(magic = 0x1c06u32)]
+    BC3_SRGB,
+    #[br(magic = 0x1d01u32)]
+    BC4_UNORM,
+    #[br(magic = 0x1d02u32)]
+    BC4_SNORM,
+    #[br(magic = 0x1e01u32)]
+    BC5_UNORM,
+    #[br(magic = 0x1e02u32)]
+    BC5_SNORM,
+    #[br(magic = 0x1f05u32)]
+    BC6H_SF16,
+    #[br(magic = 0x1f0au32)]
+    BC6H_UF16,
+    #[br(magic = 0x2001u32)]
+    BC7_UNORM,
+    #[br(magic = 0x2006u32)]
+    BC7_SRGB,
+    #[br(magic = 0x2101u32)]
+    EAC_R11_UNORM,
+    #[br(magic = 0x2102u32)]
+    EAC_R11_SNORM,
+    #[br(magic = 0x2201u32)]
+    EAC_R11G11_UNORM,
+    #[br(magic = 0x2202u32)]
+    EAC_R11G11_SNORM,
+    #[br(magic = 0x2401u32)]
+    ETC2_RGB8_UNORM,
+    #[br(magic = 0x2406u32)]
+    ETC2_RGB8_SRGB,
+    #[br(magic = 0x2501u32)]
+    ETC2_RGB8A1_UNORM,
+    #[br(magic = 0x2506u32)]
+    ETC2_RGB8A1_SRGB,
+    #[br(magic = 0x2601u32)]
+    ETC2_RGBA8_UNORM,
+    #[br(magic = 0x2606u32)]
+    ETC2_RGBA8_SRGB,
+
+    Unknown(u32),
+}
+
+impl SurfaceFormat {
+    /// Every format this crate knows, in order of their IDs
+    pub const ALL: &'static [SurfaceFormat] = {
+        use SurfaceFormat::*;
+
+        &[
+            R8_UNORM, R4G4B4A4_UNORM, R5G5B5A1_UNORM, R5G6B5_UNORM, R8G8B8A8_UNORM, R8G8B8A8_SRGB,
+            B8G8R8A8_UNORM, B8G8R8A8_SRGB, R10G10B10A2_UNORM, R16G16B16A16_FLOAT, R32G32B32A32_FLOAT,
+            BC1_UNORM, BC1_SRGB, BC2_UNORM, BC2_SRGB, BC3_UNORM, BC3_SRGB, BC4_UNORM, BC4_SNORM,
+            BC5_UNORM, BC5_SNORM, BC6H_SF16, BC6H_UF16, BC7_UNORM, BC7_SRGB, EAC_R11_UNORM,
+            EAC_R11_SNORM, EAC_R11G11_UNORM, EAC_R11G11_SNORM, ETC2_RGB8_UNORM, ETC2_RGB8_SRGB,
+            ETC2_RGB8A1_UNORM, ETC2_RGB8A1_SRGB, ETC2_RGBA8_UNORM, ETC2_RGBA8_SRGB,
+        ]
+    };
+
+    pub(crate) fn id(self) -> u32 {
+        use SurfaceFormat::*;
+
+        match self {
+            R8_UNORM => 0x0201,
+            R4G4B4A4_UNORM => 0x0301,
+            R5G5B5A1_UNORM => 0x0501,
+            R5G6B5_UNORM => 0x0701,
+            R8G8B8A8_UNORM => 0x0b01,
+            R8G8B8A8_SRGB => 0x0b06,
+            B8G8R8A8_UNORM => 0x0c01,
+            B8G8R8A8_SRGB => 0x0c06,
+            R10G10B10A2_UNORM => 0x0e01,
+            R16G16B16A16_FLOAT => 0x1505,
+            R32G32B32A32_FLOAT => 0x1905,
+            BC1_UNORM => 0x1a01,
+            BC1_SRGB => 0x1a06,
+            BC2_UNORM => 0x1b01,
+            BC2_SRGB => 0x1b06,
+            BC3_UNORM => 0x1c01,
+            BC3_SRGB => 0x1c06,
+            BC4_UNORM => 0x1d01,
+            BC4_SNORM => 0x1d02,
+            BC5_UNORM => 0x1e01,
+            BC5_SNORM => 0x1e02,
+            BC6H_SF16 => 0x1f05,
+            BC6H_UF16 => 0x1f0a,
+            BC7_UNORM => 0x2001,
+            BC7_SRGB => 0x2006,
+            EAC_R11_UNORM => 0x2101,
+            EAC_R11_SNORM => 0x2102,
+            EAC_R11G11_UNORM => 0x2201,
+            EAC_R11G11_SNORM => 0x2202,
+            ETC2_RGB8_UNORM => 0x2401,
+            ETC2_RGB8_SRGB => 0x2406,
+            ETC2_RGB8A1_UNORM => 0x2501,
+            ETC2_RGB8A1_SRGB => 0x2506,
+            ETC2_RGBA8_UNORM => 0x2601,
+            ETC2_RGBA8_SRGB => 0x2606,
+            Unknown(x) => x,
+        }
+    }
+
+    /// The format with the given ID, or `Unknown` if it isn't one this crate knows
+    pub(crate) fn from_id(id: u32) -> Self {
+        SurfaceFormat::ALL.iter()
+            .copied()
+            .find(|format| format.id() == id)
+            .unwrap_or(SurfaceFormat::Unknown(id))
+    }
+
+    /// Width and height, in pixels, of each block of data. Uncompressed formats use 1x1 blocks.
+    pub fn block_dimensions(self) -> (u32, u32) {
+        if self.is_compressed() { (4, 4) } else { (1, 1) }
+    }
+
+    /// Size of each block of data in bytes, or `None` if the format is unknown
+    pub fn bytes_per_block(self) -> Option<u32> {
+        use SurfaceFormat::*;
+
+        match self {
+            R8_UNORM => Some(1),
+            R4G4B4A4_UNORM | R5G5B5A1_UNORM | R5G6B5_UNORM => Some(2),
+            R8G8B8A8_UNORM | R8G8B8A8_SRGB | B8G8R8A8_UNORM | B8G8R8A8_SRGB
+                | R10G10B10A2_UNORM => Some(4),
+            R16G16B16A16_FLOAT => Some(8),
+            R32G32B32A32_FLOAT => Some(16),
+            BC1_UNORM | BC1_SRGB | BC4_UNORM | BC4_SNORM => Some(8),
+            BC2_UNORM | BC2_SRGB | BC3_UNORM | BC3_SRGB | BC5_UNORM | BC5_SNORM
+                | BC6H_SF16 | BC6H_UF16 | BC7_UNORM | BC7_SRGB => Some(16),
+            EAC_R11_UNORM | EAC_R11_SNORM | ETC2_RGB8_UNORM | ETC2_RGB8_SRGB | ETC2_RGB8A1_UNORM
+                | ETC2_RGB8A1_SRGB => Some(8),
+            EAC_R11G11_UNORM | EAC_R11G11_SNORM | ETC2_RGBA8_UNORM | ETC2_RGBA8_SRGB => Some(16),
+            Unknown(_) => None,
+        }
+    }
+
+    pub fn is_compressed(self) -> bool {
+        use SurfaceFormat::*;
+
+        matches!(
+            self,
+            BC1_UNORM | BC1_SRGB | BC2_UNORM | BC2_SRGB | BC3_UNORM | BC3_SRGB | BC4_UNORM
+                | BC4_SNORM | BC5_UNORM | BC5_SNORM | BC6H_SF16 | BC6H_UF16 | BC7_UNORM | BC7_SRGB
+                | EAC_R11_UNORM | EAC_R11_SNORM | EAC_R11G11_UNORM | EAC_R11G11_SNORM
+                | ETC2_RGB8_UNORM | ETC2_RGB8_SRGB | ETC2_RGB8A1_UNORM | ETC2_RGB8A1_SRGB
+                | ETC2_RGBA8_UNORM | ETC2_RGBA8_SRGB
+        )
+    }
+
+    pub fn is_srgb(self) -> bool {
+        use SurfaceFormat::*;
+
+        matches!(
+            self,
+            R8G8B8A8_SRGB | B8G8R8A8_SRGB | BC1_SRGB | BC2_SRGB | BC3_SRGB | BC7_SRGB | ETC2_RGB8_SRGB
+                | ETC2_RGB8A1_SRGB | ETC2_RGBA8_SRGB
+        )
+    }
+
+    /// Decodes a deswizzled surface, stored as rows of blocks. Blocks overhanging the edges of
+    /// sizes that aren't a multiple of the block size are decoded whole and cropped to `width`
+    /// by `height`.
+    pub(crate) fn decode(self, mut data: Vec<u8>, width: u32, height: u32) -> Result<Pixels, Error> {
+        use SurfaceFormat::*;
+
+        trace_span!("decode_surface", format = ?self, width, height);
+
+        let len = (width * height * 4) as usize;
+        let snorm = |v: i32| v as f32 / 127.0;
+        let unorm11 = |v: i32| ((v << 5) | (v >> 6)) as u16;
+        let snorm11 = |v: i32| v as f32 / 1023.0;
+
+        Ok(match self {
+            // uncompressed data is reused as-is rather than copied
+            R8G8B8A8_UNORM | R8G8B8A8_SRGB => {
+                data.truncate(len);
+                Pixels::Unorm8(data)
+            }
+            B8G8R8A8_UNORM | B8G8R8A8_SRGB => {
+                data.truncate(len);
+                swap_red_blue(&mut data);
+                Pixels::Unorm8(data)
+            }
+            R8_UNORM => Pixels::Unorm8(data[..len / 4].iter().flat_map(|&r| [r, 0, 0, 255]).collect()),
+            R4G4B4A4_UNORM => Pixels::Unorm8(decode_packed(&data, len, R4G4B4A4_LAYOUT)),
+            R5G5B5A1_UNORM => Pixels::Unorm8(decode_packed(&data, len, R5G5B5A1_LAYOUT)),
+            R5G6B5_UNORM => Pixels::Unorm8(decode_packed(&data, len, R5G6B5_LAYOUT)),
+            R10G10B10A2_UNORM => Pixels::Unorm16(decode_rgb10a2(&data[..len])),
+            R16G16B16A16_FLOAT => Pixels::Float(
+                data[..len * 2].chunks_exact(2)
+                    .map(|v| f16_to_f32(u16::from_le_bytes([v[0], v[1]])))
+                    .collect()
+            ),
+            R32G32B32A32_FLOAT => Pixels::Float(
+                data[..len * 4].chunks_exact(4)
+                    .map(|v| f32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+                    .collect()
+            ),
+            BC1_UNORM | BC1_SRGB => Pixels::Unorm8(decode_blocks(&data, width, height, 8, |block| {
+                let mut pixels = [[0; 4]; 16];
+                bcn::decode_bc1(block, &mut pixels);
+                pixels
+            })),
+            BC2_UNORM | BC2_SRGB => Pixels::Unorm8(decode_blocks(&data, width, height, 16, |block| {
+                let mut pixels = [[0; 4]; 16];
+                bcn::decode_bc2(block, &mut pixels);
+                pixels
+            })),
+            BC3_UNORM | BC3_SRGB => Pixels::Unorm8(decode_blocks(&data, width, height, 16, |block| {
+                let mut pixels = [[0; 4]; 16];
+                bcn::decode_bc3(block, &mut pixels);
+                pixels
+            })),
+            BC4_UNORM => Pixels::Unorm8(decode_blocks(&data, width, height, 8, |block| {
+                bcn::decode_bc4(block, false).map(|r| [r as u8, 0, 0, 255])
+            })),
+            BC4_SNORM => Pixels::Float(decode_blocks(&data, width, height, 8, |block| {
+                bcn::decode_bc4(block, true).map(|r| [snorm(r), 0.0, 0.0, 1.0])
+            })),
+            BC5_UNORM => Pixels::Unorm8(decode_blocks(&data, width, height, 16, |block| {
+                let (r, g) = (bcn::decode_bc4(&block[..8], false), bcn::decode_bc4(&block[8..], false));
+                std::array::from_fn(|i| [r[i] as u8, g[i] as u8, 0, 255])
+            })),
+            BC5_SNORM => Pixels::Float(decode_blocks(&data, width, height, 16, |block| {
+                let (r, g) = (bcn::decode_bc4(&block[..8], true), bcn::decode_bc4(&block[8..], true));
+                std::array::from_fn(|i| [snorm(r[i]), snorm(g[i]), 0.0, 1.0])
+            })),
+            BC6H_SF16 | BC6H_UF16 => Pixels::Float(decode_blocks(&data, width, height, 16, |block| {
+                bcn::decode_bc6h(block, self == BC6H_SF16)
+                    .map(|p| [f16_to_f32(p[0]), f16_to_f32(p[1]), f16_to_f32(p[2]), 1.0])
+            })),
+            BC7_UNORM | BC7_SRGB => Pixels::Unorm8(decode_blocks(&data, width, height, 16, |block| {
+                let mut pixels = [[0; 4]; 16];
+                bcn::decode_bc7(block, &mut pixels);
+                pixels
+            })),
+            EAC_R11_UNORM => Pixels::Unorm16(decode_blocks(&data, width, height, 8, |block| {
+                etc::decode_eac_r11(block, false).map(|r| [unorm11(r), 0, 0, u16::MAX])
+            })),
+            EAC_R11_SNORM => Pixels::Float(decode_blocks(&data, width, height, 8, |block| {
+                etc::decode_eac_r11(block, true).map(|r| [snorm11(r), 0.0, 0.0, 1.0])
+            })),
+            EAC_R11G11_UNORM => Pixels::Unorm16(decode_blocks(&data, width, height, 16, |block| {
+                let [r, g] = [&block[..8], &block[8..]].map(|half| etc::decode_eac_r11(half, false));
+                std::array::from_fn(|i| [unorm11(r[i]), unorm11(g[i]), 0, u16::MAX])
+            })),
+            EAC_R11G11_SNORM => Pixels::Float(decode_blocks(&data, width, height, 16, |block| {
+                let [r, g] = [&block[..8], &block[8..]].map(|half| etc::decode_eac_r11(half, true));
+                std::array::from_fn(|i| [snorm11(r[i]), snorm11(g[i]), 0.0, 1.0])
+            })),
+            ETC2_RGB8_UNORM | ETC2_RGB8_SRGB => {
+                Pixels::Unorm8(decode_blocks(&data, width, height, 8, etc::decode_etc2_rgb8))
+            }
+            ETC2_RGB8A1_UNORM | ETC2_RGB8A1_SRGB => {
+                Pixels::Unorm8(decode_blocks(&data, width, height, 8, etc::decode_etc2_rgb8a1))
+            }
+            ETC2_RGBA8_UNORM | ETC2_RGBA8_SRGB => {
+                Pixels::Unorm8(decode_blocks(&data, width, height, 16, etc::decode_etc2_rgba8))
+            }
+            Unknown(_) => return Err(Error::UnsupportedFormat(self)),
+        })
+    }
+
+    /// Encodes pixels as a surface of rows of blocks, ready to be swizzled, compressing blocks
+    /// on up to `threads` threads
+    pub(crate) fn encode(self, pixels: Pixels, width: u32, height: u32, threads: usize) -> Result<Vec<u8>, Error> {
+        use SurfaceFormat::*;
+
+        trace_span!("encode_surface", format = ?self, width, height);
+
+        let snorm = |v: f32| (v.clamp(-1.0, 1.0) * 127.0).round() as i32;
+
+        Ok(match self {
+            R8G8B8A8_UNORM | R8G8B8A8_SRGB => pixels.into_unorm8(),
+            B8G8R8A8_UNORM | B8G8R8A8_SRGB => {
+                let mut data = pixels.into_unorm8();
+                swap_red_blue(&mut data);
+                data
+            }
+            R8_UNORM => pixels.into_unorm8().chunks_exact(4).map(|p| p[0]).collect(),
+            R4G4B4A4_UNORM => encode_packed(&pixels.into_unorm8(), R4G4B4A4_LAYOUT),
+            R5G5B5A1_UNORM => encode_packed(&pixels.into_unorm8(), R5G5B5A1_LAYOUT),
+            R5G6B5_UNORM => encode_packed(&pixels.into_unorm8(), R5G6B5_LAYOUT),
+            R10G10B10A2_UNORM => encode_rgb10a2(&pixels.into_unorm16()),
+            R16G16B16A16_FLOAT => pixels.into_float().iter()
+                .flat_map(|&v| f32_to_f16(v).to_le_bytes())
+                .collect(),
+            R32G32B32A32_FLOAT => pixels.into_float().iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect(),
+            BC1_UNORM | BC1_SRGB => {
+                encode_blocks(&pixels.into_unorm8(), width, height, threads, |block| bcn::encode_bc1(&block))
+            }
+            BC2_UNORM | BC2_SRGB => {
+                encode_blocks(&pixels.into_unorm8(), width, height, threads, |block| bcn::encode_bc2(&block))
+            }
+            BC3_UNORM | BC3_SRGB => {
+                encode_blocks(&pixels.into_unorm8(), width, height, threads, |block| bcn::encode_bc3(&block))
+            }
+            BC4_UNORM => encode_blocks(&pixels.into_unorm8(), width, height, threads, |block: Block| {
+                bcn::encode_bc4(&block.map(|p| p[0] as i32), false)
+            }),
+            BC4_SNORM => encode_blocks(&pixels.into_float(), width, height, threads, |block: [[f32; 4]; 16]| {
+                bcn::encode_bc4(&block.map(|p| snorm(p[0])), true)
+            }),
+            BC5_UNORM => encode_blocks(&pixels.into_unorm8(), width, height, threads, |block: Block| {
+                let mut out = [0; 16];
+                out[..8].copy_from_slice(&bcn::encode_bc4(&block.map(|p| p[0] as i32), false));
+                out[8..].copy_from_slice(&bcn::encode_bc4(&block.map(|p| p[1] as i32), false));
+                out
+            }),
+            BC5_SNORM => encode_blocks(&pixels.into_float(), width, height, threads, |block: [[f32; 4]; 16]| {
+                let mut out = [0; 16];
+                out[..8].copy_from_slice(&bcn::encode_bc4(&block.map(|p| snorm(p[0])), true));
+                out[8..].copy_from_slice(&bcn::encode_bc4(&block.map(|p| snorm(p[1])), true));
+                out
+            }),
+            BC6H_SF16 | BC6H_UF16 => encode_blocks(&pixels.into_float(), width, height, threads, |block: [[f32; 4]; 16]| {
+                let block = block.map(|p| [f32_to_f16(p[0]), f32_to_f16(p[1]), f32_to_f16(p[2])]);
+                bcn::encode_bc6h(&block, self == BC6H_SF16)
+            }),
+            BC7_UNORM | BC7_SRGB => {
+                encode_blocks(&pixels.into_unorm8(), width, height, threads, |block| bcn::encode_bc7(&block))
+            }
+            // ETC2 and EAC are only decoded
+            EAC_R11_UNORM | EAC_R11_SNORM | EAC_R11G11_UNORM | EAC_R11G11_SNORM | ETC2_RGB8_UNORM
+                | ETC2_RGB8_SRGB | ETC2_RGB8A1_UNORM | ETC2_RGB8A1_SRGB | ETC2_RGBA8_UNORM
+                | ETC2_RGBA8_SRGB | Unknown(_) => return Err(Error::UnsupportedFormat(self)),
+        })
+    }
+}
+
+#[cfg(feature = "write")]
+impl BinWrite for SurfaceFormat {
+    type Args<'a> = ();
+
+    fn write_options<W: io::Write + io::Seek>(&self, writer: &mut W, endian: Endian, _: ()) -> BinResult<()> {
+        self.id().write_options(writer, endian, ())
+    }
+}
+
+impl fmt::Display for SurfaceFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SurfaceFormat::Unknown(x) => write!(f, "Unknown({:#06x})", x),
+            format => fmt::Debug::fmt(format, f),
+        }
+    }
+}
+
+/// Options for encoding textures, such as in [`Texture::convert_format_with_options`]
+///
+/// [`Texture::convert_format_with_options`]: crate::Texture::convert_format_with_options
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EncodeOptions {
+    /// How many threads to compress blocks on, or 0 to use every available core. The encoded
+    /// data is the same whatever the number of threads.
+    pub threads: usize,
+}
+
+impl EncodeOptions {
+    /// The number of threads to use, resolving 0 to the number of cores
+    pub(crate) fn thread_count(&self) -> usize {
+        match self.threads {
+            0 => std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+            threads => threads,
+        }
+    }
+}
+
+/// Conversion of color values between the sRGB and linear transfer functions, applied to the
+/// red, green and blue channels regardless of whether the format is an `_SRGB` variant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GammaConversion {
+    /// Leave the values as they are
+    None,
+    /// Treat the values as sRGB encoded and convert them to linear
+    SrgbToLinear,
+    /// Treat the values as linear and convert them to sRGB encoded
+    LinearToSrgb,
+}
+
+impl GammaConversion {
+    /// Converts 8-bit RGBA pixels in place
+    pub(crate) fn apply(self, pixels: &mut [u8]) {
+        let Some(convert) = self.function() else { return };
+
+        let table: Vec<u8> = (0..=255)
+            .map(|v| (convert(v as f32 / 255.0) * 255.0).round() as u8)
+            .collect();
+
+        for pixel in pixels.chunks_exact_mut(4) {
+            for channel in &mut pixel[..3] {
+                *channel = table[*channel as usize];
+            }
+        }
+    }
+
+    /// Converts float RGBA pixels in place, for images with more than 8 bits per channel
+    pub(crate) fn apply_float(self, pixels: &mut [f32]) {
+        let Some(convert) = self.function() else { return };
+
+        for pixel in pixels.chunks_exact_mut(4) {
+            for channel in &mut pixel[..3] {
+                *channel = convert(channel.clamp(0.0, 1.0));
+            }
+        }
+    }
+
+    fn function(self) -> Option<fn(f32) -> f32> {
+        match self {
+            GammaConversion::None => None,
+            GammaConversion::SrgbToLinear => Some(srgb_to_linear),
+            GammaConversion::LinearToSrgb => Some(linear_to_srgb),
+        }
+    }
+}
+
+fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Decoded RGBA pixels, kept as 16-bit or float values for formats with more precision or
+/// range than 8 bits per channel
+pub(crate) enum Pixels {
+    Unorm8(Vec<u8>),
+    Unorm16(Vec<u16>),
+    Float(Vec<f32>),
+}
+
+impl Pixels {
+    /// Takes an image's pixels as RGBA, keeping 16-bit and float images' precision
+    pub(crate) fn from_image(img: image::DynamicImage) -> Self {
+        use image::DynamicImage::*;
+
+        match img {
+            ImageLuma16(_) | ImageLumaA16(_) | ImageRgb16(_) | ImageRgba16(_) => {
+                Pixels::Unorm16(img.to_rgba16().into_raw())
+            }
+            ImageRgb32F(_) | ImageRgba32F(_) => Pixels::Float(img.to_rgba32f().into_raw()),
+            img => Pixels::Unorm8(img.to_rgba8().into_raw()),
+        }
+    }
+
+    pub(crate) fn into_unorm8(self) -> Vec<u8> {
+        match self {
+            Pixels::Unorm8(pixels) => pixels,
+            Pixels::Unorm16(pixels) => pixels.iter()
+                .map(|&v| ((v as u32 * 255 + 32767) / 65535) as u8)
+                .collect(),
+            Pixels::Float(pixels) => pixels.iter()
+                .map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8)
+                .collect(),
+        }
+    }
+
+    pub(crate) fn into_unorm16(self) -> Vec<u16> {
+        match self {
+            Pixels::Unorm8(pixels) => pixels.iter().map(|&v| v as u16 * 257).collect(),
+            Pixels::Unorm16(pixels) => pixels,
+            Pixels::Float(pixels) => pixels.iter()
+                .map(|v| (v.clamp(0.0, 1.0) * 65535.0).round() as u16)
+                .collect(),
+        }
+    }
+
+    pub(crate) fn into_float(self) -> Vec<f32> {
+        match self {
+            Pixels::Unorm8(pixels) => pixels.iter().map(|&v| v as f32 / 255.0).collect(),
+            Pixels::Unorm16(pixels) => pixels.iter().map(|&v| v as f32 / 65535.0).collect(),
+            Pixels::Float(pixels) => pixels,
+        }
+    }
+
+    /// Converts decoded `format` pixels to the image type that holds them without losing
+    /// precision: one channel for `R8` and BC4, 16 bits for 10-bit formats and floats for float, signed
+    /// and HDR formats, dropping alpha for BC6H which has none
+    pub(crate) fn into_image(self, width: u32, height: u32, format: SurfaceFormat) -> image::DynamicImage {
+        use image::DynamicImage;
+        use SurfaceFormat::*;
+
+        match self {
+            Pixels::Unorm8(pixels) if matches!(format, R8_UNORM | BC4_UNORM) => {
+                let red = pixels.chunks_exact(4).map(|p| p[0]).collect();
+                DynamicImage::ImageLuma8(image::GrayImage::from_raw(width, height, red).unwrap())
+            }
+            Pixels::Unorm8(pixels) => DynamicImage::ImageRgba8(
+                image::RgbaImage::from_raw(width, height, pixels).unwrap()
+            ),
+            Pixels::Unorm16(pixels) => DynamicImage::ImageRgba16(
+                image::ImageBuffer::from_raw(width, height, pixels).unwrap()
+            ),
+            Pixels::Float(pixels) if matches!(format, BC6H_SF16 | BC6H_UF16) => {
+                let rgb = pixels.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect();
+                DynamicImage::ImageRgb32F(image::Rgb32FImage::from_raw(width, height, rgb).unwrap())
+            }
+            Pixels::Float(pixels) => DynamicImage::ImageRgba32F(
+                image::Rgba32FImage::from_raw(width, height, pixels).unwrap()
+            ),
+        }
+    }
+}
+
+fn swap_red_blue(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+    }
+}
+
+/// Bit offset and width of the red, green, blue and alpha channels of a packed 16-bit format.
+/// A channel with a width of 0 isn't stored, and decodes as opaque alpha.
+type PackedLayout = [(u32, u32); 4];
+
+// blue is in the least significant bits with green and red above it, and alpha (if any) in the
+// most significant bits
+const R4G4B4A4_LAYOUT: PackedLayout = [(8, 4), (4, 4), (0, 4), (12, 4)];
+const R5G5B5A1_LAYOUT: PackedLayout = [(10, 5), (5, 5), (0, 5), (15, 1)];
+const R5G6B5_LAYOUT: PackedLayout = [(11, 5), (5, 6), (0, 5), (0, 0)];
+
+/// Expands `len` bytes worth of RGBA8 pixels from little endian 16-bit values
+fn decode_packed(data: &[u8], len: usize, layout: PackedLayout) -> Vec<u8> {
+    data.chunks_exact(2)
+        .take(len / 4)
+        .flat_map(|bytes| {
+            let value = u16::from_le_bytes([bytes[0], bytes[1]]) as u32;
+            layout.map(|(shift, bits)| match (1 << bits) - 1 {
+                0 => 255,
+                max => ((((value >> shift) & max) * 255 + max / 2) / max) as u8,
+            })
+        })
+        .collect()
+}
+
+/// Packs RGBA8 pixels into little endian 16-bit values, rounding each channel to its width
+fn encode_packed(pixels: &[u8], layout: PackedLayout) -> Vec<u8> {
+    pixels.chunks_exact(4)
+        .flat_map(|pixel| {
+            let value = layout.iter().zip(pixel).fold(0, |value, (&(shift, bits), &channel)| {
+                let max = (1 << bits) - 1;
+                value | ((channel as u32 * max + 127) / 255) << shift
+            });
+            (value as u16).to_le_bytes()
+        })
+        .collect()
+}
+
+/// Expands little endian 10:10:10:2 values, with red in the least significant bits, to 16 bits
+/// per channel
+fn decode_rgb10a2(data: &[u8]) -> Vec<u16> {
+    data.chunks_exact(4)
+        .flat_map(|bytes| {
+            let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            [(0, 10), (10, 10), (20, 10), (30, 2)].map(|(shift, bits)| {
+                let max = (1 << bits) - 1;
+                (((value >> shift) & max) * 65535 / max) as u16
+            })
+        })
+        .collect()
+}
+
+fn encode_rgb10a2(pixels: &[u16]) -> Vec<u8> {
+    pixels.chunks_exact(4)
+        .flat_map(|pixel| {
+            let value = [(0, 10), (10, 10), (20, 10), (30, 2)].iter()
+                .zip(pixel)
+                .fold(0u32, |value, (&(shift, bits), &channel)| {
+                    let max = (1 << bits) - 1;
+                    value | ((channel as u32 * max + 32767) / 65535) << shift
+                });
+            value.to_le_bytes()
+        })
+        .collect()
+}
+
+fn decode_blocks<T: Copy + Default>(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    block_size: usize,
+    decode: impl Fn(&[u8]) -> [[T; 4]; 16],
+) -> Vec<T> {
+    let (width, height) = (width as usize, height as usize);
+    let blocks_wide = width.div_ceil(4);
+    let block_count = blocks_wide * height.div_ceil(4);
+
+    let mut pixels = vec![T::default(); width * height * 4];
+    for (i, block) in data.chunks_exact(block_size).take(block_count).enumerate() {
+        for (j, pixel) in decode(block).iter().enumerate() {
+            let x = (i % blocks_wide) * 4 + j % 4;
+            let y = (i / blocks_wide) * 4 + j / 4;
+            if x < width && y < height {
+                pixels[(y * width + x) * 4..][..4].copy_from_slice(pixel);
+            }
+        }
+    }
+
+    pixels
+}
+
+/// Splits pixels into 4x4 blocks, repeating the last row and column to fill partial blocks
+fn encode_blocks<T: Copy + Sync, B: AsRef<[u8]>>(
+    pixels: &[T],
+    width: u32,
+    height: u32,
+    threads: usize,
+    encode: impl Fn([[T; 4]; 16]) -> B + Sync,
+) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+
+    let encode_rows = |block_rows: std::ops::Range<usize>| {
+        let mut data = Vec::new();
+        for block_y in block_rows.map(|row| row * 4) {
+            for block_x in (0..width).step_by(4) {
+                let block = std::array::from_fn(|j| {
+                    let x = (block_x + j % 4).min(width - 1);
+                    let y = (block_y + j / 4).min(height - 1);
+                    let i = (y * width + x) * 4;
+                    [pixels[i], pixels[i + 1], pixels[i + 2], pixels[i + 3]]
+                });
+                data.extend_from_slice(encode(block).as_ref());
+            }
+        }
+
+        data
+    };
+
+    // each thread compresses a contiguous range of block rows, which are joined in order
+    let block_rows = height.div_ceil(4);
+    let rows_per_thread = block_rows.div_ceil(threads.clamp(1, block_rows.max(1)));
+    if rows_per_thread >= block_rows {
+        return encode_rows(0..block_rows);
+    }
+
+    std::thread::scope(|scope| {
+        let chunks: Vec<_> = (0..block_rows)
+            .step_by(rows_per_thread)
+            .map(|start| {
+                let rows = start..(start + rows_per_thread).min(block_rows);
+                scope.spawn(|| encode_rows(rows))
+            })
+            .collect();
+
+        chunks.into_iter().flat_map(|chunk| chunk.join().unwrap()).collect()
+    })
+}
+
+pub(crate) fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half & 0x8000) as u32) << 16;
+    let exponent = ((half >> 10) & 0x1f) as u32;
+    let mantissa = (half & 0x3ff) as u32;
+
+    match exponent {
+        0 => {
+            let value = mantissa as f32 / (1 << 24) as f32;
+            if sign != 0 { -value } else { value }
+        }
+        0x1f => f32::from_bits(sign | 0x7f80_0000 | (mantissa << 13)),
+        _ => f32::from_bits(sign | ((exponent + 112) << 23) | (mantissa << 13)),
+    }
+}
+
+pub(crate) fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        sign | 0x7c00
+    } else if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+
+        // subnormal, with the implicit leading bit made explicit
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let half = (mantissa >> shift) + ((mantissa >> (shift - 1)) & 1);
+        sign | half as u16
+    } else {
+        let half = sign | ((exponent as u16) << 10) | (mantissa >> 13) as u16;
+        // rounding can carry into the exponent, which still gives the right result
+        half + ((mantissa >> 12) & 1) as u16
+    }
+}
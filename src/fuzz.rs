@@ -0,0 +1,125 @@
+//! Entry points for fuzzing code built on this crate with `cargo fuzz`, using the same harness
+//! this crate is fuzzed with. Two kinds of fuzz target are supported, one feeding arbitrary
+//! bytes to the parser and one generating valid files to write and read back:
+//!
+//! ```ignore
+//! fuzz_target!(|data: &[u8]| bntx::fuzz::parse_and_rewrite(data));
+//!
+//! fuzz_target!(|file: bntx::fuzz::FuzzFile| bntx::fuzz::roundtrip(&file));
+//! ```
+//!
+//! Both panic only if the crate misbehaves, such as by writing a file it can't read back, so
+//! any crash they find is a bug.
+
+use arbitrary::{Arbitrary, Unstructured};
+
+use crate::tegra_swizzle::BlockHeight;
+use crate::{BntxFile, SurfaceFormat, Texture, TileMode};
+
+/// The most pixels in a texture that's decoded, so huge dimensions in a corrupt header don't
+/// run the fuzzer out of memory
+const MAX_DECODED_PIXELS: u64 = 1 << 20;
+
+/// The largest file that's written back out, so a corrupt header that makes the layout huge,
+/// such as with lots of alignment padding, doesn't run the fuzzer out of memory
+const MAX_WRITTEN_SIZE: usize = 1 << 24;
+
+/// The largest width or height of a generated texture
+const MAX_SIZE: u32 = 128;
+
+/// The most textures in a generated file
+const MAX_TEXTURES: usize = 4;
+
+/// Parses `data` as a BNTX file and, if it parses, checks every texture's sizes, decodes the
+/// small ones and writes the file back out. Data that doesn't parse is ignored, but a file
+/// that parses must be written back to a file that also parses, with the same textures.
+pub fn parse_and_rewrite(data: &[u8]) {
+    let Ok(file) = BntxFile::from_reader(data) else { return };
+
+    for texture in file.textures() {
+        let _ = texture.size_mismatches();
+
+        let pixels = texture.width as u64 * texture.height as u64;
+        if pixels <= MAX_DECODED_PIXELS {
+            let _ = texture.to_image();
+        }
+    }
+
+    if file.predicted_size() > MAX_WRITTEN_SIZE {
+        return;
+    }
+
+    let mut written = Vec::new();
+    if file.write(&mut written).is_err() {
+        return;
+    }
+
+    let read = BntxFile::from_reader(&written[..]).expect("a rewritten file should parse");
+    assert_eq!(
+        read.textures().map(Texture::name).collect::<Vec<_>>(),
+        file.textures().map(Texture::name).collect::<Vec<_>>(),
+    );
+}
+
+/// A valid file with up to a few small textures in random formats, filled with random data.
+/// Some are linear or use another block height or alignment, to exercise readers with layouts
+/// other tools write.
+#[derive(Debug, Clone)]
+pub struct FuzzFile(pub BntxFile);
+
+impl<'a> Arbitrary<'a> for FuzzFile {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut file = BntxFile::new(&name(u)?);
+        for _ in 0..u.int_in_range(1..=MAX_TEXTURES)? {
+            file.push_texture(texture(u)?).map_err(|_| arbitrary::Error::IncorrectFormat)?;
+        }
+        file.set_share_identical_data(u.arbitrary()?);
+
+        Ok(FuzzFile(file))
+    }
+}
+
+/// Writes a generated file, reads it back and checks nothing changed, then feeds the written
+/// bytes to [`parse_and_rewrite`]
+pub fn roundtrip(file: &FuzzFile) {
+    let FuzzFile(file) = file;
+
+    let mut written = Vec::new();
+    file.write(&mut written).expect("a generated file should write");
+
+    let mismatches = file.verify_roundtrip().expect("a generated file should read back");
+    assert!(mismatches.is_empty(), "{:?}", mismatches);
+
+    parse_and_rewrite(&written);
+}
+
+fn name(u: &mut Unstructured<'_>) -> arbitrary::Result<String> {
+    let name: String = u.arbitrary()?;
+
+    Ok(name.chars().take(32).collect())
+}
+
+fn texture(u: &mut Unstructured<'_>) -> arbitrary::Result<Texture> {
+    let format = *u.choose(SurfaceFormat::ALL)?;
+    let (block_width, block_height) = format.block_dimensions();
+    let width = u.int_in_range(1..=MAX_SIZE)?.next_multiple_of(block_width);
+    let height = u.int_in_range(1..=MAX_SIZE)?.next_multiple_of(block_height);
+    let mips = u.int_in_range(1..=32 - width.max(height).leading_zeros())?;
+    let array_len = u.int_in_range(1..=3)?;
+
+    let mut texture = Texture::blank(&name(u)?, format, width, height, mips, array_len)
+        .map_err(|_| arbitrary::Error::IncorrectFormat)?;
+
+    if u.arbitrary()? {
+        let tile_mode = if u.arbitrary()? { TileMode::Linear } else { TileMode::BlockLinear };
+        let block_height = BlockHeight::from_log2(u.int_in_range(0..=5)?).unwrap();
+        texture.align = 1 << u.int_in_range(0..=12)?;
+        texture.reswizzle(tile_mode, block_height).map_err(|_| arbitrary::Error::IncorrectFormat)?;
+    }
+
+    let mut data = vec![0; texture.raw_data().len()];
+    u.fill_buffer(&mut data)?;
+    texture.set_raw_data(data).map_err(|_| arbitrary::Error::IncorrectFormat)?;
+
+    Ok(texture)
+}
@@ -0,0 +1,210 @@
+use std::sync::mpsc;
+
+use wgpu::util::DeviceExt;
+
+use crate::tegra_swizzle::{self, SurfaceParams};
+
+// Each invocation writes one 32-bit word of the deswizzled surface, gathering its bytes from
+// their block linear addresses. The address math is the same as the CPU path's, except that
+// it works per byte rather than per block, which is equivalent since blocks never straddle the
+// 16 byte runs a GOB is made of.
+const SHADER: &str = r#"
+struct Params {
+    row_bytes: u32,
+    out_len: u32,
+    src_len: u32,
+    width_in_gobs: u32,
+    block_height: u32,
+    swizzle: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> src: array<u32>;
+@group(0) @binding(2) var<storage, read_write> dst: array<u32>;
+
+fn swizzled_address(x: u32, y: u32) -> u32 {
+    let block_rows = 8u * params.block_height;
+    let gob_row = ((y % block_rows) / 8u) ^ (params.swizzle & (params.block_height - 1u));
+    let gob_address = (y / block_rows) * 512u * params.block_height * params.width_in_gobs
+        + (x / 64u) * 512u * params.block_height
+        + gob_row * 512u;
+
+    return gob_address
+        + ((x % 64u) / 32u) * 256u
+        + ((y % 8u) / 2u) * 64u
+        + ((x % 32u) / 16u) * 32u
+        + (y % 2u) * 16u
+        + (x % 16u);
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let word = id.x + id.y * 65535u * 64u;
+    if (word * 4u >= params.out_len) {
+        return;
+    }
+
+    var value = 0u;
+    for (var i = 0u; i < 4u; i++) {
+        let offset = word * 4u + i;
+        if (offset < params.out_len) {
+            let address = swizzled_address(offset % params.row_bytes, offset / params.row_bytes);
+            if (address < params.src_len) {
+                value |= ((src[address / 4u] >> ((address % 4u) * 8u)) & 0xffu) << (i * 8u);
+            }
+        }
+    }
+
+    dst[word] = value;
+}
+"#;
+
+const WORKGROUP_SIZE: u32 = 64;
+const MAX_WORKGROUPS: u32 = 65535;
+
+/// Deswizzles block linear surfaces with a compute shader, for batches of large textures where
+/// the CPU is the bottleneck. Creating one sets up a GPU device, so it should be created once
+/// and reused. Used through [`Backend::Gpu`](crate::tegra_swizzle::Backend::Gpu).
+pub struct GpuDeswizzler {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl std::fmt::Debug for GpuDeswizzler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GpuDeswizzler").finish_non_exhaustive()
+    }
+}
+
+impl GpuDeswizzler {
+    /// Sets up the default GPU, or returns `None` if there isn't one
+    pub fn new() -> Option<Self> {
+        let descriptor = wgpu::InstanceDescriptor::new_without_display_handle_from_env();
+        let instance = wgpu::Instance::new(descriptor);
+        let adapter = pollster::block_on(instance.request_adapter(&Default::default())).ok()?;
+        let (device, queue) = pollster::block_on(adapter.request_device(&Default::default())).ok()?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("deswizzle"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("deswizzle"),
+            layout: None,
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        Some(GpuDeswizzler { device, queue, pipeline })
+    }
+
+    /// Whether the surface can be deswizzled on the GPU. Pitch linear surfaces and ones too
+    /// large for a single storage buffer are left to the CPU.
+    fn supports(&self, params: &SurfaceParams) -> bool {
+        let max_size = self.device.limits().max_storage_buffer_binding_size as usize;
+
+        params.tile_mode == 0
+            && tegra_swizzle::swizzled_surface_size(params) <= max_size
+            && tegra_swizzle::deswizzled_mip_size(params) <= max_size
+    }
+
+    /// Deswizzles a surface like [`tegra_swizzle::deswizzle`], falling back to it for surfaces
+    /// the shader doesn't handle
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` is smaller than [`tegra_swizzle::swizzled_surface_size`].
+    pub fn deswizzle(&self, params: &SurfaceParams, data: &[u8]) -> Vec<u8> {
+        if !self.supports(params) {
+            return tegra_swizzle::deswizzle(params, data);
+        }
+
+        trace_span!("gpu_deswizzle", params.width, params.height, params.bytes_per_block);
+
+        let src_len = tegra_swizzle::swizzled_surface_size(params);
+        let out_len = tegra_swizzle::deswizzled_mip_size(params);
+        let row_bytes = params.width.div_ceil(params.block_dimensions.0) * params.bytes_per_block;
+
+        // storage buffers are read as 32-bit words, so both are padded to a multiple of 4
+        let mut src = data[..src_len].to_vec();
+        src.resize(crate::align(src_len.max(4), 4), 0);
+        let padded_out_len = crate::align(out_len.max(4), 4) as u64;
+
+        let uniforms = [
+            row_bytes,
+            out_len as u32,
+            src_len as u32,
+            row_bytes.div_ceil(64),
+            params.block_height as u32,
+            params.swizzle,
+            0,
+            0,
+        ];
+        let uniforms: Vec<u8> = uniforms.iter().flat_map(|value| value.to_le_bytes()).collect();
+
+        let device = &self.device;
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: &uniforms,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let src_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: &src,
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let dst_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: padded_out_len,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: padded_out_len,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: src_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: dst_buffer.as_entire_binding() },
+            ],
+        });
+
+        // past the limit on workgroups per dimension, rows of workgroups are dispatched instead
+        let workgroups = (padded_out_len as u32 / 4).div_ceil(WORKGROUP_SIZE);
+        let groups_x = workgroups.min(MAX_WORKGROUPS);
+        let groups_y = workgroups.div_ceil(MAX_WORKGROUPS);
+
+        let mut encoder = device.create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&Default::default());
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(groups_x, groups_y, 1);
+        }
+        encoder.copy_buffer_to_buffer(&dst_buffer, 0, &readback_buffer, 0, padded_out_len);
+        self.queue.submit([encoder.finish()]);
+
+        let (sender, receiver) = mpsc::channel();
+        let slice = readback_buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::PollType::wait_indefinitely()).expect("failed to wait for the GPU");
+        receiver.recv().unwrap().expect("failed to read back deswizzled data");
+
+        let mut result = slice.get_mapped_range().unwrap().to_vec();
+        result.truncate(out_len);
+
+        result
+    }
+}
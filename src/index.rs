@@ -0,0 +1,163 @@
+//! Cataloguing the textures in many files at once, such as every file in a game's dump, by
+//! reading only their headers. The texture data, usually almost all of a file, is never read,
+//! so thousands of files can be indexed in about the time it takes to parse a few of them.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use binrw::prelude::*;
+use binrw::io::{Read, Seek};
+
+use crate::{
+    read_ptr64, read_ptr_array, BntxHeader, BntxStr, ByteOrder, Error, SurfaceFormat, TextureInfo,
+    TextureKind, TileMode,
+};
+
+/// The metadata of one texture in a file, as found by [`scan`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BntxIndexEntry {
+    /// The file the texture is in
+    pub path: PathBuf,
+    /// The texture's position in the file
+    pub index: usize,
+    pub name: String,
+    pub kind: TextureKind,
+    pub info: TextureInfo,
+    /// Size in bytes of the texture's data, every mip level and array layer, as stored
+    pub data_size: u32,
+}
+
+/// Reads the textures' metadata from every file in `paths`, in parallel. Entries are in the
+/// same order as `paths`, and then in the order of the textures in each file. Files that
+/// can't be read or aren't BNTX files are skipped; use [`scan_file`] to find out why.
+pub fn scan<I, P>(paths: I) -> Vec<BntxIndexEntry>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    let paths: Vec<PathBuf> = paths.into_iter().map(|path| path.as_ref().to_owned()).collect();
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+
+    let next = AtomicUsize::new(0);
+    let files = Mutex::new(Vec::with_capacity(paths.len()));
+    thread::scope(|scope| {
+        for _ in 0..threads.min(paths.len()) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(i) else { break };
+
+                if let Ok(entries) = scan_file(path) {
+                    files.lock().unwrap().push((i, entries));
+                }
+            });
+        }
+    });
+
+    let mut files = files.into_inner().unwrap();
+    files.sort_by_key(|&(i, _)| i);
+
+    files.into_iter().flat_map(|(_, entries)| entries).collect()
+}
+
+/// Reads the textures' metadata from a single file, without reading their data. With the
+/// `zstd` or `yaz0` features, compressed files are decompressed first, like in
+/// [`BntxFile::open`](crate::BntxFile::open).
+pub fn scan_file<P: AsRef<Path>>(path: P) -> Result<Vec<BntxIndexEntry>, Error> {
+    let path = path.as_ref();
+    trace_span!("scan_file", path = %path.display());
+
+    let mut file = BufReader::new(File::open(path)?);
+
+    #[cfg(any(feature = "zstd", feature = "yaz0"))]
+    {
+        if let Some(data) = crate::compression::decompress(&mut file)? {
+            return read_entries(&mut std::io::Cursor::new(data), path);
+        }
+    }
+
+    read_entries(&mut file, path)
+}
+
+fn read_entries<R: Read + Seek>(reader: &mut R, path: &Path) -> Result<Vec<BntxIndexEntry>, Error> {
+    let headers: Headers = reader.read_le()?;
+
+    let entries = headers.nx_header.textures.into_iter()
+        .enumerate()
+        .map(|(index, texture)| BntxIndexEntry {
+            path: path.to_owned(),
+            index,
+            name: texture.name.into(),
+            kind: texture.kind,
+            info: TextureInfo {
+                width: texture.width,
+                height: texture.height,
+                depth: texture.depth,
+                format: texture.format,
+                mip_count: texture.mips_count,
+                array_len: texture.array_len,
+                tile_mode: match texture.tile_mode {
+                    1 => TileMode::Linear,
+                    _ => TileMode::BlockLinear,
+                },
+                alignment: texture.align,
+            },
+            data_size: texture.image_size,
+        })
+        .collect();
+
+    Ok(entries)
+}
+
+/// The start of a file, as far as the texture headers
+#[binrw::binread]
+#[br(little)]
+struct Headers {
+    #[br(temp)]
+    header: BntxHeader,
+
+    #[br(is_little = header.bom == ByteOrder::LittleEndian)]
+    nx_header: NxHeader,
+}
+
+/// The NX header's texture pointers, without the dictionary or memory pool
+#[binrw::binread]
+#[br(magic = b"NX  ")]
+struct NxHeader {
+    #[br(temp)]
+    count: u32,
+    #[br(temp)]
+    info_ptr_array_ptr: u64,
+
+    #[br(args(count, info_ptr_array_ptr), parse_with = read_ptr_array)]
+    textures: Vec<TextureHeader>,
+}
+
+/// The fields of a `BRTI` section that describe the texture, skipping the pointers to its
+/// data and runtime objects
+#[derive(BinRead)]
+#[br(magic = b"BRTI")]
+struct TextureHeader {
+    // the next section offset, section size, flags and dimension
+    #[br(pad_before = 0xe)]
+    tile_mode: u16,
+    #[br(pad_before = 2)]
+    mips_count: u16,
+    #[br(pad_before = 4)]
+    format: SurfaceFormat,
+    #[br(pad_before = 4)]
+    width: u32,
+    height: u32,
+    depth: u32,
+    array_len: u32,
+    #[br(pad_before = 0x1c)]
+    image_size: u32,
+    align: u32,
+    #[br(pad_before = 4)]
+    kind: TextureKind,
+    #[br(parse_with = read_ptr64)]
+    name: BntxStr,
+}
@@ -0,0 +1,210 @@
+//! Structured dumps of BNTX files for reverse engineering, as printed by `bntx inspect`
+
+use std::io::{self, Cursor, Write};
+
+use crate::reloc::RelocationTable;
+use crate::{BntxFile, ByteOrder, RawSection, SectionIter, Texture};
+
+/// Options for [`inspect`]
+#[derive(Debug, Clone)]
+pub struct InspectOptions {
+    /// Magics of the sections to hexdump, such as `BRTI` or `_RLT`, or `all` for every section.
+    /// Case and trailing spaces are ignored, so `nx` matches the `NX  ` header.
+    pub hexdump: Vec<String>,
+    /// The most bytes dumped from each section, since the `BRTD` section holds every texture's
+    /// data
+    pub hexdump_limit: usize,
+}
+
+impl Default for InspectOptions {
+    fn default() -> Self {
+        InspectOptions {
+            hexdump: Vec::new(),
+            hexdump_limit: 0x200,
+        }
+    }
+}
+
+/// Names of a section's fields, by offset from the start of the section
+type Fields = &'static [(usize, &'static str)];
+
+/// The fields of each known section
+const FIELDS: &[(&[u8; 4], Fields)] = &[
+    (b"BNTX", &[
+        (0x0, "magic"), (0x8, "version"), (0xc, "byte order"), (0xe, "revision"),
+        (0x10, "file name"), (0x16, "string pool"), (0x18, "relocation table"), (0x1c, "file size"),
+    ]),
+    (b"NX  ", &[
+        (0x0, "magic"), (0x4, "texture count"), (0x8, "texture pointers"), (0x10, "data block"),
+        (0x18, "dictionary"), (0x20, "memory pool"),
+    ]),
+    (b"_STR", &[
+        (0x0, "magic"), (0x4, "next section"), (0x8, "size"), (0x10, "string count"), (0x14, "strings"),
+    ]),
+    (b"_DIC", &[(0x0, "magic"), (0x4, "node count"), (0x8, "root node")]),
+    (b"BRTI", &[
+        (0x0, "magic"), (0x4, "next section"), (0x8, "size"), (0x10, "flags"), (0x11, "dimensions"),
+        (0x12, "tile mode"), (0x14, "swizzle"), (0x16, "mip count"), (0x18, "sample count"),
+        (0x1c, "format"), (0x20, "access flags"), (0x24, "width"), (0x28, "height"), (0x2c, "depth"),
+        (0x30, "array length"), (0x34, "block height"), (0x38, "reserved"), (0x50, "image size"),
+        (0x54, "alignment"), (0x58, "channel mapping"), (0x5c, "type"), (0x60, "name"), (0x68, "parent"),
+        (0x70, "mip offsets"), (0x78, "user data"), (0x80, "texture"), (0x88, "texture view"),
+        (0x90, "descriptor slot"), (0x98, "user data dictionary"),
+    ]),
+    (b"BRTD", &[(0x0, "magic"), (0x4, "next section"), (0x8, "size"), (0x10, "texture data")]),
+    (b"_RLT", &[(0x0, "magic"), (0x4, "position"), (0x8, "section count"), (0x10, "sections")]),
+];
+
+const HEXDUMP_WIDTH: usize = 16;
+
+/// Writes the header, each texture's metadata, the relocation table's entries and hexdumps of
+/// the sections selected in `options` to `out`, with the fields of known sections labelled.
+/// Files that don't parse still have their sections listed and dumped, along with why parsing
+/// failed, since those are often the files worth inspecting.
+pub fn inspect<W: Write>(data: &[u8], options: &InspectOptions, out: &mut W) -> io::Result<()> {
+    let sections: Vec<_> = SectionIter::new(data).collect();
+
+    match BntxFile::from_reader(Cursor::new(data)) {
+        Ok(file) => write_file(&file, data, out)?,
+        Err(err) => writeln!(out, "failed to parse: {}", err)?,
+    }
+
+    writeln!(out, "\nsections")?;
+    for section in &sections {
+        writeln!(out, "  {}  0x{:08x}  0x{:x} bytes", section.name(), section.offset, section.size)?;
+    }
+
+    if sections.iter().any(|section| &section.magic == b"_RLT") {
+        match RelocationTable::from_file(&mut Cursor::new(data)) {
+            Ok(table) => write_relocations(&table, out)?,
+            Err(err) => writeln!(out, "\nfailed to read the relocation table: {}", err)?,
+        }
+    }
+
+    for section in sections.iter().filter(|section| is_selected(section, &options.hexdump)) {
+        write_hexdump(data, section, options.hexdump_limit, out)?;
+    }
+
+    Ok(())
+}
+
+fn write_file<W: Write>(file: &BntxFile, data: &[u8], out: &mut W) -> io::Result<()> {
+    let header = &file.header;
+    writeln!(out, "header")?;
+    writeln!(out, "  version     {}.{}", header.version.1, header.version.0)?;
+    writeln!(out, "  revision    0x{:x}", header.inner.revision)?;
+    writeln!(out, "  byte order  {}", match header.bom {
+        ByteOrder::LittleEndian => "little endian",
+        ByteOrder::BigEndian => "big endian",
+    })?;
+    writeln!(out, "  file name   {}", header.inner.file_name.chars)?;
+
+    writeln!(out, "\ntextures ({})", file.nx_header.textures.len())?;
+    for texture in file.textures() {
+        write_texture(texture, out)?;
+    }
+
+    let unparsed = BntxFile::unparsed_ranges_in(data);
+    if !unparsed.is_empty() {
+        writeln!(out, "\nunparsed data")?;
+        for range in unparsed {
+            writeln!(out, "  0x{:08x} to 0x{:08x}", range.start, range.end)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_texture<W: Write>(texture: &Texture, out: &mut W) -> io::Result<()> {
+    let info = texture.info();
+    writeln!(out, "  {}", texture)?;
+    writeln!(
+        out,
+        "    {:?}, {:?} tiling, block height {}, swizzle {}, alignment 0x{:x}",
+        texture.dimension(),
+        info.tile_mode,
+        1u32.checked_shl(texture.size_range as u32).unwrap_or(0),
+        texture.swizzle,
+        info.alignment,
+    )?;
+    writeln!(
+        out,
+        "    {:?}, flags 0x{:x}, channel mapping 0x{:08x}, image size 0x{:x}",
+        texture.kind, texture.flags, texture.comp_sel, texture.image_size,
+    )?;
+
+    let offsets: Vec<_> = texture.mip_offsets.iter().map(|offset| format!("0x{:x}", offset)).collect();
+    writeln!(out, "    mip offsets {}", offsets.join(", "))
+}
+
+fn write_relocations<W: Write>(table: &RelocationTable, out: &mut W) -> io::Result<()> {
+    writeln!(out, "\nrelocations")?;
+    for (i, section) in table.sections.iter().enumerate() {
+        writeln!(
+            out,
+            "  section {}: 0x{:08x} to 0x{:08x}, {} entries",
+            i,
+            section.position,
+            section.position as u64 + section.size as u64,
+            section.count,
+        )?;
+
+        for entry in table.section_entries(section) {
+            writeln!(
+                out,
+                "    0x{:08x}  {} x {} pointers, {} padding",
+                entry.position, entry.struct_count, entry.offset_count, entry.padding_count,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn is_selected(section: &RawSection, selected: &[String]) -> bool {
+    let name = section.name();
+
+    selected.iter().any(|selected| {
+        selected.eq_ignore_ascii_case("all") || selected.trim_end().eq_ignore_ascii_case(name.trim_end())
+    })
+}
+
+/// Dumps a section 16 bytes to a row, labelling each row with the fields that start in it
+fn write_hexdump<W: Write>(data: &[u8], section: &RawSection, limit: usize, out: &mut W) -> io::Result<()> {
+    writeln!(out, "\n{} at 0x{:08x}", section.name().trim_end(), section.offset)?;
+
+    let end = section.offset.saturating_add(section.size).min(data.len());
+    let bytes = data.get(section.offset..end).unwrap_or_default();
+    let shown = &bytes[..bytes.len().min(limit)];
+
+    let fields = FIELDS.iter()
+        .find(|(magic, _)| **magic == section.magic)
+        .map(|&(_, fields)| fields)
+        .unwrap_or_default();
+
+    for (row, chunk) in shown.chunks(HEXDUMP_WIDTH).enumerate() {
+        let start = row * HEXDUMP_WIDTH;
+        let hex: Vec<_> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let text: String = chunk.iter()
+            .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+            .collect();
+
+        write!(out, "  {:08x}  {:<47}  |{:<16}|", section.offset + start, hex.join(" "), text)?;
+
+        let labels: Vec<_> = fields.iter()
+            .filter(|(offset, _)| (start..start + HEXDUMP_WIDTH).contains(offset))
+            .map(|&(_, label)| label)
+            .collect();
+        if !labels.is_empty() {
+            write!(out, "  {}", labels.join(", "))?;
+        }
+
+        writeln!(out)?;
+    }
+
+    if bytes.len() > shown.len() {
+        writeln!(out, "  ... 0x{:x} more bytes", bytes.len() - shown.len())?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,223 @@
+use crate::SurfaceFormat;
+
+/// `VkFormat` values for each format. The packed 16 and 32-bit formats use the Vulkan formats
+/// with matching bit layouts, which have their channels named from the most significant bits.
+const VK_FORMATS: &[(SurfaceFormat, u32)] = &[
+    (SurfaceFormat::R8_UNORM, 9),
+    // VK_FORMAT_A4R4G4B4_UNORM_PACK16
+    (SurfaceFormat::R4G4B4A4_UNORM, 1000340000),
+    // VK_FORMAT_A1R5G5B5_UNORM_PACK16
+    (SurfaceFormat::R5G5B5A1_UNORM, 8),
+    // VK_FORMAT_R5G6B5_UNORM_PACK16
+    (SurfaceFormat::R5G6B5_UNORM, 4),
+    (SurfaceFormat::R8G8B8A8_UNORM, 37),
+    (SurfaceFormat::R8G8B8A8_SRGB, 43),
+    (SurfaceFormat::B8G8R8A8_UNORM, 44),
+    (SurfaceFormat::B8G8R8A8_SRGB, 50),
+    // VK_FORMAT_A2B10G10R10_UNORM_PACK32
+    (SurfaceFormat::R10G10B10A2_UNORM, 64),
+    (SurfaceFormat::R16G16B16A16_FLOAT, 97),
+    (SurfaceFormat::R32G32B32A32_FLOAT, 109),
+    // the RGBA variants, since BC1 can have 1-bit alpha
+    (SurfaceFormat::BC1_UNORM, 133),
+    (SurfaceFormat::BC1_SRGB, 134),
+    (SurfaceFormat::BC2_UNORM, 135),
+    (SurfaceFormat::BC2_SRGB, 136),
+    (SurfaceFormat::BC3_UNORM, 137),
+    (SurfaceFormat::BC3_SRGB, 138),
+    (SurfaceFormat::BC4_UNORM, 139),
+    (SurfaceFormat::BC4_SNORM, 140),
+    (SurfaceFormat::BC5_UNORM, 141),
+    (SurfaceFormat::BC5_SNORM, 142),
+    (SurfaceFormat::BC6H_UF16, 143),
+    (SurfaceFormat::BC6H_SF16, 144),
+    (SurfaceFormat::BC7_UNORM, 145),
+    (SurfaceFormat::BC7_SRGB, 146),
+    (SurfaceFormat::ETC2_RGB8_UNORM, 147),
+    (SurfaceFormat::ETC2_RGB8_SRGB, 148),
+    (SurfaceFormat::ETC2_RGB8A1_UNORM, 149),
+    (SurfaceFormat::ETC2_RGB8A1_SRGB, 150),
+    (SurfaceFormat::ETC2_RGBA8_UNORM, 151),
+    (SurfaceFormat::ETC2_RGBA8_SRGB, 152),
+    (SurfaceFormat::EAC_R11_UNORM, 153),
+    (SurfaceFormat::EAC_R11_SNORM, 154),
+    (SurfaceFormat::EAC_R11G11_UNORM, 155),
+    (SurfaceFormat::EAC_R11G11_SNORM, 156),
+];
+
+/// `VK_FORMAT_BC1_RGB_UNORM_BLOCK` and `VK_FORMAT_BC1_RGB_SRGB_BLOCK`, which share BC1's data
+const VK_FORMAT_BC1_RGB: [(u32, SurfaceFormat); 2] = [
+    (131, SurfaceFormat::BC1_UNORM),
+    (132, SurfaceFormat::BC1_SRGB),
+];
+
+impl SurfaceFormat {
+    /// The raw `VkFormat` value with the same data layout, for uploading texture data to Vulkan
+    /// as-is. Returns `None` for unknown formats.
+    pub fn to_vk_format(self) -> Option<u32> {
+        VK_FORMATS.iter()
+            .find(|(format, _)| *format == self)
+            .map(|&(_, vk_format)| vk_format)
+    }
+
+    /// The format with the same data layout as a raw `VkFormat` value, if there is one
+    pub fn from_vk_format(vk_format: u32) -> Option<Self> {
+        VK_FORMATS.iter()
+            .map(|&(format, vk)| (vk, format))
+            .chain(VK_FORMAT_BC1_RGB.iter().copied())
+            .find(|&(vk, _)| vk == vk_format)
+            .map(|(_, format)| format)
+    }
+}
+
+/// An OpenGL format triple, as passed to `glTexImage2D`. Compressed formats only have an
+/// internal format, for `glCompressedTexImage2D`, and use 0 for `format` and `ty`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GlFormat {
+    pub internal_format: u32,
+    pub format: u32,
+    pub ty: u32,
+}
+
+const GL_RED: u32 = 0x1903;
+const GL_RGB: u32 = 0x1907;
+const GL_RGBA: u32 = 0x1908;
+const GL_BGRA: u32 = 0x80e1;
+
+const GL_UNSIGNED_BYTE: u32 = 0x1401;
+const GL_FLOAT: u32 = 0x1406;
+const GL_HALF_FLOAT: u32 = 0x140b;
+const GL_UNSIGNED_SHORT_5_6_5: u32 = 0x8363;
+const GL_UNSIGNED_SHORT_4_4_4_4_REV: u32 = 0x8365;
+const GL_UNSIGNED_SHORT_1_5_5_5_REV: u32 = 0x8366;
+const GL_UNSIGNED_INT_2_10_10_10_REV: u32 = 0x8368;
+
+const fn gl(internal_format: u32, format: u32, ty: u32) -> GlFormat {
+    GlFormat { internal_format, format, ty }
+}
+
+const fn gl_compressed(internal_format: u32) -> GlFormat {
+    GlFormat { internal_format, format: 0, ty: 0 }
+}
+
+/// GL formats for each format. The `_REV` packed types store the first channel in the least
+/// significant bits, which with `GL_BGRA` matches the packed formats' layouts.
+const GL_FORMATS: &[(SurfaceFormat, GlFormat)] = &[
+    // GL_R8
+    (SurfaceFormat::R8_UNORM, gl(0x8229, GL_RED, GL_UNSIGNED_BYTE)),
+    // GL_RGBA4
+    (SurfaceFormat::R4G4B4A4_UNORM, gl(0x8056, GL_BGRA, GL_UNSIGNED_SHORT_4_4_4_4_REV)),
+    // GL_RGB5_A1
+    (SurfaceFormat::R5G5B5A1_UNORM, gl(0x8057, GL_BGRA, GL_UNSIGNED_SHORT_1_5_5_5_REV)),
+    // GL_RGB565
+    (SurfaceFormat::R5G6B5_UNORM, gl(0x8d62, GL_RGB, GL_UNSIGNED_SHORT_5_6_5)),
+    // GL_RGBA8 and GL_SRGB8_ALPHA8
+    (SurfaceFormat::R8G8B8A8_UNORM, gl(0x8058, GL_RGBA, GL_UNSIGNED_BYTE)),
+    (SurfaceFormat::R8G8B8A8_SRGB, gl(0x8c43, GL_RGBA, GL_UNSIGNED_BYTE)),
+    (SurfaceFormat::B8G8R8A8_UNORM, gl(0x8058, GL_BGRA, GL_UNSIGNED_BYTE)),
+    (SurfaceFormat::B8G8R8A8_SRGB, gl(0x8c43, GL_BGRA, GL_UNSIGNED_BYTE)),
+    // GL_RGB10_A2
+    (SurfaceFormat::R10G10B10A2_UNORM, gl(0x8059, GL_RGBA, GL_UNSIGNED_INT_2_10_10_10_REV)),
+    // GL_RGBA16F and GL_RGBA32F
+    (SurfaceFormat::R16G16B16A16_FLOAT, gl(0x881a, GL_RGBA, GL_HALF_FLOAT)),
+    (SurfaceFormat::R32G32B32A32_FLOAT, gl(0x8814, GL_RGBA, GL_FLOAT)),
+    // EXT_texture_compression_s3tc and EXT_texture_sRGB
+    (SurfaceFormat::BC1_UNORM, gl_compressed(0x83f1)),
+    (SurfaceFormat::BC1_SRGB, gl_compressed(0x8c4d)),
+    (SurfaceFormat::BC2_UNORM, gl_compressed(0x83f2)),
+    (SurfaceFormat::BC2_SRGB, gl_compressed(0x8c4e)),
+    (SurfaceFormat::BC3_UNORM, gl_compressed(0x83f3)),
+    (SurfaceFormat::BC3_SRGB, gl_compressed(0x8c4f)),
+    // ARB_texture_compression_rgtc
+    (SurfaceFormat::BC4_UNORM, gl_compressed(0x8dbb)),
+    (SurfaceFormat::BC4_SNORM, gl_compressed(0x8dbc)),
+    (SurfaceFormat::BC5_UNORM, gl_compressed(0x8dbd)),
+    (SurfaceFormat::BC5_SNORM, gl_compressed(0x8dbe)),
+    // ARB_texture_compression_bptc
+    (SurfaceFormat::BC6H_SF16, gl_compressed(0x8e8e)),
+    (SurfaceFormat::BC6H_UF16, gl_compressed(0x8e8f)),
+    (SurfaceFormat::BC7_UNORM, gl_compressed(0x8e8c)),
+    (SurfaceFormat::BC7_SRGB, gl_compressed(0x8e8d)),
+    // ETC2 and EAC, from OpenGL 4.3
+    (SurfaceFormat::EAC_R11_UNORM, gl_compressed(0x9270)),
+    (SurfaceFormat::EAC_R11_SNORM, gl_compressed(0x9271)),
+    (SurfaceFormat::EAC_R11G11_UNORM, gl_compressed(0x9272)),
+    (SurfaceFormat::EAC_R11G11_SNORM, gl_compressed(0x9273)),
+    (SurfaceFormat::ETC2_RGB8_UNORM, gl_compressed(0x9274)),
+    (SurfaceFormat::ETC2_RGB8_SRGB, gl_compressed(0x9275)),
+    (SurfaceFormat::ETC2_RGB8A1_UNORM, gl_compressed(0x9276)),
+    (SurfaceFormat::ETC2_RGB8A1_SRGB, gl_compressed(0x9277)),
+    (SurfaceFormat::ETC2_RGBA8_UNORM, gl_compressed(0x9278)),
+    (SurfaceFormat::ETC2_RGBA8_SRGB, gl_compressed(0x9279)),
+];
+
+impl SurfaceFormat {
+    /// The OpenGL format triple for uploading the format's deswizzled data as-is. Returns
+    /// `None` for unknown formats.
+    pub fn to_gl_format(self) -> Option<GlFormat> {
+        GL_FORMATS.iter()
+            .find(|(format, _)| *format == self)
+            .map(|&(_, gl_format)| gl_format)
+    }
+
+    /// The format matching an OpenGL format triple, if there is one. `format` and `ty` are
+    /// ignored for compressed internal formats.
+    pub fn from_gl_format(gl_format: GlFormat) -> Option<Self> {
+        GL_FORMATS.iter()
+            .find(|(format, gl)| {
+                gl.internal_format == gl_format.internal_format
+                    && (format.is_compressed() || *gl == gl_format)
+            })
+            .map(|&(format, _)| format)
+    }
+}
+
+/// `DXGI_FORMAT` values for each format. DXGI names channels from the least significant bits,
+/// so the packed formats' red-highest layouts are its `B...R` formats.
+const DXGI_FORMATS: &[(SurfaceFormat, u32)] = &[
+    (SurfaceFormat::R8_UNORM, 61),
+    // DXGI_FORMAT_B4G4R4A4_UNORM
+    (SurfaceFormat::R4G4B4A4_UNORM, 115),
+    // DXGI_FORMAT_B5G5R5A1_UNORM
+    (SurfaceFormat::R5G5B5A1_UNORM, 86),
+    // DXGI_FORMAT_B5G6R5_UNORM
+    (SurfaceFormat::R5G6B5_UNORM, 85),
+    (SurfaceFormat::R8G8B8A8_UNORM, 28),
+    (SurfaceFormat::R8G8B8A8_SRGB, 29),
+    (SurfaceFormat::B8G8R8A8_UNORM, 87),
+    (SurfaceFormat::B8G8R8A8_SRGB, 91),
+    (SurfaceFormat::R10G10B10A2_UNORM, 24),
+    (SurfaceFormat::R16G16B16A16_FLOAT, 10),
+    (SurfaceFormat::R32G32B32A32_FLOAT, 2),
+    (SurfaceFormat::BC1_UNORM, 71),
+    (SurfaceFormat::BC1_SRGB, 72),
+    (SurfaceFormat::BC2_UNORM, 74),
+    (SurfaceFormat::BC2_SRGB, 75),
+    (SurfaceFormat::BC3_UNORM, 77),
+    (SurfaceFormat::BC3_SRGB, 78),
+    (SurfaceFormat::BC4_UNORM, 80),
+    (SurfaceFormat::BC4_SNORM, 81),
+    (SurfaceFormat::BC5_UNORM, 83),
+    (SurfaceFormat::BC5_SNORM, 84),
+    (SurfaceFormat::BC6H_UF16, 95),
+    (SurfaceFormat::BC6H_SF16, 96),
+    (SurfaceFormat::BC7_UNORM, 98),
+    (SurfaceFormat::BC7_SRGB, 99),
+];
+
+impl SurfaceFormat {
+    /// The raw `DXGI_FORMAT` value with the same data layout, as used in DDS files and by
+    /// DirectXTex. Returns `None` for unknown formats and ETC2/EAC, which DXGI doesn't have.
+    pub fn to_dxgi_format(self) -> Option<u32> {
+        DXGI_FORMATS.iter()
+            .find(|(format, _)| *format == self)
+            .map(|&(_, dxgi_format)| dxgi_format)
+    }
+
+    /// The format with the same data layout as a raw `DXGI_FORMAT` value, if there is one
+    pub fn from_dxgi_format(dxgi_format: u32) -> Option<Self> {
+        DXGI_FORMATS.iter()
+            .find(|&&(_, dxgi)| dxgi == dxgi_format)
+            .map(|&(format, _)| format)
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+    align, BntxFile, BntxStr, DictSection, StrSection, Texture,
+    BNTX_HEADER_SIZE, BRTD_DATA_ALIGN, DATA_PTR_SIZE, EMPTY_STR_SIZE, HEADER_SIZE,
+    NX_HEADER_SIZE, SIZE_OF_BRTD, SIZE_OF_BRTI, SIZE_OF_GFX_OBJECT, STR_HEADER_SIZE,
+};
+use crate::reloc::RelocationTable;
+
+/// Where a section of a file is written, as listed by [`BntxFile::layout`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionInfo {
+    /// The section's magic, such as `BRTI` or `_STR`, or a description for parts of the file
+    /// without one
+    pub name: &'static str,
+    /// The texture the section belongs to, for texture headers and data
+    pub texture: Option<String>,
+    pub offset: usize,
+    pub size: usize,
+    /// Bytes of padding between the end of the section and the start of the next one
+    pub padding: usize,
+}
+
+/// Offsets of the NX header's pointers, from the start of the header
+const NX_INFO_PTR_ARRAY_FIELD: usize = 0x8;
+const NX_DATA_BLOCK_FIELD: usize = 0x10;
+const NX_DICT_FIELD: usize = 0x18;
+const NX_MEM_POOL_FIELD: usize = 0x20;
+
+/// Offsets of everything belonging to a single texture
+pub(crate) struct TextureLayout {
+    pub brti: usize,
+    pub mip_table: usize,
+    pub data: usize,
+    /// Whether the data is shared with an earlier texture with identical data, rather than
+    /// written again
+    pub shared: bool,
+}
+
+/// Where each section of a file ends up when written, computed before writing anything so
+/// sections can point to each other regardless of the order they're written in. Public only so
+/// it can be passed to the sections' `BinWrite` impls, since the module itself is private.
+pub struct Layout {
+    /// Where the NX header's memory pool pointer points, or 0 for a null pointer
+    pub(crate) mem_pool_ptr: usize,
+    pub(crate) info_ptr_array_offset: usize,
+
+    pub(crate) str_section: StrSection,
+    pub(crate) str_section_offset: usize,
+    pub(crate) str_section_size: usize,
+
+    pub(crate) dict: DictSection,
+    pub(crate) dict_offset: usize,
+
+    pub(crate) textures: Vec<TextureLayout>,
+    pub(crate) headers_end: usize,
+
+    pub(crate) brtd_offset: usize,
+    pub(crate) brtd_size: usize,
+
+    pub(crate) reloc_table: RelocationTable,
+    pub(crate) reloc_table_offset: usize,
+
+    pub(crate) file_size: usize,
+}
+
+impl Layout {
+    pub fn new(file: &BntxFile) -> Self {
+        trace_span!("layout");
+
+        let textures = &file.nx_header.textures;
+
+        // like NintendoWare's tools, every distinct name is pooled once, sorted by its bytes
+        let strings: BTreeSet<&[u8]> = std::iter::once(&file.header.inner.file_name.bytes[..])
+            .chain(textures.iter().map(Texture::name_bytes))
+            .collect();
+
+        let str_section = StrSection {
+            strings: strings.into_iter()
+                .map(|string| BntxStr::from(string.to_vec()))
+                .collect(),
+        };
+
+        let mem_pool = &file.nx_header.mem_pool;
+        let mem_pool_ptr = file.nx_header.mem_pool_info
+            .filter(|&offset| offset < mem_pool.len())
+            .map_or(0, |offset| HEADER_SIZE + offset);
+        let info_ptr_array_offset = HEADER_SIZE + mem_pool.len();
+        let str_section_offset = info_ptr_array_offset + (textures.len() * DATA_PTR_SIZE);
+        let str_section_size = align(str_section.get_size(), 8);
+
+        let dict = DictSection::from_names(textures.iter().map(Texture::name_bytes));
+        let dict_offset = str_section_offset + str_section_size;
+
+        let mut pos = dict_offset + dict.get_size();
+        let headers: Vec<_> = textures.iter()
+            .map(|texture| {
+                let brti = pos;
+                let mip_table = brti + SIZE_OF_BRTI + (SIZE_OF_GFX_OBJECT * 2);
+                pos = mip_table + (texture.mip_offsets.len() * DATA_PTR_SIZE);
+
+                (brti, mip_table)
+            })
+            .collect();
+        let headers_end = pos;
+
+        let brtd_offset = align(headers_end + SIZE_OF_BRTD, BRTD_DATA_ALIGN) - SIZE_OF_BRTD;
+
+        let mut pos = brtd_offset + SIZE_OF_BRTD;
+        let mut written = HashMap::new();
+        let textures: Vec<_> = textures.iter()
+            .zip(headers)
+            .map(|(texture, (brti, mip_table))| {
+                let align = (texture.align as usize).max(1);
+                if file.nx_header.share_identical_data {
+                    if let Some(&data) = written.get(&texture.texture.0[..]) {
+                        if data % align == 0 {
+                            return TextureLayout { brti, mip_table, data, shared: true };
+                        }
+                    }
+                }
+
+                let data = pos.next_multiple_of(align);
+                pos = data + texture.texture.0.len();
+                written.entry(&texture.texture.0[..]).or_insert(data);
+
+                TextureLayout { brti, mip_table, data, shared: false }
+            })
+            .collect();
+
+        let brtd_size = pos - brtd_offset;
+        let reloc_table_offset = align(pos, 8);
+
+        // pointers into the headers
+        let mut header_ptrs = vec![
+            BNTX_HEADER_SIZE + NX_INFO_PTR_ARRAY_FIELD,
+            BNTX_HEADER_SIZE + NX_DICT_FIELD,
+        ];
+
+        if mem_pool_ptr != 0 {
+            header_ptrs.push(BNTX_HEADER_SIZE + NX_MEM_POOL_FIELD);
+        }
+
+        header_ptrs.extend((0..textures.len()).map(|i| info_ptr_array_offset + (i * DATA_PTR_SIZE)));
+        header_ptrs.extend(dict.key_pointer_offsets().map(|offset| dict_offset + offset));
+
+        for tex in &textures {
+            // name, parent, mip table, texture, texture view
+            header_ptrs.extend([0x60, 0x68, 0x70, 0x80, 0x88].iter().map(|offset| tex.brti + offset));
+        }
+
+        // pointers into the texture data
+        let mut data_ptrs = vec![BNTX_HEADER_SIZE + NX_DATA_BLOCK_FIELD];
+        for (texture, tex) in file.nx_header.textures.iter().zip(&textures) {
+            data_ptrs.extend((0..texture.mip_offsets.len()).map(|i| tex.mip_table + (i * DATA_PTR_SIZE)));
+        }
+
+        let reloc_table = RelocationTable::from_pointers(&[
+            (0, headers_end, header_ptrs),
+            (brtd_offset, brtd_size, data_ptrs),
+        ]);
+
+        let file_size = reloc_table_offset + reloc_table.get_size();
+        trace_event!(file_size, brtd_size, textures = textures.len());
+
+        Layout {
+            mem_pool_ptr,
+            info_ptr_array_offset,
+            str_section,
+            str_section_offset,
+            str_section_size,
+            dict,
+            dict_offset,
+            textures,
+            headers_end,
+            brtd_offset,
+            brtd_size,
+            reloc_table,
+            reloc_table_offset,
+            file_size,
+        }
+    }
+
+    /// Every section of the file in the order they're written, with the padding after each
+    pub fn sections(&self, file: &BntxFile) -> Vec<SectionInfo> {
+        let textures = &file.nx_header.textures;
+        let section = |name, texture: Option<&Texture>, offset, size| SectionInfo {
+            name,
+            texture: texture.map(|texture| texture.name().to_owned()),
+            offset,
+            size,
+            padding: 0,
+        };
+
+        let mut sections = vec![
+            section("BNTX", None, 0, BNTX_HEADER_SIZE),
+            section("NX", None, BNTX_HEADER_SIZE, NX_HEADER_SIZE),
+            section("memory pool", None, HEADER_SIZE, file.nx_header.mem_pool.len()),
+            section("texture pointers", None, self.info_ptr_array_offset, textures.len() * DATA_PTR_SIZE),
+            section("_STR", None, self.str_section_offset, self.str_section.get_size()),
+            section("_DIC", None, self.dict_offset, self.dict.get_size()),
+        ];
+
+        for (texture, tex) in textures.iter().zip(&self.textures) {
+            let end = tex.mip_table + texture.mip_offsets.len() * DATA_PTR_SIZE;
+            sections.push(section("BRTI", Some(texture), tex.brti, end - tex.brti));
+        }
+
+        sections.push(section("BRTD", None, self.brtd_offset, SIZE_OF_BRTD));
+        for (texture, tex) in textures.iter().zip(&self.textures).filter(|(_, tex)| !tex.shared) {
+            sections.push(section("texture data", Some(texture), tex.data, texture.texture.0.len()));
+        }
+
+        sections.push(section("_RLT", None, self.reloc_table_offset, self.reloc_table.get_size()));
+
+        let starts: Vec<_> = sections.iter().skip(1).map(|section| section.offset).collect();
+        for (section, next) in sections.iter_mut().zip(starts.into_iter().chain([self.file_size])) {
+            section.padding = next - (section.offset + section.size);
+        }
+
+        sections
+    }
+
+    /// Distance from the start of the string pool to the section after it, which the pool's
+    /// header stores
+    pub fn str_section_next(&self) -> usize {
+        self.textures.first().map(|tex| tex.brti).unwrap_or(self.brtd_offset) - self.str_section_offset
+    }
+
+    /// Distance from the start of a texture's BRTI to the section after it, which the BRTI
+    /// stores
+    pub fn texture_section_size(&self, index: usize) -> usize {
+        self.textures.get(index + 1).map(|tex| tex.brti).unwrap_or(self.brtd_offset) - self.textures[index].brti
+    }
+
+    /// Offset of the given string within the string pool
+    pub fn string_offset(&self, string: &[u8]) -> usize {
+        let mut offset = self.str_section_offset + STR_HEADER_SIZE;
+        if string.is_empty() {
+            return offset;
+        }
+
+        offset += EMPTY_STR_SIZE;
+        for pooled in &self.str_section.strings {
+            if pooled.bytes == string {
+                return offset;
+            }
+
+            offset += BntxStr::size_of(&pooled.bytes);
+        }
+
+        unreachable!("string {:?} missing from the string pool", String::from_utf8_lossy(string))
+    }
+}
@@ -1,31 +1,125 @@
 use std::{fmt, io};
+use std::borrow::Cow;
+use std::convert::{TryFrom, TryInto};
+use std::collections::HashMap;
+use std::hash::Hasher;
 use std::path::Path;
-use binread::prelude::*;
-use binread::derive_binread;
-use binread::{FilePtr16, FilePtr32, FilePtr64, NullString};
+use std::ops::Range;
+use binrw::prelude::*;
+use binrw::io::{Read, Seek, SeekFrom};
+use binrw::{FilePtr32, NullString};
+#[cfg(feature = "write")]
+use binrw::Endian;
 
-use binwrite::{BinWrite, WriterOption};
+#[macro_use]
+mod trace;
 
 pub mod tegra_swizzle;
+use tegra_swizzle::{AddressTable, BlockHeight, SurfaceParams};
 
-#[derive(BinRead, PartialEq, Debug, Clone, Copy)]
+mod dict;
+use dict::DictSection;
+
+pub mod reloc;
+mod sections;
+pub use sections::{RawSection, SectionIter};
+
+#[cfg(feature = "write")]
+mod layout;
+#[cfg(feature = "write")]
+use layout::Layout;
+#[cfg(feature = "write")]
+pub use layout::SectionInfo;
+
+mod builder;
+pub use builder::{AlphaConversion, AtlasRegion, ColorSpace, ImageTransform, NormalMap, SizeAlignment, TextureBuilder};
+
+mod bcn;
+mod etc;
+mod format;
+pub use format::{EncodeOptions, GammaConversion, SurfaceFormat};
+mod interop;
+pub use interop::GlFormat;
+
+mod error;
+pub use error::Error;
+
+mod hash;
+use hash::Xxh64;
+
+mod compare;
+pub use compare::{compare, ChannelStats, CompareReport};
+
+#[cfg(feature = "tokio")]
+mod async_io;
+
+#[cfg(feature = "wgpu")]
+mod gpu;
+mod progress;
+pub use progress::Progress;
+use progress::NO_PROGRESS;
+mod preset;
+pub use preset::Preset;
+mod dds;
+mod png_export;
+pub use png_export::PngColorSpace;
+mod raw;
+pub use raw::RawLayout;
+mod sidecar;
+mod surface;
+pub use surface::Surface;
+mod usage;
+pub use usage::{GpuAccess, TextureFlags};
+mod container;
+pub use container::{SwizzledSurface, TextureContainer};
+pub mod index;
+#[cfg(feature = "write")]
+mod patch;
+#[cfg(feature = "write")]
+pub use patch::patch_file;
+#[cfg(feature = "batch")]
+pub mod batch;
+#[cfg(feature = "batch")]
+pub use batch::extract_archive;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+#[cfg(feature = "cli")]
+pub mod inspect;
+#[cfg(feature = "bfres")]
+mod bfres;
+#[cfg(feature = "preview")]
+mod preview;
+
+#[cfg(any(feature = "zstd", feature = "yaz0"))]
+mod compression;
+
+#[cfg_attr(feature = "write", binrw::binrw)]
+#[cfg_attr(not(feature = "write"), binrw::binread)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 enum ByteOrder {
-    #[br(magic = 0xFFFEu16)]
+    #[brw(magic = 0xFFFEu16)]
     LittleEndian,
-    #[br(magic = 0xFEFFu16)]
+    #[brw(magic = 0xFEFFu16)]
     BigEndian,
 }
 
-#[derive(BinRead, Debug)]
-#[br(magic = b"BNTX")]
+// files are always written little endian, whatever their byte order mark says
+#[cfg_attr(feature = "write", binrw::binrw)]
+#[cfg_attr(not(feature = "write"), binrw::binread)]
+#[derive(Debug, Clone)]
+#[brw(magic = b"BNTX")]
+#[bw(import(layout: &Layout))]
 struct BntxHeader {
-    #[br(pad_before = 4)]
+    #[brw(pad_before = 4)]
     version: (u16, u16),
 
-    #[br(big)]
+    #[brw(big)]
     bom: ByteOrder,
 
     #[br(is_little = bom == ByteOrder::LittleEndian)]
+    #[bw(args(layout))]
     inner: HeaderInner,
 }
 
@@ -33,287 +127,621 @@ const BNTX_HEADER_SIZE: usize = 0x20;
 const NX_HEADER_SIZE: usize = 0x28;
 const HEADER_SIZE: usize = BNTX_HEADER_SIZE + NX_HEADER_SIZE;
 const MEM_POOL_SIZE: usize = 0x150;
+/// Offset of the memory pool's info from the start of the memory pool, after the current
+/// memory pool pointer and the pool's offset base
+const MEM_POOL_INFO_OFFSET: usize = 0x10;
 const DATA_PTR_SIZE: usize = 8;
 
-const START_OF_STR_SECTION: usize = HEADER_SIZE + MEM_POOL_SIZE + DATA_PTR_SIZE;
 
+#[cfg(feature = "write")]
 const STR_HEADER_SIZE: usize = 0x14;
+#[cfg(feature = "write")]
 const EMPTY_STR_SIZE: usize = 4;
 
-const FILENAME_STR_OFFSET: usize = START_OF_STR_SECTION + STR_HEADER_SIZE + EMPTY_STR_SIZE;
-
-const BRTD_SECTION_START: usize = 0xFF0;
+#[cfg(feature = "write")]
 const SIZE_OF_BRTD: usize = 0x10;
-const START_OF_TEXTURE_DATA: usize = BRTD_SECTION_START + SIZE_OF_BRTD;
+#[cfg(feature = "write")]
+const BRTD_DATA_ALIGN: usize = 0x1000;
 
-impl BntxHeader {
-    fn write_options<W: io::Write>(
-        &self,
-        writer: &mut W,
-        options: &WriterOption,
-        parent: &BntxFile
-    ) -> io::Result<()> {
-        let start_of_reloc_section = (
-            START_OF_TEXTURE_DATA + parent.nx_header.info_ptr.texture.0.len()
-        ) as u32;
-        (
-            b"BNTX",
-            0u32,
-            self.version,
-            match self.bom {
-                ByteOrder::LittleEndian => b"\xFF\xFE",
-                ByteOrder::BigEndian => b"\xFE\xFF",
-            },
-            self.inner.revision,
-            FILENAME_STR_OFFSET as u32 + 2,
-            0u16,
-            START_OF_STR_SECTION as u16,
-            start_of_reloc_section,
-            start_of_reloc_section + (self.inner.reloc_table.get_size() as u32),
-        ).write_options(writer, options)
-    }
+/// The BNTX version and revision fields a file is written with, which some games check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Target {
+    /// The format version, stored as (minor, major)
+    pub version: (u16, u16),
+    pub revision: u16,
 }
 
-#[derive_binread]
-#[derive(Debug)]
-struct HeaderInner {
-    revision: u16,
-
-    #[br(parse_with = FilePtr32::parse, map = NullString::into_string)]
-    file_name: String,
-
-    #[br(pad_before = 2, parse_with = FilePtr16::parse)]
-    str_section: StrSection,
-
-    #[br(parse_with = FilePtr32::parse)]
-    reloc_table: RelocationTable,
-
-    #[br(temp)]
-    file_size: u32,
+impl Target {
+    /// Version 4.0, which most Switch games use and new files are created with
+    pub const V4: Target = Target { version: (0, 4), revision: 0x400c };
 }
 
-#[derive(BinRead, BinWrite, Debug)]
-struct RelocationSection {
-    pointer: u64,
-    position: u32,
-    size: u32,
-    index: u32,
-    count: u32,
+impl Default for Target {
+    fn default() -> Self {
+        Target::V4
+    }
 }
 
-const SIZE_OF_RELOC_SECTION: usize = size_of::<u64>() + (size_of::<u32>() * 4);
+/// The largest width or height, in pixels, a texture can be created with
+pub const MAX_TEXTURE_SIZE: u32 = 16384;
 
-#[derive(BinRead, BinWrite, Debug)]
-struct RelocationEntry {
-    position: u32,
-    struct_count: u16,
-    offset_count: u8,
-    padding_count: u8,
-}
+#[cfg_attr(feature = "write", binrw::binrw)]
+#[cfg_attr(not(feature = "write"), binrw::binread)]
+#[derive(Debug, Clone)]
+#[bw(import(layout: &Layout))]
+struct HeaderInner {
+    revision: u16,
 
-const SIZE_OF_RELOC_ENTRY: usize = size_of::<u32>() + size_of::<u16>() + (size_of::<u8>() * 2);
+    // points past the length, directly to the characters
+    #[br(parse_with = FilePtr32::parse, map = |name: NullString| BntxStr::from(name.0))]
+    #[bw(map = |name: &BntxStr| layout.string_offset(&name.bytes) as u32 + 2)]
+    file_name: BntxStr,
 
-#[derive_binread]
-#[derive(Debug)]
-#[br(magic = b"_RLT")]
-struct RelocationTable {
+    // the string pool, dictionary and relocation table are all regenerated on write
     #[br(temp)]
-    rlt_section_pos: u32,
-    
+    #[bw(try_calc = header_field("string section offset", layout.str_section_offset))]
+    #[brw(pad_before = 2)]
+    str_section_offset: u16,
+
     #[br(temp)]
-    count: u32,
+    #[bw(try_calc = header_field("relocation table offset", layout.reloc_table_offset))]
+    reloc_table_offset: u32,
 
-    #[br(pad_before = 4, count = count)]
-    sections: Vec<RelocationSection>,
-    
-    #[br(count = sections.iter().map(|x| x.count).sum::<u32>())]
-    entries: Vec<RelocationEntry>,
+    // recomputed on write, but checked against the data on read
+    #[br(temp, parse_with = read_file_size)]
+    #[bw(try_calc = header_field("size", layout.file_size))]
+    file_size: u32,
 }
 
-use core::mem::size_of;
-
-impl RelocationTable {
-    fn get_size(&self) -> usize {
-        b"_RLT".len() +
-        size_of::<u32>() +
-        size_of::<u32>() +
-        size_of::<u32>() +
-        (self.sections.len() * SIZE_OF_RELOC_SECTION) +
-        (self.entries.len() * SIZE_OF_RELOC_ENTRY)
-    }
-
-    fn write_options<W: io::Write>(&self, writer: &mut W, options: &WriterOption, parent: &BntxFile) -> io::Result<()> {
-        (
-            b"_RLT",
-            (START_OF_TEXTURE_DATA + parent.nx_header.info_ptr.texture.0.len()) as u32,
-            self.sections.len() as u32,
-            0u32,
-            &self.sections,
-            &self.entries
-        ).write_options(writer, options)
-    }
+/// An offset or size as stored in a header field, failing rather than truncating it for files
+/// too large for the field
+#[cfg(feature = "write")]
+fn header_field<T: TryFrom<usize>>(field: &'static str, value: usize) -> Result<T, Error> {
+    T::try_from(value).map_err(|_| Error::FileTooLarge { field, value: value as u64 })
 }
 
-#[derive_binread]
-#[derive(Debug)]
-#[br(magic = b"_STR")]
+/// The `_STR` section, containing the file name and the name of every texture
+#[cfg(feature = "write")]
+#[binrw::binwrite]
+#[bw(magic = b"_STR", import(layout: &Layout))]
 struct StrSection {
-    unk: u32,
-    unk2: u32,
-    unk3: u32,
-
-    #[br(temp)]
-    str_count: u32,
+    // offset of the next section, twice, then the reserved field
+    #[bw(calc = layout.str_section_next() as u32)]
+    next_section: u32,
+    #[bw(calc = next_section, pad_after = 4)]
+    size: u32,
 
-    #[br(temp)]
+    #[bw(calc = strings.len() as u32)]
+    count: u32,
+    #[bw(calc = BntxStr::from(String::new()))]
     empty: BntxStr,
 
-    #[br(count = str_count)]
+    #[bw(pad_after = layout.str_section_size - self.get_size())]
     strings: Vec<BntxStr>,
 }
 
-impl BinWrite for StrSection {
-    fn write_options<W: io::Write>(&self, writer: &mut W, options: &WriterOption) -> io::Result<()> {
-        (
-            b"_STR",
-            self.unk,
-            self.unk2,
-            self.unk3,
-            self.strings.len() as u32,
-            BntxStr::from(String::new()),
-            &self.strings,
-        ).write_options(writer, options)
-    }
-}
-
+#[cfg(feature = "write")]
 impl StrSection {
     fn get_size(&self) -> usize {
-        (5 * size_of::<u32>())
+        STR_HEADER_SIZE
             + EMPTY_STR_SIZE
             + self.strings.iter()
-                .map(|x| x.get_size())
+                .map(|x| BntxStr::size_of(&x.bytes))
                 .sum::<usize>()
     }
 }
 
-#[derive_binread]
-#[derive(BinWrite, Debug)]
+#[cfg_attr(feature = "write", binrw::binrw)]
+#[cfg_attr(not(feature = "write"), binrw::binread)]
+#[derive(Debug, Clone)]
 struct BntxStr {
+    // checked when written rather than when created, so a file with a name that's too long
+    // fails to write instead of being written with a truncated length
+    #[br(temp)]
+    #[bw(try_calc = BntxStr::stored_len(bytes))]
     len: u16,
 
-    #[br(align_after = 4, count = len, map = |x: Vec<u8>| String::from_utf8_lossy(&x).into_owned())]
-    #[binwrite(cstr, align_after(4))]
+    /// The string exactly as stored, which isn't always UTF-8, so names are written back
+    /// unchanged
+    // null terminated and padded to a multiple of 4 bytes
+    #[br(align_after = 4, count = len)]
+    #[bw(pad_after = BntxStr::size_of(bytes) - (size_of::<u16>() + bytes.len()))]
+    bytes: Vec<u8>,
+
+    /// A lossy UTF-8 copy of `bytes`
+    #[br(calc = String::from_utf8_lossy(&bytes).into_owned())]
+    #[bw(ignore)]
     chars: String,
 }
 
+#[cfg(any(feature = "write", feature = "wgpu"))]
 fn align(x: usize, n: usize) -> usize {
     (x + n - 1) & !(n - 1)
 }
 
 impl BntxStr {
-    fn get_size(&self) -> usize {
+    #[cfg(feature = "write")]
+    fn size_of(bytes: &[u8]) -> usize {
         align(
             size_of::<u16>()
-                + self.chars.bytes().len()
+                + bytes.len()
                 + 1,
             4
         )
     }
+
+    #[cfg(feature = "write")]
+    fn stored_len(bytes: &[u8]) -> Result<u16, String> {
+        u16::try_from(bytes.len()).map_err(|_| Error::NameTooLong { len: bytes.len() }.to_string())
+    }
+
+    /// Creates a string for a texture name given by the user, failing if it's empty or longer
+    /// than the 16-bit length can hold
+    fn try_new(name: &str) -> Result<Self, Error> {
+        if name.is_empty() {
+            return Err(Error::EmptyName);
+        }
+        if name.len() > u16::MAX as usize {
+            return Err(Error::NameTooLong { len: name.len() });
+        }
+
+        Ok(BntxStr::from(name.to_owned()))
+    }
 }
 
 impl From<String> for BntxStr {
     fn from(chars: String) -> Self {
         BntxStr {
-            len: chars.len() as u16,
+            bytes: chars.clone().into_bytes(),
             chars
         }
     }
 }
 
+impl From<Vec<u8>> for BntxStr {
+    fn from(bytes: Vec<u8>) -> Self {
+        BntxStr {
+            chars: String::from_utf8_lossy(&bytes).into_owned(),
+            bytes
+        }
+    }
+}
+
 impl From<BntxStr> for String {
     fn from(bntx_str: BntxStr) -> String {
         bntx_str.chars
     }
 }
 
-#[derive_binread]
-#[derive(Debug)]
-#[br(magic = b"NX  ")]
+#[cfg_attr(feature = "write", binrw::binrw)]
+#[cfg_attr(not(feature = "write"), binrw::binread)]
+#[derive(Debug, Clone)]
+#[brw(magic = b"NX  ")]
+#[bw(import(layout: &Layout))]
 struct NxHeader {
     #[br(temp)]
+    #[bw(calc = textures.len() as u32)]
     count: u32,
 
-    #[br(parse_with = read_double_indirect)]
-    info_ptr: BrtiSection,
+    #[br(temp)]
+    #[bw(calc = layout.info_ptr_array_offset as u64)]
+    info_ptr_array_ptr: u64,
+
+    // the textures are written after the other sections, see `BntxFile`'s `BinWrite` impl
+    #[br(args(count, info_ptr_array_ptr), parse_with = read_ptr_array)]
+    #[br(map = |textures: Vec<Texture>| textures.into_iter().map(Texture::with_pitch_alignment).collect())]
+    #[bw(ignore)]
+    textures: Vec<Texture>,
 
     #[br(temp)]
+    #[bw(calc = layout.brtd_offset as u64)]
     data_blk_ptr: u64,
 
-    #[br(parse_with = FilePtr64::parse)]
+    #[br(temp)]
+    #[bw(calc = layout.dict_offset as u64)]
+    dict_ptr: u64,
+
+    // the dictionary is regenerated on write, so files without one are read as if they had it
+    #[br(temp, args(dict_ptr), parse_with = read_optional_ptr)]
+    #[bw(ignore)]
+    file_dict: Option<DictSection>,
+
+    #[br(calc = dict_or_from_names(&file_dict, &textures))]
+    #[bw(ignore)]
     dict: DictSection,
-    dict_size: u64,
+
+    // points to the memory pool's info in the memory pool, recomputed on write
+    #[br(temp)]
+    #[bw(calc = layout.mem_pool_ptr as u64)]
+    mem_pool_ptr: u64,
+
+    #[br(temp, calc = mem_pool_size(info_ptr_array_ptr, &[data_blk_ptr, dict_ptr]))]
+    #[bw(ignore)]
+    file_mem_pool_size: Option<usize>,
+
+    /// The memory pool between the headers and the texture pointers, kept byte for byte. Some
+    /// games make it larger than usual. Files where the pointers don't come before the other
+    /// sections have no pool, and get an empty one of the usual size.
+    #[br(args(file_mem_pool_size), parse_with = read_mem_pool)]
+    #[bw(ignore)]
+    mem_pool: Vec<u8>,
+
+    /// Offset of the memory pool's info in the pool, which the NX header points to, or `None`
+    /// if the file leaves the pointer null
+    #[br(calc = mem_pool_info_offset(mem_pool_ptr, mem_pool.len()))]
+    #[bw(ignore)]
+    mem_pool_info: Option<usize>,
+
+    /// Whether textures with byte-identical data point to a single copy of it when written
+    #[br(default)]
+    #[bw(ignore)]
+    share_identical_data: bool,
+
+    /// Whether writing fails for textures games are known to choke on
+    #[br(default)]
+    #[bw(ignore)]
+    strict_game_compat: bool,
 }
 
 impl NxHeader {
-    fn write_options<W: io::Write>(
-        &self,
-        writer: &mut W,
-        options: &WriterOption,
-        parent: &BntxFile
-    ) -> io::Result<()> {
-        (
-            b"NX  ",
-            1u32, // count
-            (HEADER_SIZE + MEM_POOL_SIZE) as u64,
-            BRTD_SECTION_START as u64,
-            (START_OF_STR_SECTION + parent.header.inner.str_section.get_size()) as u64,
-            self.dict_size,
-        ).write_options(writer, options)
+    /// Records which file the textures' data was read from
+    fn with_source(mut self, source: &Option<FileSource>) -> Self {
+        for texture in &mut self.textures {
+            texture.source = texture.source.zip(source.as_ref())
+                .map(|(data, file)| DataSource { file: file.id, ..data });
+        }
+
+        self
     }
 }
 
-#[derive(BinRead, Debug)]
-#[br(magic = b"_DIC")]
-struct DictSection {
-    // lol
+fn dict_or_from_names(dict: &Option<DictSection>, textures: &[Texture]) -> DictSection {
+    dict.clone().unwrap_or_else(|| DictSection::from_names(textures.iter().map(Texture::name_bytes)))
 }
 
-static DICT_SECTION: &[u8] = b"\x5F\x44\x49\x43\x01\x00\x00\x00\xFF\xFF\xFF\xFF\x01\x00\x00\x00\xB4\x01\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x01\x00\xB8\x01\x00\x00\x00\x00\x00\x00";
+/// The size of the memory pool implied by where the texture pointer array starts, or `None`
+/// if the array isn't the first section after the headers
+fn mem_pool_size(info_ptr_array_ptr: u64, other_sections: &[u64]) -> Option<usize> {
+    let first_section = other_sections.iter()
+        .copied()
+        .filter(|&ptr| ptr != 0)
+        .min()
+        .unwrap_or(u64::MAX);
 
-impl DictSection {
-    fn get_size(&self) -> usize {
-        DICT_SECTION.len()
-    }
+    (info_ptr_array_ptr as usize)
+        .checked_sub(HEADER_SIZE)
+        .filter(|_| info_ptr_array_ptr < first_section)
 }
 
-impl BinWrite for DictSection {
-    fn write_options<W: io::Write>(&self, writer: &mut W, options: &WriterOption) -> io::Result<()> {
-        DICT_SECTION.write_options(writer, options)
+/// Reads the `size` bytes of the memory pool after the headers, or an empty pool of the usual
+/// size if the file doesn't have one
+#[binrw::parser(reader)]
+fn read_mem_pool(size: Option<usize>) -> BinResult<Vec<u8>> {
+    let Some(size) = size else {
+        return Ok(vec![0; MEM_POOL_SIZE]);
+    };
+
+    let saved_pos = reader.stream_position()?;
+
+    reader.seek(SeekFrom::Start(HEADER_SIZE as u64))?;
+    let mut pool = vec![0; size];
+    reader.read_exact(&mut pool)?;
+
+    reader.seek(SeekFrom::Start(saved_pos))?;
+
+    Ok(pool)
+}
+
+/// Where in the memory pool its pointer leads, falling back to where NintendoWare's tools put
+/// the pool's info if the pointer is outside the pool
+fn mem_pool_info_offset(mem_pool_ptr: u64, pool_size: usize) -> Option<usize> {
+    if mem_pool_ptr == 0 {
+        return None;
     }
+
+    let offset = (mem_pool_ptr as usize).checked_sub(HEADER_SIZE).filter(|&offset| offset < pool_size);
+
+    Some(offset.unwrap_or(MEM_POOL_INFO_OFFSET))
 }
 
-#[derive(BinRead, Debug)]
-enum SurfaceFormat {
-    #[br(magic = 0x0b06u32)]
-    R8G8B8A8_SRGB,
+/// How texture data is laid out in memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileMode {
+    /// Tegra X1 block linear swizzling
+    BlockLinear,
+    /// Rows of pixels stored one after another
+    Linear,
+}
+
+/// How [`Texture::to_webp`] compresses the image
+#[cfg(feature = "webp")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WebpQuality {
+    /// Keep every pixel exactly
+    Lossless,
+    /// Lossy compression at a quality from 0 to 100, where higher is larger but looks better
+    Lossy(f32),
+}
+
+/// How many dimensions a texture has
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dimension {
+    /// A single row of pixels, such as a gradient lookup table, with a height of 1
+    D1,
+    D2,
+    /// A stack of `depth` 2D slices
+    D3,
+}
+
+/// The kind of texture a texture's header says it is, which the GPU samples it as
+#[derive(BinRead, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureKind {
+    #[br(magic = 0u32)]
+    D1,
+    #[br(magic = 1u32)]
+    D2,
+    #[br(magic = 2u32)]
+    D3,
+    /// The six faces of a cube, stored as a 2D array of six layers
+    #[br(magic = 3u32)]
+    Cube,
+    #[br(magic = 4u32)]
+    D1Array,
+    #[br(magic = 5u32)]
+    D2Array,
+    #[br(magic = 6u32)]
+    D2Multisample,
+    #[br(magic = 7u32)]
+    D2MultisampleArray,
+    /// Any number of cubes, stored as a 2D array of six layers per cube
+    #[br(magic = 8u32)]
+    CubeArray,
 
     Unknown(u32),
 }
 
-impl BinWrite for SurfaceFormat {
-    fn write_options<W: io::Write>(&self, writer: &mut W, options: &WriterOption) -> io::Result<()> {
+/// Number of array layers making up each cube of a cube map
+const CUBE_FACES: u32 = 6;
+
+impl TextureKind {
+    /// The value stored in the texture's header
+    pub fn id(self) -> u32 {
         match self {
-            SurfaceFormat::R8G8B8A8_SRGB => 0x0b06,
-            SurfaceFormat::Unknown(x) => *x,
-        }.write_options(writer, options)
+            TextureKind::D1 => 0,
+            TextureKind::D2 => 1,
+            TextureKind::D3 => 2,
+            TextureKind::Cube => 3,
+            TextureKind::D1Array => 4,
+            TextureKind::D2Array => 5,
+            TextureKind::D2Multisample => 6,
+            TextureKind::D2MultisampleArray => 7,
+            TextureKind::CubeArray => 8,
+            TextureKind::Unknown(id) => id,
+        }
+    }
+
+    /// The kind with the given ID, or `Unknown` if it isn't one this crate knows
+    pub(crate) fn from_id(id: u32) -> Self {
+        match id {
+            0 => TextureKind::D1,
+            1 => TextureKind::D2,
+            2 => TextureKind::D3,
+            3 => TextureKind::Cube,
+            4 => TextureKind::D1Array,
+            5 => TextureKind::D2Array,
+            6 => TextureKind::D2Multisample,
+            7 => TextureKind::D2MultisampleArray,
+            8 => TextureKind::CubeArray,
+            id => TextureKind::Unknown(id),
+        }
+    }
+
+    /// The kind of a texture with `dim` dimensions, that's an array if `array_len` is more than
+    /// 1. Cube maps and multisampled textures are never picked.
+    pub(crate) fn for_texture(dim: u8, array_len: u32) -> Self {
+        match (dim, array_len > 1) {
+            (1, false) => TextureKind::D1,
+            (1, true) => TextureKind::D1Array,
+            (3, _) => TextureKind::D3,
+            (_, false) => TextureKind::D2,
+            (_, true) => TextureKind::D2Array,
+        }
+    }
+
+    /// Checks the kind agrees with the texture's number of dimensions, depth and array length:
+    /// only 3D textures have a depth, only arrays have more than one layer, and cube maps have
+    /// six layers per cube. Unknown kinds are never checked.
+    pub(crate) fn check(self, dim: u8, depth: u32, array_len: u32) -> Result<(), Error> {
+        let expected_dim = match self {
+            TextureKind::D1 | TextureKind::D1Array => 1,
+            TextureKind::D3 => 3,
+            TextureKind::Unknown(_) => return Ok(()),
+            _ => 2,
+        };
+
+        let layers_ok = match self {
+            TextureKind::D1 | TextureKind::D2 | TextureKind::D3 | TextureKind::D2Multisample => array_len <= 1,
+            TextureKind::Cube => array_len == CUBE_FACES,
+            TextureKind::CubeArray => array_len != 0 && array_len.is_multiple_of(CUBE_FACES),
+            _ => true,
+        };
+
+        if dim == expected_dim && layers_ok && (depth <= 1 || self == TextureKind::D3) {
+            Ok(())
+        } else {
+            Err(Error::InconsistentKind { kind: self, dim, depth, array_len })
+        }
+    }
+}
+
+#[cfg(feature = "write")]
+impl BinWrite for TextureKind {
+    type Args<'a> = ();
+
+    fn write_options<W: io::Write + Seek>(&self, writer: &mut W, endian: Endian, _: ()) -> BinResult<()> {
+        self.id().write_options(writer, endian, ())
+    }
+}
+
+/// Metadata describing a texture, independent of how it's stored in the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureInfo {
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+    pub format: SurfaceFormat,
+    pub mip_count: u16,
+    pub array_len: u32,
+    pub tile_mode: TileMode,
+    pub alignment: u32,
+}
+
+/// A rectangle of pixels within a texture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl From<AtlasRegion> for Rect {
+    fn from(region: AtlasRegion) -> Self {
+        Rect { x: region.x, y: region.y, width: region.width, height: region.height }
     }
 }
 
-#[derive(BinRead, Debug)]
-#[br(magic = b"BRTI")]
-struct BrtiSection {
+/// Every mip level of every array layer of a texture, decoded to 8-bit RGBA by
+/// [`Texture::decode_all`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedTexture {
+    /// Indexed by array layer, then by mip level, largest first
+    pub layers: Vec<Vec<image::RgbaImage>>,
+}
+
+impl DecodedTexture {
+    /// A single mip level of a single array layer, or `None` if it doesn't exist
+    pub fn mip(&self, layer: u32, mip: u32) -> Option<&image::RgbaImage> {
+        self.layers.get(layer as usize)?.get(mip as usize)
+    }
+}
+
+/// A header field of a texture that doesn't match the value derived from its dimensions, format
+/// and mip count, as found by [`Texture::size_mismatches`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SizeMismatch {
+    /// The size of the texture's data
+    ImageSize { stored: u32, expected: u32 },
+    /// The base 2 logarithm of the block height
+    SizeRange { stored: i32, expected: i32 },
+    /// The alignment of the texture's data
+    Alignment { stored: u32, expected: u32 },
+    /// The offset of each mip level within an array layer
+    MipOffsets { stored: Vec<u64>, expected: Vec<u64> },
+}
+
+/// A difference between a file and the same file written and read back, as found by
+/// [`BntxFile::verify_roundtrip`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoundtripMismatch {
+    /// The version and revision the file was read back with
+    Target { written: Target, read: Target },
+    /// A texture that wasn't read back, or was read back in a different position
+    MissingTexture(String),
+    /// A header field of a texture, such as its format, dimensions or channel mapping
+    Field { texture: String, field: &'static str, written: u64, read: u64 },
+    /// The deswizzled data of a mip level of an array layer
+    Data { texture: String, layer: u32, mip: u32 },
+    /// The stored data of a texture in a format that can't be deswizzled
+    RawData { texture: String },
+}
+
+/// Bits of entropy per byte above which data is assumed to be block compressed
+const COMPRESSED_ENTROPY: f64 = 6.0;
+
+/// Shannon entropy of the bytes of `data`, from 0 for a single repeated byte to 8 for random
+/// data. Only the first MiB is sampled, which is plenty to tell compressed data apart.
+fn byte_entropy(data: &[u8]) -> f64 {
+    let data = &data[..data.len().min(1 << 20)];
+
+    let mut counts = [0usize; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+
+    counts.iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / data.len() as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Alignment of the texture data written for new textures
+const TEXTURE_ALIGN: u32 = 512;
+
+/// The largest alignment between mip levels looked for by [`Texture::mip_alignment`]
+const MAX_MIP_ALIGN: usize = 0x10000;
+
+/// The row pitch alignment the hardware requires of linear textures, in bytes
+const LINEAR_PITCH_ALIGN: u32 = 32;
+
+/// The largest row pitch alignment looked for when inferring [`Texture::pitch_alignment`]
+const MAX_PITCH_ALIGN: u32 = 0x200;
+
+/// The largest texture alignment accepted, far more than games use, so a corrupt header can't
+/// make writing pad the file by gigabytes
+const MAX_TEXTURE_ALIGN: u32 = 0x10000;
+
+/// Whether data can be aligned to `align`: a power of two of at most [`MAX_TEXTURE_ALIGN`]
+fn valid_alignment(align: u32) -> bool {
+    align.is_power_of_two() && align <= MAX_TEXTURE_ALIGN
+}
+
+/// Checks an alignment read from a file. Zero, which games reject but is laid out like 1, is
+/// kept so such files can still be read and fixed.
+fn read_alignment(align: u32) -> Result<u32, Error> {
+    if align == 0 || valid_alignment(align) {
+        Ok(align)
+    } else {
+        Err(Error::InvalidAlignment { alignment: align })
+    }
+}
+
+/// What each array layer's data is padded to a multiple of: the texture's alignment, and for
+/// arrays at least a block of GOBs, so every layer starts on a block boundary
+fn layer_alignment(align: u32, array_len: u32, block_height: BlockHeight) -> usize {
+    let align = (align as usize).max(1);
+    if array_len > 1 {
+        align.max(512 * block_height as usize)
+    } else {
+        align
+    }
+}
+
+/// Pads `data` so the next mip level starts a multiple of `mip_alignment` bytes after the start
+/// of its layer, at `layer_start`
+fn pad_mip(data: &mut Vec<u8>, layer_start: usize, mip_alignment: usize) {
+    let offset = (data.len() - layer_start).next_multiple_of(mip_alignment);
+    data.resize(layer_start + offset, 0);
+}
+
+/// A single texture (BRTI section) along with its image data
+#[cfg_attr(feature = "write", binrw::binrw)]
+#[cfg_attr(not(feature = "write"), binrw::binread)]
+#[derive(Debug, Clone)]
+#[brw(magic = b"BRTI")]
+#[bw(import(layout: &Layout, index: usize))]
+pub struct Texture {
+    // offset of the next section and size of this one, both recomputed on write
+    #[br(temp)]
+    #[bw(calc = layout.texture_section_size(index) as u32)]
     size: u32,
+    #[br(temp)]
+    #[bw(calc = size as u64)]
     size2: u64,
+
     flags: u8,
     dim: u8,
     tile_mode: u16,
@@ -329,97 +757,195 @@ struct BrtiSection {
     size_range: i32,
     unk4: [u32; 6],
     image_size: u32,
+    #[br(try_map = read_alignment)]
     align: u32,
     comp_sel: u32,
-    ty: u32,
+    #[br(try_map = |kind: TextureKind| kind.check(dim, depth, array_len).map(|()| kind))]
+    kind: TextureKind,
+
+    #[br(parse_with = read_ptr64)]
+    #[bw(map = |name: &BntxStr| layout.string_offset(&name.bytes) as u64)]
+    name: BntxStr,
 
-    #[br(parse_with = FilePtr64::parse)]
-    name_addr: BntxStr,
+    #[br(temp)]
+    #[bw(calc = BNTX_HEADER_SIZE as u64)]
     parent_addr: u64,
 
-    #[br(args(image_size), parse_with = read_double_indirect)]
+    #[br(temp)]
+    #[bw(calc = layout.textures[index].mip_table as u64)]
+    mip_table_ptr: u64,
+
+    #[br(temp)]
+    #[bw(calc = 0)]
+    user_data_ptr: u64,
+
+    // the runtime texture and texture view, reserved right after the header
+    #[br(temp)]
+    #[bw(calc = (layout.textures[index].brti + SIZE_OF_BRTI) as u64)]
+    texture_ptr: u64,
+    #[br(temp)]
+    #[bw(calc = texture_ptr + SIZE_OF_GFX_OBJECT as u64)]
+    texture_view_ptr: u64,
+
+    #[br(temp)]
+    #[bw(calc = 0)]
+    desc_slot_data_ptr: u64,
+    #[br(temp)]
+    #[bw(calc = 0)]
+    user_dict_ptr: u64,
+
+    #[br(temp, seek_before = SeekFrom::Start(mip_table_ptr), count = mips_count.max(1))]
+    #[bw(calc = mip_offsets.iter().map(|offset| layout.textures[index].data as u64 + offset).collect())]
+    #[bw(pad_before = SIZE_OF_GFX_OBJECT * 2)]
+    mip_ptrs: Vec<u64>,
+
+    /// Offset of each mip level, relative to the start of the image data
+    #[br(calc = mip_ptrs.iter().map(|ptr| ptr.saturating_sub(mip_ptrs[0])).collect())]
+    #[bw(ignore)]
+    mip_offsets: Vec<u64>,
+
+    // written along with the other textures' data in the BRTD section
+    #[br(args(mip_ptrs[0], image_size), parse_with = read_image_data)]
+    #[bw(ignore)]
     texture: ImageData,
+
+    /// Where the data was read from, until it's changed. The file is filled in once the whole
+    /// file is read, see [`NxHeader::with_source`].
+    #[br(calc = Some(DataSource { file: 0, offset: mip_ptrs[0] }))]
+    #[bw(ignore)]
+    source: Option<DataSource>,
+
+    /// What the rows are padded to when the texture is linear, see
+    /// [`Texture::pitch_alignment`]. Inferred once the rest of the texture is read, see
+    /// [`Texture::with_pitch_alignment`].
+    #[br(calc = LINEAR_PITCH_ALIGN)]
+    #[bw(ignore)]
+    pitch_alignment: u32,
+}
+
+/// Where a texture's data is in a file it was read from or saved to, so saving over the same
+/// file again can leave data that hasn't changed where it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(feature = "write"), allow(dead_code))]
+struct DataSource {
+    /// The [`FileSource::id`] of the file
+    file: u64,
+    offset: u64,
+}
+
+/// Textures are equal if every header field, their names as stored and their data, including
+/// any padding, are the same. Where they were read from isn't compared, so a texture equals
+/// itself read back from a written file.
+impl PartialEq for Texture {
+    fn eq(&self, other: &Self) -> bool {
+        self.name.bytes == other.name.bytes
+            && self.flags == other.flags
+            && self.dim == other.dim
+            && self.tile_mode == other.tile_mode
+            && self.swizzle == other.swizzle
+            && self.mips_count == other.mips_count
+            && self.num_multi_sample == other.num_multi_sample
+            && self.format == other.format
+            && self.unk2 == other.unk2
+            && self.width == other.width
+            && self.height == other.height
+            && self.depth == other.depth
+            && self.array_len == other.array_len
+            && self.size_range == other.size_range
+            && self.unk4 == other.unk4
+            && self.image_size == other.image_size
+            && self.align == other.align
+            && self.comp_sel == other.comp_sel
+            && self.kind == other.kind
+            && self.mip_offsets == other.mip_offsets
+            && self.texture.0 == other.texture.0
+    }
 }
 
+impl Eq for Texture {}
+
+#[cfg(feature = "write")]
 const SIZE_OF_BRTI: usize = 0xA0;
 
-impl BrtiSection {
-    fn write_options<W: io::Write>(&self, writer: &mut W, options: &WriterOption, parent: &BntxFile) -> io::Result<()> {
-        (
-            (
-                b"BRTI",
-                self.size,
-                self.size2,
-                self.flags,
-                self.dim,
-                self.tile_mode,
-                self.swizzle,
-                self.mips_count,
-                self.num_multi_sample,
-                &self.format,
-                self.unk2,
-                self.width,
-                self.height,
-                self.depth,
-                self.array_len,
-                self.size_range,
-                self.unk4,
-                self.image_size,
-                self.align,
-                self.comp_sel,
-            ),
-            self.ty,
-            FILENAME_STR_OFFSET as u64,
-            BNTX_HEADER_SIZE as u64,
-            (
-                START_OF_STR_SECTION +
-                parent.header.inner.str_section.get_size() +
-                parent.nx_header.dict.get_size() +
-                SIZE_OF_BRTI +
-                0x200
-            ) as u64,
-            0u64,
-            (
-                START_OF_STR_SECTION +
-                parent.header.inner.str_section.get_size() +
-                parent.nx_header.dict.get_size() +
-                SIZE_OF_BRTI
-            ) as u64,
-            (
-                START_OF_STR_SECTION +
-                parent.header.inner.str_section.get_size() +
-                parent.nx_header.dict.get_size() +
-                SIZE_OF_BRTI +
-                0x100
-            ) as u64,
-            0u64,
-            0u64
-        ).write_options(writer, options)
-    }
-}
-
-use binread::{io::{Read, Seek}, ReadOptions};
-
-fn read_double_indirect<T: BinRead, R: Read + Seek>(
-    reader: &mut R,
-    options: &ReadOptions,
-    args: T::Args
-) -> BinResult<T> {
-
-    let mut data = <FilePtr64<FilePtr64<T>> as BinRead>::read_options(
-        reader,
-        options,
-        args
-    )?;
-
-    data.after_parse(reader, options, args)?;
-
-    Ok(data.into_inner().into_inner())
-}
-
-#[derive(BinRead)]
+/// Size of each of the two blocks reserved after a BRTI for the runtime texture and texture view
+#[cfg(feature = "write")]
+const SIZE_OF_GFX_OBJECT: usize = 0x100;
+
+#[binrw::parser(reader, endian)]
+fn read_ptr_array<T: for<'a> BinRead<Args<'a> = ()>>(count: u32, array_ptr: u64) -> BinResult<Vec<T>> {
+    trace_span!("read_ptr_array", count);
+
+    let saved_pos = reader.stream_position()?;
+
+    reader.seek(SeekFrom::Start(array_ptr))?;
+    let args = binrw::VecArgs::builder().count(count as usize).finalize();
+    let ptrs: Vec<u64> = reader.read_type_args(endian, args)?;
+
+    let values = ptrs.into_iter()
+        .map(|ptr| {
+            reader.seek(SeekFrom::Start(ptr))?;
+            T::read_options(reader, endian, ())
+        })
+        .collect();
+
+    reader.seek(SeekFrom::Start(saved_pos))?;
+
+    values
+}
+
+/// Reads the value at `ptr`, relative to the start of the file, or `None` for a null pointer
+#[binrw::parser(reader, endian)]
+fn read_optional_ptr<T: for<'a> BinRead<Args<'a> = ()>>(ptr: u64) -> BinResult<Option<T>> {
+    if ptr == 0 {
+        return Ok(None);
+    }
+
+    let saved_pos = reader.stream_position()?;
+
+    reader.seek(SeekFrom::Start(ptr))?;
+    let value = T::read_options(reader, endian, ())?;
+
+    reader.seek(SeekFrom::Start(saved_pos))?;
+
+    Ok(Some(value))
+}
+
+/// Reads `len` bytes of image data at `ptr`, relative to the start of the file
+#[binrw::parser(reader, endian)]
+fn read_image_data(ptr: u64, len: u32) -> BinResult<ImageData> {
+    trace_span!("read_image_data", ptr, len);
+
+    let saved_pos = reader.stream_position()?;
+
+    reader.seek(SeekFrom::Start(ptr))?;
+    let data = ImageData::read_options(reader, endian, (len,))?;
+
+    reader.seek(SeekFrom::Start(saved_pos))?;
+
+    Ok(data)
+}
+
+/// Reads a value at a 64-bit pointer, relative to the start of the file. Unlike
+/// `FilePtr64::parse`, huge pointers in corrupt files are an error rather than a panic.
+fn read_ptr64<R, T>(reader: &mut R, endian: binrw::Endian, _: ()) -> BinResult<T>
+where
+    R: Read + Seek,
+    T: for<'a> BinRead<Args<'a> = ()>,
+{
+    let ptr = u64::read_options(reader, endian, ())?;
+    let saved_pos = reader.stream_position()?;
+
+    reader.seek(SeekFrom::Start(ptr))?;
+    let value = T::read_options(reader, endian, ())?;
+
+    reader.seek(SeekFrom::Start(saved_pos))?;
+
+    Ok(value)
+}
+
+#[derive(BinRead, Clone)]
 #[br(import(len: u32))]
-struct ImageData(#[br(count = len, parse_with = binread::helpers::read_bytes)] pub Vec<u8>);
+struct ImageData(#[br(count = len)] pub Vec<u8>);
 
 impl fmt::Debug for ImageData {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -427,292 +953,4889 @@ impl fmt::Debug for ImageData {
     }
 }
 
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Debug, Clone)]
+#[br(little)]
 pub struct BntxFile {
+    // only used by `save_incremental`
+    #[br(parse_with = read_file_source, map = Some)]
+    #[cfg_attr(not(feature = "write"), allow(dead_code))]
+    source: Option<FileSource>,
+
     header: BntxHeader,
 
     #[br(is_little = header.bom == ByteOrder::LittleEndian)]
+    #[br(map = |nx_header: NxHeader| nx_header.with_source(&source))]
     nx_header: NxHeader,
 }
 
-impl BntxFile {
-    pub fn to_image(&self) -> image::DynamicImage {
-        let info: &BrtiSection = &self.nx_header.info_ptr;
-
-        let data = tegra_swizzle::deswizzle(
-            info.width, info.height, info.depth,
-            1,
-            1,
-            1,
-            false,
-            4,
-            info.tile_mode as _,
-            info.size_range,
-            &info.texture.0
-        );
+/// Files are equal if they'd be written the same, whatever offsets their sections were read
+/// from. The dictionary, which is regenerated from the texture names on write, isn't compared.
+impl PartialEq for BntxFile {
+    fn eq(&self, other: &Self) -> bool {
+        let (header, nx_header) = (&self.header, &self.nx_header);
+        let (other_header, other_nx_header) = (&other.header, &other.nx_header);
 
-        let base_size = info.width as usize * info.height as usize * 4;
-        
-        image::DynamicImage::ImageRgba8(
-            image::RgbaImage::from_raw(info.width, info.height, data[..base_size].to_owned())
-                .unwrap()
-        )
+        header.version == other_header.version
+            && header.bom == other_header.bom
+            && header.inner.revision == other_header.inner.revision
+            && header.inner.file_name.bytes == other_header.inner.file_name.bytes
+            && nx_header.textures == other_nx_header.textures
+            && nx_header.mem_pool == other_nx_header.mem_pool
+            && nx_header.mem_pool_info == other_nx_header.mem_pool_info
+            && nx_header.share_identical_data == other_nx_header.share_identical_data
     }
+}
 
-    pub fn write<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
-        let options = binwrite::writer_option_new!(endian: binwrite::Endian::Little);
-        self.header.write_options(writer, &options, self)?;
-        self.nx_header.write_options(writer, &options, self)?;
+impl Eq for BntxFile {}
 
-        (
-            // memory pool
-            &[0u8; 0x150][..],
-            (
-                START_OF_STR_SECTION
-                    + self.header.inner.str_section.get_size()
-                    + self.nx_header.dict.get_size()
-            ) as u64,
-            &self.header.inner.str_section,
-            &self.nx_header.dict,
-        ).write_options(writer, &options)?;
+/// Checks that a size is a whole number of the format's blocks
+fn check_block_alignment(format: SurfaceFormat, width: u32, height: u32) -> Result<(), Error> {
+    let (block_width, block_height) = format.block_dimensions();
+    if !width.is_multiple_of(block_width) || !height.is_multiple_of(block_height) {
+        return Err(Error::UnalignedSize { width, height, multiple: block_width });
+    }
 
+    Ok(())
+}
 
-        self.nx_header.info_ptr.write_options(writer, &options, self)?;
+/// Reads the header's file size, failing if the data ends before it, such as for a truncated
+/// download
+#[binrw::parser(reader, endian)]
+fn read_file_size() -> BinResult<u32> {
+    let pos = reader.stream_position()?;
+    let file_size = u32::read_options(reader, endian, ())?;
 
-        (
-            &[0; 0x100][..],
-            &[0; 0x100][..],
-        ).write_options(writer, &options)?;
-
-        0x1000u64.write_options(writer, &options)?;
-
-        let padding_size = BRTD_SECTION_START - (
-            START_OF_STR_SECTION +
-            self.header.inner.str_section.get_size() +
-            self.nx_header.dict.get_size() +
-            SIZE_OF_BRTI +
-            0x200 +
-            DATA_PTR_SIZE
-        );
+    let saved_pos = reader.stream_position()?;
+    let available = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(saved_pos))?;
 
-        vec![0u8; padding_size].write_options(writer, &options)?;
+    if available < file_size as u64 {
+        return Err(binrw::Error::AssertFail {
+            pos,
+            message: format!("file is {} bytes but its header says {}", available, file_size),
+        });
+    }
 
-        // BRTD
-        (
-            b"BRTD",
-            0,
-            self.nx_header.info_ptr.texture.0.len() as u64 + 0x10
-        ).write_options(writer, &options)?;
-        
+    Ok(file_size)
+}
 
-        writer.write_all(&self.nx_header.info_ptr.texture.0)?;
+/// How much of the start of a file is hashed to tell whether it's still the file that was read
+const FINGERPRINT_SIZE: u64 = 0x10000;
 
-        self.header.inner.reloc_table.write_options(writer, &options, self)?;
+/// The file a [`BntxFile`] was read from or last saved to with
+/// [`save_incremental`](BntxFile::save_incremental), whose textures' data can be left in place
+/// when saving over it again
+#[derive(Debug)]
+#[cfg_attr(not(feature = "write"), allow(dead_code))]
+struct FileSource {
+    /// Unique to each file read, and to each copy of one, since a copy can be edited and saved
+    /// over the same file separately
+    id: u64,
+    /// The file's size and a hash of its start, to notice it being replaced by another file
+    size: u64,
+    fingerprint: u64,
+}
 
-        Ok(())
-    }
+impl FileSource {
+    fn new(size: u64, fingerprint: u64) -> Self {
+        static NEXT_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
 
-    pub fn from_image(img: image::DynamicImage, name: &str) -> Self {
-        let img = img.to_rgba8();
+        FileSource { id: NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed), size, fingerprint }
+    }
 
-        let (width, height) = img.dimensions();
-        
-        let data = img.into_raw();
-
-        let data = tegra_swizzle::swizzle(
-            width, height, 1,
-            1,
-            1,
-            1,
-            false,
-            4,
-            0,
-            if width <= 64 && height <= 64 { 3 } else { 4 },
-            &data
-        );
+    /// Hashes the start of a file read from `reader`
+    fn fingerprint<R: Read>(reader: R) -> io::Result<u64> {
+        let mut start = Vec::new();
+        reader.take(FINGERPRINT_SIZE).read_to_end(&mut start)?;
 
-        let str_section = StrSection {
-            unk: 0x48,
-            unk2: 0x48,
-            unk3: 0,
-            strings: vec![BntxStr::from(name.to_owned())],
-        };
+        let mut hasher = Xxh64::new();
+        hasher.write(&start);
 
-        let str_section_size = str_section.get_size();
-        let dict_section_size = (DictSection {}).get_size();
+        Ok(hasher.finish())
+    }
+}
 
-        BntxFile {
-            header: BntxHeader {
-                version: (0, 4),
-                bom: ByteOrder::LittleEndian,
-                inner: HeaderInner {
-                    revision: 0x400c,
-                    file_name: name.into(),
-                    str_section,
-                    reloc_table: RelocationTable {
-                        sections: vec![
-                            RelocationSection {
-                                pointer: 0,
-                                position: 0,
-                                size: (
-                                    START_OF_STR_SECTION +
-                                    str_section_size +
-                                    dict_section_size +
-                                    SIZE_OF_BRTI +
-                                    0x208
-                                ) as u32,
-                                index: 0,
-                                count: 4,
-                            },
-                            RelocationSection {
-                                pointer: 0,
-                                position: BRTD_SECTION_START as u32,
-                                size: (data.len() + SIZE_OF_BRTD) as u32,
-                                index: 4,
-                                count: 1,
-                            },
-                        ],
-                        entries: vec![
-                            RelocationEntry {
-                                position: BNTX_HEADER_SIZE as u32 + 8,
-                                struct_count: 2,
-                                offset_count: 1,
-                                padding_count: (
-                                    (
-                                        (HEADER_SIZE + MEM_POOL_SIZE) - (BNTX_HEADER_SIZE + 0x10)
-                                    ) / 8
-                                ) as u8,
-                            },
-                            RelocationEntry {
-                                position: BNTX_HEADER_SIZE as u32 + 0x18,
-                                struct_count: 2,
-                                offset_count: 2,
-                                padding_count: (
-                                    (
-                                        START_OF_STR_SECTION
-                                            + str_section_size
-                                            + dict_section_size
-                                            + 0x80
-                                            - HEADER_SIZE
-                                    ) / 8
-                                ) as u8,
-                            },
-                            RelocationEntry {
-                                position:(
-                                    START_OF_STR_SECTION
-                                        + str_section_size
-                                        + 0x10
-                                ) as u32,
-                                struct_count: 2,
-                                offset_count: 1,
-                                padding_count: 1,
-                            },
-                            RelocationEntry {
-                                position:(
-                                    START_OF_STR_SECTION
-                                        + str_section_size
-                                        + dict_section_size
-                                        + 0x60
-                                ) as u32,
-                                struct_count: 1,
-                                offset_count: 3,
-                                padding_count: 0,
-                            },
-                            RelocationEntry {
-                                position:(
-                                    BNTX_HEADER_SIZE + 0x10
-                                ) as u32,
-                                struct_count: 2,
-                                offset_count: 1,
-                                padding_count: (
-                                    (
-                                        (
-                                            START_OF_STR_SECTION
-                                                + str_section_size
-                                                + dict_section_size
-                                                + SIZE_OF_BRTI
-                                                + 0x200
-                                        ) - (BNTX_HEADER_SIZE + 0x18)
-                                    ) / 8
-                                ) as u8,
-                            }
-                        ]
-                    }
-                }
-            },
-            nx_header: NxHeader {
-                dict: DictSection {},
-                dict_size: 0x58,
-                info_ptr: BrtiSection {
-                    size: 3592,
-                    size2: 3592,
-                    flags: 1,
-                    dim: 2,
-                    tile_mode: 0,
-                    swizzle: 0,
-                    mips_count: 1,
-                    num_multi_sample: 1,
-                    format: SurfaceFormat::R8G8B8A8_SRGB,
-                    unk2: 32,
-                    width,
-                    height,
-                    depth: 1,
-                    array_len: 1,
-                    size_range: 4,
-                    unk4: [
-                        65543,
-                        0,
-                        0,
-                        0,
-                        0,
-                        0,
-                    ],
-                    image_size: data.len() as _,
-                    align: 512,
-                    comp_sel: 84148994,
-                    ty: 1,
-                    name_addr: name.to_owned().into(),
-                    parent_addr: 32,
-                    texture: ImageData(data)
-                }
-            }
-        }
+impl Clone for FileSource {
+    fn clone(&self) -> Self {
+        FileSource::new(self.size, self.fingerprint)
     }
+}
 
-    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
-        let mut file = std::fs::File::create(path.as_ref())?;
+/// Identifies the stream a file is read from, see [`FileSource`]
+#[binrw::parser(reader)]
+fn read_file_source() -> BinResult<FileSource> {
+    let start = reader.stream_position()?;
+    let size = reader.seek(SeekFrom::End(0))?;
 
-        self.write(&mut file)
-    }
+    reader.seek(SeekFrom::Start(0))?;
+    let fingerprint = FileSource::fingerprint(&mut *reader)?;
+    reader.seek(SeekFrom::Start(start))?;
+
+    Ok(FileSource::new(size, fingerprint))
 }
 
-#[cfg(test)]
-mod tests {
-    use binread::prelude::*;
-    use binread::io::*;
-    use super::BntxFile;
+impl Texture {
+    /// The texture's name. Names that aren't valid UTF-8 have their invalid bytes replaced, see
+    /// [`name_bytes`](Texture::name_bytes) for the name exactly as stored.
+    pub fn name(&self) -> &str {
+        &self.name.chars
+    }
+
+    /// The texture's name exactly as stored in the file, such as names in Shift JIS from some
+    /// Japanese games
+    pub fn name_bytes(&self) -> &[u8] {
+        &self.name.bytes
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn dimension(&self) -> Dimension {
+        match self.dim {
+            1 => Dimension::D1,
+            3 => Dimension::D3,
+            _ => Dimension::D2,
+        }
+    }
+
+    pub fn kind(&self) -> TextureKind {
+        self.kind
+    }
+
+    /// Changes the kind of texture the header says this is, such as to mark a six layer array as
+    /// a cube map. The kind must agree with the texture's dimensions and array length.
+    pub fn set_kind(&mut self, kind: TextureKind) -> Result<(), Error> {
+        kind.check(self.dim, self.depth, self.array_len)?;
+        self.kind = kind;
+
+        Ok(())
+    }
+
+    pub fn flags(&self) -> TextureFlags {
+        TextureFlags::from_bits_retain(self.flags)
+    }
+
+    /// Replaces the flags byte of the texture's header. Bits [`TextureFlags`] doesn't know are
+    /// written as given.
+    pub fn set_flags(&mut self, flags: TextureFlags) {
+        self.flags = flags.bits();
+    }
+
+    /// How the GPU may access the texture, which is only [`GpuAccess::TEXTURE`] for textures
+    /// made by this crate
+    pub fn gpu_access(&self) -> GpuAccess {
+        GpuAccess::from_bits_retain(self.unk2)
+    }
+
+    /// Replaces how the GPU may access the texture, such as adding
+    /// [`GpuAccess::COLOR_BUFFER`] for a texture a game renders into. Bits [`GpuAccess`]
+    /// doesn't know are written as given.
+    pub fn set_gpu_access(&mut self, access: GpuAccess) {
+        self.unk2 = access.bits();
+    }
+
+    pub fn info(&self) -> TextureInfo {
+        TextureInfo {
+            width: self.width,
+            height: self.height,
+            depth: self.depth,
+            format: self.format,
+            mip_count: self.mips_count,
+            array_len: self.array_len,
+            tile_mode: match self.tile_mode {
+                1 => TileMode::Linear,
+                _ => TileMode::BlockLinear,
+            },
+            alignment: self.align,
+        }
+    }
+
+    /// Decodes the texture into the image type that best fits its format, so nothing is lost:
+    /// `Luma8` for `R8` and BC4, `Rgba16` for `R10G10B10A2` and unsigned EAC, `Rgb32F` for BC6H,
+    /// `Rgba32F` for the other float and signed formats, and `Rgba8` for everything else
+    pub fn to_image(&self) -> Result<image::DynamicImage, Error> {
+        self.decode(0, self.size_range)
+    }
+
+    /// Decodes the texture to 8-bit RGBA regardless of its format
+    pub fn to_rgba8_image(&self) -> Result<image::RgbaImage, Error> {
+        Ok(self.to_image()?.into_rgba8())
+    }
+
+    /// Decodes the texture to tightly packed 8-bit RGBA pixels, [`width`](Texture::width) by
+    /// [`height`](Texture::height), the same as [`to_rgba8_image`](Texture::to_rgba8_image) but
+    /// without any of the `image` crate's types. Useful for uploading straight to a GPU or for
+    /// code doing its own image processing.
+    pub fn decode_rgba8(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.to_rgba8_image()?.into_raw())
+    }
+
+    /// Decodes the texture using the given block height instead of the one stored in the file,
+    /// for textures where the stored value is wrong
+    pub fn decode_with_block_height(&self, block_height: BlockHeight) -> Result<image::DynamicImage, Error> {
+        self.decode(0, block_height.log2())
+    }
+
+    /// Decodes the texture, converting its colors between sRGB and linear. Useful for viewing
+    /// or exporting textures whose values don't match what their format claims.
+    pub fn to_image_with_gamma(&self, gamma: GammaConversion) -> Result<image::DynamicImage, Error> {
+        let mut image = self.to_rgba8_image()?;
+        gamma.apply(&mut image);
+
+        Ok(image::DynamicImage::ImageRgba8(image))
+    }
+
+    /// Decodes the texture to 32-bit float RGBA, keeping the range and precision of HDR formats
+    /// such as BC6H and `R16G16B16A16_FLOAT` that would be lost in an 8-bit image
+    pub fn to_float_image(&self) -> Result<image::Rgba32FImage, Error> {
+        let data = self.deswizzle_surface(0, 0, self.size_range)?;
+        let pixels = self.format.decode(data, self.width, self.height)?.into_float();
+
+        Ok(image::Rgba32FImage::from_raw(self.width, self.height, pixels).unwrap())
+    }
+
+    /// Decodes every mip level of every array layer to 8-bit RGBA, spreading the layers across
+    /// every available core
+    pub fn decode_all(&self) -> Result<DecodedTexture, Error> {
+        trace_span!("decode_all", name = self.name(), format = ?self.format, self.array_len);
+
+        let tables = self.address_tables(&self.surface_params(self.format, self.size_range)?);
+        let decode_layer = |layer: u32| -> Result<Vec<image::RgbaImage>, Error> {
+            (0..).zip(&tables)
+                .map(|(mip, table)| {
+                    let (width, height) = (table.params().width, table.params().height);
+                    let data = table.deswizzle(&self.swizzled_surface(layer, mip, table.params()));
+                    let pixels = self.format.decode(data, width, height)?;
+
+                    Ok(pixels.into_image(width, height, self.format).into_rgba8())
+                })
+                .collect()
+        };
+
+        // each thread decodes a contiguous range of layers, which are joined in order
+        let layer_count = self.array_len.max(1);
+        let threads = EncodeOptions::default().thread_count().clamp(1, layer_count as usize) as u32;
+        let layers_per_thread = layer_count.div_ceil(threads);
+        let layers = if threads == 1 {
+            (0..layer_count).map(decode_layer).collect::<Result<_, _>>()?
+        } else {
+            let decode_layer = &decode_layer;
+            std::thread::scope(|scope| {
+                let chunks: Vec<_> = (0..layer_count)
+                    .step_by(layers_per_thread as usize)
+                    .map(|start| {
+                        let layers = start..(start + layers_per_thread).min(layer_count);
+                        scope.spawn(move || layers.map(decode_layer).collect::<Vec<_>>())
+                    })
+                    .collect();
+
+                chunks.into_iter().flat_map(|chunk| chunk.join().unwrap()).collect::<Result<_, _>>()
+            })?
+        };
+
+        Ok(DecodedTexture { layers })
+    }
+
+    /// Writes the texture as an OpenEXR image, preserving floating point values for HDR
+    /// skyboxes and lookup tables
+    #[cfg(feature = "exr")]
+    pub fn to_exr<W: io::Write + io::Seek>(&self, writer: &mut W) -> Result<(), Error> {
+        image::DynamicImage::ImageRgba32F(self.to_float_image()?)
+            .write_to(writer, image::ImageFormat::OpenExr)?;
+
+        Ok(())
+    }
+
+    /// Writes the texture as a WebP image, which is much smaller than a PNG, for thumbnails and
+    /// previews such as on mod distribution sites
+    #[cfg(feature = "webp")]
+    pub fn to_webp<W: io::Write>(&self, writer: &mut W, quality: WebpQuality) -> Result<(), Error> {
+        let image = self.to_rgba8_image()?;
+        let encoder = webp::Encoder::from_rgba(&image, self.width, self.height);
+
+        let encoded = match quality {
+            WebpQuality::Lossless => encoder.encode_simple(true, 75.0),
+            WebpQuality::Lossy(quality) => encoder.encode_simple(false, quality.clamp(0.0, 100.0)),
+        }.map_err(Error::Webp)?;
+
+        writer.write_all(&encoded)?;
+
+        Ok(())
+    }
+
+    /// Decodes the texture into a separate grayscale image for each of its red, green, blue and
+    /// alpha channels, in that order, for textures that pack unrelated data into each channel
+    pub fn split_channels(&self) -> Result<[image::GrayImage; 4], Error> {
+        let image = self.to_rgba8_image()?;
+
+        let channel = |i: usize| image::GrayImage::from_fn(self.width, self.height, |x, y| {
+            image::Luma([image.get_pixel(x, y)[i]])
+        });
+
+        Ok([channel(0), channel(1), channel(2), channel(3)])
+    }
+
+    /// Decodes a preview image no larger than `max_dim` in either dimension, keeping the aspect
+    /// ratio. The smallest mip level at least that large is decoded instead of the base image,
+    /// which makes this much faster for textures with mips.
+    pub fn thumbnail(&self, max_dim: u32) -> Result<image::DynamicImage, Error> {
+        let mip = (1..self.mip_levels())
+            .take_while(|mip| (self.width >> mip).max(self.height >> mip) >= max_dim)
+            .last()
+            .unwrap_or(0);
+
+        let image = self.decode(mip, self.size_range)?;
+        if image.width() <= max_dim && image.height() <= max_dim {
+            return Ok(image);
+        }
+
+        Ok(image.thumbnail(max_dim, max_dim))
+    }
+
+    /// Decodes the mip levels of the first array layer one at a time, smallest first, so a
+    /// viewer can show a low resolution preview while the larger levels are still decoding.
+    /// Each level is only decoded once the iterator reaches it, and is paired with its index.
+    pub fn stream_mips(&self) -> impl Iterator<Item = Result<(u32, image::DynamicImage), Error>> + '_ {
+        (0..self.mip_levels()).rev().map(move |mip| Ok((mip, self.decode(mip, self.size_range)?)))
+    }
+
+    /// Decodes a rectangle of a mip level of the first array layer, such as to preview part of
+    /// a very large texture. Only the GOBs overlapping the rectangle are deswizzled and only
+    /// its blocks are decoded, so this is much cheaper than decoding the whole level.
+    pub fn decode_region(
+        &self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        mip: u32,
+    ) -> Result<image::DynamicImage, Error> {
+        trace_span!("decode_region", name = self.name(), x, y, width, height, mip);
+
+        let rect = Rect { x, y, width, height };
+        let (data, blocks) = self.deswizzle_region(rect, mip)?;
+
+        self.decode_blocks(data, blocks, rect)
+    }
+
+    /// Copies a rectangle of the first mip level of the first array layer into a new texture
+    /// in the same format, such as to pull a sprite out of an atlas. If the rectangle lines up
+    /// with the format's blocks they're copied as they are, otherwise the rectangle is decoded
+    /// and re-encoded. Its size must be a multiple of the format's block size.
+    pub fn crop(&self, rect: Rect, name: &str) -> Result<Texture, Error> {
+        trace_span!("crop", name = self.name(), rect.x, rect.y, rect.width, rect.height);
+
+        check_block_alignment(self.format, rect.width, rect.height)?;
+
+        let (data, blocks) = self.deswizzle_region(rect, 0)?;
+        let data = if (blocks.x, blocks.y) == (rect.x, rect.y) {
+            data
+        } else {
+            let pixels = format::Pixels::from_image(self.decode_blocks(data, blocks, rect)?);
+            let threads = EncodeOptions::default().thread_count();
+            self.format.encode(pixels, rect.width, rect.height, threads)?
+        };
+
+        let surface = Surface::new(self.format, rect.width, rect.height, 1, 1, data)?;
+        let mut texture = Texture::from_surface(name, &surface)?;
+        texture.comp_sel = self.comp_sel;
+
+        Ok(texture)
+    }
+
+    /// Deswizzles the blocks of a mip level of the first array layer that overlap `rect`,
+    /// returning them along with the rectangle, in pixels, they cover
+    fn deswizzle_region(&self, rect: Rect, mip: u32) -> Result<(Vec<u8>, Rect), Error> {
+        if mip >= self.mip_levels() {
+            return Err(Error::MissingMip { level: mip, count: self.mip_levels() });
+        }
+
+        let Rect { x, y, width, height } = rect;
+        let params = self.surface_params(self.format, self.size_range)?.mip(mip);
+        let (end_x, end_y) = (x.saturating_add(width), y.saturating_add(height));
+        if width == 0 || height == 0 || end_x > params.width || end_y > params.height {
+            return Err(Error::InvalidRegion { x, y, width, height });
+        }
+
+        let (block_width, block_height) = self.format.block_dimensions();
+        let (start_x, start_y) = (x / block_width, y / block_height);
+        let blocks_x = end_x.div_ceil(block_width) - start_x;
+        let blocks_y = end_y.div_ceil(block_height) - start_y;
+
+        let surface = self.swizzled_surface(0, mip, &params);
+        let data = tegra_swizzle::deswizzle_region(&params, &surface, start_x, start_y, blocks_x, blocks_y);
+
+        Ok((data, Rect {
+            x: start_x * block_width,
+            y: start_y * block_height,
+            width: blocks_x * block_width,
+            height: blocks_y * block_height,
+        }))
+    }
+
+    /// Decodes blocks covering the rectangle `blocks`, cropping them down to `rect`
+    fn decode_blocks(&self, data: Vec<u8>, blocks: Rect, rect: Rect) -> Result<image::DynamicImage, Error> {
+        let pixels = self.format.decode(data, blocks.width, blocks.height)?;
+        let image = pixels.into_image(blocks.width, blocks.height, self.format);
+
+        Ok(image.crop_imm(rect.x - blocks.x, rect.y - blocks.y, rect.width, rect.height))
+    }
+
+    fn decode(&self, mip: u32, block_height_log2: i32) -> Result<image::DynamicImage, Error> {
+        trace_span!("decode", name = self.name(), format = ?self.format, self.width, self.height, mip);
+
+        let data = self.deswizzle_surface(0, mip, block_height_log2)?;
+        let width = (self.width >> mip).max(1);
+        let height = (self.height >> mip).max(1);
+        let pixels = self.format.decode(data, width, height)?;
+
+        Ok(pixels.into_image(width, height, self.format))
+    }
+
+    /// Re-encodes every mip level and array layer of the texture in another format, for example
+    /// compressing RGBA8 data to BC3 or converting between block compressed formats
+    pub fn convert_format(&mut self, format: SurfaceFormat) -> Result<(), Error> {
+        self.convert_format_with_progress(format, NO_PROGRESS)
+    }
+
+    /// Like [`convert_format`](Texture::convert_format), reporting progress after each mip
+    /// level of each array layer
+    pub fn convert_format_with_progress(
+        &mut self,
+        format: SurfaceFormat,
+        progress: &dyn Progress,
+    ) -> Result<(), Error> {
+        self.convert_format_with_options(format, &EncodeOptions::default(), progress)
+    }
+
+    /// Like [`convert_format_with_progress`](Texture::convert_format_with_progress), with
+    /// control over how the data is encoded
+    pub fn convert_format_with_options(
+        &mut self,
+        format: SurfaceFormat,
+        options: &EncodeOptions,
+        progress: &dyn Progress,
+    ) -> Result<(), Error> {
+        trace_span!("convert_format", name = self.name(), from = ?self.format, to = ?format);
+
+        // an invalid block height picks the hardware's default for the new format
+        let params = self.surface_params(format, -1)?;
+        let src_tables = self.address_tables(&self.surface_params(self.format, self.size_range)?);
+        let tables = self.address_tables(&params);
+        let mip_alignment = self.mip_alignment();
+
+        // everything is decoded before the texture is modified, so a failure leaves it untouched
+        let mut data = Vec::new();
+        let mut mip_offsets = Vec::new();
+        for layer in 0..self.array_len.max(1) {
+            let layer_start = data.len();
+
+            for mip in 0..self.mip_levels() {
+                let (src_table, table) = (&src_tables[mip as usize], &tables[mip as usize]);
+                let (width, height) = (table.params().width, table.params().height);
+
+                let surface = src_table.deswizzle(&self.swizzled_surface(layer, mip, src_table.params()));
+                let pixels = self.format.decode(surface, width, height)?;
+                let encoded = format.encode(pixels, width, height, options.thread_count())?;
+
+                pad_mip(&mut data, layer_start, mip_alignment);
+                if layer == 0 {
+                    mip_offsets.push((data.len() - layer_start) as u64);
+                }
+
+                data.extend(table.swizzle(&encoded));
+
+                let done = (layer * self.mip_levels() + mip + 1) as u64;
+                progress.update(done, (self.array_len.max(1) * self.mip_levels()) as u64);
+            }
+
+            data.resize(data.len().next_multiple_of(self.layer_alignment(params.block_height)), 0);
+        }
+
+        self.format = format;
+        self.size_range = params.block_height.log2();
+        self.image_size = data.len() as u32;
+        self.mip_offsets = mip_offsets;
+        self.set_image_data(data);
+
+        Ok(())
+    }
+
+    /// Replaces the texture's data, which is then no longer where it was read from
+    fn set_image_data(&mut self, data: Vec<u8>) {
+        self.texture = ImageData(data);
+        self.source = None;
+    }
+
+    /// The texture's data as stored in the file, swizzled and with every mip level and array
+    /// layer
+    pub fn raw_data(&self) -> &[u8] {
+        &self.texture.0
+    }
+
+    /// How the data returned by [`raw_data`](Texture::raw_data) is laid out: the swizzle
+    /// parameters of the first mip level of the first array layer, as stored in the texture's
+    /// header. Use [`SurfaceParams::mip`] for the smaller mip levels.
+    pub fn layout(&self) -> Result<SurfaceParams, Error> {
+        self.surface_params(self.format, self.size_range)
+    }
+
+    /// Where a mip level starts in each array layer of [`raw_data`](Texture::raw_data), in
+    /// bytes from the start of the layer. The offset is computed from the texture's format,
+    /// dimensions, block height and [`mip_alignment`](Texture::mip_alignment) rather than read
+    /// from its header, so it can be trusted even if the header's offsets are wrong. Returns
+    /// `None` if the level doesn't exist or the format is unknown.
+    pub fn mip_offset(&self, level: u32) -> Option<usize> {
+        let (_, mip_offsets, _) = self.computed_layout(self.format, self.size_range).ok()?;
+
+        mip_offsets.get(level as usize).map(|&offset| offset as usize)
+    }
+
+    /// Where an array layer starts in [`raw_data`](Texture::raw_data), in bytes. Layers of
+    /// texture arrays are aligned to the size of a block of GOBs. Returns `None` if the layer
+    /// doesn't exist or the format is unknown.
+    pub fn layer_offset(&self, layer: u32) -> Option<usize> {
+        let (_, _, layer_size) = self.computed_layout(self.format, self.size_range).ok()?;
+
+        (layer < self.array_len.max(1)).then(|| layer as usize * layer_size)
+    }
+
+    /// The alignment, in bytes from the start of each array layer, of every mip level's data.
+    /// Mip levels are usually packed back to back, giving an alignment of 1, but some files
+    /// pad between them. The alignment is inferred from the header's mip offsets as the
+    /// smallest power of two up to 64 KiB that reproduces them, and kept when the texture is
+    /// converted or its layout recomputed. Offsets that don't match any alignment are treated
+    /// as packed.
+    pub fn mip_alignment(&self) -> usize {
+        if self.mip_offsets.len() < 2 {
+            return 1;
+        }
+
+        (0..=MAX_MIP_ALIGN.trailing_zeros())
+            .map(|log2| 1 << log2)
+            .find(|&alignment| {
+                self.aligned_layout(self.format, self.size_range, alignment)
+                    .is_ok_and(|(_, mip_offsets, _)| mip_offsets == self.mip_offsets)
+            })
+            .unwrap_or(1)
+    }
+
+    /// What each row of a linear texture's data is padded to a multiple of, in bytes. The
+    /// hardware requires 32, but some tools pad further or pack rows tightly, which would skew
+    /// every row if decoded with the wrong pitch. The alignment is inferred when the texture is
+    /// read as the one that reproduces the header's mip offsets and image size, preferring 32
+    /// when several do, and kept when the texture is converted. Block linear textures always
+    /// return 1, since their rows are padded to whole GOBs instead.
+    pub fn pitch_alignment(&self) -> u32 {
+        if self.tile_mode != 1 {
+            return 1;
+        }
+
+        self.pitch_alignment
+    }
+
+    /// Sets the pitch alignment to the one inferred from the header's layout
+    fn with_pitch_alignment(mut self) -> Self {
+        self.pitch_alignment = self.infer_pitch_alignment();
+        self
+    }
+
+    fn infer_pitch_alignment(&self) -> u32 {
+        if self.tile_mode != 1 {
+            return LINEAR_PITCH_ALIGN;
+        }
+
+        let others = (0..=MAX_PITCH_ALIGN.trailing_zeros())
+            .map(|log2| 1 << log2)
+            .filter(|&alignment| alignment != LINEAR_PITCH_ALIGN);
+        let mip_alignments = || (0..=MAX_MIP_ALIGN.trailing_zeros()).map(|log2| 1 << log2);
+
+        std::iter::once(LINEAR_PITCH_ALIGN)
+            .chain(others)
+            .find(|&pitch_alignment| {
+                let Ok(params) = self.pitched_surface_params(self.format, self.size_range, pitch_alignment) else {
+                    return false;
+                };
+
+                mip_alignments().any(|mip_alignment| {
+                    let (_, mip_offsets, layer_size) = self.layout_for_params(params, mip_alignment);
+
+                    mip_offsets == self.mip_offsets
+                        && layer_size * self.array_len.max(1) as usize == self.image_size as usize
+                })
+            })
+            .unwrap_or(LINEAR_PITCH_ALIGN)
+    }
+
+    /// Replaces the texture's data with already swizzled data, such as from another tool. The
+    /// data must be exactly as long as the current data (`image_size`), since the texture's
+    /// format, dimensions and layout are kept.
+    pub fn set_raw_data(&mut self, data: Vec<u8>) -> Result<(), Error> {
+        if data.len() != self.image_size as usize {
+            return Err(Error::MismatchedDataSize {
+                expected: self.image_size as usize,
+                found: data.len(),
+            });
+        }
+
+        self.set_image_data(data);
+
+        Ok(())
+    }
+
+    /// Replaces a single mip level of the first array layer with an image, encoding it in the
+    /// texture's format and leaving every other level as it is. The image must be the size of
+    /// the level.
+    pub fn replace_mip(&mut self, level: u32, img: image::DynamicImage) -> Result<(), Error> {
+        trace_span!("replace_mip", name = self.name(), level);
+
+        if level >= self.mip_levels() {
+            return Err(Error::MissingMip { level, count: self.mip_levels() });
+        }
+
+        let params = self.surface_params(self.format, self.size_range)?.mip(level);
+        let expected = (params.width, params.height);
+        let found = (img.width(), img.height());
+        if found != expected {
+            return Err(Error::MismatchedSize { expected, found });
+        }
+
+        let pixels = format::Pixels::from_image(img);
+        let threads = EncodeOptions::default().thread_count();
+        let encoded = self.format.encode(pixels, params.width, params.height, threads)?;
+
+        let start = self.mip_offsets.get(level as usize).copied().unwrap_or(0) as usize;
+        let end = start + tegra_swizzle::swizzled_surface_size(&params);
+        if end > self.texture.0.len() {
+            return Err(Error::MismatchedDataSize { expected: end, found: self.texture.0.len() });
+        }
+
+        tegra_swizzle::swizzle_into(&params, &encoded, &mut self.texture.0[start..end]);
+        self.source = None;
+
+        Ok(())
+    }
+
+    /// Replaces every mip level of the first array layer with an image the size of the
+    /// texture, scaled down for each smaller level. The format, layout and every other header
+    /// field are kept, as with [`replace_mip`](Texture::replace_mip).
+    pub fn replace_image(&mut self, img: image::DynamicImage) -> Result<(), Error> {
+        let params = self.surface_params(self.format, self.size_range)?;
+        for level in 0..self.mip_levels() {
+            let mip = match level {
+                0 => img.clone(),
+                _ => {
+                    let mip = params.mip(level);
+                    img.resize_exact(mip.width, mip.height, image::imageops::FilterType::Triangle)
+                }
+            };
+
+            self.replace_mip(level, mip)?;
+        }
+
+        Ok(())
+    }
+
+    /// A copy of the texture with its data zeroed, keeping its name, format, flags, tile mode,
+    /// swizzle, block height, alignment, channel mapping and mip layout. Filling it with
+    /// [`replace_image`](Texture::replace_image) or [`replace_mip`](Texture::replace_mip) gives
+    /// a texture with exactly the parameters of the original, as games expect of replacements.
+    pub fn metadata_template(&self) -> Texture {
+        Texture {
+            texture: ImageData(vec![0; self.texture.0.len()]),
+            source: None,
+            ..self.clone()
+        }
+    }
+
+    /// Changes how the texture's data is laid out in memory, converting every mip level and
+    /// array layer to the given tile mode and block height. Unlike
+    /// [`convert_format`](Texture::convert_format), the data isn't decoded, so compressed blocks
+    /// are kept bit-exact.
+    pub fn reswizzle(&mut self, tile_mode: TileMode, block_height: BlockHeight) -> Result<(), Error> {
+        trace_span!("reswizzle", name = self.name(), ?tile_mode, ?block_height);
+
+        let src = self.surface_params(self.format, self.size_range)?;
+        let dst = SurfaceParams {
+            tile_mode: match tile_mode {
+                TileMode::BlockLinear => 0,
+                TileMode::Linear => 1,
+            },
+            block_height,
+            // rows already padded for a linear layout stay that way
+            pitch_alignment: if src.tile_mode == 1 { self.pitch_alignment } else { LINEAR_PITCH_ALIGN },
+            ..src
+        };
+
+        let (src_tables, dst_tables) = (self.address_tables(&src), self.address_tables(&dst));
+        let mip_alignment = self.mip_alignment();
+
+        let mut data = Vec::new();
+        let mut mip_offsets = Vec::new();
+        for layer in 0..self.array_len.max(1) {
+            let layer_start = data.len();
+
+            for (mip, (src, dst)) in (0..).zip(src_tables.iter().zip(&dst_tables)) {
+                pad_mip(&mut data, layer_start, mip_alignment);
+                if layer == 0 {
+                    mip_offsets.push((data.len() - layer_start) as u64);
+                }
+
+                let surface = self.swizzled_surface(layer, mip, src.params());
+                data.extend(src.reswizzle(dst, &surface));
+            }
+
+            data.resize(data.len().next_multiple_of(self.layer_alignment(block_height)), 0);
+        }
+
+        self.tile_mode = dst.tile_mode as u16;
+        self.pitch_alignment = dst.pitch_alignment;
+        self.size_range = block_height.log2();
+        self.image_size = data.len() as u32;
+        self.mip_offsets = mip_offsets;
+        self.set_image_data(data);
+
+        Ok(())
+    }
+
+    /// Compares the texture's size-related header fields with the values derived from its
+    /// dimensions, format and mip count, returning every field that differs. Hand-edited or
+    /// corrupted headers can be fixed with [`recompute_sizes`](Texture::recompute_sizes).
+    pub fn size_mismatches(&self) -> Result<Vec<SizeMismatch>, Error> {
+        let (params, mip_offsets, image_size) = self.expected_layout()?;
+        let image_size = image_size as u32;
+
+        let mut mismatches = Vec::new();
+        if self.size_range != params.block_height.log2() {
+            mismatches.push(SizeMismatch::SizeRange {
+                stored: self.size_range,
+                expected: params.block_height.log2(),
+            });
+        }
+        if self.mip_offsets != mip_offsets {
+            mismatches.push(SizeMismatch::MipOffsets {
+                stored: self.mip_offsets.clone(),
+                expected: mip_offsets,
+            });
+        }
+        if self.image_size != image_size || self.texture.0.len() != image_size as usize {
+            mismatches.push(SizeMismatch::ImageSize { stored: self.image_size, expected: image_size });
+        }
+        // games may ask for more alignment, such as 0x1000, but never less
+        if !self.align.is_power_of_two() || self.align < TEXTURE_ALIGN {
+            mismatches.push(SizeMismatch::Alignment { stored: self.align, expected: TEXTURE_ALIGN });
+        }
+
+        Ok(mismatches)
+    }
+
+    /// Fixes every field reported by [`size_mismatches`](Texture::size_mismatches), returning
+    /// what was changed. If the block height or mip layout was wrong, the data is reswizzled to
+    /// the corrected layout, so it still decodes the same.
+    pub fn recompute_sizes(&mut self) -> Result<Vec<SizeMismatch>, Error> {
+        let mismatches = self.size_mismatches()?;
+
+        if mismatches.iter().any(|mismatch| matches!(mismatch, SizeMismatch::Alignment { .. })) {
+            self.align = TEXTURE_ALIGN;
+        }
+
+        // the alignment is fixed first, since the corrected layout depends on it
+        if !self.size_mismatches()?.is_empty() {
+            let params = self.surface_params(self.format, -1)?;
+            self.reswizzle(self.info().tile_mode, params.block_height)?;
+        }
+
+        Ok(mismatches)
+    }
+
+    /// Checks the texture for header values known to hang or crash games, which the rest of
+    /// this crate reads and writes as they are: a size outside the hardware's limits or that
+    /// isn't a whole number of the format's blocks, an alignment of 0 or that isn't a power of
+    /// two, a mip count the size doesn't allow, and a data size, block height or mip offset
+    /// table that doesn't match the texture's layout.
+    pub fn check_game_compat(&self) -> Result<(), Error> {
+        let problem = |reason: String| Err(Error::GameCompat { texture: self.name().into(), reason });
+
+        let (width, height) = (self.width, self.height);
+        if width == 0 || height == 0 || width > MAX_TEXTURE_SIZE || height > MAX_TEXTURE_SIZE {
+            return problem(format!(
+                "its size of {}x{} is outside the hardware's limits of 1x1 to {max}x{max}",
+                width, height, max = MAX_TEXTURE_SIZE
+            ));
+        }
+
+        let (block_width, block_height) = self.format.block_dimensions();
+        if !width.is_multiple_of(block_width) || !height.is_multiple_of(block_height) {
+            return problem(format!(
+                "its size of {}x{} isn't a whole number of {}'s {}x{} blocks",
+                width, height, self.format, block_width, block_height
+            ));
+        }
+
+        if !self.align.is_power_of_two() {
+            return problem(format!("its alignment of {} isn't a power of two", self.align));
+        }
+
+        let max_mips = 32 - width.max(height).leading_zeros();
+        if self.mips_count == 0 || self.mips_count as u32 > max_mips {
+            return problem(format!(
+                "it has {} mip levels, but a {}x{} texture has 1 to {}",
+                self.mips_count, width, height, max_mips
+            ));
+        }
+
+        let mismatches = match self.size_mismatches() {
+            Ok(mismatches) => mismatches,
+            Err(err) => return problem(err.to_string()),
+        };
+        let fields: Vec<_> = mismatches.iter()
+            .map(|mismatch| match mismatch {
+                SizeMismatch::ImageSize { .. } => "data size",
+                SizeMismatch::SizeRange { .. } => "block height",
+                SizeMismatch::Alignment { .. } => "alignment",
+                SizeMismatch::MipOffsets { .. } => "mip offsets",
+            })
+            .collect();
+        if !fields.is_empty() {
+            return problem(format!(
+                "its {} {} match its size and format, which Texture::recompute_sizes can fix",
+                fields.join(" and "),
+                if fields.len() == 1 { "doesn't" } else { "don't" }
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Changes the size of the texture. The dimensions must fit the hardware's limits and be a
+    /// multiple of the format's block size, only 3D textures can have a depth other than 1, and
+    /// 1D textures must have a height of 1.
+    /// Mips that no longer fit are dropped, and since the data can't be kept at a new size, it
+    /// is cleared to zeros.
+    pub fn set_dimensions(&mut self, width: u32, height: u32, depth: u32) -> Result<(), Error> {
+        if width == 0 || height == 0 || width > MAX_TEXTURE_SIZE || height > MAX_TEXTURE_SIZE {
+            return Err(Error::InvalidDimensions { width, height });
+        }
+        if depth == 0 || (depth > 1 && self.dim != 3) {
+            return Err(Error::InvalidDepth { depth });
+        }
+        if height != 1 && self.dim == 1 {
+            return Err(Error::InvalidHeight { height });
+        }
+        check_block_alignment(self.format, width, height)?;
+
+        let max_mips = 32 - width.max(height).leading_zeros();
+        self.width = width;
+        self.height = height;
+        self.depth = depth;
+        self.mips_count = self.mips_count.min(max_mips as u16);
+        self.clear_data()
+    }
+
+    /// Changes the texture's format without converting its data, for example to switch between
+    /// the `UNORM` and `SRGB` variants of a format. If the new format's blocks are a different
+    /// size, the data is cleared to zeros; use [`convert_format`](Texture::convert_format) to
+    /// re-encode it instead.
+    pub fn set_format(&mut self, format: SurfaceFormat) -> Result<(), Error> {
+        let bytes_per_block = format.bytes_per_block().ok_or(Error::UnsupportedFormat(format))?;
+        check_block_alignment(format, self.width, self.height)?;
+
+        let same_layout = bytes_per_block == self.format.bytes_per_block().unwrap_or(0)
+            && format.block_dimensions() == self.format.block_dimensions();
+
+        self.format = format;
+        if same_layout {
+            Ok(())
+        } else {
+            self.clear_data()
+        }
+    }
+
+    /// Changes the texture's tile mode, converting its data to the new layout while keeping the
+    /// block height. See [`reswizzle`](Texture::reswizzle) to also change the block height.
+    pub fn set_tile_mode(&mut self, tile_mode: TileMode) -> Result<(), Error> {
+        let params = self.surface_params(self.format, self.size_range)?;
+        self.reswizzle(tile_mode, params.block_height)
+    }
+
+    /// Drops every mip level after the first `keep`, shrinking the texture's data. Keeping 0 or 1
+    /// levels leaves only the base image, and keeping more levels than the texture has does
+    /// nothing.
+    pub fn strip_mips(&mut self, keep: u16) {
+        let keep = keep.max(1);
+        if keep >= self.mips_count {
+            return;
+        }
+
+        let layer_size = self.layer_size();
+        let kept_size = self.mip_offsets.get(keep as usize)
+            .map_or(layer_size, |&offset| (offset as usize).min(layer_size));
+
+        let block_height = BlockHeight::from_log2(self.size_range.clamp(0, 5)).unwrap_or(BlockHeight::One);
+        let layer_alignment = self.layer_alignment(block_height);
+
+        let mut data = Vec::new();
+        for layer in self.texture.0.chunks(layer_size.max(1)).take(self.array_len.max(1) as usize) {
+            data.extend_from_slice(&layer[..kept_size.min(layer.len())]);
+            data.resize(data.len().next_multiple_of(layer_alignment), 0);
+        }
+
+        self.mips_count = keep;
+        self.mip_offsets.truncate(keep as usize);
+        self.image_size = data.len() as u32;
+        self.set_image_data(data);
+    }
+
+    /// Hash of the texture's format, dimensions and deswizzled data, which doesn't depend on how
+    /// the data is laid out in the file
+    pub fn data_hash(&self) -> Result<u64, Error> {
+        let mut hasher = Xxh64::new();
+
+        let header = [
+            self.format.id(),
+            self.width,
+            self.height,
+            self.depth,
+            self.array_len,
+            self.mip_levels(),
+        ];
+        for value in header {
+            hasher.write(&value.to_le_bytes());
+        }
+
+        for layer in 0..self.array_len.max(1) {
+            for mip in 0..self.mip_levels() {
+                hasher.write(&self.deswizzle_surface(layer, mip, self.size_range)?);
+            }
+        }
+
+        Ok(hasher.finish())
+    }
+
+    /// Every difference between the texture and `read`, the same texture after a round trip
+    /// through a file
+    #[cfg(feature = "write")]
+    fn roundtrip_mismatches(&self, read: &Texture) -> Result<Vec<RoundtripMismatch>, Error> {
+        let fields = |texture: &Texture| [
+            ("format", texture.format.id() as u64),
+            ("flags", texture.flags as u64),
+            ("dim", texture.dim as u64),
+            ("ty", texture.kind.id() as u64),
+            ("tile_mode", texture.tile_mode as u64),
+            ("swizzle", texture.swizzle as u64),
+            ("width", texture.width as u64),
+            ("height", texture.height as u64),
+            ("depth", texture.depth as u64),
+            ("array_len", texture.array_len as u64),
+            ("mips_count", texture.mips_count as u64),
+            ("size_range", texture.size_range as u64),
+            ("image_size", texture.image_size as u64),
+            ("align", texture.align as u64),
+            ("comp_sel", texture.comp_sel as u64),
+        ];
+
+        let mut mismatches: Vec<_> = fields(self).iter()
+            .zip(&fields(read))
+            .filter(|(written, read)| written != read)
+            .map(|(&(field, written), &(_, read))| RoundtripMismatch::Field {
+                texture: self.name().into(),
+                field,
+                written,
+                read,
+            })
+            .collect();
+        if !mismatches.is_empty() {
+            return Ok(mismatches);
+        }
+
+        if self.format.bytes_per_block().is_none() {
+            if self.raw_data() != read.raw_data() {
+                mismatches.push(RoundtripMismatch::RawData { texture: self.name().into() });
+            }
+
+            return Ok(mismatches);
+        }
+
+        for layer in 0..self.array_len.max(1) {
+            for mip in 0..self.mip_levels() {
+                let surface = self.deswizzle_surface(layer, mip, self.size_range)?;
+                if surface != read.deswizzle_surface(layer, mip, read.size_range)? {
+                    mismatches.push(RoundtripMismatch::Data { texture: self.name().into(), layer, mip });
+                }
+            }
+        }
+
+        Ok(mismatches)
+    }
+
+    /// The block height, mip offsets and data size the texture's dimensions, format and mip
+    /// count call for
+    fn expected_layout(&self) -> Result<(SurfaceParams, Vec<u64>, usize), Error> {
+        let (params, mip_offsets, layer_size) = self.computed_layout(self.format, -1)?;
+
+        Ok((params, mip_offsets, layer_size * self.array_len.max(1) as usize))
+    }
+
+    /// The swizzle parameters, mip offsets and aligned layer size for the texture's dimensions
+    /// and mip count in `format` with the given block height
+    fn computed_layout(
+        &self,
+        format: SurfaceFormat,
+        block_height_log2: i32,
+    ) -> Result<(SurfaceParams, Vec<u64>, usize), Error> {
+        self.aligned_layout(format, block_height_log2, self.mip_alignment())
+    }
+
+    /// Like [`computed_layout`](Texture::computed_layout), starting each mip level at a
+    /// multiple of `mip_alignment` bytes from the start of the layer
+    fn aligned_layout(
+        &self,
+        format: SurfaceFormat,
+        block_height_log2: i32,
+        mip_alignment: usize,
+    ) -> Result<(SurfaceParams, Vec<u64>, usize), Error> {
+        let params = self.surface_params(format, block_height_log2)?;
+
+        Ok(self.layout_for_params(params, mip_alignment))
+    }
+
+    /// The mip offsets and aligned layer size of a surface whose first mip is described by
+    /// `params`
+    fn layout_for_params(&self, params: SurfaceParams, mip_alignment: usize) -> (SurfaceParams, Vec<u64>, usize) {
+
+        let mut mip_offsets = Vec::new();
+        let mut layer_size = 0usize;
+        for mip in 0..self.mip_levels() {
+            layer_size = layer_size.next_multiple_of(mip_alignment);
+            mip_offsets.push(layer_size as u64);
+            layer_size += tegra_swizzle::swizzled_surface_size(&params.mip(mip));
+        }
+        layer_size = layer_size.next_multiple_of(self.layer_alignment(params.block_height));
+
+        (params, mip_offsets, layer_size)
+    }
+
+    /// Suggests formats the texture's data could be in, most likely first, for textures with a
+    /// format ID this crate doesn't know. Only formats that would take up exactly the texture's
+    /// `image_size` are suggested. They're ranked by how much of the format ID they share, then
+    /// by whether the data looks compressed, judged by how random its bytes are.
+    pub fn guess_format(&self) -> Vec<SurfaceFormat> {
+        let id = self.format.id();
+        let looks_compressed = byte_entropy(&self.texture.0) > COMPRESSED_ENTROPY;
+
+        let mut candidates: Vec<_> = SurfaceFormat::ALL.iter()
+            .copied()
+            .filter(|&format| {
+                self.computed_layout(format, self.size_range).ok().map(|(_, _, layer_size)| {
+                    layer_size * self.array_len.max(1) as usize
+                }) == Some(self.image_size as usize)
+            })
+            .collect();
+
+        candidates.sort_by_key(|format| std::cmp::Reverse((
+            format.id() >> 8 == id >> 8,
+            format.id() & 0xff == id & 0xff,
+            format.is_compressed() == looks_compressed,
+        )));
+
+        candidates
+    }
+
+    /// Replaces the texture's data with zeros laid out for its current header
+    fn clear_data(&mut self) -> Result<(), Error> {
+        let (params, mip_offsets, image_size) = self.expected_layout()?;
+        if image_size as u64 > u32::MAX as u64 {
+            return Err(Error::TooLarge { size: image_size as u64 });
+        }
+
+        self.size_range = params.block_height.log2();
+        self.mip_offsets = mip_offsets;
+        self.image_size = image_size as u32;
+        self.set_image_data(vec![0; image_size]);
+
+        Ok(())
+    }
+
+    fn mip_levels(&self) -> u32 {
+        self.mips_count.max(1) as u32
+    }
+
+    /// Size of each array layer's data, including all of its mip levels
+    fn layer_size(&self) -> usize {
+        self.texture.0.len() / self.array_len.max(1) as usize
+    }
+
+    fn layer_alignment(&self, block_height: BlockHeight) -> usize {
+        layer_alignment(self.align, self.array_len, block_height)
+    }
+
+    /// Swizzle parameters for the first mip level of the texture in `format`, falling back to
+    /// the block height the hardware would pick if the given one isn't valid
+    fn surface_params(&self, format: SurfaceFormat, block_height_log2: i32) -> Result<SurfaceParams, Error> {
+        self.pitched_surface_params(format, block_height_log2, self.pitch_alignment())
+    }
+
+    /// Like [`surface_params`](Texture::surface_params), padding the rows of linear textures to
+    /// a multiple of `pitch_alignment` bytes
+    fn pitched_surface_params(
+        &self,
+        format: SurfaceFormat,
+        block_height_log2: i32,
+        pitch_alignment: u32,
+    ) -> Result<SurfaceParams, Error> {
+        let block_dimensions = format.block_dimensions();
+        let bytes_per_block = format.bytes_per_block().ok_or(Error::UnsupportedFormat(format))?;
+
+        // sizes of larger surfaces would overflow, and the hardware can't use them anyway
+        if self.width > MAX_TEXTURE_SIZE || self.height > MAX_TEXTURE_SIZE {
+            return Err(Error::InvalidDimensions { width: self.width, height: self.height });
+        }
+
+        // a corrupt mip count would otherwise halve the dimensions by more bits than they have
+        let max = 32 - self.width.max(self.height).max(1).leading_zeros();
+        if self.mip_levels() > max {
+            return Err(Error::TooManyMips { count: self.mip_levels(), max });
+        }
+
+        let block_height = BlockHeight::from_log2(block_height_log2).unwrap_or_else(|| {
+            tegra_swizzle::block_height(self.height.div_ceil(block_dimensions.1))
+        });
+
+        Ok(SurfaceParams {
+            width: self.width,
+            height: self.height,
+            block_dimensions,
+            bytes_per_block,
+            tile_mode: self.tile_mode as _,
+            block_height,
+            swizzle: self.swizzle as _,
+            pitch_alignment,
+        })
+    }
+
+    /// The block offsets of each mip level of a surface whose first mip is described by
+    /// `params`, computed once to be reused for every array layer
+    fn address_tables(&self, params: &SurfaceParams) -> Vec<AddressTable> {
+        (0..self.mip_levels()).map(|mip| AddressTable::new(&params.mip(mip))).collect()
+    }
+
+    /// The swizzled data of a single mip level of a single array layer
+    fn swizzled_surface(&self, layer: u32, mip: u32, params: &SurfaceParams) -> Cow<'_, [u8]> {
+        let size = tegra_swizzle::swizzled_surface_size(params);
+        let offset = layer as usize * self.layer_size()
+            + self.mip_offsets.get(mip as usize).copied().unwrap_or(0) as usize;
+
+        // data missing from the end of the texture is treated as zeroes, which is the only case
+        // the swizzled surface needs to be copied
+        let available = self.texture.0.get(offset..).unwrap_or_default();
+        if available.len() >= size {
+            Cow::Borrowed(&available[..size])
+        } else {
+            let mut surface = available.to_vec();
+            surface.resize(size, 0);
+            Cow::Owned(surface)
+        }
+    }
+
+    /// Deswizzles a single mip level of a single array layer into rows of blocks
+    fn deswizzle_surface(&self, layer: u32, mip: u32, block_height_log2: i32) -> Result<Vec<u8>, Error> {
+        let params = self.surface_params(self.format, block_height_log2)?.mip(mip);
+
+        Ok(tegra_swizzle::deswizzle(&params, &self.swizzled_surface(layer, mip, &params)))
+    }
+
+    pub fn from_image(img: image::DynamicImage, name: &str) -> Result<Self, Error> {
+        TextureBuilder::new(name).build(img)
+    }
+
+    /// Creates a texture from tightly packed RGBA8 pixels, `width * height * 4` bytes long
+    pub fn from_rgba8(width: u32, height: u32, pixels: &[u8], name: &str) -> Result<Self, Error> {
+        let expected = width as usize * height as usize * 4;
+        if pixels.len() != expected {
+            return Err(Error::MismatchedDataSize { expected, found: pixels.len() });
+        }
+
+        let img = image::RgbaImage::from_raw(width, height, pixels.to_vec()).unwrap();
+
+        Texture::from_image(image::DynamicImage::ImageRgba8(img), name)
+    }
+
+    /// Creates an `R8G8B8A8_SRGB` texture from an RGBA8 image, storing its pixels as-is
+    pub fn from_rgba_image(img: image::RgbaImage, name: &str) -> Result<Self, Error> {
+        let (width, height) = img.dimensions();
+
+        Texture::from_image_data(name, SurfaceFormat::R8G8B8A8_SRGB, width, height, img.into_raw())
+    }
+
+    /// Creates a single channel `R8_UNORM` texture from a grayscale image, such as a mask or
+    /// roughness map, without expanding it to RGBA
+    pub fn from_gray_image(img: image::GrayImage, name: &str) -> Result<Self, Error> {
+        let (width, height) = img.dimensions();
+
+        Texture::from_image_data(name, SurfaceFormat::R8_UNORM, width, height, img.into_raw())
+    }
+
+    /// Creates an `R32G32B32A32_FLOAT` texture from a float image, keeping HDR values and
+    /// values outside of 0 to 1 that converting to RGBA8 would clamp
+    pub fn from_rgba32f_image(img: image::Rgba32FImage, name: &str) -> Result<Self, Error> {
+        let (width, height) = img.dimensions();
+        let data = img.iter().flat_map(|v| v.to_le_bytes()).collect();
+
+        Texture::from_image_data(name, SurfaceFormat::R32G32B32A32_FLOAT, width, height, data)
+    }
+
+    /// Creates a single mip texture from an image's pixels, already in `format`
+    fn from_image_data(
+        name: &str,
+        format: SurfaceFormat,
+        width: u32,
+        height: u32,
+        data: Vec<u8>,
+    ) -> Result<Self, Error> {
+        Texture::from_surface(name, &Surface::new(format, width, height, 1, 1, data)?)
+    }
+
+    /// Creates a 2D texture or texture array with zeroed data, checking that the dimensions
+    /// and mip count are valid for the format
+    pub(crate) fn blank(
+        name: &str,
+        format: SurfaceFormat,
+        width: u32,
+        height: u32,
+        mips: u32,
+        array_len: u32,
+    ) -> Result<Texture, Error> {
+        let max = 32 - width.max(height).leading_zeros();
+        if mips > max {
+            return Err(Error::TooManyMips { count: mips, max });
+        }
+
+        let mut texture = Texture::from_rgba8(1, 1, &[0; 4], name)?;
+        texture.set_dimensions(width, height, 1)?;
+        texture.set_format(format)?;
+        texture.mips_count = mips.max(1) as u16;
+        texture.array_len = array_len.max(1);
+        texture.kind = TextureKind::for_texture(texture.dim, texture.array_len);
+        texture.clear_data()?;
+
+        Ok(texture)
+    }
+
+    /// Creates a texture in `format` from a base image and its mip levels, largest first, for
+    /// mips generated by another tool. See [`TextureBuilder::build_with_mips`].
+    pub fn from_image_with_mips(
+        base: image::DynamicImage,
+        mips: Vec<image::DynamicImage>,
+        name: &str,
+        format: SurfaceFormat,
+    ) -> Result<Self, Error> {
+        let mut texture = TextureBuilder::new(name).build_with_mips(base, mips)?;
+        if format != texture.format {
+            texture.convert_format(format)?;
+        }
+
+        Ok(texture)
+    }
+}
+
+fn plural(count: impl Into<u64>, singular: &str) -> String {
+    match count.into() {
+        1 => format!("1 {}", singular),
+        count => format!("{} {}s", count, singular),
+    }
+}
+
+impl fmt::Display for Texture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}x{}", self.name(), self.width, self.height)?;
+        if self.depth > 1 {
+            write!(f, "x{}", self.depth)?;
+        }
+
+        write!(
+            f,
+            " {}, {}, {}, {}",
+            self.format,
+            plural(self.mips_count, "mip"),
+            plural(self.array_len, "layer"),
+            plural(self.texture.0.len() as u64, "byte"),
+        )
+    }
+}
+
+impl fmt::Display for BntxFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({})",
+            self.header.inner.file_name.chars,
+            plural(self.nx_header.textures.len() as u64, "texture")
+        )?;
+
+        for texture in self.textures() {
+            write!(f, "\n  {}", texture)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl BntxFile {
+    pub fn textures(&self) -> impl Iterator<Item = &Texture> {
+        self.nx_header.textures.iter()
+    }
+
+    pub fn textures_mut(&mut self) -> impl Iterator<Item = &mut Texture> {
+        self.nx_header.textures.iter_mut()
+    }
+
+    /// Looks up a texture by name, using the file's dictionary and falling back to a linear
+    /// search if the dictionary doesn't have it
+    pub fn texture(&self, name: &str) -> Option<&Texture> {
+        self.texture_index(name).map(|i| &self.nx_header.textures[i])
+    }
+
+    pub fn texture_mut(&mut self, name: &str) -> Option<&mut Texture> {
+        self.texture_index(name).map(move |i| &mut self.nx_header.textures[i])
+    }
+
+    fn texture_index(&self, name: &str) -> Option<usize> {
+        let textures = &self.nx_header.textures;
+
+        self.nx_header.dict.find(name)
+            .filter(|&i| textures.get(i).is_some_and(|tex| tex.name() == name))
+            .or_else(|| textures.iter().position(|tex| tex.name() == name))
+    }
+
+    /// Ranges of `data`, a whole file, that aren't part of any section or texture, such as
+    /// data appended after the file, or payloads from other tools hidden in gaps between
+    /// sections. Zeros are left out, since those are alignment padding, so each range starts
+    /// and ends with a non-zero byte.
+    ///
+    /// The ranges are found from the bytes given rather than kept when a file is read, so
+    /// reading doesn't need the whole stream, and anything after the file in `data` counts as
+    /// trailing data.
+    pub fn unparsed_ranges_in(data: &[u8]) -> Vec<Range<usize>> {
+        sections::unparsed_ranges(data)
+    }
+
+    /// Decodes the first texture in the file
+    pub fn to_image(&self) -> Result<image::DynamicImage, Error> {
+        self.nx_header.textures.first().ok_or(Error::NoTextures)?.to_image()
+    }
+
+    /// Hash of every texture's name and [data hash](Texture::data_hash), in order. Files with
+    /// the same textures hash the same even if they were written by different tools.
+    pub fn content_hash(&self) -> Result<u64, Error> {
+        let mut hasher = Xxh64::new();
+
+        for texture in self.textures() {
+            hasher.write(&(texture.name_bytes().len() as u32).to_le_bytes());
+            hasher.write(texture.name_bytes());
+            hasher.write(&texture.data_hash()?.to_le_bytes());
+        }
+
+        Ok(hasher.finish())
+    }
+
+    /// Writes the file to memory and reads it back, returning every difference in the file's
+    /// version, its textures' header fields and their deswizzled data. An empty list means the
+    /// file survives being saved, which is worth checking before shipping a file that could
+    /// crash a game.
+    #[cfg(feature = "write")]
+    pub fn verify_roundtrip(&self) -> Result<Vec<RoundtripMismatch>, Error> {
+        let mut data = Vec::new();
+        self.write(&mut data)?;
+        let read = BntxFile::from_reader(&data[..])?;
+
+        let mut mismatches = Vec::new();
+        if read.target() != self.target() {
+            mismatches.push(RoundtripMismatch::Target { written: self.target(), read: read.target() });
+        }
+
+        for (index, texture) in self.textures().enumerate() {
+            match read.nx_header.textures.get(index) {
+                Some(read) if read.name() == texture.name() => {
+                    mismatches.extend(texture.roundtrip_mismatches(read)?);
+                }
+                _ => mismatches.push(RoundtripMismatch::MissingTexture(texture.name().into())),
+            }
+        }
+
+        Ok(mismatches)
+    }
+
+    /// Adds a texture to the file, replacing (and returning) any existing texture with the
+    /// same name. Fails, leaving the file unchanged, if the texture's name is empty or the file
+    /// already has textures with the same name, such as one read that way.
+    pub fn push_texture(&mut self, texture: Texture) -> Result<Option<Texture>, Error> {
+        let index = self.texture_index(texture.name());
+        let replaced = match index {
+            Some(i) => Some(std::mem::replace(&mut self.nx_header.textures[i], texture)),
+            None => {
+                self.nx_header.textures.push(texture);
+                None
+            }
+        };
+
+        if let Err(err) = self.update_dict() {
+            match (index, replaced) {
+                (Some(i), Some(replaced)) => self.nx_header.textures[i] = replaced,
+                _ => {
+                    self.nx_header.textures.pop();
+                }
+            }
+
+            return Err(err);
+        }
+
+        Ok(replaced)
+    }
+
+    /// Copies a texture, including its data, from another file into this one, replacing any
+    /// texture with the same name. Returns the replaced texture.
+    pub fn copy_texture_from(&mut self, other: &BntxFile, name: &str) -> Result<Option<Texture>, Error> {
+        let texture = other.texture(name).ok_or_else(|| Error::MissingTexture(name.to_owned()))?;
+
+        self.push_texture(texture.clone())
+    }
+
+    pub fn remove_texture(&mut self, name: &str) -> Option<Texture> {
+        let texture = self.nx_header.textures.remove(self.texture_index(name)?);
+
+        // removing a texture can't add a duplicate name, but files read with one keep theirs
+        self.nx_header.dict = DictSection::from_names(self.textures().map(Texture::name_bytes));
+
+        Some(texture)
+    }
+
+    fn update_dict(&mut self) -> Result<(), Error> {
+        self.nx_header.dict = DictSection::try_from_names(self.textures().map(Texture::name_bytes))?;
+
+        Ok(())
+    }
+
+    /// Writes the file. Each texture's data is written exactly as it's stored, so textures that
+    /// weren't changed since the file was read keep their original bytes, and compressed data
+    /// is never re-encoded.
+    ///
+    /// The output only depends on the file's contents: its name, target, memory pool, textures
+    /// in order, and whether [identical data is shared](BntxFile::set_share_identical_data).
+    /// The same contents always give byte-identical output, however the file was built or
+    /// edited, so generated files can be cached and diffed. Strings are pooled in sorted order,
+    /// every byte of padding is zero, and shared data is that of the first texture with it.
+    #[cfg(feature = "write")]
+    pub fn write<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        // positions are tracked from where writing starts, so nothing needs to seek
+        self.write_le(&mut binrw::io::NoSeek::new(writer)).map_err(|err| match err {
+            binrw::Error::Io(err) => err,
+            err => io::Error::other(err),
+        })
+    }
+
+    /// Writes the file starting at `base_offset` in `writer`, such as into an archive being
+    /// repacked. The file's pointers are relative to its own start, so it can be read back with
+    /// [`read_at`](BntxFile::read_at). Texture data is aligned to 0x1000 bytes from the start
+    /// of the file, so `base_offset` should be too for it to stay aligned in memory.
+    #[cfg(feature = "write")]
+    pub fn write_at<W: io::Write + io::Seek>(&self, writer: &mut W, base_offset: u64) -> io::Result<()> {
+        writer.seek(io::SeekFrom::Start(base_offset))?;
+
+        self.write(writer)
+    }
+
+    /// The exact number of bytes [`write`](BntxFile::write) would produce, computed from the
+    /// file's layout without writing anything. Useful for reserving space in an archive, or for
+    /// checking the size fits in the header's 32-bit file size before writing.
+    #[cfg(feature = "write")]
+    pub fn predicted_size(&self) -> usize {
+        Layout::new(self).file_size
+    }
+
+    /// Makes textures whose data is byte-identical to an earlier texture's point to that
+    /// texture's data rather than storing another copy, which shrinks files with repeated
+    /// placeholder textures. Off by default, since a game that modifies a texture at runtime
+    /// would modify every texture sharing its data.
+    pub fn set_share_identical_data(&mut self, share: bool) {
+        self.nx_header.share_identical_data = share;
+    }
+
+    /// Makes writing the file fail with [`Error::GameCompat`] if any texture fails
+    /// [`check_game_compat`](Texture::check_game_compat), rather than writing a file that can
+    /// hang or crash the game loading it. Off by default, since tools inspecting or repairing
+    /// broken files need to write them as they are. The error is returned inside the
+    /// `io::Error` from [`write`](BntxFile::write).
+    pub fn set_strict_game_compat(&mut self, strict: bool) {
+        self.nx_header.strict_game_compat = strict;
+    }
+
+    /// Checks every texture with [`Texture::check_game_compat`], returning the first problem
+    pub fn check_game_compat(&self) -> Result<(), Error> {
+        self.textures().try_for_each(Texture::check_game_compat)
+    }
+
+    /// The memory pool between the headers and the texture pointers, exactly as read. It's
+    /// zeros in files from NintendoWare's tools, since the runtime fills it in when the file is
+    /// loaded, but other tools may store metadata in it.
+    pub fn mem_pool(&self) -> &[u8] {
+        &self.nx_header.mem_pool
+    }
+
+    /// Replaces the memory pool, padded with zeros to a multiple of 8 bytes so the texture
+    /// pointers after it stay aligned. Its size moves everything after it, and an empty pool
+    /// omits it entirely, for versions that don't have one.
+    pub fn set_mem_pool(&mut self, mut pool: Vec<u8>) {
+        pool.resize(pool.len().next_multiple_of(DATA_PTR_SIZE), 0);
+        self.nx_header.mem_pool = pool;
+    }
+
+    /// Offset of the memory pool's info in the pool, which the NX header points to, or `None`
+    /// if the pointer is null
+    pub fn mem_pool_info_offset(&self) -> Option<usize> {
+        self.nx_header.mem_pool_info
+    }
+
+    /// Moves the memory pool's info to another offset in the pool, or makes the NX header's
+    /// pointer to it null with `None`. Offsets outside the pool are written as a null pointer.
+    pub fn set_mem_pool_info_offset(&mut self, offset: Option<usize>) {
+        self.nx_header.mem_pool_info = offset;
+    }
+
+    /// Pairs of textures with byte-identical data, each duplicate paired with the first texture
+    /// it matches, for finding repeated textures whether or not their data is shared
+    pub fn identical_textures(&self) -> Vec<(&str, &str)> {
+        let mut first = HashMap::new();
+        let mut duplicates = Vec::new();
+        for texture in self.textures() {
+            match first.get(&texture.texture.0[..]) {
+                Some(&original) => duplicates.push((texture.name(), original)),
+                None => {
+                    first.insert(&texture.texture.0[..], texture.name());
+                }
+            }
+        }
+
+        duplicates
+    }
+
+    /// Every section of the file as it will be written, in order, with its offset, size and the
+    /// padding after it. Useful for finding what makes a file larger than expected.
+    #[cfg(feature = "write")]
+    pub fn layout(&self) -> Vec<SectionInfo> {
+        Layout::new(self).sections(self)
+    }
+
+    /// The version and revision the file was read with or will be written as
+    pub fn target(&self) -> Target {
+        Target {
+            version: self.header.version,
+            revision: self.header.inner.revision,
+        }
+    }
+
+    pub fn set_target(&mut self, target: Target) {
+        self.header.version = target.version;
+        self.header.inner.revision = target.revision;
+    }
+
+    /// Creates an empty file with no textures
+    pub fn new(name: &str) -> Self {
+        BntxFile::with_target(name, Target::default())
+    }
+
+    /// Creates an empty file with no textures, written as the given version for games that
+    /// expect something other than the default
+    pub fn with_target(name: &str, target: Target) -> Self {
+        BntxFile {
+            header: BntxHeader {
+                version: target.version,
+                bom: ByteOrder::LittleEndian,
+                inner: HeaderInner {
+                    revision: target.revision,
+                    file_name: BntxStr::from(name.to_owned()),
+                }
+            },
+            nx_header: NxHeader {
+                dict: DictSection::from_names(std::iter::empty()),
+                textures: Vec::new(),
+                mem_pool: vec![0; MEM_POOL_SIZE],
+                mem_pool_info: Some(MEM_POOL_INFO_OFFSET),
+                share_identical_data: false,
+                strict_game_compat: false,
+            },
+            source: None,
+        }
+    }
+
+    pub fn from_image(img: image::DynamicImage, name: &str) -> Result<Self, Error> {
+        let mut file = BntxFile::new(name);
+
+        file.push_texture(Texture::from_image(img, name)?)?;
+
+        Ok(file)
+    }
+
+    /// Creates a file with a single texture from tightly packed RGBA8 pixels, for generating
+    /// textures without going through the `image` crate
+    pub fn from_rgba8(width: u32, height: u32, pixels: &[u8], name: &str) -> Result<Self, Error> {
+        let mut file = BntxFile::new(name);
+
+        file.push_texture(Texture::from_rgba8(width, height, pixels, name)?)?;
+
+        Ok(file)
+    }
+
+    /// Creates a copy of a file, usually one from a game, with its first texture's pixels
+    /// replaced by an image the size of that texture. Every other part of the file, including
+    /// the texture's format, flags, tile mode, swizzle, block height and alignment, is kept, so
+    /// the result matches what the game expects. Any other layers of a texture array are left
+    /// blank. See [`Texture::replace_image`].
+    pub fn from_image_like(reference: &BntxFile, img: image::DynamicImage) -> Result<Self, Error> {
+        let mut texture = reference.nx_header.textures.first().ok_or(Error::NoTextures)?.metadata_template();
+        texture.replace_image(img)?;
+
+        let mut file = reference.clone();
+        file.nx_header.textures[0] = texture;
+
+        Ok(file)
+    }
+
+    /// Creates a file with a single texture made with the settings a game expects for textures
+    /// with the preset's role
+    pub fn from_image_with_preset(img: image::DynamicImage, name: &str, preset: Preset) -> Result<Self, Error> {
+        let mut texture = TextureBuilder::new(name)
+            .size_alignment(preset.size_alignment())
+            .build(img)?;
+        texture.convert_format(preset.format())?;
+        texture.comp_sel = preset.comp_sel();
+
+        let mut file = BntxFile::new(name);
+        file.push_texture(texture)?;
+
+        Ok(file)
+    }
+
+    /// Reads a file from disk. With the `zstd` or `yaz0` features, zstd (`.bntx.zs`) or Yaz0
+    /// compressed files are decompressed automatically.
+    pub fn open<P: AsRef<Path>>(path: P) -> BinResult<Self> {
+        trace_span!("open", path = %path.as_ref().display());
+
+        let mut file = io::BufReader::new(std::fs::File::open(path.as_ref())?);
+
+        #[cfg(any(feature = "zstd", feature = "yaz0"))]
+        {
+            if let Some(data) = compression::decompress(&mut file)? {
+                return io::Cursor::new(data).read_le();
+            }
+        }
+
+        file.read_le()
+    }
+
+    /// Reads a file from a stream that can't seek, such as stdin or a pipe, by reading it into
+    /// memory first. Compressed files are decompressed the same way as in [`open`](BntxFile::open).
+    pub fn from_reader<R: io::Read>(mut reader: R) -> BinResult<Self> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        Self::from_bytes(data)
+    }
+
+    /// Parses a whole file already read into memory, decompressing it first if it's compressed
+    pub(crate) fn from_bytes(data: Vec<u8>) -> BinResult<Self> {
+        let mut data = io::Cursor::new(data);
+
+        #[cfg(any(feature = "zstd", feature = "yaz0"))]
+        {
+            if let Some(decompressed) = compression::decompress(&mut data)? {
+                data = io::Cursor::new(decompressed);
+            }
+        }
+
+        data.read_le()
+    }
+
+    /// Reads a file from a stream that can't seek, such as a network or decompression stream,
+    /// without reading past its end. The header is read first to find the file's size, so
+    /// unlike [`from_reader`](BntxFile::from_reader) the stream doesn't have to end with the
+    /// file, and anything after it, such as another file, is left in the stream. Compressed
+    /// files aren't supported, since their size isn't known up front.
+    pub fn read_streaming<R: io::Read>(mut reader: R) -> BinResult<Self> {
+        trace_span!("read_streaming");
+
+        let mut data = vec![0; BNTX_HEADER_SIZE];
+        reader.read_exact(&mut data)?;
+
+        if &data[..4] != b"BNTX" {
+            return Err(binrw::Error::BadMagic { pos: 0, found: Box::new(data[..4].to_vec()) });
+        }
+
+        // the size is the last field of the header, in the byte order the mark at 0xC gives
+        let size_bytes = data[0x1c..0x20].try_into().unwrap();
+        let file_size = match data[0xc..0xe] {
+            [0xfe, 0xff] => u32::from_be_bytes(size_bytes),
+            _ => u32::from_le_bytes(size_bytes),
+        };
+
+        let remaining = (file_size as u64).saturating_sub(BNTX_HEADER_SIZE as u64);
+        reader.take(remaining).read_to_end(&mut data)?;
+
+        io::Cursor::new(data).read_le()
+    }
+
+    /// Reads a file that starts `offset` bytes into `reader`, such as one embedded in an
+    /// archive, without copying it out first
+    pub fn read_at<R: Read + Seek>(reader: &mut R, offset: u64) -> BinResult<Self> {
+        trace_span!("read_at", offset);
+
+        let mut reader = Subfile { inner: reader, start: offset };
+        reader.seek(SeekFrom::Start(0))?;
+
+        reader.read_le()
+    }
+
+    /// Reads a file whose texture data is stored apart from the rest, such as in another file
+    /// of a container. `data` holds what would be at `data_offset` in a single file, where the
+    /// textures' data pointers lead, and `reader` holds the rest. Anything in neither, such as
+    /// a `BRTD` header left out of both, reads as zeros. The file is written back as a single
+    /// file.
+    pub fn read_with_data<R, D>(reader: &mut R, data: &mut D, data_offset: u64) -> BinResult<Self>
+    where
+        R: Read + Seek,
+        D: Read + Seek,
+    {
+        trace_span!("read_with_data", data_offset);
+
+        SplitFile::new(reader, data, data_offset)?.read_le()
+    }
+
+    /// Writes the file to disk. With the `zstd` feature, paths ending in `.zs` are zstd
+    /// compressed.
+    #[cfg(feature = "write")]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = std::fs::File::create(path.as_ref())?;
+
+        #[cfg(feature = "zstd")]
+        {
+            if compression::saves_as_zstd(path.as_ref()) {
+                let mut data = Vec::new();
+                self.write(&mut data)?;
+
+                return io::Write::write_all(&mut file, &compression::compress_zstd(&data)?);
+            }
+        }
+
+        self.write(&mut file)
+    }
+}
+
+/// Writes the file the same way as [`BntxFile::write`], so it can be nested in other `binrw`
+/// structures such as an archive containing it. Offsets are laid out in a pass over the file
+/// before anything is written, and are relative to the start of the BNTX file itself.
+#[cfg(feature = "write")]
+impl BinWrite for BntxFile {
+    type Args<'a> = ();
+
+    fn write_options<W: io::Write + Seek>(&self, writer: &mut W, _: Endian, _: ()) -> BinResult<()> {
+        trace_span!("write", textures = self.nx_header.textures.len());
+
+        if self.nx_header.strict_game_compat {
+            self.check_game_compat().map_err(|err| binrw::Error::Io(io::Error::other(err)))?;
+        }
+
+        // the byte order mark is always little endian, whatever the surrounding data uses
+        let endian = Endian::Little;
+        let layout = Layout::new(self);
+
+        self.header.write_options(writer, endian, (&layout,))?;
+        self.nx_header.write_options(writer, endian, (&layout,))?;
+
+        writer.write_all(&self.nx_header.mem_pool)?;
+        layout.textures.iter()
+            .map(|tex| tex.brti as u64)
+            .collect::<Vec<_>>()
+            .write_options(writer, endian, ())?;
+
+        layout.str_section.write_options(writer, endian, (&layout,))?;
+        layout.dict.write_options(writer, endian, (&layout,))?;
+
+        for (i, texture) in self.textures().enumerate() {
+            texture.write_options(writer, endian, (&layout, i))?;
+        }
+
+        write_zeroes(writer, layout.brtd_offset - layout.headers_end)?;
+
+        // BRTD
+        (
+            *b"BRTD",
+            0u32,
+            layout.brtd_size as u64,
+        ).write_options(writer, endian, ())?;
+
+        let mut pos = layout.brtd_offset + SIZE_OF_BRTD;
+        for (texture, tex_layout) in self.textures().zip(&layout.textures).filter(|(_, tex)| !tex.shared) {
+            write_zeroes(writer, tex_layout.data - pos)?;
+            writer.write_all(&texture.texture.0)?;
+
+            pos = tex_layout.data + texture.texture.0.len();
+        }
+
+        write_zeroes(writer, layout.reloc_table_offset - pos)?;
+
+        layout.reloc_table.write_options(writer, endian, (layout.reloc_table_offset as u32,))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "write")]
+fn write_zeroes<W: io::Write>(writer: &mut W, count: usize) -> io::Result<()> {
+    io::copy(&mut io::repeat(0).take(count as u64), writer).map(drop)
+}
+
+/// A file embedded in a larger stream, such as an archive, starting `start` bytes into it.
+/// Positions are relative to the start of the embedded file, so its pointers can be followed
+/// as-is.
+struct Subfile<'a, R> {
+    inner: &'a mut R,
+    start: u64,
+}
+
+impl<R: Read> Read for Subfile<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl<R: Seek> Seek for Subfile<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => {
+                let pos = self.start.checked_add(pos).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "seek past the end of the stream")
+                })?;
+                self.inner.seek(SeekFrom::Start(pos))?
+            }
+            pos => self.inner.seek(pos)?,
+        };
+
+        Ok(pos.saturating_sub(self.start))
+    }
+}
+
+/// A file with its texture data in another stream, read as one stream with the data at
+/// `data_offset`. Gaps between the two read as zeros.
+struct SplitFile<'a, R, D> {
+    header: &'a mut R,
+    header_len: u64,
+    data: &'a mut D,
+    data_offset: u64,
+    data_len: u64,
+    pos: u64,
+}
+
+impl<'a, R: Seek, D: Seek> SplitFile<'a, R, D> {
+    fn new(header: &'a mut R, data: &'a mut D, data_offset: u64) -> io::Result<Self> {
+        let header_len = header.seek(SeekFrom::End(0))?;
+        let data_len = data.seek(SeekFrom::End(0))?;
+        data_offset.checked_add(data_len).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "texture data extends past the end of the stream")
+        })?;
+
+        Ok(SplitFile { header, header_len, data, data_offset, data_len, pos: 0 })
+    }
+}
+
+impl<R, D> SplitFile<'_, R, D> {
+    fn data_end(&self) -> u64 {
+        self.data_offset + self.data_len
+    }
+}
+
+impl<R: Read + Seek, D: Read + Seek> Read for SplitFile<'_, R, D> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let pos = self.pos;
+
+        let read = if (self.data_offset..self.data_end()).contains(&pos) {
+            let len = buf.len().min((self.data_end() - pos) as usize);
+            self.data.seek(SeekFrom::Start(pos - self.data_offset))?;
+            self.data.read(&mut buf[..len])?
+        } else if pos < self.header_len {
+            // the header's bytes, up to where the data starts
+            let end = if pos < self.data_offset { self.header_len.min(self.data_offset) } else { self.header_len };
+            let len = buf.len().min((end - pos) as usize);
+            self.header.seek(SeekFrom::Start(pos))?;
+            self.header.read(&mut buf[..len])?
+        } else if pos < self.data_offset {
+            let len = buf.len().min((self.data_offset - pos) as usize);
+            buf[..len].fill(0);
+            len
+        } else {
+            0
+        };
+
+        self.pos += read as u64;
+
+        Ok(read)
+    }
+}
+
+impl<R, D> Seek for SplitFile<'_, R, D> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+            SeekFrom::End(offset) => self.header_len.max(self.data_end()).checked_add_signed(offset),
+        };
+
+        self.pos = pos.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek before the start of the stream")
+        })?;
+
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "write")]
+    use binrw::prelude::*;
+    #[cfg(feature = "write")]
+    use binrw::io::*;
+    use super::{BntxFile, BlockHeight, SurfaceParams};
+
+    /*
+    #[test]
+    fn try_parse() {
+        let mut data = Cursor::new(&include_bytes!("/home/jam/Downloads/ester.bntx")[..]);
+        //let mut data = Cursor::new(&include_bytes!("/home/jam/dev/ult/bntx/test.bntx")[..]);
+
+        let test: BntxFile = data.read_le().unwrap();
+
+        dbg!(&test);
+
+        test.to_image()
+            .save("test.png");
+    }
+    */
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn try_from_png() {
+        let image = image::DynamicImage::ImageRgba8(image::RgbaImage::new(64, 64));
+
+        let tex = BntxFile::from_image(image, "ester").unwrap();
+
+        tex.write(&mut Cursor::new(Vec::new())).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn read_written_textures() {
+        let image = image::DynamicImage::ImageRgba8(image::RgbaImage::new(32, 16));
+
+        let mut data = Cursor::new(Vec::new());
+        BntxFile::from_image(image, "ester").unwrap().write(&mut data).unwrap();
+        data.set_position(0);
+
+        let file: BntxFile = data.read_le().unwrap();
+        let textures: Vec<_> = file.textures().collect();
+
+        assert_eq!(textures.len(), 1);
+        assert_eq!(textures[0].name(), "ester");
+        assert_eq!((textures[0].width(), textures[0].height()), (32, 16));
+        assert_eq!(textures[0].info().format, super::SurfaceFormat::R8G8B8A8_SRGB);
+
+        assert!(file.texture("ester").is_some());
+        assert!(file.texture("chara").is_none());
+
+        assert!(matches!(BntxFile::new("empty").to_image(), Err(super::Error::NoTextures)));
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn verify_roundtrip() {
+        use super::RoundtripMismatch;
+
+        let mut file = BntxFile::from_image(gradient(64, 64), "ester").unwrap();
+        file.push_texture(super::Texture::from_gray_image(gradient(32, 32).to_luma8(), "maya").unwrap()).unwrap();
+        assert_eq!(file.verify_roundtrip().unwrap(), []);
+
+        // the comparison itself, since a file that doesn't survive a round trip is a bug
+        let texture = file.texture("ester").unwrap();
+        let mut changed = texture.clone();
+        changed.comp_sel = crate::preset::GRAYSCALE_COMP_SEL;
+        assert_eq!(texture.roundtrip_mismatches(&changed).unwrap(), [RoundtripMismatch::Field {
+            texture: "ester".into(),
+            field: "comp_sel",
+            written: 0x0504_0302,
+            read: 0x0102_0202,
+        }]);
+
+        let mut changed = texture.clone();
+        changed.texture.0[0] ^= 1;
+        assert_eq!(
+            texture.roundtrip_mismatches(&changed).unwrap(),
+            [RoundtripMismatch::Data { texture: "ester".into(), layer: 0, mip: 0 }]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn structural_equality() {
+        use super::{GpuAccess, Texture};
+
+        let mut file = BntxFile::from_image(gradient(64, 64), "ester").unwrap();
+        file.push_texture(Texture::from_image(gradient(32, 16), "maya").unwrap()).unwrap();
+
+        // offsets differ once read, but the content doesn't
+        let mut data = Vec::new();
+        file.write(&mut Cursor::new(&mut data)).unwrap();
+        let read = BntxFile::from_reader(&data[..]).unwrap();
+        assert_eq!(read, file);
+        assert_eq!(read.clone(), read);
+
+        // an undo snapshot stays as it was while the file is edited
+        let snapshot = file.clone();
+        file.texture_mut("maya").unwrap().set_gpu_access(GpuAccess::TEXTURE | GpuAccess::COLOR_BUFFER);
+        assert_ne!(file, snapshot);
+        assert_ne!(file.texture("maya"), snapshot.texture("maya"));
+        assert_eq!(file.texture("ester"), snapshot.texture("ester"));
+
+        let mut file = snapshot.clone();
+        file.texture_mut("ester").unwrap().texture.0[0] ^= 1;
+        assert_ne!(file, snapshot);
+
+        let mut file = snapshot.clone();
+        file.remove_texture("maya");
+        assert_ne!(file, snapshot);
+        file.push_texture(snapshot.texture("maya").unwrap().clone()).unwrap();
+        assert_eq!(file, snapshot);
+
+        let mut file = snapshot.clone();
+        file.set_target(super::Target { revision: 0x400b, ..super::Target::V4 });
+        assert_ne!(file, snapshot);
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn push_and_remove_textures() {
+        let image = || image::DynamicImage::ImageRgba8(image::RgbaImage::new(16, 16));
+
+        let mut file = BntxFile::from_image(image(), "ester").unwrap();
+        for name in ["chara_0", "chara_1", "chara_2", "ester"] {
+            file.push_texture(super::Texture::from_image(image(), name).unwrap()).unwrap();
+        }
+        assert!(file.remove_texture("chara_1").is_some());
+
+        let mut data = Cursor::new(Vec::new());
+        file.write(&mut data).unwrap();
+        data.set_position(0);
+
+        let file: BntxFile = data.read_le().unwrap();
+        let names: Vec<_> = file.textures().map(|tex| tex.name()).collect();
+
+        assert_eq!(names, ["ester", "chara_0", "chara_2"]);
+        for (i, name) in names.iter().enumerate() {
+            assert_eq!(file.nx_header.dict.find(name), Some(i));
+        }
+        assert!(file.texture("chara_1").is_none());
+
+        // names the dictionary can't hold are rejected, leaving the file as it was
+        use super::{BntxStr, Error, Texture};
+        let mut file = file;
+        assert!(matches!(Texture::from_image(image(), ""), Err(Error::EmptyName)));
+        let mut empty = Texture::from_image(image(), "maya").unwrap();
+        empty.name = BntxStr::from(String::new());
+        assert!(matches!(file.push_texture(empty), Err(Error::EmptyName)));
+        assert_eq!(file.textures().count(), 3);
+
+        let duplicate = file.texture("ester").unwrap().clone();
+        file.nx_header.textures.push(duplicate);
+        assert!(matches!(
+            file.push_texture(Texture::from_image(image(), "maya").unwrap()),
+            Err(Error::DuplicateName(name)) if name == "ester"
+        ));
+        assert_eq!(file.textures().count(), 4);
+        assert!(file.texture("maya").is_none());
+    }
+
+    use super::SurfaceFormat::*;
+    const KNOWN_FORMATS: [super::SurfaceFormat; 25] = [
+        R8_UNORM, R4G4B4A4_UNORM, R5G5B5A1_UNORM, R5G6B5_UNORM, R8G8B8A8_UNORM, R8G8B8A8_SRGB,
+        B8G8R8A8_UNORM, B8G8R8A8_SRGB, R10G10B10A2_UNORM, R16G16B16A16_FLOAT,
+        R32G32B32A32_FLOAT, BC1_UNORM, BC1_SRGB, BC2_UNORM, BC2_SRGB, BC3_UNORM, BC3_SRGB,
+        BC4_UNORM, BC4_SNORM, BC5_UNORM, BC5_SNORM, BC6H_UF16, BC6H_SF16, BC7_UNORM, BC7_SRGB,
+    ];
+
+    fn gradient(width: u32, height: u32) -> image::DynamicImage {
+        image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(width, height, |x, y| {
+            image::Rgba([x as u8, y as u8, (x ^ y) as u8, 255])
+        }))
+    }
+
+    /// A file in the temp directory, removed even if an assertion fails
+    #[cfg(feature = "write")]
+    struct TempFile(std::path::PathBuf);
+
+    #[cfg(feature = "write")]
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn swizzle_value_roundtrip() {
+        for swizzle in [0, 5] {
+            let texture = super::TextureBuilder::new("ester")
+                .swizzle(swizzle)
+                .build(gradient(128, 128))
+                .unwrap();
+
+            let mut file = BntxFile::new("ester");
+            file.push_texture(texture).unwrap();
+
+            let mut data = Cursor::new(Vec::new());
+            file.write(&mut data).unwrap();
+            data.set_position(0);
+
+            let file: BntxFile = data.read_le().unwrap();
+            assert_eq!(file.to_image().unwrap(), gradient(128, 128));
+        }
+    }
+
+    #[test]
+    fn swizzle_into_reuses_buffer() {
+        use super::tegra_swizzle::{self, deswizzle_into, swizzle_into};
+
+        let pixels = gradient(64, 48).into_rgba8().into_raw();
+        let mut swizzled = vec![0xff; 0x10000];
+        let mut deswizzled = vec![0xff; 64 * 48 * 4 + 16];
+
+        for block_height in [BlockHeight::One, BlockHeight::Four, BlockHeight::Sixteen] {
+            let params = SurfaceParams { block_height, ..SurfaceParams::new(64, 48, 4) };
+
+            let expected = tegra_swizzle::swizzle(&params, &pixels);
+            assert_eq!(expected.len(), tegra_swizzle::swizzled_surface_size(&params));
+            assert_eq!(tegra_swizzle::deswizzle(&params, &expected), pixels);
+
+            swizzle_into(&params, &pixels, &mut swizzled);
+            deswizzle_into(&params, &swizzled, &mut deswizzled);
+            assert_eq!(deswizzled[..pixels.len()], pixels[..]);
+            assert_eq!(deswizzled[pixels.len()..], [0xff; 16]);
+        }
+    }
+
+    #[test]
+    fn swizzle_options() {
+        use super::tegra_swizzle::{self, AddressTable, Backend, SwizzleOptions};
+
+        let layouts = [(0, BlockHeight::One), (0, BlockHeight::Sixteen), (1, BlockHeight::One)];
+        for (tile_mode, block_height) in layouts {
+            for (width, height, bpp) in [(64, 48, 4), (37, 91, 16), (5, 3, 1), (130, 70, 8)] {
+                let params = SurfaceParams {
+                    tile_mode, block_height, ..SurfaceParams::new(width, height, bpp)
+                };
+                let table = AddressTable::new(&params);
+                let pixels: Vec<u8> = (0..tegra_swizzle::deswizzled_mip_size(&params))
+                    .map(|i| (i * 7 % 251) as u8)
+                    .collect();
+
+                // the region path copies a block at a time, so it's the reference for the rest
+                let swizzled = table.swizzle(&pixels);
+                assert_eq!(
+                    tegra_swizzle::deswizzle_region(&params, &swizzled, 0, 0, width, height),
+                    pixels
+                );
+
+                for gob_rows in [0, 1, 3] {
+                    for unchecked in [false, true] {
+                        let options = SwizzleOptions { gob_rows, unchecked };
+
+                        let mut out = vec![0; swizzled.len()];
+                        table.swizzle_into_with(&pixels, &mut out, &options);
+                        assert_eq!(out, swizzled, "{:?}", options);
+                        assert_eq!(table.deswizzle_with(&swizzled, &options), pixels, "{:?}", options);
+                        assert_eq!(Backend::CpuWith(options).deswizzle(&params, &swizzled), pixels);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn address_table() {
+        use super::tegra_swizzle::{self, AddressTable};
+
+        let pixels = gradient(64, 48).into_rgba8().into_raw();
+        let layouts = [(0, BlockHeight::One, 0), (0, BlockHeight::Four, 3), (1, BlockHeight::One, 0)];
+        for (tile_mode, block_height, swizzle) in layouts {
+            let params = SurfaceParams {
+                tile_mode, block_height, swizzle, ..SurfaceParams::new(64, 48, 4)
+            };
+            let table = AddressTable::new(&params);
+
+            let swizzled = table.swizzle(&pixels);
+            assert_eq!(swizzled, tegra_swizzle::swizzle(&params, &pixels));
+            assert_eq!(table.deswizzle(&swizzled), pixels);
+        }
+
+        // the pixel at (16, 1) is in the second GOB, on its second row
+        let params = SurfaceParams { block_height: BlockHeight::One, ..SurfaceParams::new(64, 48, 4) };
+        let mut pixels = vec![0; 64 * 48 * 4];
+        pixels[(64 + 16) * 4] = 0xff;
+        assert_eq!(AddressTable::new(&params).swizzle(&pixels)[512 + 16], 0xff);
+
+        // every layer of an array is deswizzled with the same tables
+        let layers: Vec<_> = (0..6).map(|i| gradient(32, 32).huerotate(i * 60)).collect();
+        let texture = super::TextureBuilder::new("cube").build_array(layers.clone()).unwrap();
+        let surface = texture.to_surface().unwrap();
+        for (layer, img) in (0..).zip(layers) {
+            assert_eq!(surface.mip(layer, 0).unwrap(), &img.into_rgba8().into_raw()[..]);
+        }
+    }
+
+    #[test]
+    fn surface_params_mips() {
+        use super::tegra_swizzle;
+
+        assert_eq!(tegra_swizzle::block_height(256), BlockHeight::Sixteen);
+        assert_eq!(tegra_swizzle::block_height(8), BlockHeight::One);
+
+        let params = SurfaceParams {
+            block_dimensions: (4, 4),
+            bytes_per_block: 16,
+            ..SurfaceParams::new(1024, 1024, 16)
+        };
+        assert_eq!(params.block_height, BlockHeight::Sixteen);
+
+        let mip = params.mip(3);
+        assert_eq!((mip.width, mip.height), (128, 128));
+        assert_eq!(mip.block_height, BlockHeight::Four);
+        assert_eq!(tegra_swizzle::deswizzled_mip_size(&mip), 32 * 32 * 16);
+        assert_eq!(tegra_swizzle::swizzled_surface_size(&mip), 32 * 16 * 32);
+
+        let smallest = params.mip(10);
+        assert_eq!((smallest.width, smallest.height), (1, 1));
+        assert_eq!(tegra_swizzle::deswizzled_mip_size(&smallest), 16);
+    }
+
+    #[test]
+    fn surface_sizes() {
+        use super::{tegra_swizzle, Error, SurfaceFormat::*};
+
+        // (format, width, height, depth, mips, deswizzled size, swizzled size). Swizzled
+        // sizes pad each mip to whole GOBs and blocks, and 3D textures to whole block depths.
+        let sizes = [
+            (R8G8B8A8_SRGB, 64, 64, 1, 1, 0x4000, 0x4000),
+            (R8G8B8A8_SRGB, 64, 64, 1, 7, 0x5554, 0x5c00),
+            (R8G8B8A8_UNORM, 1920, 1080, 1, 1, 0x7e9000, 0x870000),
+            (R8G8B8A8_UNORM, 16, 16, 16, 1, 0x4000, 0x4000),
+            (R8G8B8A8_UNORM, 16, 16, 16, 5, 0x4924, 0x5e00),
+            (R8_UNORM, 100, 30, 1, 1, 0xbb8, 0x1000),
+            (B8G8R8A8_UNORM, 1, 1, 1, 1, 4, 0x200),
+            (R16G16B16A16_FLOAT, 256, 128, 1, 1, 0x40000, 0x40000),
+            (BC1_UNORM, 1024, 1024, 1, 1, 0x80000, 0x80000),
+            (BC1_UNORM, 256, 256, 1, 9, 0xaab8, 0xb400),
+            (BC3_SRGB, 2048, 2048, 1, 12, 0x555570, 0x555e00),
+            (BC4_UNORM, 128, 64, 1, 1, 0x1000, 0x1000),
+            (BC5_UNORM, 512, 512, 1, 10, 0x55570, 0x55e00),
+            (BC6H_UF16, 64, 64, 6, 1, 0x6000, 0x8000),
+            (BC7_SRGB, 1024, 1024, 1, 11, 0x155570, 0x155e00),
+            (BC7_UNORM, 100, 100, 1, 1, 0x2710, 0x3800),
+            (ETC2_RGB8_UNORM, 4, 4, 1, 1, 8, 0x200),
+        ];
+        for (format, width, height, depth, mips, deswizzled, swizzled) in sizes {
+            let args = (format, width, height, depth, mips);
+            let deswizzled_size = tegra_swizzle::deswizzled_size(format, width, height, depth, mips);
+            let swizzled_size = tegra_swizzle::swizzled_size(format, width, height, depth, mips);
+            assert_eq!(deswizzled_size.unwrap(), deswizzled, "{:?}", args);
+            assert_eq!(swizzled_size.unwrap(), swizzled, "{:?}", args);
+        }
+
+        // agrees with the layout of imported textures
+        let base = gradient(200, 120);
+        let mips = vec![base.thumbnail_exact(100, 60), base.thumbnail_exact(50, 30)];
+        let texture = super::TextureBuilder::new("ester").build_with_mips(base, mips).unwrap();
+        let size = tegra_swizzle::swizzled_size(texture.format, 200, 120, 1, 3).unwrap();
+        assert_eq!(texture.texture.0.len(), size);
+
+        assert!(matches!(
+            tegra_swizzle::swizzled_size(Unknown(0x5555), 16, 16, 1, 1),
+            Err(Error::UnsupportedFormat(Unknown(0x5555)))
+        ));
+    }
+
+    #[test]
+    fn block_height_override() {
+        let mut file = BntxFile::from_image(gradient(128, 128), "ester").unwrap();
+        file.nx_header.textures[0].size_range = 0;
+
+        let texture = file.texture("ester").unwrap();
+        assert_ne!(texture.to_image().unwrap(), gradient(128, 128));
+        assert_eq!(texture.decode_with_block_height(BlockHeight::Sixteen).unwrap(), gradient(128, 128));
+    }
+
+    #[test]
+    fn display_summary() {
+        let image = image::DynamicImage::ImageRgba8(image::RgbaImage::new(64, 64));
+
+        assert_eq!(
+            BntxFile::from_image(image, "ester").unwrap().to_string(),
+            "ester (1 texture)\n  ester: 64x64 R8G8B8A8_SRGB, 1 mip, 1 layer, 16384 bytes"
+        );
+    }
+
+    #[test]
+    fn premultiplied_alpha() {
+        let image = image::DynamicImage::ImageRgba8(
+            image::RgbaImage::from_pixel(16, 16, image::Rgba([200, 100, 50, 128]))
+        );
+
+        let texture = super::TextureBuilder::new("ester")
+            .alpha_conversion(super::AlphaConversion::Premultiply)
+            .build(image)
+            .unwrap();
+
+        let decoded = texture.to_image().unwrap().to_rgba8();
+        assert_eq!(decoded.get_pixel(3, 5).0, [100, 50, 25, 128]);
+    }
+
+    #[test]
+    fn gamma_conversion() {
+        use super::GammaConversion;
+
+        let image = image::DynamicImage::ImageRgba8(
+            image::RgbaImage::from_pixel(16, 16, image::Rgba([128, 0, 255, 128]))
+        );
+
+        let texture = super::TextureBuilder::new("ester")
+            .gamma_conversion(GammaConversion::LinearToSrgb)
+            .build(image)
+            .unwrap();
+
+        let decoded = texture.to_image().unwrap().to_rgba8();
+        assert_eq!(decoded.get_pixel(0, 0).0, [188, 0, 255, 128]);
+
+        let decoded = texture.to_image_with_gamma(GammaConversion::SrgbToLinear).unwrap().to_rgba8();
+        assert_eq!(decoded.get_pixel(0, 0).0, [128, 0, 255, 128]);
+    }
+
+    #[test]
+    fn size_alignment() {
+        use super::{SizeAlignment, TextureBuilder};
+
+        let build = |alignment| TextureBuilder::new("ester")
+            .size_alignment(alignment)
+            .build(gradient(30, 18));
+
+        let padded = build(SizeAlignment::Pad(4)).unwrap();
+        assert_eq!((padded.width(), padded.height()), (32, 20));
+        assert_eq!(padded.to_image().unwrap().to_rgba8().get_pixel(31, 19).0, [0, 0, 0, 0]);
+
+        let resized = build(SizeAlignment::Resize(16)).unwrap();
+        assert_eq!((resized.width(), resized.height()), (32, 32));
+
+        assert!(matches!(
+            build(SizeAlignment::Reject(4)),
+            Err(super::Error::UnalignedSize { width: 30, height: 18, multiple: 4 })
+        ));
+    }
+
+    #[test]
+    fn image_transform() {
+        use super::{ImageTransform, SizeAlignment, TextureBuilder};
+
+        let build = |transform| TextureBuilder::new("ester")
+            .transform(transform)
+            .build(gradient(30, 18))
+            .unwrap()
+            .to_image()
+            .unwrap()
+            .to_rgba8();
+
+        let flipped = build(ImageTransform { flip_vertical: true, ..Default::default() });
+        assert_eq!(flipped.get_pixel(3, 0).0, [3, 17, 3 ^ 17, 255]);
+
+        let rotated = build(ImageTransform { rotate_90: true, ..Default::default() });
+        assert_eq!(rotated.dimensions(), (18, 30));
+        assert_eq!(rotated.get_pixel(17, 2).0, [2, 0, 2, 255]);
+
+        let swapped = build(ImageTransform { swap_red_blue: true, ..Default::default() });
+        assert_eq!(swapped.get_pixel(5, 3).0, [5 ^ 3, 3, 5, 255]);
+
+        // the size alignment applies after rotating, and mips are transformed too
+        let transform = ImageTransform { rotate_90: true, flip_vertical: true, swap_red_blue: true };
+        let texture = TextureBuilder::new("ester")
+            .transform(transform)
+            .size_alignment(SizeAlignment::Reject(2))
+            .build_with_mips(gradient(30, 18), vec![gradient(15, 9)])
+            .unwrap();
+        assert_eq!((texture.width(), texture.height()), (18, 30));
+        let mip = texture.decode(1, texture.size_range).unwrap().to_rgba8();
+        assert_eq!(mip.dimensions(), (9, 15));
+        assert_eq!(mip.get_pixel(0, 0).0, [14 ^ 8, 8, 14, 255]);
+    }
+
+    #[test]
+    fn arrays_and_atlases() {
+        let images = || vec![gradient(32, 32), gradient(32, 32), gradient(32, 32)];
+
+        let array = super::TextureBuilder::new("ester").build_array(images()).unwrap();
+        assert_eq!(array.info().array_len, 3);
+        assert_eq!(array.to_image().unwrap(), gradient(32, 32));
+
+        let (atlas, regions) = super::TextureBuilder::new("ester").build_atlas(images()).unwrap();
+        assert_eq!((atlas.width(), atlas.height()), (64, 64));
+        assert_eq!((regions[2].x, regions[2].y), (0, 32));
+        assert_eq!(regions[1].uv_min, [0.5, 0.0]);
+        assert_eq!(regions[1].uv_max, [1.0, 0.5]);
+
+        let mismatched = vec![gradient(32, 32), gradient(16, 16)];
+        assert!(super::TextureBuilder::new("ester").build_array(mismatched).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn read_at_offset() {
+        let mut data = Cursor::new(vec![0xAA; 0x123]);
+        data.set_position(0x123);
+        BntxFile::from_image(gradient(64, 64), "ester").unwrap().write(&mut data).unwrap();
+        data.get_mut().extend_from_slice(&[0xBB; 0x40]);
+
+        let file = BntxFile::read_at(&mut data, 0x123).unwrap();
+
+        assert_eq!(file.nx_header.dict.find("ester"), Some(0));
+        assert_eq!(file.texture("ester").unwrap().to_image().unwrap(), gradient(64, 64));
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn read_with_data() {
+        let mut file = BntxFile::from_image(gradient(64, 64), "ester").unwrap();
+        file.push_texture(super::Texture::from_image(gradient(32, 16), "maya").unwrap()).unwrap();
+        let mut data = Vec::new();
+        file.write(&mut Cursor::new(&mut data)).unwrap();
+
+        let brtd = data.windows(4).position(|magic| magic == b"BRTD").unwrap();
+        let raw = file.textures().next().unwrap().raw_data();
+        let start = data.windows(raw.len()).position(|window| window == raw).unwrap();
+
+        // split at the section, and with the section's header in neither part
+        for (header_end, data_offset) in [(brtd, brtd), (brtd, start)] {
+            let read = BntxFile::read_with_data(
+                &mut Cursor::new(&data[..header_end]),
+                &mut Cursor::new(&data[data_offset..]),
+                data_offset as u64,
+            ).unwrap();
+
+            assert_eq!(read.texture("ester").unwrap().to_image().unwrap(), gradient(64, 64));
+            assert_eq!(read.texture("maya").unwrap().to_image().unwrap(), gradient(32, 16));
+        }
+
+        // data that doesn't reach as far as the pointers is an error
+        assert!(BntxFile::read_with_data(
+            &mut Cursor::new(&data[..brtd]),
+            &mut Cursor::new(&data[start..start + 0x10]),
+            start as u64,
+        ).is_err());
+    }
+
+    #[test]
+    fn from_image_validation() {
+        use super::{Error, Texture};
+
+        // width and height must not be swapped for non-square images
+        let texture = Texture::from_image(gradient(96, 32), "ester").unwrap();
+        assert_eq!((texture.width(), texture.height()), (96, 32));
+        assert_eq!(texture.to_image().unwrap(), gradient(96, 32));
+
+        assert!(matches!(
+            Texture::from_image(gradient(0, 16), "ester"),
+            Err(Error::InvalidDimensions { width: 0, height: 16 })
+        ));
+        assert!(matches!(
+            Texture::from_image(image::DynamicImage::new_luma8(16385, 1), "ester"),
+            Err(Error::InvalidDimensions { width: 16385, height: 1 })
+        ));
+    }
+
+    #[test]
+    fn xxh64() {
+        use std::hash::Hasher;
+
+        let hash = |data: &[u8]| {
+            let mut hasher = super::Xxh64::new();
+            // split writes to exercise the buffering
+            for chunk in data.chunks(5) {
+                hasher.write(chunk);
+            }
+            hasher.finish()
+        };
+
+        assert_eq!(hash(b""), 0xef46db3751d8e999);
+        assert_eq!(hash(b"a"), 0xd24ec4f1a98c6e5b);
+        assert_eq!(hash(b"abc"), 0x44bc2cf5ad770999);
+        assert_eq!(hash(b"Nobody inspects the spammish repetition"), 0xfbcea83c8a378bf1);
+    }
+
+    #[test]
+    fn content_hash_ignores_layout() {
+        let build = |swizzle, image| super::TextureBuilder::new("ester")
+            .swizzle(swizzle)
+            .build(image)
+            .unwrap();
+
+        let hash = build(0, gradient(128, 128)).data_hash().unwrap();
+        assert_eq!(build(5, gradient(128, 128)).data_hash().unwrap(), hash);
+        assert_ne!(build(0, gradient(128, 64)).data_hash().unwrap(), hash);
+
+        // names that only differ in bytes that aren't valid UTF-8 still hash differently
+        let file = |name: &[u8]| {
+            let mut texture = build(0, gradient(16, 16));
+            texture.name = super::BntxStr::from(name.to_vec());
+            let mut file = BntxFile::new("ester");
+            file.push_texture(texture).unwrap();
+            file.content_hash().unwrap()
+        };
+        assert_ne!(file(b"\x83"), file(b"\x84"));
+    }
+
+    #[test]
+    fn convert_format_roundtrip() {
+        use super::SurfaceFormat::*;
+
+        let original = gradient(64, 48).to_rgba8();
+        let formats = [
+            (B8G8R8A8_SRGB, 0),
+            (R4G4B4A4_UNORM, 9),
+            (R5G5B5A1_UNORM, 5),
+            (R5G6B5_UNORM, 5),
+            (BC1_UNORM, 8),
+            (BC3_SRGB, 8),
+            (BC7_UNORM, 4),
+            (BC6H_UF16, 4),
+        ];
+
+        for (format, tolerance) in formats {
+            let mut texture = super::Texture::from_image(gradient(64, 48), "ester").unwrap();
+            texture.convert_format(format).unwrap();
+            assert_eq!(texture.info().format, format);
+
+            texture.convert_format(R8G8B8A8_UNORM).unwrap();
+            let decoded = texture.to_image().unwrap().to_rgba8();
+
+            let max_error = decoded.as_raw().iter()
+                .zip(original.as_raw())
+                .map(|(a, b)| (*a as i32 - *b as i32).abs())
+                .max()
+                .unwrap();
+            assert!(max_error <= tolerance, "{} differed by {}", format, max_error);
+        }
+    }
+
+    #[test]
+    fn packed_format_layout() {
+        use super::SurfaceFormat::*;
+
+        let red = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 0, 0, 255])));
+        let layouts = [(R4G4B4A4_UNORM, 0xff00u16), (R5G5B5A1_UNORM, 0xfc00), (R5G6B5_UNORM, 0xf800)];
+
+        for (format, expected) in layouts {
+            let mut texture = super::Texture::from_image(red.clone(), "ester").unwrap();
+            texture.convert_format(format).unwrap();
+            assert_eq!(texture.texture.0[..2], expected.to_le_bytes(), "{}", format);
+            assert_eq!(texture.to_image().unwrap(), red);
+        }
+    }
+
+    #[test]
+    fn rgb10a2_keeps_16_bits() {
+        let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(4, 4, image::Rgba([255, 128, 0, 255])));
+
+        let mut texture = super::Texture::from_image(image, "ester").unwrap();
+        texture.convert_format(super::SurfaceFormat::R10G10B10A2_UNORM).unwrap();
+        assert_eq!(texture.texture.0[..4], (0x3ffu32 | 0x202 << 10 | 0x3 << 30).to_le_bytes());
+
+        let decoded = texture.to_image().unwrap();
+        assert_eq!(decoded.as_rgba16().unwrap().get_pixel(0, 0).0, [65535, 32927, 0, 65535]);
+    }
+
+    #[test]
+    fn float_formats_keep_hdr_values() {
+        use super::SurfaceFormat::*;
+
+        for format in [R16G16B16A16_FLOAT, R32G32B32A32_FLOAT] {
+            let mut texture = super::Texture::from_image(gradient(16, 16), "ester").unwrap();
+            texture.convert_format(format).unwrap();
+
+            // values past 1.0 only survive in a float image
+            let pixels = [4.0f32, 0.5, 0.0, 1.0].iter()
+                .flat_map(|v| if format == R16G16B16A16_FLOAT {
+                    super::format::f32_to_f16(*v).to_le_bytes().to_vec()
+                } else {
+                    v.to_le_bytes().to_vec()
+                })
+                .collect::<Vec<_>>();
+            texture.texture.0[..pixels.len()].copy_from_slice(&pixels);
+
+            let image = texture.to_float_image().unwrap();
+            assert_eq!(image.get_pixel(0, 0).0, [4.0, 0.5, 0.0, 1.0]);
+            assert_eq!(texture.to_image().unwrap().to_rgba8().get_pixel(0, 0).0, [255, 128, 0, 255]);
+
+            #[cfg(feature = "exr")]
+            {
+                let mut exr = Cursor::new(Vec::new());
+                texture.to_exr(&mut exr).unwrap();
+
+                let decoded = image::load_from_memory(exr.get_ref()).unwrap().into_rgba32f();
+                assert_eq!(decoded.get_pixel(0, 0).0, [4.0, 0.5, 0.0, 1.0]);
+            }
+        }
+    }
+
+    #[test]
+    fn reswizzle_keeps_blocks() {
+        use super::{SurfaceFormat, TileMode};
+
+        let mut texture = super::Texture::from_image(gradient(64, 48), "ester").unwrap();
+        texture.mips_count = 3;
+        texture.mip_offsets = vec![0; 3];
+        texture.convert_format(SurfaceFormat::BC1_UNORM).unwrap();
+
+        let surfaces = |texture: &super::Texture| (0..3)
+            .map(|mip| texture.deswizzle_surface(0, mip, texture.size_range).unwrap())
+            .collect::<Vec<_>>();
+        let original = surfaces(&texture);
+
+        texture.reswizzle(TileMode::BlockLinear, BlockHeight::One).unwrap();
+        assert_eq!(texture.size_range, 0);
+        assert_eq!(surfaces(&texture), original);
+
+        texture.reswizzle(TileMode::Linear, BlockHeight::One).unwrap();
+        assert_eq!(texture.info().tile_mode, TileMode::Linear);
+        assert_eq!(surfaces(&texture), original);
+        // a linear surface is just its rows of blocks
+        assert_eq!(texture.texture.0[..original[0].len()], original[0][..]);
+    }
+
+    #[test]
+    fn user_provided_mips() {
+        use super::{Error, SurfaceFormat, Texture};
+
+        let mips = vec![gradient(32, 12), gradient(16, 6), gradient(8, 3)];
+        let texture = Texture::from_image_with_mips(
+            gradient(64, 24), mips.clone(), "ester", SurfaceFormat::R8G8B8A8_UNORM
+        ).unwrap();
+        assert_eq!(texture.info().mip_count, 4);
+        assert_eq!(texture.to_image().unwrap(), gradient(64, 24));
+        for (mip, expected) in (1..).zip(&mips) {
+            let data = texture.deswizzle_surface(0, mip, texture.size_range).unwrap();
+            assert_eq!(data, expected.to_rgba8().into_raw());
+        }
+
+        let bc1 = Texture::from_image_with_mips(
+            gradient(64, 24), mips, "ester", SurfaceFormat::BC1_UNORM
+        ).unwrap();
+        assert_eq!(bc1.info().format, SurfaceFormat::BC1_UNORM);
+        assert_eq!(bc1.info().mip_count, 4);
+
+        assert!(matches!(
+            Texture::from_image_with_mips(gradient(64, 24), vec![gradient(32, 24)], "ester", SurfaceFormat::R8G8B8A8_UNORM),
+            Err(Error::MismatchedSize { expected: (32, 12), found: (32, 24) })
+        ));
+        assert!(matches!(
+            Texture::from_image_with_mips(gradient(2, 2), vec![gradient(1, 1); 2], "ester", SurfaceFormat::R8G8B8A8_UNORM),
+            Err(Error::TooManyMips { count: 3, max: 2 })
+        ));
+    }
+
+    #[test]
+    fn set_raw_data() {
+        use super::{Error, Texture};
+
+        let source = Texture::from_image(gradient(64, 64).fliph(), "ester").unwrap();
+        let mut texture = Texture::from_image(gradient(64, 64), "ester").unwrap();
+
+        texture.set_raw_data(source.raw_data().to_vec()).unwrap();
+        assert_eq!(texture.to_image().unwrap(), gradient(64, 64).fliph());
+
+        assert!(matches!(
+            texture.set_raw_data(vec![0; 16]),
+            Err(Error::MismatchedDataSize { found: 16, .. })
+        ));
+        assert_eq!(texture.to_image().unwrap(), gradient(64, 64).fliph());
+    }
+
+    #[test]
+    fn from_rgba8() {
+        let pixels = gradient(24, 40).to_rgba8().into_raw();
+
+        let file = BntxFile::from_rgba8(24, 40, &pixels, "ester").unwrap();
+        assert_eq!(file.to_image().unwrap(), gradient(24, 40));
+
+        assert!(matches!(
+            BntxFile::from_rgba8(40, 40, &pixels, "ester"),
+            Err(super::Error::MismatchedDataSize { expected: 6400, found: 3840 })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn file_size_header() {
+        let file = BntxFile::from_image(gradient(64, 64), "ester").unwrap();
+
+        let mut data = Cursor::new(Vec::new());
+        file.write(&mut data).unwrap();
+        let mut data = data.into_inner();
+        assert_eq!(data[0x1c..0x20], (data.len() as u32).to_le_bytes());
+
+        data.truncate(data.len() - 1);
+        let err = Cursor::new(&data).read_le::<BntxFile>().unwrap_err();
+        assert!(matches!(err.root_cause(), binrw::Error::AssertFail { .. }), "{:?}", err);
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn write_at_offset() {
+        let file = BntxFile::from_image(gradient(64, 64), "ester").unwrap();
+
+        let mut data = Cursor::new(b"SARC".to_vec());
+        file.write_at(&mut data, 0x1000).unwrap();
+        assert_eq!(&data.get_ref()[..4], b"SARC");
+        assert_eq!(&data.get_ref()[0x1000..0x1004], b"BNTX");
+
+        let file = BntxFile::read_at(&mut data, 0x1000).unwrap();
+        assert_eq!(file.to_image().unwrap(), gradient(64, 64));
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn predicted_size() {
+        let mut file = BntxFile::new("ester");
+        for (name, size) in [("ester", 64), ("maya", 16), ("chara", 128)] {
+            file.push_texture(super::Texture::from_image(gradient(size, size), name).unwrap()).unwrap();
+
+            let mut data = Vec::new();
+            file.write(&mut data).unwrap();
+            assert_eq!(file.predicted_size(), data.len());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn relocation_table() {
+        use super::reloc::RelocationTable;
+
+        let mut file = BntxFile::from_image(gradient(64, 64), "ester").unwrap();
+        file.push_texture(super::Texture::from_image(gradient(16, 16), "maya").unwrap()).unwrap();
+        let mut data = Vec::new();
+        file.write(&mut data).unwrap();
+
+        let table = RelocationTable::from_file(&mut Cursor::new(&data)).unwrap();
+        let layout = super::Layout::new(&file);
+        assert_eq!(table, layout.reloc_table);
+
+        // the NX header's pointer to the texture pointer array, and the mip table's pointers
+        let resolved = table.resolve(&data);
+        assert!(resolved.contains(&(0x28, layout.info_ptr_array_offset as u64)));
+        assert!(resolved.contains(&(layout.textures[1].mip_table, layout.textures[1].data as u64)));
+        assert_eq!(resolved.len(), table.pointers().count());
+
+        let regions: Vec<_> = table.sections.iter()
+            .map(|section| (
+                section.position as usize,
+                section.size as usize,
+                table.section_entries(section).iter().flat_map(|entry| entry.pointers()).collect(),
+            ))
+            .collect();
+        assert_eq!(RelocationTable::from_pointers(&regions), table);
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn section_layout() {
+        let mut file = BntxFile::from_image(gradient(64, 64), "ester").unwrap();
+        file.push_texture(super::Texture::from_image(gradient(16, 16), "maya").unwrap()).unwrap();
+        let mut data = Vec::new();
+        file.write(&mut data).unwrap();
+
+        let sections = file.layout();
+        let names: Vec<_> = sections.iter().map(|section| section.name).collect();
+        assert_eq!(names, [
+            "BNTX", "NX", "memory pool", "texture pointers", "_STR", "_DIC", "BRTI", "BRTI", "BRTD",
+            "texture data", "texture data", "_RLT",
+        ]);
+        assert_eq!(sections[7].texture.as_deref(), Some("maya"));
+        assert_eq!(&data[sections[8].offset..][..4], b"BRTD");
+
+        let mut end = 0;
+        for section in &sections {
+            assert_eq!(section.offset, end);
+            end = section.offset + section.size + section.padding;
+        }
+        assert_eq!(end, data.len());
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn strict_game_compat() {
+        use super::{Error, SurfaceFormat, Texture};
+
+        // nothing this crate creates is refused
+        for &format in SurfaceFormat::ALL {
+            let texture = Texture::blank("ester", format, 64, 32, 7, 2).unwrap();
+            texture.check_game_compat().unwrap();
+        }
+
+        let mut file = BntxFile::from_image(gradient(64, 64), "ester").unwrap();
+        file.push_texture(Texture::blank("maya", SurfaceFormat::BC1_SRGB, 64, 64, 7, 1).unwrap()).unwrap();
+        file.set_strict_game_compat(true);
+        file.write(&mut Vec::new()).unwrap();
+
+        type Breakage = fn(&mut Texture);
+        let breakages: [(&str, Breakage); 5] = [
+            ("isn't a whole number of", |texture| texture.width = 62),
+            ("alignment of 0", |texture| texture.align = 0),
+            ("40 mip levels", |texture| texture.mips_count = 40),
+            ("0 mip levels", |texture| texture.mips_count = 0),
+            ("mip offsets doesn't match", |texture| texture.mip_offsets[1] += 0x200),
+        ];
+        for (reason, breakage) in breakages {
+            let mut broken = file.clone();
+            breakage(broken.texture_mut("maya").unwrap());
+
+            let err = broken.check_game_compat().unwrap_err();
+            assert!(
+                matches!(&err, Error::GameCompat { texture, .. } if texture == "maya"),
+                "{:?}", err
+            );
+            assert!(err.to_string().contains(reason), "{}", err);
+
+            let err = broken.write(&mut Vec::new()).unwrap_err();
+            let err = err.get_ref().and_then(|err| err.downcast_ref::<Error>());
+            assert!(matches!(err, Some(Error::GameCompat { .. })), "{:?}", err);
+
+            // the default writes files as they are, so broken files can be inspected and repaired
+            broken.set_strict_game_compat(false);
+            broken.write(&mut Vec::new()).unwrap();
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn share_identical_data() {
+        let mut file = BntxFile::from_image(gradient(64, 64), "ester").unwrap();
+        file.push_texture(super::Texture::from_image(gradient(32, 32), "chara").unwrap()).unwrap();
+        file.push_texture(super::Texture::from_image(gradient(64, 64), "maya").unwrap()).unwrap();
+        assert_eq!(file.identical_textures(), [("maya", "ester")]);
+
+        let size = file.predicted_size();
+        file.set_share_identical_data(true);
+        assert_eq!(file.predicted_size(), size - 64 * 64 * 4);
+        assert_eq!(file.verify_roundtrip().unwrap(), []);
+
+        let mut data = Vec::new();
+        file.write(&mut data).unwrap();
+        let read = BntxFile::from_reader(&data[..]).unwrap();
+        assert_eq!(read.texture("maya").unwrap().to_image().unwrap(), gradient(64, 64));
+        assert_eq!(file.layout().iter().filter(|section| section.name == "texture data").count(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn nested_binrw() {
+        let file = BntxFile::from_image(gradient(64, 64), "ester").unwrap();
+        let mut standalone = Vec::new();
+        file.write(&mut standalone).unwrap();
+
+        // a big endian archive with a header before the file
+        let mut archive = Cursor::new(Vec::new());
+        (*b"ARC0", 1u32).write_be(&mut archive).unwrap();
+        file.write_be(&mut archive).unwrap();
+        let archive = archive.into_inner();
+        assert_eq!(&archive[4..8], [0, 0, 0, 1]);
+        assert_eq!(archive[8..], standalone[..]);
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn layout_variants() {
+        let mut file = BntxFile::from_image(gradient(64, 64), "ester").unwrap();
+        file.push_texture(super::Texture::from_image(gradient(32, 32), "maya").unwrap()).unwrap();
+        file.set_mem_pool(vec![0; 0x200]);
+
+        let mut data = Vec::new();
+        file.write(&mut data).unwrap();
+
+        // a larger memory pool is followed on read and kept on write
+        let read = BntxFile::from_reader(&data[..]).unwrap();
+        assert_eq!(read.mem_pool().len(), 0x200);
+        assert_eq!(read.texture("maya").unwrap().to_image().unwrap(), gradient(32, 32));
+        let mut rewritten = Vec::new();
+        read.write(&mut rewritten).unwrap();
+        assert_eq!(rewritten, data);
+
+        // files without a dictionary can still look textures up by name
+        data[super::BNTX_HEADER_SIZE + 0x18..][..8].fill(0);
+        let read = BntxFile::from_reader(&data[..]).unwrap();
+        assert_eq!(read.texture("maya").unwrap().width(), 32);
+
+        // offsets too large for their header fields fail the write instead of being truncated
+        file.set_mem_pool(vec![0; 0x10000]);
+        let err = file.write(&mut Vec::new()).unwrap_err();
+        let err = err.get_ref().and_then(|err| err.downcast_ref::<binrw::Error>()).unwrap();
+        assert!(matches!(
+            err.root_cause().custom_err::<super::Error>(),
+            Some(super::Error::FileTooLarge { field: "string section offset", .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn copy_textures_between_files() {
+        let mut other = BntxFile::from_image(gradient(64, 32), "ester").unwrap();
+        other.push_texture(super::Texture::from_image(gradient(16, 16), "joker").unwrap()).unwrap();
+
+        let mut file = BntxFile::from_image(gradient(32, 32), "ester").unwrap();
+        let replaced = file.copy_texture_from(&other, "ester").unwrap();
+        assert_eq!(replaced.unwrap().width(), 32);
+        assert!(file.copy_texture_from(&other, "joker").unwrap().is_none());
+        assert!(matches!(
+            file.copy_texture_from(&other, "mario"),
+            Err(super::Error::MissingTexture(name)) if name == "mario"
+        ));
+
+        let mut data = Cursor::new(Vec::new());
+        file.write(&mut data).unwrap();
+        data.set_position(0);
+
+        let file: BntxFile = data.read_le().unwrap();
+        assert_eq!(file.texture("ester").unwrap().to_image().unwrap(), gradient(64, 32));
+        assert_eq!(file.texture("joker").unwrap().to_image().unwrap(), gradient(16, 16));
+    }
+
+    #[cfg(feature = "bfres")]
+    #[test]
+    fn read_from_bfres() {
+        let file = BntxFile::from_image(gradient(64, 64), "ester").unwrap();
+
+        // a BFRES header followed by a string that happens to contain the magic
+        let mut bfres = b"FRES    ".to_vec();
+        bfres.resize(0x100, 0);
+        bfres.extend(b"BNTX is not here");
+        let mut data = Cursor::new(bfres);
+        file.write_at(&mut data, 0x1000).unwrap();
+        data.set_position(0);
+
+        let file = BntxFile::from_bfres(&mut data).unwrap();
+        assert_eq!(file.to_image().unwrap(), gradient(64, 64));
+
+        let mut data = Cursor::new(b"FRES    ".to_vec());
+        assert!(BntxFile::from_bfres(&mut data).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn index_scan() {
+        use super::index::{scan, scan_file};
+
+        let dir = std::env::temp_dir().join(format!("bntx-index-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut ester = BntxFile::from_image(gradient(64, 32), "ester").unwrap();
+        ester.push_texture(super::Texture::blank("maya", BC7_SRGB, 128, 128, 8, 6).unwrap()).unwrap();
+        ester.save(dir.join("ester.bntx")).unwrap();
+        BntxFile::from_image(gradient(16, 16), "ryder").unwrap().save(dir.join("ryder.bntx")).unwrap();
+        std::fs::write(dir.join("broken.bntx"), b"not a bntx").unwrap();
+
+        let paths = ["ester.bntx", "broken.bntx", "missing.bntx", "ryder.bntx"].map(|name| dir.join(name));
+        let entries = scan(&paths);
+        assert_eq!(
+            entries.iter().map(|entry| (entry.name.as_str(), entry.index)).collect::<Vec<_>>(),
+            [("ester", 0), ("maya", 1), ("ryder", 0)]
+        );
+        assert_eq!(entries[2].path, dir.join("ryder.bntx"));
+
+        for (entry, texture) in entries.iter().zip(ester.textures()) {
+            assert_eq!(entry.info, texture.info());
+            assert_eq!(entry.kind, texture.kind());
+            assert_eq!(entry.data_size as usize, texture.raw_data().len());
+        }
+
+        assert!(scan_file(dir.join("broken.bntx")).is_err());
+        assert!(scan_file(dir.join("missing.bntx")).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_open_and_save() {
+        let dir = std::env::temp_dir().join(format!("bntx-zstd-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let file = BntxFile::from_image(gradient(64, 64), "ester").unwrap();
+        file.save(dir.join("ester.bntx.zs")).unwrap();
+        file.save(dir.join("ester.bntx")).unwrap();
+
+        let compressed = std::fs::read(dir.join("ester.bntx.zs")).unwrap();
+        assert_eq!(compressed[..4], [0x28, 0xb5, 0x2f, 0xfd]);
+
+        for name in ["ester.bntx.zs", "ester.bntx"] {
+            let file = BntxFile::open(dir.join(name)).unwrap();
+            assert_eq!(file.to_image().unwrap(), gradient(64, 64));
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn surface_roundtrip() {
+        use super::Surface;
+
+        let texture = super::Texture::from_image_with_mips(
+            gradient(64, 32), vec![gradient(32, 16), gradient(16, 8)], "ester", R8G8B8A8_UNORM
+        ).unwrap();
+
+        let mut surface = texture.to_surface().unwrap();
+        assert_eq!((surface.mip_count(), surface.layer_count()), (3, 1));
+        assert_eq!(surface.mip(0, 0).unwrap(), gradient(64, 32).into_rgba8().as_raw().as_slice());
+        assert_eq!(surface.mip(0, 2).unwrap().len(), 16 * 8 * 4);
+        assert!(surface.mip(1, 0).is_none());
+
+        surface.mip_mut(0, 1).unwrap().fill(0xff);
+        let edited = super::Texture::from_surface("ester", &surface).unwrap();
+        assert_eq!(edited.to_surface().unwrap(), surface);
+        assert_eq!(edited.to_image().unwrap(), texture.to_image().unwrap());
+
+        let array = Surface::zeroed(BC1_UNORM, 32, 32, 2, 3).unwrap();
+        let texture = super::Texture::from_surface("maya", &array).unwrap();
+        assert_eq!(texture.info().array_len, 3);
+        assert_eq!(texture.to_surface().unwrap(), array);
+        assert!(Surface::new(BC1_UNORM, 32, 32, 1, 1, vec![0; 10]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn target_roundtrip() {
+        use super::Target;
+
+        let mut file = BntxFile::from_image(gradient(16, 16), "ester").unwrap();
+        assert_eq!(file.target(), Target::V4);
+
+        let target = Target { version: (1, 3), revision: 0x400c };
+        file.set_target(target);
+
+        let mut data = Vec::new();
+        file.write(&mut data).unwrap();
+        assert_eq!(data[8..12], [1, 0, 3, 0]);
+
+        let read = BntxFile::read_at(&mut std::io::Cursor::new(&data), 0).unwrap();
+        assert_eq!(read.target(), target);
+        assert_eq!(BntxFile::with_target("maya", target).target(), target);
+    }
+
+    #[test]
+    fn split_channels() {
+        let image = gradient(16, 8);
+        let texture = super::Texture::from_image(image.clone(), "ester").unwrap();
+
+        let [r, g, b, a] = texture.split_channels().unwrap();
+        let image = image.into_rgba8();
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let split = [r[(x, y)][0], g[(x, y)][0], b[(x, y)][0], a[(x, y)][0]];
+            assert_eq!(split, pixel.0);
+        }
+    }
+
+    #[test]
+    fn build_from_channels() {
+        use super::{Error, TextureBuilder};
+
+        let texture = super::Texture::from_image(gradient(16, 8), "ester").unwrap();
+        let [r, g, b, a] = texture.split_channels().unwrap().map(image::DynamicImage::ImageLuma8);
+
+        let packed = TextureBuilder::new("ester")
+            .build_from_channels([Some(r.clone()), Some(g), Some(b.clone()), Some(a)])
+            .unwrap();
+        assert_eq!(packed.to_image().unwrap(), gradient(16, 8));
+
+        let partial = TextureBuilder::new("ester").build_from_channels([None, None, Some(b), None]).unwrap();
+        let pixel = *partial.to_image().unwrap().into_rgba8().get_pixel(3, 5);
+        assert_eq!(pixel.0, [0, 0, gradient(16, 8).into_rgba8().get_pixel(3, 5)[2], 255]);
+
+        let small = image::DynamicImage::new_luma8(8, 8);
+        assert!(matches!(
+            TextureBuilder::new("ester").build_from_channels([Some(r), Some(small), None, None]),
+            Err(Error::MismatchedSize { .. })
+        ));
+        assert!(matches!(
+            TextureBuilder::new("ester").build_from_channels([None, None, None, None]),
+            Err(Error::EmptyArray)
+        ));
+    }
+
+    #[test]
+    fn from_image_with_preset() {
+        use super::{Error, Preset};
+
+        let file = BntxFile::from_image_with_preset(gradient(64, 32), "chara_0_ester_00", Preset::UltimateChara)
+            .unwrap();
+        let texture = file.textures().next().unwrap();
+        assert_eq!(texture.format, BC7_SRGB);
+        assert_eq!(texture.mips_count, 1);
+        assert_eq!(texture.size_mismatches().unwrap(), []);
+
+        assert!(matches!(
+            BntxFile::from_image_with_preset(gradient(30, 32), "stage_0_ester", Preset::UltimateStagePreview),
+            Err(Error::UnalignedSize { multiple: 4, .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn from_reader() {
+        let file = BntxFile::from_image(gradient(32, 32), "ester").unwrap();
+        let mut data = Vec::new();
+        file.write(&mut data).unwrap();
+
+        // a slice can be read but not seeked, like a pipe
+        let read = BntxFile::from_reader(&data[..]).unwrap();
+        assert_eq!(read.to_image().unwrap(), gradient(32, 32));
+        assert!(BntxFile::from_reader(&data[..0x40]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn read_streaming() {
+        let mut data = Vec::new();
+        BntxFile::from_image(gradient(32, 32), "ester").unwrap().write(&mut data).unwrap();
+        BntxFile::from_image(gradient(16, 16), "maya").unwrap().write(&mut data).unwrap();
+
+        // two files back to back in a stream, read one after the other
+        let mut stream = &data[..];
+        let first = BntxFile::read_streaming(&mut stream).unwrap();
+        let second = BntxFile::read_streaming(&mut stream).unwrap();
+        assert_eq!(first.to_image().unwrap(), gradient(32, 32));
+        assert_eq!(second.texture("maya").unwrap().to_image().unwrap(), gradient(16, 16));
+        assert!(stream.is_empty());
+
+        assert!(BntxFile::read_streaming(&data[..0x40]).is_err());
+        assert!(BntxFile::read_streaming(&[0u8; 0x20][..]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn non_utf8_names() {
+        // "テクスチャ" in Shift JIS
+        let name = b"\x83\x65\x83\x4e\x83\x58\x83\x60\x83\x83".to_vec();
+        let mut texture = super::Texture::from_image(gradient(16, 16), "ester").unwrap();
+        texture.name = super::BntxStr::from(name.clone());
+        let mut file = BntxFile::new("ester");
+        file.push_texture(texture).unwrap();
+
+        let mut data = Vec::new();
+        file.write(&mut data).unwrap();
+        let read = BntxFile::from_reader(&data[..]).unwrap();
+        let texture = read.textures().next().unwrap();
+        assert_eq!(texture.name_bytes(), &name[..]);
+        assert_eq!(texture.name(), String::from_utf8_lossy(&name));
+
+        let mut rewritten = Vec::new();
+        read.write(&mut rewritten).unwrap();
+        assert_eq!(rewritten, data);
+
+        // too long for the 16-bit length, rather than being truncated
+        let long = "a".repeat(u16::MAX as usize + 1);
+        assert!(matches!(
+            super::Texture::from_image(gradient(16, 16), &long),
+            Err(super::Error::NameTooLong { len: 65536 })
+        ));
+        assert!(BntxFile::new(&long).write(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn section_iter() {
+        let mut file = BntxFile::from_image(gradient(64, 64), "ester").unwrap();
+        file.push_texture(super::Texture::from_image(gradient(32, 32), "maya").unwrap()).unwrap();
+        let mut data = Vec::new();
+        file.write(&mut data).unwrap();
+
+        let sections: Vec<_> = super::SectionIter::new(&data).collect();
+        let names: Vec<_> = sections.iter().map(|section| section.name()).collect();
+        assert_eq!(names, ["BNTX", "NX  ", "_STR", "_DIC", "BRTI", "BRTI", "BRTD", "_RLT"]);
+
+        // sizes are the ones stored in the file, such as the BRTD's covering all the data
+        let offsets: Vec<_> = sections.iter().map(|section| section.offset).collect();
+        let expected: Vec<_> = file.layout()
+            .into_iter()
+            .filter(|section| !section.name.contains(' '))
+            .map(|section| section.offset)
+            .collect();
+        assert_eq!(offsets, expected);
+        assert_eq!(sections[6].size, data.len() - sections[6].offset - sections[7].size);
+
+        // a truncated file yields the sections before the cut
+        let truncated: Vec<_> = super::SectionIter::new(&data[..0x200])
+            .map(|section| section.name())
+            .collect();
+        assert_eq!(truncated, ["BNTX", "NX  ", "_STR", "_DIC"]);
+    }
+
+    #[test]
+    fn one_dimensional() {
+        use super::Dimension;
+
+        let ramp = gradient(64, 1);
+        let texture = super::TextureBuilder::new("ramp")
+            .one_dimensional(true)
+            .build_with_mips(ramp.clone(), vec![gradient(32, 1), gradient(16, 1)])
+            .unwrap();
+        assert_eq!(texture.dimension(), Dimension::D1);
+        assert_eq!(texture.kind(), super::TextureKind::D1);
+        assert_eq!(texture.to_image().unwrap(), ramp);
+        assert_eq!(texture.decode_all().unwrap().mip(0, 2).unwrap(), &gradient(16, 1).to_rgba8());
+
+        let mut texture = texture;
+        let err = texture.set_dimensions(64, 2, 1);
+        assert!(matches!(err, Err(super::Error::InvalidHeight { height: 2 })));
+        assert!(matches!(
+            super::TextureBuilder::new("ramp").one_dimensional(true).build(gradient(64, 2)),
+            Err(super::Error::InvalidHeight { height: 2 })
+        ));
+
+        let array = super::TextureBuilder::new("ramps")
+            .one_dimensional(true)
+            .build_array(vec![gradient(64, 1), gradient(64, 1)])
+            .unwrap();
+        assert_eq!(array.kind(), super::TextureKind::D1Array);
+    }
+
+    #[test]
+    #[cfg(feature = "webp")]
+    fn to_webp() {
+        use super::WebpQuality;
+
+        let texture = super::Texture::from_image(gradient(64, 64), "ester").unwrap();
+
+        let mut lossless = Vec::new();
+        texture.to_webp(&mut lossless, WebpQuality::Lossless).unwrap();
+        let decoded = image::load_from_memory_with_format(&lossless, image::ImageFormat::WebP).unwrap();
+        assert_eq!(decoded.to_rgba8(), gradient(64, 64).to_rgba8());
+
+        let mut lossy = Vec::new();
+        texture.to_webp(&mut lossy, WebpQuality::Lossy(50.0)).unwrap();
+        let decoded = image::load_from_memory_with_format(&lossy, image::ImageFormat::WebP).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (64, 64));
+    }
+
+    #[test]
+    fn convert_format_progress() {
+        use std::sync::Mutex;
+
+        let mut texture = super::TextureBuilder::new("ester")
+            .build_array(vec![gradient(16, 16), gradient(16, 16)])
+            .unwrap();
+
+        let updates = Mutex::new(Vec::new());
+        texture.convert_format_with_progress(BC1_UNORM, &|done, total| {
+            updates.lock().unwrap().push((done, total));
+        }).unwrap();
+        assert_eq!(updates.into_inner().unwrap(), [(1, 2), (2, 2)]);
+
+        let updates = Mutex::new(Vec::new());
+        let surface = texture.to_surface_with_progress(&|done, total| {
+            updates.lock().unwrap().push((done, total));
+        }).unwrap();
+        assert_eq!(surface, texture.to_surface().unwrap());
+        assert_eq!(updates.into_inner().unwrap(), [(1, 2), (2, 2)]);
+    }
+
+    #[cfg(feature = "wgpu")]
+    #[test]
+    fn gpu_deswizzle() {
+        use super::tegra_swizzle::{self, Backend, GpuDeswizzler};
+
+        // without a GPU there's nothing to compare against
+        let gpu = match GpuDeswizzler::new() {
+            Some(gpu) => std::sync::Arc::new(gpu),
+            None => return,
+        };
+
+        let surfaces = [(64, 64, 4, 0), (100, 36, 2, 0), (36, 300, 16, 3)];
+        for (width, height, bytes_per_block, swizzle) in surfaces {
+            let params = SurfaceParams { swizzle, ..SurfaceParams::new(width, height, bytes_per_block) };
+            let data: Vec<u8> = (0..tegra_swizzle::swizzled_surface_size(&params))
+                .map(|i| (i * 7 % 251) as u8)
+                .collect();
+
+            let expected = tegra_swizzle::deswizzle(&params, &data);
+            assert_eq!(Backend::Gpu(gpu.clone()).deswizzle(&params, &data), expected);
+        }
+
+        let texture = super::Texture::from_image(gradient(128, 64), "ester").unwrap();
+        let surface = texture.to_surface_with_backend(&Backend::Gpu(gpu), &|_, _| {}).unwrap();
+        assert_eq!(surface, texture.to_surface().unwrap());
+    }
+
+    #[test]
+    fn threaded_encode_matches() {
+        use super::EncodeOptions;
+
+        let texture = super::Texture::from_image(gradient(64, 44), "ester").unwrap();
+        let encode = |format, threads| {
+            let mut texture = texture.clone();
+            texture.convert_format_with_options(format, &EncodeOptions { threads }, &|_, _| {}).unwrap();
+            texture
+        };
+
+        for format in [BC1_UNORM, BC7_UNORM] {
+            let single = encode(format, 1);
+            for threads in [0, 3, 64] {
+                assert_eq!(encode(format, threads).raw_data(), single.raw_data());
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn deterministic_output() {
+        let write = |file: &BntxFile| {
+            let mut data = Vec::new();
+            file.write(&mut data).unwrap();
+            data
+        };
+
+        let mut file = BntxFile::new("ester");
+        for name in ["ester", "maya", "ester_copy"] {
+            file.push_texture(super::Texture::from_image(gradient(32, 32), name).unwrap()).unwrap();
+        }
+        file.set_share_identical_data(true);
+        let expected = write(&file);
+        assert_eq!(write(&file), expected);
+
+        // the same contents, reached through edits that leave nothing behind
+        let mut edited = BntxFile::new("ester");
+        for name in ["zoe", "ester", "maya"] {
+            edited.push_texture(super::Texture::from_image(gradient(64, 16), name).unwrap()).unwrap();
+        }
+        edited.remove_texture("zoe");
+        edited.push_texture(super::Texture::from_image(gradient(32, 32), "ester").unwrap()).unwrap();
+        edited.push_texture(super::Texture::from_image(gradient(32, 32), "maya").unwrap()).unwrap();
+        edited.push_texture(super::Texture::from_image(gradient(32, 32), "ester_copy").unwrap()).unwrap();
+        edited.set_share_identical_data(true);
+        assert_eq!(write(&edited), expected);
+
+        // writing over existing data doesn't leave any of it in the padding
+        let mut dirty = Cursor::new(vec![0xff; expected.len()]);
+        file.write_at(&mut dirty, 0).unwrap();
+        assert_eq!(dirty.into_inner(), expected);
+
+        // a file read back writes the same bytes, once it's set to share data again
+        let mut read: BntxFile = Cursor::new(&expected).read_le().unwrap();
+        read.set_share_identical_data(true);
+        assert_eq!(write(&read), expected);
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn untouched_textures_keep_data() {
+        let mut file = BntxFile::new("ester");
+        for (name, format) in [("ester", BC7_UNORM), ("maya", BC1_SRGB)] {
+            file.push_texture(super::Texture::from_image_with_mips(gradient(32, 32), vec![], name, format).unwrap()).unwrap();
+        }
+
+        let mut data = Vec::new();
+        file.write(&mut data).unwrap();
+        let mut file = BntxFile::from_reader(&data[..]).unwrap();
+        let original = file.texture("maya").unwrap().raw_data().to_vec();
+
+        file.push_texture(super::Texture::from_image(gradient(64, 16), "ester").unwrap()).unwrap();
+        let mut data = Vec::new();
+        file.write(&mut data).unwrap();
+
+        let file = BntxFile::from_reader(&data[..]).unwrap();
+        assert_eq!(file.texture("maya").unwrap().raw_data(), original);
+        assert_eq!(file.texture("ester").unwrap().width(), 64);
+    }
+
+    #[test]
+    fn raw_data_layout() {
+        use super::tegra_swizzle;
+
+        let texture = super::Texture::from_image_with_mips(gradient(64, 64), vec![], "ester", BC1_UNORM).unwrap();
+        let layout = texture.layout().unwrap();
+        assert_eq!((layout.block_dimensions, layout.bytes_per_block), ((4, 4), 8));
+        assert_eq!(layout.block_height.log2(), texture.size_range);
+
+        let deswizzled = tegra_swizzle::deswizzle(&layout, texture.raw_data());
+        assert_eq!(deswizzled, texture.to_surface().unwrap().into_data());
+    }
+
+    #[test]
+    fn image_variants() {
+        use image::DynamicImage;
+
+        let texture = |format| super::Texture::from_image_with_mips(gradient(16, 16), vec![], "ester", format);
+        let decode = |format| texture(format).unwrap().to_image().unwrap();
+
+        assert!(matches!(decode(BC4_UNORM), DynamicImage::ImageLuma8(_)));
+        assert!(matches!(decode(R10G10B10A2_UNORM), DynamicImage::ImageRgba16(_)));
+        assert!(matches!(decode(BC6H_UF16), DynamicImage::ImageRgb32F(_)));
+        assert!(matches!(decode(R16G16B16A16_FLOAT), DynamicImage::ImageRgba32F(_)));
+        assert!(matches!(decode(BC5_SNORM), DynamicImage::ImageRgba32F(_)));
+        assert!(matches!(decode(BC7_UNORM), DynamicImage::ImageRgba8(_)));
+
+        let red = gradient(16, 16).into_rgba8().get_pixel(5, 9)[0];
+        let luma = decode(BC4_UNORM).into_luma8().get_pixel(5, 9)[0];
+        assert!((red as i32 - luma as i32).abs() <= 4);
+
+        assert_eq!(texture(BC6H_UF16).unwrap().to_rgba8_image().unwrap().dimensions(), (16, 16));
+    }
+
+    #[test]
+    fn etc2_eac_decode() {
+        use super::{Surface, Texture};
+
+        let decode = |format, data: &[u8]| {
+            let surface = Surface::new(format, 4, 4, 1, 1, data.to_vec()).unwrap();
+            Texture::from_surface("ester", &surface).unwrap().to_image().unwrap()
+        };
+
+        // individual mode with a red left half and a black right half, both brightened by 2
+        let rgb = [0xf0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let image = decode(ETC2_RGB8_UNORM, &rgb).into_rgba8();
+        assert_eq!(image.get_pixel(1, 3).0, [255, 2, 2, 255]);
+        assert_eq!(image.get_pixel(2, 0).0, [2, 2, 2, 255]);
+
+        // the same block under a constant alpha of 200
+        let rgba: Vec<u8> = [0xc8, 0, 0, 0, 0, 0, 0, 0].iter().chain(&rgb).copied().collect();
+        assert_eq!(decode(ETC2_RGBA8_SRGB, &rgba).into_rgba8().get_pixel(0, 0).0, [255, 2, 2, 200]);
+
+        // a non-opaque punchthrough block with only the top left pixel transparent
+        let image = decode(ETC2_RGB8A1_UNORM, &[0x80, 0, 0, 0, 0, 0x01, 0, 0]).into_rgba8();
+        assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 0]);
+        assert_eq!(image.get_pixel(1, 0).0, [132, 0, 0, 255]);
+
+        // a base of 128 with every pixel using a modifier of +2
+        let image = decode(EAC_R11_UNORM, &[0x80, 0x10, 0x92, 0x49, 0x24, 0x92, 0x49, 0x24]);
+        assert!(matches!(image, image::DynamicImage::ImageRgba16(_)));
+        assert_eq!(image.into_rgba16().get_pixel(3, 3).0, [(1044 << 5) | (1044 >> 6), 0, 0, 65535]);
+
+        assert!(Texture::from_image_with_mips(gradient(4, 4), vec![], "ester", ETC2_RGB8_UNORM).is_err());
+    }
+
+    #[test]
+    fn mip_and_layer_offsets() {
+        use super::{tegra_swizzle, Surface, Texture};
+
+        let mut surface = Surface::zeroed(BC1_UNORM, 64, 32, 3, 2).unwrap();
+        for (i, byte) in surface.data_mut().iter_mut().enumerate() {
+            *byte = (i * 7 % 251) as u8;
+        }
+        let texture = Texture::from_surface("ester", &surface).unwrap();
+
+        let offsets: Vec<_> = (0..3).map(|mip| texture.mip_offset(mip).unwrap() as u64).collect();
+        assert_eq!(offsets, texture.mip_offsets);
+        assert_eq!(texture.mip_offset(3), None);
+        assert_eq!(texture.layer_offset(1), Some(texture.raw_data().len() / 2));
+        assert_eq!(texture.layer_offset(2), None);
+
+        // a single mip level found from the offsets alone
+        let start = texture.layer_offset(1).unwrap() + texture.mip_offset(2).unwrap();
+        let params = texture.layout().unwrap().mip(2);
+        let mip = tegra_swizzle::deswizzle(&params, &texture.raw_data()[start..]);
+        assert_eq!(mip, surface.mip(1, 2).unwrap());
+    }
+
+    #[test]
+    fn typed_image_import() {
+        use super::Texture;
+        use image::{DynamicImage, GrayImage, Rgba32FImage};
+
+        let rgba = Texture::from_rgba_image(gradient(24, 8).into_rgba8(), "ester").unwrap();
+        assert_eq!(rgba.format, R8G8B8A8_SRGB);
+        assert_eq!(rgba.to_image().unwrap(), gradient(24, 8));
+
+        let gray = GrayImage::from_fn(24, 8, |x, y| image::Luma([(x * 8 + y) as u8]));
+        let texture = Texture::from_gray_image(gray.clone(), "ester").unwrap();
+        assert_eq!(texture.format, R8_UNORM);
+        assert_eq!(texture.to_image().unwrap(), DynamicImage::ImageLuma8(gray));
+
+        let hdr = Rgba32FImage::from_pixel(24, 8, image::Rgba([4.5, -1.0, 0.25, 1.0]));
+        let texture = Texture::from_rgba32f_image(hdr.clone(), "ester").unwrap();
+        assert_eq!(texture.format, R32G32B32A32_FLOAT);
+        assert_eq!(texture.to_image().unwrap(), DynamicImage::ImageRgba32F(hdr));
+    }
+
+    #[test]
+    fn dds_roundtrip() {
+        let mut texture = super::Texture::from_image_with_mips(
+            gradient(64, 32), vec![gradient(32, 16)], "ester", BC3_UNORM
+        ).unwrap();
+
+        let mut dds = Vec::new();
+        texture.to_dds(&mut dds).unwrap();
+        assert_eq!(dds[..4], *b"DDS ");
+        assert_eq!(dds.len(), 4 + 124 + 20 + 64 * 32 + 32 * 16);
+
+        let read = super::Texture::from_dds(&mut &dds[..], "ester").unwrap();
+        assert_eq!(read.data_hash().unwrap(), texture.data_hash().unwrap());
+
+        texture.set_format(BC6H_UF16).unwrap();
+        let mut dds = Vec::new();
+        texture.to_dds(&mut dds).unwrap();
+        dds[0x54..0x58].copy_from_slice(b"DXT5");
+        assert!(matches!(super::Texture::from_dds(&mut &dds[..], "ester"), Ok(texture) if texture.format == BC3_UNORM));
+        assert!(super::Texture::from_dds(&mut &dds[4..], "ester").is_err());
+    }
+
+    #[cfg(feature = "batch")]
+    #[test]
+    fn batch_convert_dir() {
+        use super::batch::{convert_dir, convert_dir_with_progress, ConvertOptions, ImageFormat};
+
+        let dir = std::env::temp_dir().join(format!("bntx-batch-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("in")).unwrap();
+        gradient(64, 64).save(dir.join("in/ester.png")).unwrap();
+        gradient(32, 32).save(dir.join("in/maya.png")).unwrap();
+        std::fs::write(dir.join("in/broken.png"), b"not a png").unwrap();
+
+        let options = ConvertOptions { texture_format: BC1_UNORM, ..Default::default() };
+        let reports = convert_dir(dir.join("in/*.png").to_str().unwrap(), dir.join("bntx"), &options).unwrap();
+        assert_eq!(reports.len(), 3);
+        assert!(reports[0].result.is_err());
+        assert_eq!(reports[1].result.as_ref().unwrap(), &[dir.join("bntx/ester.bntx")]);
+
+        let options = ConvertOptions { image_format: ImageFormat::Dds, threads: 1, ..options };
+        let reports = convert_dir(dir.join("bntx/*.bntx").to_str().unwrap(), dir.join("dds"), &options).unwrap();
+        assert!(reports.iter().all(|report| report.result.is_ok()));
+
+        let last = std::sync::Mutex::new((0, 0));
+        let reports = convert_dir_with_progress(
+            dir.join("dds/*.dds").to_str().unwrap(),
+            dir.join("out"),
+            &options,
+            &|done, total| *last.lock().unwrap() = (done, total),
+        ).unwrap();
+        assert!(reports.iter().all(|report| report.result.is_ok()));
+        assert_eq!(last.into_inner().unwrap(), (2, 2));
+        let file = BntxFile::open(dir.join("out/maya.bntx")).unwrap();
+        assert_eq!(file.textures().next().unwrap().format, BC1_UNORM);
+
+        // texture names that would write outside the output directory fail that file
+        std::fs::create_dir_all(dir.join("evil")).unwrap();
+        let absolute = dir.join("escaped").to_str().unwrap().to_owned();
+        for (i, name) in ["../escaped", &absolute, "C:escaped", "a\\..\\escaped"].iter().enumerate() {
+            let mut file = BntxFile::from_image(gradient(16, 16), "ester").unwrap();
+            file.push_texture(super::Texture::from_image(gradient(8, 8), name).unwrap()).unwrap();
+            file.save(dir.join(format!("evil/{}.bntx", i))).unwrap();
+        }
+        let reports = convert_dir(dir.join("evil/*.bntx").to_str().unwrap(), dir.join("extracted"), &options).unwrap();
+        assert_eq!(reports.len(), 4);
+        assert!(reports.iter().all(|report| matches!(report.result, Err(super::Error::UnsafeName(_)))));
+        assert!(!dir.join("escaped.dds").exists());
+        assert!(!dir.join("extracted/ester.dds").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "batch")]
+    #[test]
+    fn batch_extract_archive() {
+        use super::batch::{ConvertOptions, ImageFormat};
+
+        let dir = std::env::temp_dir().join(format!("bntx-archive-{}", std::process::id()));
+        let blobs: Vec<Vec<u8>> = (0..6)
+            .map(|i| {
+                // every file has a texture with the same name
+                let mut file = BntxFile::from_image(gradient(16, 16).huerotate(i * 60), "ester").unwrap();
+                file.push_texture(super::Texture::from_image(gradient(8, 8), &format!("maya{}", i)).unwrap()).unwrap();
+                let mut data = Vec::new();
+                file.write(&mut data).unwrap();
+                data
+            })
+            .chain([b"not a bntx".to_vec()])
+            .collect();
+
+        let options = ConvertOptions { threads: 2, ..Default::default() };
+        let readers = blobs.iter().map(|blob| Cursor::new(&blob[..]));
+        let reports = super::extract_archive(readers, &dir, &options).unwrap();
+        assert_eq!(reports.iter().map(|report| report.index).collect::<Vec<_>>(), (0..7).collect::<Vec<_>>());
+        assert!(reports[6].result.is_err());
+        assert_eq!(
+            reports[3].result.as_ref().unwrap(),
+            &[dir.join("3_ester/ester.png"), dir.join("3_ester/maya3.png")]
+        );
+        let img = image::open(dir.join("3_ester/ester.png")).unwrap();
+        assert_eq!(img.to_rgba8(), gradient(16, 16).huerotate(180).to_rgba8());
+
+        let options = ConvertOptions { image_format: ImageFormat::Dds, threads: 0, ..options };
+        let reports = super::extract_archive([&blobs[0][..]], dir.join("dds"), &options).unwrap();
+        assert_eq!(reports[0].result.as_ref().unwrap()[0], dir.join("dds/0_ester/ester.dds"));
+        assert!(super::extract_archive(std::iter::empty::<&[u8]>(), &dir, &options).unwrap().is_empty());
+
+        // file names that would put the file's directory outside the output directory fail
+        let mut evil = BntxFile::from_image(gradient(16, 16), "ester").unwrap();
+        evil.header.inner.file_name = super::BntxStr::from("../../evilfile".to_owned());
+        let mut data = Vec::new();
+        evil.write(&mut data).unwrap();
+        let reports = super::extract_archive([&data[..]], dir.join("evil"), &options).unwrap();
+        assert!(matches!(&reports[0].result, Err(super::Error::UnsafeName(name)) if name == "../../evilfile"));
+        assert!(!dir.join("evilfile").exists());
+
+        // sidecars bring back the original parameters rather than the options' format
+        let options = ConvertOptions { sidecar: true, texture_format: BC1_UNORM, ..Default::default() };
+        let reports = super::extract_archive([&blobs[2][..]], dir.join("side"), &options).unwrap();
+        assert_eq!(reports[0].result.as_ref().unwrap()[1], dir.join("side/0_ester/ester.bntx.toml"));
+        let reports = super::batch::convert_dir(
+            dir.join("side/0_ester/*").to_str().unwrap(), dir.join("rebuilt"), &options
+        ).unwrap();
+        assert!(reports.iter().all(|report| report.result.is_ok()));
+        let rebuilt = BntxFile::open(dir.join("rebuilt/maya2.bntx")).unwrap();
+        assert_eq!(rebuilt.textures().next().unwrap().format, R8G8B8A8_SRGB);
+        assert_eq!(rebuilt.header.inner.file_name.bytes, b"ester");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "test-utils")]
+    proptest::proptest! {
+        #![proptest_config(proptest::prelude::ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn write_read_write(file in super::test_utils::bntx_file(3)) {
+            let mut written = Vec::new();
+            file.write(&mut written).unwrap();
+
+            let read = BntxFile::read_at(&mut std::io::Cursor::new(&written), 0).unwrap();
+            let mut rewritten = Vec::new();
+            read.write(&mut rewritten).unwrap();
+
+            proptest::prop_assert_eq!(written, rewritten);
+            proptest::prop_assert_eq!(read.content_hash().unwrap(), file.content_hash().unwrap());
+        }
+    }
+
+    #[cfg(feature = "fuzz")]
+    #[test]
+    fn fuzz_harness() {
+        use arbitrary::{Arbitrary, Unstructured};
+        use super::fuzz::{parse_and_rewrite, roundtrip, FuzzFile};
+
+        // a fixed xorshift stream stands in for the fuzzer's input
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut bytes = |len: usize| -> Vec<u8> {
+            (0..len).map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            }).collect()
+        };
+
+        for _ in 0..8 {
+            let input = bytes(0x4000);
+            let Ok(file) = FuzzFile::arbitrary(&mut Unstructured::new(&input)) else { continue };
+            roundtrip(&file);
+
+            // corrupt the written file a byte at a time, and cut it short
+            let mut written = Vec::new();
+            file.0.write(&mut written).unwrap();
+            for (&position, &value) in bytes(16).iter().zip(&bytes(16)) {
+                let mut corrupt = written.clone();
+                let index = position as usize * corrupt.len() / 256;
+                corrupt[index] = value;
+                parse_and_rewrite(&corrupt);
+                parse_and_rewrite(&written[..index]);
+            }
+
+            // a huge alignment would pad the rewritten file by gigabytes
+            let brti = super::SectionIter::new(&written).find(|section| &section.magic == b"BRTI").unwrap();
+            written[brti.offset + 0x54..][..4].copy_from_slice(&0x8000_0000u32.to_le_bytes());
+            parse_and_rewrite(&written);
+        }
+    }
+
+    #[cfg(feature = "yaz0")]
+    #[test]
+    fn yaz0_open() {
+        use super::compression::decompress_yaz0;
+
+        let header = |size: u32| [&b"Yaz0"[..], &size.to_be_bytes(), &[0; 8]].concat();
+
+        // three literals, then a back reference 3 bytes back copying 9 bytes
+        let abc = [header(12), vec![0b1110_0000, b'a', b'b', b'c', 0x70, 0x02]].concat();
+        assert_eq!(decompress_yaz0(&abc).unwrap(), b"abcabcabcabc");
+        assert!(decompress_yaz0(&[header(4), vec![0, 0x10, 0x00]].concat()).is_err());
+        assert!(decompress_yaz0(&[header(u32::MAX), vec![0xff, b'a']].concat()).is_err());
+
+        // a file stored entirely as literals
+        let mut data = Vec::new();
+        BntxFile::from_image(gradient(64, 64), "ester").unwrap().write(&mut data).unwrap();
+        let mut compressed = header(data.len() as u32);
+        for chunk in data.chunks(8) {
+            compressed.push(0xff);
+            compressed.extend(chunk);
+        }
+
+        let path = std::env::temp_dir().join(format!("bntx-yaz0-{}.szs", std::process::id()));
+        std::fs::write(&path, compressed).unwrap();
+        let file = BntxFile::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(file.to_image().unwrap(), gradient(64, 64));
+    }
+
+    #[test]
+    fn vulkan_formats() {
+        use super::SurfaceFormat::{self, *};
+
+        for format in KNOWN_FORMATS {
+            let vk_format = format.to_vk_format().unwrap();
+            assert_eq!(SurfaceFormat::from_vk_format(vk_format), Some(format));
+        }
+
+        assert_eq!(BC7_SRGB.to_vk_format(), Some(146));
+        assert_eq!(SurfaceFormat::from_vk_format(131), Some(BC1_UNORM));
+        assert_eq!(SurfaceFormat::from_vk_format(0), None);
+        assert_eq!(Unknown(0x1234).to_vk_format(), None);
+    }
+
+    #[test]
+    fn gl_formats() {
+        use super::{GlFormat, SurfaceFormat::{self, *}};
+
+        for format in KNOWN_FORMATS {
+            let gl_format = format.to_gl_format().unwrap();
+            assert_eq!(SurfaceFormat::from_gl_format(gl_format), Some(format));
+        }
+
+        // the same internal format with BGRA data
+        let bgra = GlFormat { internal_format: 0x8058, format: 0x80e1, ty: 0x1401 };
+        assert_eq!(SurfaceFormat::from_gl_format(bgra), Some(B8G8R8A8_UNORM));
+        let bc7 = GlFormat { internal_format: 0x8e8c, format: 0x1908, ty: 0x1401 };
+        assert_eq!(SurfaceFormat::from_gl_format(bc7), Some(BC7_UNORM));
+        assert_eq!(Unknown(0x1234).to_gl_format(), None);
+    }
+
+    #[test]
+    fn dxgi_formats() {
+        use super::SurfaceFormat;
+
+        for format in KNOWN_FORMATS {
+            let dxgi_format = format.to_dxgi_format().unwrap();
+            assert_eq!(SurfaceFormat::from_dxgi_format(dxgi_format), Some(format));
+        }
+
+        assert_eq!(BC7_SRGB.to_dxgi_format(), Some(99));
+        assert_eq!(SurfaceFormat::from_dxgi_format(0), None);
+    }
+
+    #[test]
+    fn thumbnail_uses_mips() {
+        // each mip is a different color, so the one used is easy to tell
+        let color = |width, height, value| image::DynamicImage::ImageRgba8(
+            image::RgbaImage::from_pixel(width, height, image::Rgba([value, 0, 0, 255]))
+        );
+        let texture = super::Texture::from_image_with_mips(
+            color(256, 128, 0),
+            vec![color(128, 64, 1), color(64, 32, 2), color(32, 16, 3)],
+            "ester",
+            R8G8B8A8_UNORM,
+        ).unwrap();
+
+        let thumbnail = texture.thumbnail(48).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (48, 24));
+        assert_eq!(thumbnail.to_rgba8().get_pixel(0, 0).0, [2, 0, 0, 255]);
+
+        let thumbnail = texture.thumbnail(64).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (64, 32));
+        assert_eq!(thumbnail.to_rgba8().get_pixel(0, 0).0, [2, 0, 0, 255]);
+
+        let thumbnail = texture.thumbnail(512).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (256, 128));
+        assert_eq!(thumbnail.to_rgba8().get_pixel(0, 0).0, [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn stream_mips_smallest_first() {
+        let color = |width, height, value| image::DynamicImage::ImageRgba8(
+            image::RgbaImage::from_pixel(width, height, image::Rgba([value, 0, 0, 255]))
+        );
+        let texture = super::Texture::from_image_with_mips(
+            color(64, 32, 0), vec![color(32, 16, 1), color(16, 8, 2)], "ester", R8G8B8A8_UNORM
+        ).unwrap();
+
+        let mut mips = texture.stream_mips();
+        let (level, image) = mips.next().unwrap().unwrap();
+        assert_eq!((level, image.width(), image.height()), (2, 16, 8));
+        assert_eq!(image.to_rgba8().get_pixel(0, 0).0, [2, 0, 0, 255]);
+
+        let rest: Vec<_> = mips.map(|mip| mip.map(|(level, image)| (level, image.width())).unwrap()).collect();
+        assert_eq!(rest, [(1, 32), (0, 64)]);
+    }
+
+    #[test]
+    fn replace_mip() {
+        use super::Error;
+
+        let color = |width, height, value| image::DynamicImage::ImageRgba8(
+            image::RgbaImage::from_pixel(width, height, image::Rgba([value, 0, 0, 255]))
+        );
+        let mut texture = super::Texture::from_image_with_mips(
+            color(64, 32, 0), vec![color(32, 16, 0), color(16, 8, 132)], "ester", BC1_UNORM
+        ).unwrap();
+        let before = texture.raw_data().to_vec();
+
+        texture.replace_mip(1, color(32, 16, 255)).unwrap();
+        let levels: Vec<_> = texture.stream_mips()
+            .map(|mip| mip.unwrap().1.to_rgba8().get_pixel(0, 0).0[0])
+            .collect();
+        assert_eq!(levels, [132, 255, 0]);
+
+        let first_mip = texture.mip_offset(1).unwrap();
+        assert_eq!(texture.raw_data()[..first_mip], before[..first_mip]);
+
+        assert!(matches!(
+            texture.replace_mip(1, color(16, 16, 0)),
+            Err(Error::MismatchedSize { expected: (32, 16), found: (16, 16) })
+        ));
+        assert!(matches!(
+            texture.replace_mip(3, color(8, 4, 0)),
+            Err(Error::MissingMip { level: 3, count: 3 })
+        ));
+    }
 
-    /*
     #[test]
-    fn try_parse() {
-        let mut data = Cursor::new(&include_bytes!("/home/jam/Downloads/ester.bntx")[..]);
-        //let mut data = Cursor::new(&include_bytes!("/home/jam/dev/ult/bntx/test.bntx")[..]);
+    fn from_image_like() {
+        use super::{Error, TileMode};
 
-        let test: BntxFile = data.read_le().unwrap();
+        let (base, mips) = (gradient(64, 32), vec![gradient(32, 16), gradient(16, 8)]);
+        let mut reference = super::TextureBuilder::new("ester")
+            .swizzle(3)
+            .alignment(0x1000)
+            .build_with_mips(base, mips)
+            .unwrap();
+        reference.reswizzle(TileMode::BlockLinear, BlockHeight::One).unwrap();
+        reference.comp_sel = 0x05040302;
+        let mut file = BntxFile::new("maya");
+        file.push_texture(reference.clone()).unwrap();
+        file.set_mem_pool(vec![0xaa; 0x200]);
 
-        dbg!(&test);
+        let template = reference.metadata_template();
+        assert_eq!(template.info(), reference.info());
+        assert_eq!(template.raw_data(), vec![0; reference.raw_data().len()]);
 
-        test.to_image()
-            .save("test.png");
+        let img = gradient(64, 32).huerotate(90);
+        let modded = BntxFile::from_image_like(&file, img.clone()).unwrap();
+        let texture = modded.texture("ester").unwrap();
+        assert_eq!(texture.to_image().unwrap(), img);
+        assert_eq!(
+            (texture.swizzle, texture.align, texture.size_range, texture.comp_sel, texture.image_size),
+            (3, 0x1000, 0, 0x05040302, reference.image_size)
+        );
+        assert_eq!(texture.mip_offsets, reference.mip_offsets);
+        let mip = img.resize_exact(32, 16, image::imageops::FilterType::Triangle);
+        assert_eq!(texture.decode(1, texture.size_range).unwrap().to_rgba8(), mip.to_rgba8());
+        assert_eq!(modded.mem_pool(), file.mem_pool());
+        assert_eq!(modded.header.inner.file_name.bytes, b"maya");
+
+        assert!(matches!(
+            BntxFile::from_image_like(&file, gradient(32, 32)),
+            Err(Error::MismatchedSize { expected: (64, 32), found: (32, 32) })
+        ));
+        assert!(matches!(
+            BntxFile::from_image_like(&BntxFile::new("maya"), img),
+            Err(Error::NoTextures)
+        ));
     }
-    */
 
     #[test]
-    fn try_from_png() {
-        let image = image::open("/home/jam/dev/ult/bntx/test.png").unwrap();
+    fn decode_region() {
+        use super::Error;
+
+        let mips = vec![gradient(64, 48), gradient(32, 24)];
+        for format in [R8G8B8A8_SRGB, BC1_UNORM] {
+            let texture = super::Texture::from_image_with_mips(
+                gradient(128, 96), mips.clone(), "ester", format
+            ).unwrap();
+
+            let full = texture.to_image().unwrap();
+            assert_eq!(texture.decode_region(13, 7, 40, 30, 0).unwrap(), full.crop_imm(13, 7, 40, 30));
+            assert_eq!(texture.decode_region(0, 0, 128, 96, 0).unwrap(), full);
+
+            let (_, mip) = texture.stream_mips().nth(1).unwrap().unwrap();
+            assert_eq!(texture.decode_region(5, 9, 21, 3, 1).unwrap(), mip.crop_imm(5, 9, 21, 3));
+
+            assert!(matches!(texture.decode_region(100, 0, 40, 8, 0), Err(Error::InvalidRegion { .. })));
+            assert!(matches!(texture.decode_region(0, 0, 8, 8, 3), Err(Error::MissingMip { .. })));
+        }
+    }
+
+    #[test]
+    fn sprite_sheet_frames() {
+        let mut sheet = image::RgbaImage::new(100, 40);
+        for (x, _, pixel) in sheet.enumerate_pixels_mut() {
+            *pixel = image::Rgba([(x / 32) as u8 * 50, 0, 0, 255]);
+        }
+
+        let texture = super::TextureBuilder::new("ester")
+            .build_from_sprite_sheet(image::DynamicImage::ImageRgba8(sheet), 32, 32)
+            .unwrap();
+        assert_eq!((texture.width, texture.height, texture.array_len), (32, 32, 3));
+
+        let surface = texture.to_surface().unwrap();
+        let reds: Vec<_> = (0..3).map(|layer| surface.mip(layer, 0).unwrap()[0]).collect();
+        assert_eq!(reds, [0, 50, 100]);
+
+        let sheet = image::DynamicImage::ImageRgba8(image::RgbaImage::new(16, 16));
+        let frames = super::TextureBuilder::new("ester").build_from_sprite_sheet(sheet, 32, 32);
+        assert!(matches!(frames, Err(super::Error::EmptyArray)));
+    }
+
+    #[test]
+    fn crop() {
+        use super::{Error, Rect};
+
+        let images = vec![gradient(8, 24); 3];
+        let (mut atlas, regions) = super::TextureBuilder::new("ester").build_atlas(images).unwrap();
+        atlas.convert_format(BC1_UNORM).unwrap();
+        let full = atlas.to_image().unwrap();
+
+        let (region, sprite) = (regions[1], atlas.crop(regions[1].into(), "maya").unwrap());
+        assert_eq!((sprite.name(), sprite.format, sprite.width, sprite.height), ("maya", BC1_UNORM, 8, 24));
+        assert_eq!(sprite.to_image().unwrap(), full.crop_imm(region.x, region.y, 8, 24));
+
+        // off the block grid, so re-encoded
+        let rect = Rect { x: 2, y: 6, width: 8, height: 4 };
+        let sprite = atlas.crop(rect, "maya").unwrap();
+        assert_eq!((sprite.width, sprite.height), (8, 4));
+
+        let rect = Rect { x: 2, y: 6, width: 6, height: 4 };
+        assert!(matches!(atlas.crop(rect, "maya"), Err(Error::UnalignedSize { .. })));
+    }
+
+    #[test]
+    fn compare_textures() {
+        let texture = super::Texture::from_image(gradient(64, 64), "ester").unwrap();
+        let report = super::compare(&texture, &texture).unwrap();
+        assert_eq!((report.max_diff, report.psnr), (0.0, f64::INFINITY));
+
+        let mut compressed = texture.clone();
+        compressed.convert_format(BC1_UNORM).unwrap();
+        let report = super::compare(&texture, &compressed).unwrap();
+        assert!(report.max_diff > 0.0 && report.psnr > 30.0 && report.psnr.is_finite());
+        assert_eq!(report.channels[3].max_diff, 0.0);
+
+        let smaller = super::Texture::from_image(gradient(32, 64), "maya").unwrap();
+        assert!(super::compare(&texture, &smaller).is_err());
+    }
+
+    #[test]
+    fn guess_format() {
+        use super::SurfaceFormat;
+
+        let mut texture = super::Texture::from_image(gradient(64, 64), "ester").unwrap();
+        texture.convert_format(BC1_UNORM).unwrap();
+
+        // same channel layout with an unknown type, then formats with the same block size
+        texture.format = SurfaceFormat::Unknown(0x1a03);
+        let guesses = texture.guess_format();
+        assert_eq!(guesses[..2], [BC1_UNORM, BC1_SRGB]);
+        assert!(guesses.contains(&BC4_UNORM) && guesses.contains(&ETC2_RGB8_UNORM));
+        assert!(!guesses.contains(&R8G8B8A8_UNORM) && !guesses.contains(&BC3_UNORM));
+
+        texture.image_size += 1;
+        assert_eq!(texture.guess_format(), []);
+    }
+
+    #[test]
+    fn raw_export_import() {
+        use super::{RawLayout, SurfaceFormat, Texture};
+
+        let mut texture = Texture::from_image_with_mips(
+            gradient(64, 32), vec![gradient(32, 16)], "ester", BC1_UNORM
+        ).unwrap();
+        texture.comp_sel = crate::preset::GRAYSCALE_COMP_SEL;
+
+        let (mut data, mut metadata) = (Vec::new(), Vec::new());
+        texture.export_raw(&mut data, &mut metadata, RawLayout::Deswizzled).unwrap();
+        let imported = Texture::import_raw(&mut &data[..], &mut &metadata[..]).unwrap();
+        assert_eq!((imported.name(), imported.comp_sel), ("ester", texture.comp_sel));
+        assert_eq!(imported.raw_data(), texture.raw_data());
+
+        // unknown formats can't be deswizzled, but still make it through swizzled
+        texture.format = SurfaceFormat::Unknown(0x9901);
+        assert!(texture.export_raw(&mut Vec::new(), &mut Vec::new(), RawLayout::Deswizzled).is_err());
+
+        let (mut data, mut metadata) = (Vec::new(), Vec::new());
+        texture.export_raw(&mut data, &mut metadata, RawLayout::Swizzled).unwrap();
+        let imported = Texture::import_raw(&mut &data[..], &mut &metadata[..]).unwrap();
+        assert_eq!(imported.format, texture.format);
+        assert_eq!(imported.raw_data(), texture.raw_data());
+        assert_eq!(imported.mip_offsets, texture.mip_offsets);
+
+        let metadata = String::from_utf8(metadata).unwrap().replace("width", "wide");
+        assert!(Texture::import_raw(&mut &data[..], &mut metadata.as_bytes()).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn texture_flags() {
+        use super::{GpuAccess, Texture, TextureFlags};
+
+        let mut texture = Texture::from_image(gradient(32, 32), "ester").unwrap();
+        assert_eq!(texture.flags(), TextureFlags::SPECIFY_TEXTURE_LAYOUT);
+        assert_eq!(texture.gpu_access(), GpuAccess::TEXTURE);
+
+        // unknown bits survive changing the known ones and a write
+        let mut access = GpuAccess::from_bits_retain(0x8000_0020);
+        access |= GpuAccess::COLOR_BUFFER;
+        texture.set_gpu_access(access);
+        let mut flags = texture.flags() | TextureFlags::from_bits_retain(0x80);
+        flags.set(TextureFlags::SPARSE_BINDING, true);
+        texture.set_flags(flags);
+
+        let mut file = BntxFile::new("ester");
+        file.push_texture(texture).unwrap();
+        let mut data = Cursor::new(Vec::new());
+        file.write(&mut data).unwrap();
+        let texture = BntxFile::from_reader(&data.get_ref()[..]).unwrap().nx_header.textures.remove(0);
+
+        assert_eq!(texture.gpu_access().bits(), 0x8000_00a0);
+        assert_eq!(texture.gpu_access().unknown_bits(), 0x8000_0000);
+        assert!(texture.gpu_access().contains(GpuAccess::TEXTURE | GpuAccess::COLOR_BUFFER));
+        assert_eq!(texture.flags().bits(), 0x83);
+        assert_eq!(
+            format!("{:?}", texture.flags()),
+            "TextureFlags(SPECIFY_TEXTURE_LAYOUT | SPARSE_BINDING | 0x80)"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn degenerate_headers() {
+        use super::{Error, ImageData, SurfaceFormat::*, Texture};
+
+        let texture = || Texture::from_image_with_mips(gradient(32, 32), vec![gradient(16, 16)], "ester", R8G8B8A8_SRGB).unwrap();
+        let reread = |texture: Texture| {
+            let mut file = BntxFile::new("ester");
+            file.push_texture(texture).unwrap();
+            let mut data = Cursor::new(Vec::new());
+            file.write(&mut data).unwrap();
+            BntxFile::from_reader(&data.get_ref()[..]).unwrap().nx_header.textures.remove(0)
+        };
+
+        // no mips is treated as one, missing data as zeroes and a zero height as one
+        let mut empty = texture();
+        empty.mips_count = 0;
+        empty.image_size = 0;
+        empty.texture = ImageData(Vec::new());
+        let empty = reread(empty);
+        assert_eq!(empty.decode_all().unwrap().layers[0].len(), 1);
+        assert_eq!(empty.to_image().unwrap().to_rgba8().into_raw(), vec![0; 32 * 32 * 4]);
+
+        let mut flat = texture();
+        flat.height = 0;
+        let flat = reread(flat);
+        assert_eq!(flat.to_image().unwrap().to_rgba8().dimensions(), (32, 1));
+        flat.to_dds(&mut Cursor::new(Vec::new())).unwrap();
+
+        let mut corrupt = texture();
+        corrupt.mips_count = 40;
+        let corrupt = reread(corrupt);
+        assert!(matches!(corrupt.to_image(), Err(Error::TooManyMips { count: 40, max: 6 })));
+        assert!(corrupt.stream_mips().all(|mip| mip.is_err()));
+
+        let mut huge = texture();
+        huge.height = u32::MAX;
+        let huge = reread(huge);
+        assert!(matches!(huge.to_image(), Err(Error::InvalidDimensions { .. })));
+        assert!(huge.size_mismatches().is_err());
+
+        // an empty name can't be added to a file, but one read with it still works
+        let mut file = BntxFile::new("maya");
+        file.push_texture(texture()).unwrap();
+        let mut data = Vec::new();
+        file.write(&mut data).unwrap();
+        let name = data.windows(7).position(|bytes| bytes == b"\x05\x00ester").unwrap();
+        data[name] = 0;
+        let read = BntxFile::from_reader(&data[..]).unwrap();
+        assert_eq!(read.textures().next().unwrap().name(), "");
+        assert!(read.texture("").is_some());
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn corrupt_offsets() {
+        let file = BntxFile::from_image(gradient(32, 32), "ester").unwrap();
+        let layout = super::Layout::new(&file);
+        let mut data = Vec::new();
+        file.write(&mut data).unwrap();
+
+        // a relocation table claiming billions of sections isn't walked
+        let mut corrupt = data.clone();
+        let count = layout.reloc_table_offset + 8;
+        corrupt[count..count + 4].copy_from_slice(&0xff00_0002u32.to_le_bytes());
+        assert_eq!(BntxFile::from_reader(&corrupt[..]).unwrap().textures().count(), 1);
+
+        // pointers too large to seek to are an error
+        let mut corrupt = data.clone();
+        let name = layout.textures[0].brti + 0x60;
+        corrupt[name..name + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(BntxFile::from_reader(&corrupt[..]).is_err());
+    }
+
+    #[test]
+    fn png_color_space() {
+        use super::{PngColorSpace, SurfaceFormat::*, Texture};
+
+        let gray = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(4, 4, image::Rgba([128, 128, 128, 255])));
+        let png = |format, color_space| {
+            let mut texture = Texture::from_image(gray.clone(), "ester").unwrap();
+            texture.convert_format(format).unwrap();
+            let mut data = Vec::new();
+            texture.to_png(&mut data, color_space).unwrap();
+
+            let reader = png::Decoder::new(&data[..]).read_info().unwrap();
+            let srgb = reader.info().srgb.is_some();
+            (srgb, image::load_from_memory(&data).unwrap())
+        };
+
+        let (srgb, untagged) = png(R8G8B8A8_UNORM, PngColorSpace::Untagged);
+        assert!(!srgb);
+        assert_eq!(untagged.to_rgba8().get_pixel(0, 0).0, [128, 128, 128, 255]);
+
+        // linear values are converted so they display the same, and back when rebuilt
+        let (srgb, tagged) = png(R8G8B8A8_UNORM, PngColorSpace::Srgb);
+        assert!(srgb);
+        assert_eq!(tagged.to_rgba8().get_pixel(0, 0).0, [188, 188, 188, 255]);
+        let rebuilt = PngColorSpace::Srgb.decode(tagged, R8G8B8A8_UNORM);
+        assert_eq!(rebuilt.to_rgba8().get_pixel(0, 0).0, [128, 128, 128, 255]);
+
+        let (srgb, tagged) = png(R8G8B8A8_SRGB, PngColorSpace::Srgb);
+        assert!(srgb);
+        assert_eq!(tagged.to_rgba8().get_pixel(0, 0).0, [128, 128, 128, 255]);
+    }
+
+    #[test]
+    fn sidecar() {
+        use super::{Target, TileMode};
+
+        let (base, mips) = (gradient(64, 32), vec![gradient(32, 16), gradient(16, 8)]);
+        let mut texture = super::TextureBuilder::new("ester \"alt\"")
+            .swizzle(2)
+            .alignment(0x1000)
+            .build_with_mips(base, mips)
+            .unwrap();
+        texture.convert_format(BC1_UNORM).unwrap();
+        texture.reswizzle(TileMode::BlockLinear, BlockHeight::One).unwrap();
+        texture.comp_sel = 0x0504_0202;
+        let target = Target { version: (1, 4), revision: 0x4011 };
+        let mut file = BntxFile::with_target("maya", target);
+        file.push_texture(texture.clone()).unwrap();
+
+        let mut sidecar = Vec::new();
+        file.write_sidecar("ester \"alt\"", &mut sidecar).unwrap();
+        let text = String::from_utf8(sidecar.clone()).unwrap();
+        assert!(text.contains("name = \"ester \\\"alt\\\"\"\n"));
+        assert!(text.contains("mip_offsets = [0x0, 0x400, 0x600]\n"));
+
+        let edited = gradient(64, 32).huerotate(120);
+        let rebuilt = BntxFile::from_image_with_sidecar(edited.clone(), &mut &sidecar[..]).unwrap();
+        assert_eq!(rebuilt.target(), target);
+        assert_eq!(rebuilt.header.inner.file_name.bytes, b"maya");
+        let rebuilt = rebuilt.texture("ester \"alt\"").unwrap();
+        assert_eq!(rebuilt.raw_fields(), texture.raw_fields());
+        assert_eq!((&rebuilt.mip_offsets, rebuilt.unk4), (&texture.mip_offsets, texture.unk4));
+
+        let mut expected = texture.clone();
+        expected.replace_image(edited).unwrap();
+        assert_eq!(rebuilt.raw_data(), expected.raw_data());
+
+        assert!(matches!(
+            BntxFile::from_image_with_sidecar(gradient(32, 32), &mut &sidecar[..]),
+            Err(super::Error::MismatchedSize { .. })
+        ));
+        assert!(matches!(file.write_sidecar("zoe", &mut Vec::new()), Err(super::Error::MissingTexture(_))));
+    }
+
+    #[test]
+    fn recompute_sizes() {
+        use super::SizeMismatch;
+
+        let mut texture = super::Texture::from_image_with_mips(
+            gradient(64, 48), vec![gradient(32, 24), gradient(16, 12)], "ester", BC1_UNORM
+        ).unwrap();
+        assert_eq!(texture.size_mismatches().unwrap(), []);
+
+        let original = texture.clone();
+        texture.reswizzle(super::TileMode::BlockLinear, BlockHeight::Sixteen).unwrap();
+        texture.image_size += 0x200;
+        texture.align = 0x300;
+
+        let mismatches = texture.recompute_sizes().unwrap();
+        assert!(matches!(mismatches[0], SizeMismatch::SizeRange { stored: 4, expected: 1 }));
+        assert!(mismatches.contains(&SizeMismatch::Alignment { stored: 0x300, expected: 512 }));
+        assert_eq!(mismatches.len(), 4);
+
+        assert_eq!(texture.size_mismatches().unwrap(), []);
+        assert_eq!(texture.raw_data(), original.raw_data());
+        assert_eq!(texture.image_size, original.image_size);
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn texture_alignment() {
+        let mut texture = super::TextureBuilder::new("maya")
+            .alignment(0x1000)
+            .build(gradient(16, 16))
+            .unwrap();
+        assert_eq!((texture.align, texture.image_size), (0x1000, 0x1000));
+        assert_eq!(texture.size_mismatches().unwrap(), []);
+
+        texture.convert_format(BC1_UNORM).unwrap();
+        assert_eq!((texture.align, texture.image_size), (0x1000, 0x1000));
+
+        let mut file = BntxFile::from_image(gradient(16, 16), "ester").unwrap();
+        file.push_texture(texture).unwrap();
+        let layout = super::Layout::new(&file);
+        assert_eq!(layout.textures[1].data % 0x1000, 0);
+
+        let mut data = Vec::new();
+        file.write(&mut data).unwrap();
+        let file = BntxFile::from_reader(&data[..]).unwrap();
+        assert_eq!(file.texture("maya").unwrap().image_size, 0x1000);
+
+        for alignment in [0, 0x300, 0x20000] {
+            assert!(matches!(
+                super::TextureBuilder::new("maya").alignment(alignment).build(gradient(16, 16)),
+                Err(super::Error::InvalidAlignment { alignment: found }) if found == alignment
+            ));
+        }
+
+        // a corrupt alignment is rejected when read, rather than padding the file when written
+        let brti = super::SectionIter::new(&data).find(|section| &section.magic == b"BRTI").unwrap();
+        data[brti.offset + 0x54..][..4].copy_from_slice(&0x8000_0000u32.to_le_bytes());
+        let err = BntxFile::from_reader(&data[..]).unwrap_err();
+        let err = err.root_cause().custom_err::<super::Error>().unwrap();
+        assert!(matches!(err, super::Error::InvalidAlignment { alignment: 0x8000_0000 }));
+    }
+
+    #[test]
+    fn grayscale_as_r8() {
+        let gray = image::DynamicImage::ImageLuma8(gradient(64, 64).to_luma8());
+        let build = |grayscale| super::TextureBuilder::new("ester")
+            .grayscale_as_r8(grayscale)
+            .build(gray.clone())
+            .unwrap();
+
+        let (rgba, r8) = (build(false), build(true));
+        assert_eq!(r8.format, R8_UNORM);
+        assert_eq!(r8.comp_sel, 0x0102_0202);
+        assert_eq!(r8.image_size * 4, rgba.image_size);
+        assert_eq!(r8.to_image().unwrap(), gray);
+
+        let color = super::TextureBuilder::new("ester").grayscale_as_r8(true).build(gradient(64, 64));
+        assert_eq!(color.unwrap().format, R8G8B8A8_SRGB);
+    }
+
+    #[test]
+    fn validated_setters() {
+        use super::{Error, TileMode};
+
+        let mut texture = super::Texture::from_image(gradient(64, 64), "ester").unwrap();
+        texture.convert_format(BC1_UNORM).unwrap();
+        let image = texture.to_image().unwrap();
+
+        texture.set_format(BC1_SRGB).unwrap();
+        assert_eq!(texture.to_image().unwrap(), image);
+        texture.set_format(R8G8B8A8_UNORM).unwrap();
+        assert!(texture.raw_data().iter().all(|&byte| byte == 0));
+        texture.set_format(BC1_UNORM).unwrap();
+
+        texture.set_tile_mode(TileMode::Linear).unwrap();
+        assert_eq!(texture.info().tile_mode, TileMode::Linear);
+        texture.set_tile_mode(TileMode::BlockLinear).unwrap();
+
+        assert!(matches!(texture.set_dimensions(30, 32, 1), Err(Error::UnalignedSize { multiple: 4, .. })));
+        assert!(matches!(texture.set_dimensions(32, 32, 2), Err(Error::InvalidDepth { depth: 2 })));
+        assert!(matches!(texture.set_dimensions(0, 32, 1), Err(Error::InvalidDimensions { .. })));
+        assert_eq!(texture.width(), 64);
+
+        texture.set_dimensions(128, 32, 1).unwrap();
+        assert_eq!((texture.width(), texture.height()), (128, 32));
+        assert_eq!(texture.size_mismatches().unwrap(), []);
+        assert_eq!(texture.raw_data().len(), 128 * 32 / 2);
+    }
+
+    #[test]
+    fn strip_mips() {
+        let mut texture = super::TextureBuilder::new("ester")
+            .build_array(vec![gradient(64, 64), gradient(64, 64).fliph()])
+            .unwrap();
+        texture.mips_count = 4;
+        texture.mip_offsets = vec![0; 4];
+        // lays the mips out for real
+        texture.convert_format(super::SurfaceFormat::R8G8B8A8_UNORM).unwrap();
+
+        let layer = texture.deswizzle_surface(1, 0, texture.size_range).unwrap();
+        let mip = texture.deswizzle_surface(1, 1, texture.size_range).unwrap();
+        let old_size = texture.texture.0.len();
+
+        texture.strip_mips(2);
+        assert_eq!(texture.mips_count, 2);
+        assert_eq!(texture.mip_offsets.len(), 2);
+        assert!(texture.texture.0.len() < old_size);
+        assert_eq!(texture.image_size as usize, texture.texture.0.len());
+        assert_eq!(texture.deswizzle_surface(1, 0, texture.size_range).unwrap(), layer);
+        assert_eq!(texture.deswizzle_surface(1, 1, texture.size_range).unwrap(), mip);
+
+        texture.strip_mips(0);
+        assert_eq!(texture.mips_count, 1);
+        assert_eq!(texture.deswizzle_surface(1, 0, texture.size_range).unwrap(), layer);
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn mip_offsets_roundtrip() {
+        let image = image::DynamicImage::ImageRgba8(image::RgbaImage::new(64, 64));
+
+        let mut file = BntxFile::from_image(image, "ester").unwrap();
+        let texture = &mut file.nx_header.textures[0];
+        texture.mips_count = 3;
+        texture.mip_offsets = vec![0, 0x3000, 0x3800];
+
+        let mut data = Cursor::new(Vec::new());
+        file.write(&mut data).unwrap();
+        data.set_position(0);
+
+        let file: BntxFile = data.read_le().unwrap();
+
+        assert_eq!(file.nx_header.textures[0].mip_offsets, [0, 0x3000, 0x3800]);
+    }
+
+    #[test]
+    fn decode_unaligned_size() {
+        use super::SurfaceFormat::*;
+
+        // flat 4x4 areas of black or full channels compress losslessly, so shifted rows or blocks
+        // show up as errors
+        let flat = |width, height| {
+            let image = image::RgbaImage::from_fn(width, height, |x, y| {
+                let (x, y) = (x / 4, y / 4);
+                let full = |on: bool| if on { 255 } else { 0 };
+                image::Rgba([full(x % 2 == 1), full(y % 2 == 1), full((x + y) % 3 == 0), 255])
+            });
+            image::DynamicImage::ImageRgba8(image)
+        };
+
+        for (width, height) in [(13, 7), (5, 3), (30, 18)] {
+            for format in [R8G8B8A8_UNORM, BC1_UNORM, BC3_UNORM] {
+                let mut texture = super::TextureBuilder::new("ester").build(flat(width, height)).unwrap();
+                texture.convert_format(format).unwrap();
+                assert_eq!(texture.to_image().unwrap().into_rgba8(), flat(width, height).into_rgba8());
+            }
+        }
+
+        // mip 1 of a 26x14 texture is 13x7
+        let mip = flat(13, 7);
+        let builder = super::TextureBuilder::new("ester");
+        let mut texture = builder.build_with_mips(flat(26, 14), vec![mip.clone()]).unwrap();
+        texture.convert_format(BC1_UNORM).unwrap();
+        assert_eq!(texture.decode(1, texture.size_range).unwrap().into_rgba8(), mip.into_rgba8());
+    }
+
+    #[test]
+    fn mip_alignment() {
+        let (base, mips) = (gradient(64, 64), vec![gradient(32, 32), gradient(16, 16)]);
+        let mut texture = super::TextureBuilder::new("ester").build_with_mips(base, mips).unwrap();
+        assert_eq!(texture.mip_alignment(), 1);
+        let expected: Vec<_> = (0..3).map(|mip| texture.decode(mip, texture.size_range).unwrap()).collect();
+
+        // moves each mip level to the next 16 KiB boundary
+        let mut ends = texture.mip_offsets[1..].to_vec();
+        ends.push(texture.texture.0.len() as u64);
+        let mut data = Vec::new();
+        let mut mip_offsets = Vec::new();
+        for (&start, &end) in texture.mip_offsets.iter().zip(&ends) {
+            data.resize(data.len().next_multiple_of(0x4000), 0);
+            mip_offsets.push(data.len() as u64);
+            data.extend_from_slice(&texture.texture.0[start as usize..end as usize]);
+        }
+        texture.mip_offsets = mip_offsets;
+        texture.image_size = data.len() as u32;
+        texture.texture = super::ImageData(data);
+
+        assert_eq!(texture.mip_alignment(), 0x4000);
+        assert_eq!(texture.mip_offset(2), Some(0x8000));
+        assert!(texture.size_mismatches().unwrap().is_empty());
+        for (mip, expected) in (0..).zip(&expected) {
+            assert_eq!(&texture.decode(mip, texture.size_range).unwrap(), expected);
+        }
+
+        texture.convert_format(super::SurfaceFormat::B8G8R8A8_UNORM).unwrap();
+        assert_eq!(texture.mip_alignment(), 0x4000);
+        assert!(texture.mip_offsets.iter().all(|offset| offset % 0x4000 == 0));
+        for (mip, expected) in (0..).zip(&expected) {
+            assert_eq!(&texture.decode(mip, texture.size_range).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn decode_all() {
+        let layers: Vec<_> = (0..5).map(|i| gradient(16, 8).huerotate(i * 70)).collect();
+        let texture = super::TextureBuilder::new("ester").build_array(layers.clone()).unwrap();
+
+        let decoded = texture.decode_all().unwrap();
+        assert_eq!(decoded.layers.len(), 5);
+        for (layer, img) in (0..).zip(layers) {
+            assert_eq!(decoded.mip(layer, 0), Some(&img.into_rgba8()));
+        }
+        assert_eq!(decoded.mip(5, 0), None);
+
+        let (base, mips) = (gradient(32, 32), vec![gradient(16, 16), gradient(8, 8)]);
+        let texture = super::TextureBuilder::new("ester").build_with_mips(base, mips.clone()).unwrap();
+        let decoded = texture.decode_all().unwrap();
+        assert_eq!(decoded.layers[0].len(), 3);
+        assert_eq!(decoded.mip(0, 2), Some(&mips[1].to_rgba8()));
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn string_pool_order() {
+        let mut file = BntxFile::from_image(gradient(8, 8), "ester").unwrap();
+        file.push_texture(super::Texture::from_image(gradient(8, 8), "maya").unwrap()).unwrap();
+        file.push_texture(super::Texture::from_image(gradient(8, 8), "Chara").unwrap()).unwrap();
+
+        // the file name is shared with the first texture's
+        let layout = super::layout::Layout::new(&file);
+        let strings: Vec<_> = layout.str_section.strings.iter().map(|string| string.chars.as_str()).collect();
+        assert_eq!(strings, ["Chara", "ester", "maya"]);
+
+        let mut data = Vec::new();
+        file.write(&mut data).unwrap();
+        let read = BntxFile::from_reader(&data[..]).unwrap();
+        for name in ["ester", "maya", "Chara"] {
+            assert_eq!(read.texture(name).unwrap().name(), name);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "preview")]
+    fn preview_buffer() {
+        let mut image = image::RgbaImage::new(16, 1);
+        image.put_pixel(0, 0, image::Rgba([255, 0, 0, 255]));
+        image.put_pixel(1, 0, image::Rgba([0, 0, 255, 128]));
+
+        let buffer = super::preview::to_buffer(&image);
+        assert_eq!(buffer[..2], [0xff0000, 0x6666e6]);
+        // transparent pixels show the checkerboard
+        assert_eq!((buffer[2], buffer[8]), (0xcccccc, 0x888888));
+    }
+
+    #[test]
+    fn swizzled_surface() {
+        use super::{SwizzledSurface, TextureContainer};
+
+        // only uses the trait, as code shared with other containers would
+        fn deswizzle_all<C: TextureContainer>(container: &C, name: &str) -> Vec<Vec<u8>> {
+            let surface = container.surface_by_name(name).unwrap();
+            (0..surface.layer_count())
+                .flat_map(|layer| (0..surface.mip_count()).map(move |mip| (layer, mip)))
+                .map(|(layer, mip)| surface.deswizzled_mip(layer, mip).unwrap())
+                .collect()
+        }
+
+        let (base, mips) = (gradient(32, 32), vec![gradient(16, 16), gradient(8, 8)]);
+        let mut file = BntxFile::new("ester");
+        file.push_texture(super::TextureBuilder::new("ester").build_with_mips(base, mips).unwrap()).unwrap();
+        let layers: Vec<_> = (0..3).map(|i| gradient(16, 8).huerotate(i * 70)).collect();
+        file.push_texture(super::TextureBuilder::new("maya").build_array(layers).unwrap()).unwrap();
+
+        assert_eq!(file.surface_count(), 2);
+        for name in ["ester", "maya"] {
+            let surface = file.texture(name).unwrap().to_surface().unwrap();
+            let expected: Vec<_> = (0..surface.layer_count())
+                .flat_map(|layer| (0..surface.mip_count()).map(move |mip| (layer, mip)))
+                .map(|(layer, mip)| surface.mip(layer, mip).unwrap().to_vec())
+                .collect();
+            assert_eq!(deswizzle_all(&file, name), expected);
+        }
+
+        let maya = file.surface(1).unwrap();
+        assert!(maya.swizzled_mip(3, 0).is_none());
+        assert!(matches!(maya.deswizzled_mip(0, 1), Err(super::Error::MissingMip { level: 1, .. })));
+    }
+
+    #[test]
+    fn color_space() {
+        use super::ColorSpace;
+        use super::SurfaceFormat::{R8G8B8A8_SRGB, R8G8B8A8_UNORM};
+
+        let normal = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(16, 16, |x, y| {
+            image::Rgba([120 + x as u8, 136 - y as u8, 250, 255])
+        }));
+        let texture = super::TextureBuilder::new("ester")
+            .color_space(ColorSpace::Linear)
+            .build(normal.clone())
+            .unwrap();
+        assert_eq!(texture.format, R8G8B8A8_UNORM);
+        // the pixels are stored as they are either way, only how they're sampled changes
+        assert_eq!(texture.to_image().unwrap().to_rgba8(), normal.to_rgba8());
+        assert_eq!(super::Texture::from_image(normal.clone(), "ester").unwrap().format, R8G8B8A8_SRGB);
+
+        assert_eq!(ColorSpace::guess("ester", &normal), ColorSpace::Linear);
+        assert_eq!(ColorSpace::guess("ester", &gradient(16, 16)), ColorSpace::Srgb);
+        assert_eq!(ColorSpace::guess("def_ester_001_PRM", &gradient(16, 16)), ColorSpace::Linear);
+        assert_eq!(ColorSpace::guess("ester_nor2", &gradient(16, 16)), ColorSpace::Linear);
+        assert_eq!(ColorSpace::guess("ester_col", &gradient(16, 16)), ColorSpace::Srgb);
+    }
+
+    #[test]
+    fn normal_map() {
+        use super::{NormalMap, TextureBuilder};
+        use super::SurfaceFormat::{BC5_UNORM, R8G8B8A8_UNORM};
+
+        // stripes of normals tilted left and right, which average to a normal pointing straight
+        // out but only 0.6 long
+        let stripes = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(64, 64, |x, _| {
+            let red = if x % 2 == 0 { 230 } else { 25 };
+            image::Rgba([red, 128, 204, 255])
+        }));
+
+        let texture = TextureBuilder::new("ester_nor")
+            .normal_map(NormalMap::Rgb)
+            .build_with_generated_mips(stripes.clone())
+            .unwrap();
+        assert_eq!((texture.format, texture.mips_count), (R8G8B8A8_UNORM, 7));
+        let mip = texture.decode(1, texture.size_range).unwrap().to_rgba8();
+        let [red, _, blue, _] = mip.get_pixel(8, 8).0;
+        assert!(red.abs_diff(128) <= 2 && blue >= 253, "{:?}", mip.get_pixel(8, 8));
+
+        // without renormalizing, the mips come out shorter
+        let texture = TextureBuilder::new("ester_nor").build_with_generated_mips(stripes.clone()).unwrap();
+        let mip = texture.decode(1, texture.size_range).unwrap().to_rgba8();
+        assert!(mip.get_pixel(8, 8)[2] < 220);
+
+        let texture = TextureBuilder::new("ester_nor")
+            .normal_map(NormalMap::TwoChannel)
+            .build_with_generated_mips(stripes)
+            .unwrap();
+        assert_eq!((texture.format, texture.comp_sel, texture.mips_count), (BC5_UNORM, 0x0101_0302, 7));
+        let mip = texture.decode(1, texture.size_range).unwrap().to_rgba8();
+        assert!(mip.get_pixel(8, 8)[0].abs_diff(128) <= 4);
+    }
+
+    #[test]
+    #[cfg(all(feature = "cli", feature = "write"))]
+    fn inspect() {
+        use super::inspect::{inspect, InspectOptions};
+
+        let mut file = BntxFile::from_image(gradient(64, 64), "ester").unwrap();
+        file.push_texture(super::Texture::from_image(gradient(32, 32), "maya").unwrap()).unwrap();
+        let mut data = Vec::new();
+        file.write(&mut data).unwrap();
+
+        let options = InspectOptions { hexdump: vec!["brti".to_owned()], ..Default::default() };
+        let mut out = Vec::new();
+        inspect(&data, &options, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("  file name   ester\n"));
+        assert!(out.contains("\ntextures (2)\n  ester: 64x64 R8G8B8A8_SRGB"));
+        assert!(out.contains("\nrelocations\n  section 0: 0x00000000"));
+        assert_eq!(out.matches("\nBRTI at ").count(), 2);
+        assert!(out.lines().any(|line| line.ends_with("|BRTI............|  magic, next section, size")));
+
+        // files that don't parse are still listed
+        let mut out = Vec::new();
+        inspect(&data[..0x200], &options, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("failed to parse: "));
+        assert!(out.contains("\nsections\n  BNTX  0x00000000  0x20 bytes\n"));
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn texture_kind() {
+        use super::TextureKind;
+
+        let faces: Vec<_> = (0..6).map(|i| gradient(16, 16).huerotate(i * 60)).collect();
+        let mut cube = super::TextureBuilder::new("ester").build_array(faces).unwrap();
+        assert_eq!(cube.kind(), TextureKind::D2Array);
+        cube.set_kind(TextureKind::Cube).unwrap();
+        assert!(matches!(
+            cube.set_kind(TextureKind::D3),
+            Err(super::Error::InconsistentKind { kind: TextureKind::D3, dim: 2, .. })
+        ));
+
+        let mut file = BntxFile::from_image(gradient(16, 16), "maya").unwrap();
+        file.push_texture(cube).unwrap();
+        let mut data = Vec::new();
+        file.write(&mut data).unwrap();
+        let read = BntxFile::from_reader(&data[..]).unwrap();
+        assert_eq!(read.texture("ester").unwrap().kind(), TextureKind::Cube);
+
+        // a single layer texture claiming to be a cube map is rejected when read
+        let brti = super::SectionIter::new(&data).find(|section| &section.magic == b"BRTI").unwrap();
+        data[brti.offset + 0x5c] = 3;
+        let err = BntxFile::from_reader(&data[..]).unwrap_err();
+        let err = err.root_cause().custom_err::<super::Error>().unwrap();
+        assert!(matches!(err, super::Error::InconsistentKind { kind: TextureKind::Cube, array_len: 1, .. }));
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn linear_pitch() {
+        use super::{ImageData, TileMode};
+
+        // 120 byte rows, which the hardware pads to 128
+        let (base, mip) = (gradient(30, 30), gradient(15, 15));
+        let mut texture = super::TextureBuilder::new("ester")
+            .build_with_mips(base.clone(), vec![mip.clone()])
+            .unwrap();
+        texture.set_tile_mode(TileMode::Linear).unwrap();
+        assert_eq!(texture.pitch_alignment(), 32);
+        assert_eq!(texture.layout().unwrap().pitch(), 128);
+        assert_eq!(texture.mip_offsets, [0, 128 * 30]);
+        assert_eq!(texture.to_image().unwrap(), base);
+
+        // another tool's tightly packed rows are detected from the mip offsets
+        let mut packed = base.to_rgba8().into_raw();
+        packed.extend(mip.to_rgba8().into_raw());
+        packed.resize(packed.len().next_multiple_of(512), 0);
+        texture.mip_offsets = vec![0, 120 * 30];
+        texture.image_size = packed.len() as u32;
+        texture.texture = ImageData(packed);
+        assert_eq!(texture.pitch_alignment(), 32);
+
+        let mut file = BntxFile::new("ester");
+        file.push_texture(texture).unwrap();
+        let mut data = Vec::new();
+        file.write(&mut data).unwrap();
+        let file = BntxFile::from_reader(&data[..]).unwrap();
+        let texture = file.texture("ester").unwrap();
+        assert_eq!(texture.pitch_alignment(), 1);
+        assert_eq!(texture.layout().unwrap().pitch(), 120);
+        let decoded = texture.decode_all().unwrap();
+        assert_eq!(decoded.mip(0, 0), Some(&base.to_rgba8()));
+        assert_eq!(decoded.mip(0, 1), Some(&mip.to_rgba8()));
+    }
+
+    #[test]
+    fn decode_rgba8() {
+        let img = gradient(24, 20);
+        let texture = super::Texture::from_image(img.clone(), "ester").unwrap();
+        let pixels = texture.decode_rgba8().unwrap();
+        assert_eq!(pixels.len(), (texture.width() * texture.height() * 4) as usize);
+        assert_eq!(pixels, img.to_rgba8().into_raw());
+
+        // grayscale formats are expanded the same way as to_rgba8_image
+        let gray = image::GrayImage::from_fn(8, 8, |x, _| image::Luma([x as u8 * 30]));
+        let texture = super::Texture::from_gray_image(gray.clone(), "maya").unwrap();
+        let expected = image::DynamicImage::ImageLuma8(gray).into_rgba8().into_raw();
+        assert_eq!(texture.decode_rgba8().unwrap(), expected);
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn mem_pool_ptr() {
+        use std::convert::TryInto;
+
+        let mem_pool_ptr = |data: &[u8]| u64::from_le_bytes(data[0x40..0x48].try_into().unwrap());
+
+        let mut file = BntxFile::from_image(gradient(16, 16), "ester").unwrap();
+        file.set_mem_pool(vec![0; 0x200]);
+        let mut data = Vec::new();
+        file.write(&mut data).unwrap();
+        assert_eq!(mem_pool_ptr(&data), 0x58);
+
+        // a null pointer stays null, and isn't relocated
+        data[0x40] = 0;
+        let read = BntxFile::from_reader(&data[..]).unwrap();
+        let mut rewritten = Vec::new();
+        read.write(&mut rewritten).unwrap();
+        assert_eq!(mem_pool_ptr(&rewritten), 0);
+        let reloc = super::reloc::RelocationTable::from_file(&mut Cursor::new(&rewritten)).unwrap();
+        assert!(reloc.pointers().all(|ptr| ptr != 0x40));
+        assert!(read.verify_roundtrip().unwrap().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn unparsed_ranges() {
+        let mut data = Vec::new();
+        BntxFile::from_image(gradient(16, 16), "ester").unwrap().write(&mut data).unwrap();
+        assert!(BntxFile::unparsed_ranges_in(&data).is_empty());
+
+        // a payload in the padding before the texture data, and more after the file
+        let brtd = super::SectionIter::new(&data).find(|section| &section.magic == b"BRTD").unwrap().offset;
+        let hidden = brtd - 0x10..brtd - 0xc;
+        data[hidden.clone()].copy_from_slice(b"hide");
+        let end = data.len();
+        data.extend_from_slice(b"trailing");
+
+        assert_eq!(BntxFile::unparsed_ranges_in(&data), [hidden, end..end + 8]);
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn patch_file() {
+        let mut file = BntxFile::from_image(gradient(32, 32), "ester").unwrap();
+        file.push_texture(super::Texture::from_image(gradient(16, 16), "maya").unwrap()).unwrap();
+
+        let path = std::env::temp_dir().join(format!("bntx-patch-{}.bntx", std::process::id()));
+        file.save(&path).unwrap();
+        let before = std::fs::read(&path).unwrap();
+
+        let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(16, 16, |x, y| {
+            image::Rgba([255 - x as u8 * 16, y as u8 * 16, 0x40, 255])
+        }));
+        super::patch_file(&path, "maya", img.clone()).unwrap();
+        let wrong_size = super::patch_file(&path, "maya", gradient(32, 32));
+        let missing = super::patch_file(&path, "lucina", img.clone());
+        let after = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(wrong_size, Err(super::Error::MismatchedSize { .. })));
+        assert!(matches!(missing, Err(super::Error::MissingTexture(_))));
+
+        // only the patched texture's data changed
+        assert_eq!(before.len(), after.len());
+        let changed: Vec<_> = (0..before.len()).filter(|&i| before[i] != after[i]).collect();
+        let patched = BntxFile::from_reader(&after[..]).unwrap();
+        let size = patched.texture("maya").unwrap().raw_data().len();
+        assert!(changed.last().unwrap() - changed.first().unwrap() < size);
+
+        assert_eq!(patched.texture("maya").unwrap().to_image().unwrap(), img);
+        assert_eq!(patched.texture("ester").unwrap().to_image().unwrap(), gradient(32, 32));
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn save_incremental() {
+        let temp = TempFile(std::env::temp_dir().join(format!("bntx-incremental-{}.bntx", std::process::id())));
+        let path = &temp.0;
+        let written = |file: &BntxFile| {
+            let mut data = Vec::new();
+            file.write(&mut data).unwrap();
+            data
+        };
+
+        // a file that wasn't read from `path` is written in full
+        let mut file = BntxFile::from_image(gradient(256, 256), "ester").unwrap();
+        file.push_texture(super::Texture::from_image(gradient(16, 16), "maya").unwrap()).unwrap();
+        let created = file.save_incremental(path).unwrap();
+        assert_eq!(created, written(&file).len() as u64);
+
+        // only the headers and relocation table, out of several hundred KB
+        let mut file = BntxFile::open(path).unwrap();
+        let unchanged = file.save_incremental(path).unwrap();
+        assert!(unchanged < 0x2000, "{:#x}", unchanged);
+        assert_eq!(std::fs::read(path).unwrap(), written(&file));
+
+        let snapshot = file.clone();
+        file.texture_mut("maya").unwrap().replace_image(gradient(16, 16).fliph()).unwrap();
+        let edited = file.save_incremental(path).unwrap();
+        assert!(edited > unchanged && edited < unchanged + 0x1000, "{:#x}", edited);
+        assert_eq!(std::fs::read(path).unwrap(), written(&file));
+
+        // once saved, the edited texture is in place too
+        assert_eq!(file.save_incremental(path).unwrap(), unchanged);
+
+        // a copy can't know what was saved over the file since it was made
+        let mut snapshot = snapshot;
+        assert_eq!(snapshot.save_incremental(path).unwrap(), created);
+        assert_eq!(std::fs::read(path).unwrap(), written(&snapshot));
+
+        // moved data is written again
+        let mut file = BntxFile::open(path).unwrap();
+        file.remove_texture("ester");
+        file.save_incremental(path).unwrap();
+        assert_eq!(std::fs::read(path).unwrap(), written(&file));
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn mem_pool() {
+        use std::convert::TryInto;
+
+        let mem_pool_ptr = |data: &[u8]| u64::from_le_bytes(data[0x40..0x48].try_into().unwrap());
+
+        let mut file = BntxFile::from_image(gradient(16, 16), "ester").unwrap();
+        assert_eq!(file.mem_pool(), [0; 0x150]);
+        assert_eq!(file.mem_pool_info_offset(), Some(0x10));
+
+        // the pool's contents survive a roundtrip
+        let mut pool = vec![0; 0x150];
+        pool[0x20..0x24].copy_from_slice(b"pool");
+        file.set_mem_pool(pool.clone());
+        file.set_mem_pool_info_offset(Some(0x40));
+        let mut data = Vec::new();
+        file.write(&mut data).unwrap();
+        assert_eq!(mem_pool_ptr(&data), 0x48 + 0x40);
+
+        let read = BntxFile::from_reader(&data[..]).unwrap();
+        assert_eq!(read.mem_pool(), pool);
+        assert_eq!(read.mem_pool_info_offset(), Some(0x40));
+        assert!(BntxFile::unparsed_ranges_in(&data).is_empty());
+
+        // without a pool the texture pointers follow the headers
+        file.set_mem_pool(Vec::new());
+        file.set_mem_pool_info_offset(None);
+        let mut data = Vec::new();
+        file.write(&mut data).unwrap();
+        assert_eq!(mem_pool_ptr(&data), 0);
+        assert_eq!(u64::from_le_bytes(data[0x28..0x30].try_into().unwrap()), 0x48);
+
+        let read = BntxFile::from_reader(&data[..]).unwrap();
+        assert!(read.mem_pool().is_empty());
+        assert_eq!(read.texture("ester").unwrap().to_image().unwrap(), gradient(16, 16));
+    }
+
+    #[cfg(all(feature = "tokio", feature = "write"))]
+    #[tokio::test]
+    async fn async_roundtrip() {
+        let mut file = BntxFile::from_image(gradient(64, 32), "ester").unwrap();
+        file.push_texture(super::Texture::from_image(gradient(16, 16), "maya").unwrap()).unwrap();
+
+        let mut data = Vec::new();
+        file.write_async(&mut data).await.unwrap();
+        let mut written = Vec::new();
+        file.write(&mut written).unwrap();
+        assert_eq!(data, written);
+        assert_eq!(BntxFile::read_async(&mut &data[..]).await.unwrap(), file);
+
+        let temp = TempFile(std::env::temp_dir().join(format!("bntx-async-{}.bntx", std::process::id())));
+        file.save_async(&temp.0).await.unwrap();
+        assert_eq!(std::fs::read(&temp.0).unwrap(), data);
+        assert_eq!(BntxFile::open_async(&temp.0).await.unwrap(), file);
+
+        assert!(BntxFile::read_async(&mut &data[..0x20]).await.is_err());
+    }
+
+    #[cfg(all(feature = "tokio", feature = "zstd", feature = "write"))]
+    #[tokio::test]
+    async fn async_zstd_roundtrip() {
+        let file = BntxFile::from_image(gradient(64, 32), "ester").unwrap();
 
-        let tex = BntxFile::from_image(image, "ester");
+        let temp = TempFile(std::env::temp_dir().join(format!("bntx-async-{}.bntx.zs", std::process::id())));
+        file.save_async(&temp.0).await.unwrap();
+        let compressed = std::fs::read(&temp.0).unwrap();
+        assert_eq!(compressed[..4], [0x28, 0xb5, 0x2f, 0xfd]);
 
-        tex.save("test.bntx").unwrap();
+        assert_eq!(BntxFile::open_async(&temp.0).await.unwrap(), file);
+        assert_eq!(BntxFile::read_async(&mut &compressed[..]).await.unwrap(), file);
     }
 }
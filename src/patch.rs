@@ -0,0 +1,174 @@
+//! Replacing a texture's data in a file on disk without laying the rest of the file out again,
+//! and saving over a file without rewriting the data of textures that didn't change
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
+
+use binrw::BinReaderExt;
+
+use crate::layout::Layout;
+use crate::{sections, BntxFile, DataSource, Error, FileSource};
+
+/// Replaces the texture called `name` in the file at `path` with an image, overwriting only
+/// the texture's data in place. Every other byte of the file is left as it is, which makes this
+/// much faster than reading and saving the whole file for large bundles, and keeps anything
+/// this crate doesn't understand intact.
+///
+/// Since the data has to fit where the old data was, the image must be the same size as the
+/// texture. It's encoded in the texture's format, tiling and block height, with each mip level
+/// resized from the image. Only the first array layer is replaced. Compressed files can't be
+/// patched in place, and fail to parse.
+pub fn patch_file<P: AsRef<Path>>(path: P, name: &str, img: image::DynamicImage) -> Result<(), Error> {
+    trace_span!("patch_file", path = %path.as_ref().display(), name);
+
+    let mut file = OpenOptions::new().read(true).write(true).open(path.as_ref())?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+
+    let bntx: BntxFile = Cursor::new(&data).read_le()?;
+    let index = bntx.texture_index(name).ok_or_else(|| Error::MissingTexture(name.to_owned()))?;
+    let mut texture = bntx.nx_header.textures.into_iter().nth(index).unwrap();
+
+    let expected = (texture.width, texture.height);
+    let found = (img.width(), img.height());
+    if found != expected {
+        return Err(Error::MismatchedSize { expected, found });
+    }
+
+    for level in 1..texture.mip_levels() {
+        let width = (texture.width >> level).max(1);
+        let height = (texture.height >> level).max(1);
+        let mip = img.resize_exact(width, height, image::imageops::FilterType::Triangle);
+        texture.replace_mip(level, mip)?;
+    }
+    texture.replace_mip(0, img)?;
+
+    // the data hasn't changed size, so it goes exactly where the old data was
+    let new_data = texture.raw_data();
+    let offset = sections::texture_data_offset(&data, index).unwrap_or(data.len());
+    let available = data.len().saturating_sub(offset);
+    if available < new_data.len() {
+        return Err(Error::MismatchedDataSize { expected: new_data.len(), found: available });
+    }
+
+    file.seek(SeekFrom::Start(offset as u64))?;
+    file.write_all(new_data)?;
+
+    Ok(())
+}
+
+/// How much is buffered before being written, so headers aren't written a field at a time
+const WRITE_BUFFER: usize = 0x10000;
+
+impl BntxFile {
+    /// Saves the file over the one at `path`, the file it was read from, leaving the data of
+    /// textures that haven't changed since it was read where it is. Only the headers, the data
+    /// of changed textures and of any that moved, and the relocation table are written, so
+    /// saving a few edited textures in a large bundle costs little more than writing them.
+    /// The result is exactly what [`save`](BntxFile::save) would write.
+    ///
+    /// Afterwards the file counts as read from `path`, so saving again only writes what changed
+    /// since. If the file at `path` isn't the one read or last saved, such as when it's been
+    /// replaced or doesn't exist yet, everything is written. Only its size and start are
+    /// checked, so it shouldn't be changed by anything else in between.
+    ///
+    /// Returns how many bytes were written. With the `zstd` feature, paths ending in `.zs` are
+    /// compressed and saved in full, returning the compressed size.
+    pub fn save_incremental<P: AsRef<Path>>(&mut self, path: P) -> io::Result<u64> {
+        trace_span!("save_incremental", path = %path.as_ref().display());
+
+        #[cfg(feature = "zstd")]
+        {
+            if path.as_ref().extension() == Some("zs".as_ref()) {
+                self.save(path.as_ref())?;
+                return Ok(std::fs::metadata(path.as_ref())?.len());
+            }
+        }
+
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+
+        let size = file.metadata()?.len();
+        let fingerprint = FileSource::fingerprint(&mut file)?;
+        let source = self.source.as_ref()
+            .filter(|source| (source.size, source.fingerprint) == (size, fingerprint))
+            .map(|source| source.id);
+
+        // data already where it's about to be written is skipped rather than written again
+        let layout = Layout::new(self);
+        let skip = self.textures()
+            .zip(&layout.textures)
+            .filter(|(texture, tex)| {
+                let in_place = source.map(|file| DataSource { file, offset: tex.data as u64 });
+                !tex.shared && in_place.is_some() && texture.source == in_place
+            })
+            .map(|(texture, tex)| tex.data as u64..(tex.data + texture.texture.0.len()) as u64)
+            .collect();
+
+        let mut writer = BufWriter::with_capacity(WRITE_BUFFER, IncrementalWriter {
+            file: &mut file,
+            pos: 0,
+            file_pos: None,
+            skip,
+            written: 0,
+        });
+        self.write(&mut writer)?;
+        let IncrementalWriter { pos, written, .. } = writer.into_inner().map_err(|err| err.into_error())?;
+
+        file.set_len(pos)?;
+        trace_event!(written, size = pos);
+
+        file.seek(SeekFrom::Start(0))?;
+        let source = FileSource::new(pos, FileSource::fingerprint(&mut file)?);
+        for (texture, tex) in self.nx_header.textures.iter_mut().zip(&layout.textures) {
+            texture.source = Some(DataSource { file: source.id, offset: tex.data as u64 });
+        }
+        self.source = Some(source);
+
+        Ok(written)
+    }
+}
+
+/// Writes over an existing file, skipping ranges that already hold what would be written
+struct IncrementalWriter<'a> {
+    file: &'a mut File,
+    /// Where the next write goes
+    pos: u64,
+    /// Where the file's cursor is, if it's known
+    file_pos: Option<u64>,
+    /// Sorted ranges to skip
+    skip: Vec<Range<u64>>,
+    written: u64,
+}
+
+impl Write for IncrementalWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let next_skip = self.skip.iter().find(|range| range.end > self.pos);
+
+        let len = match next_skip {
+            Some(range) if range.start <= self.pos => {
+                let len = buf.len().min((range.end - self.pos) as usize);
+                self.pos += len as u64;
+                return Ok(len);
+            }
+            Some(range) => buf.len().min((range.start - self.pos) as usize),
+            None => buf.len(),
+        };
+
+        if self.file_pos != Some(self.pos) {
+            self.file.seek(SeekFrom::Start(self.pos))?;
+        }
+        self.file.write_all(&buf[..len])?;
+
+        self.pos += len as u64;
+        self.file_pos = Some(self.pos);
+        self.written += len as u64;
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
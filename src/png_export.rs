@@ -0,0 +1,114 @@
+use std::io::Write;
+
+use image::DynamicImage;
+
+use crate::{Error, GammaConversion, SurfaceFormat, Texture};
+
+/// How colors are stored in PNGs written by [`Texture::to_png`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PngColorSpace {
+    /// The decoded values as they are, with no color space information, so image editors
+    /// assume sRGB whatever the texture's format
+    #[default]
+    Untagged,
+    /// Tagged as sRGB with an `sRGB` chunk. Textures in linear formats are converted to sRGB
+    /// first, so they look the same in image editors as in game, and are converted back when
+    /// rebuilt from the PNG with the same setting.
+    Srgb,
+}
+
+impl PngColorSpace {
+    /// Converts values decoded from a texture in `format` to how they're stored in the PNG
+    pub(crate) fn encode(self, image: DynamicImage, format: SurfaceFormat) -> DynamicImage {
+        match self {
+            PngColorSpace::Srgb if !format.is_srgb() => convert(image, GammaConversion::LinearToSrgb),
+            _ => image,
+        }
+    }
+
+    /// Converts values read from a PNG back to how a texture in `format` stores them, undoing
+    /// [`encode`](PngColorSpace::encode)
+    pub(crate) fn decode(self, image: DynamicImage, format: SurfaceFormat) -> DynamicImage {
+        match self {
+            PngColorSpace::Srgb if !format.is_srgb() => convert(image, GammaConversion::SrgbToLinear),
+            _ => image,
+        }
+    }
+}
+
+impl Texture {
+    /// Writes the texture as a PNG, with its colors stored as `color_space` says. Float
+    /// formats are written as 16-bit, clamping HDR values.
+    pub fn to_png<W: Write>(&self, writer: &mut W, color_space: PngColorSpace) -> Result<(), Error> {
+        let image = color_space.encode(png_compatible(self.to_image()?), self.format);
+
+        let (color_type, bit_depth) = match image.color() {
+            image::ColorType::L8 => (png::ColorType::Grayscale, png::BitDepth::Eight),
+            image::ColorType::La8 => (png::ColorType::GrayscaleAlpha, png::BitDepth::Eight),
+            image::ColorType::Rgb8 => (png::ColorType::Rgb, png::BitDepth::Eight),
+            image::ColorType::L16 => (png::ColorType::Grayscale, png::BitDepth::Sixteen),
+            image::ColorType::La16 => (png::ColorType::GrayscaleAlpha, png::BitDepth::Sixteen),
+            image::ColorType::Rgb16 => (png::ColorType::Rgb, png::BitDepth::Sixteen),
+            image::ColorType::Rgba16 => (png::ColorType::Rgba, png::BitDepth::Sixteen),
+            _ => (png::ColorType::Rgba, png::BitDepth::Eight),
+        };
+
+        let mut encoder = png::Encoder::new(writer, image.width(), image.height());
+        encoder.set_color(color_type);
+        encoder.set_depth(bit_depth);
+        if color_space == PngColorSpace::Srgb {
+            encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+        }
+
+        let mut writer = encoder.write_header().map_err(png_error)?;
+        let data = match bit_depth {
+            // PNG stores 16-bit values big endian
+            png::BitDepth::Sixteen => image.as_bytes()
+                .chunks_exact(2)
+                .flat_map(|value| u16::from_ne_bytes([value[0], value[1]]).to_be_bytes())
+                .collect(),
+            _ if color_type == png::ColorType::Rgba => image.into_rgba8().into_raw(),
+            _ => image.into_bytes(),
+        };
+        writer.write_image_data(&data).map_err(png_error)?;
+        writer.finish().map_err(png_error)?;
+
+        Ok(())
+    }
+}
+
+/// PNGs can't store floats, so float images are saved as 16-bit, clamping HDR values
+pub(crate) fn png_compatible(image: DynamicImage) -> DynamicImage {
+    match image {
+        DynamicImage::ImageRgb32F(_) => DynamicImage::ImageRgb16(image.to_rgb16()),
+        DynamicImage::ImageRgba32F(_) => DynamicImage::ImageRgba16(image.to_rgba16()),
+        image => image,
+    }
+}
+
+/// Converts the transfer function of an image's colors, keeping 16-bit images' precision
+fn convert(image: DynamicImage, gamma: GammaConversion) -> DynamicImage {
+    match image {
+        DynamicImage::ImageLuma16(_) | DynamicImage::ImageLumaA16(_)
+            | DynamicImage::ImageRgb16(_) | DynamicImage::ImageRgba16(_) => {
+            let mut pixels = image.into_rgba32f();
+            gamma.apply_float(&mut pixels);
+            DynamicImage::ImageRgba16(DynamicImage::ImageRgba32F(pixels).into_rgba16())
+        }
+        image => {
+            let mut pixels = image.into_rgba8();
+            gamma.apply(&mut pixels);
+            DynamicImage::ImageRgba8(pixels)
+        }
+    }
+}
+
+fn png_error(err: png::EncodingError) -> Error {
+    match err {
+        png::EncodingError::IoError(err) => Error::Io(err),
+        err => Error::Image(image::ImageError::Encoding(image::error::EncodingError::new(
+            image::ImageFormat::Png.into(),
+            err,
+        ))),
+    }
+}
@@ -0,0 +1,53 @@
+use crate::{SizeAlignment, SurfaceFormat};
+
+/// Texture settings for common roles in specific games, so files can be made the way the game
+/// expects without knowing the values involved. See [`BntxFile::from_image_with_preset`].
+/// UI textures are drawn at their original size, so their presets don't add mip levels.
+///
+/// [`BntxFile::from_image_with_preset`]: crate::BntxFile::from_image_with_preset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Preset {
+    /// Smash Ultimate character portraits and icons, the `chara_*` files in `ui/replace/chara`
+    UltimateChara,
+    /// Smash Ultimate stage select previews, the `stage_*` files in `ui/replace/stage`
+    UltimateStagePreview,
+}
+
+/// The identity channel mapping, with each channel read from itself. Each byte selects the
+/// source of a channel, red first: 0 for zero, 1 for one and 2 to 5 for red to alpha.
+pub(crate) const IDENTITY_COMP_SEL: u32 = 0x0504_0302;
+
+/// Red, green and blue all read from red with alpha set to one, so single channel textures
+/// show up as grayscale
+pub(crate) const GRAYSCALE_COMP_SEL: u32 = 0x0102_0202;
+
+/// Red and green read as they are with blue and alpha set to one, for normal maps that only
+/// store X and Y
+pub(crate) const NORMAL_MAP_COMP_SEL: u32 = 0x0101_0302;
+
+impl Preset {
+    /// The format the image is encoded in
+    pub fn format(self) -> SurfaceFormat {
+        match self {
+            Preset::UltimateChara | Preset::UltimateStagePreview => SurfaceFormat::BC7_SRGB,
+        }
+    }
+
+    /// The channel mapping stored in the texture
+    pub fn comp_sel(self) -> u32 {
+        match self {
+            Preset::UltimateChara | Preset::UltimateStagePreview => IDENTITY_COMP_SEL,
+        }
+    }
+
+    /// How images with dimensions the format can't store are handled. Resizing would shift UI
+    /// elements the game lines up by pixel, so they're rejected instead.
+    pub fn size_alignment(self) -> SizeAlignment {
+        let (block_width, _) = self.format().block_dimensions();
+
+        match self {
+            Preset::UltimateChara | Preset::UltimateStagePreview => SizeAlignment::Reject(block_width),
+        }
+    }
+}
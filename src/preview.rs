@@ -0,0 +1,99 @@
+//! A window for viewing textures during development
+
+use minifb::{Key, KeyRepeat, ScaleMode, Window, WindowOptions};
+
+use crate::{DecodedTexture, Error, Texture};
+
+/// The shortest and longest a side of the preview window can start out, in pixels. Smaller and
+/// larger textures are scaled to fit.
+const MIN_WINDOW_SIZE: u32 = 256;
+const MAX_WINDOW_SIZE: u32 = 1024;
+
+/// Size of the squares of the checkerboard shown behind transparent pixels
+const CHECKER_SIZE: u32 = 8;
+
+impl Texture {
+    /// Opens a window showing the decoded texture, and blocks until it's closed. The left and
+    /// right arrow keys switch between mip levels, up and down between array layers, and escape
+    /// closes the window. Transparent pixels are drawn over a checkerboard.
+    pub fn show(&self) -> Result<(), Error> {
+        let decoded = self.decode_all()?;
+        let (width, height) = window_size(self.width, self.height);
+
+        let mut window = Window::new(self.name(), width as usize, height as usize, WindowOptions {
+            resize: true,
+            scale_mode: ScaleMode::AspectRatioStretch,
+            ..WindowOptions::default()
+        })?;
+        window.set_target_fps(60);
+
+        let (mut layer, mut mip) = (0, 0);
+        let mut buffer = Vec::new();
+        let mut shown = None;
+        while window.is_open() && !window.is_key_down(Key::Escape) {
+            let pressed = |key| window.is_key_pressed(key, KeyRepeat::Yes);
+            let mip_count = decoded.layers[layer].len();
+            if pressed(Key::Right) {
+                mip = (mip + 1) % mip_count;
+            } else if pressed(Key::Left) {
+                mip = (mip + mip_count - 1) % mip_count;
+            } else if pressed(Key::Down) {
+                layer = (layer + 1) % decoded.layers.len();
+            } else if pressed(Key::Up) {
+                layer = (layer + decoded.layers.len() - 1) % decoded.layers.len();
+            }
+
+            let image = &decoded.layers[layer][mip];
+            if shown != Some((layer, mip)) {
+                buffer = to_buffer(image);
+                window.set_title(&title(self, &decoded, layer, mip));
+                shown = Some((layer, mip));
+            }
+
+            window.update_with_buffer(&buffer, image.width() as usize, image.height() as usize)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The window's starting size, the texture's size scaled to fit between
+/// [`MIN_WINDOW_SIZE`] and [`MAX_WINDOW_SIZE`] with its aspect ratio kept
+fn window_size(width: u32, height: u32) -> (u32, u32) {
+    let longest = width.max(height) as f32;
+    let scale = (MIN_WINDOW_SIZE as f32 / longest).max(1.0).min(MAX_WINDOW_SIZE as f32 / longest);
+
+    (((width as f32 * scale) as u32).max(1), ((height as f32 * scale) as u32).max(1))
+}
+
+fn title(texture: &Texture, decoded: &DecodedTexture, layer: usize, mip: usize) -> String {
+    let image = &decoded.layers[layer][mip];
+
+    format!(
+        "{} ({:?}) - layer {}/{}, mip {}/{}, {}x{}",
+        texture.name(),
+        texture.format,
+        layer + 1,
+        decoded.layers.len(),
+        mip + 1,
+        decoded.layers[layer].len(),
+        image.width(),
+        image.height(),
+    )
+}
+
+/// Converts an image to the `0RGB` pixels minifb draws, blending it over a checkerboard
+pub(crate) fn to_buffer(image: &image::RgbaImage) -> Vec<u32> {
+    image.enumerate_pixels()
+        .map(|(x, y, pixel)| {
+            let light = (x / CHECKER_SIZE + y / CHECKER_SIZE).is_multiple_of(2);
+            let background = if light { 0xcc } else { 0x88 };
+            let alpha = pixel[3] as u32;
+
+            pixel.0[..3].iter().fold(0, |rgb, &channel| {
+                let blended = (channel as u32 * alpha + background * (255 - alpha) + 127) / 255;
+                (rgb << 8) | blended
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,19 @@
+/// Receives updates on how far along a long operation is, such as for showing a progress bar.
+/// Implemented for closures taking the number of steps finished and the total, so an
+/// `indicatif::ProgressBar` can be driven with
+/// `&|done, total| { bar.set_length(total); bar.set_position(done) }`.
+///
+/// Operations that run on multiple threads call this from each of them.
+pub trait Progress: Sync {
+    /// Called after each step with the number of steps finished so far and the total
+    fn update(&self, done: u64, total: u64);
+}
+
+impl<F: Fn(u64, u64) + Sync> Progress for F {
+    fn update(&self, done: u64, total: u64) {
+        self(done, total)
+    }
+}
+
+/// A [`Progress`] that ignores every update
+pub(crate) const NO_PROGRESS: &dyn Progress = &|_, _| {};
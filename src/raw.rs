@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use crate::{BntxStr, Error, ImageData, Surface, SurfaceFormat, Texture, TextureKind};
+
+/// How the data of a texture exported with [`Texture::export_raw`] is laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawLayout {
+    /// Exactly as stored in the file, swizzled and with any padding
+    Swizzled,
+    /// Deswizzled and tightly packed, in the order described by [`Surface`]. Requires the
+    /// format's block size, so unknown formats can only be exported swizzled.
+    Deswizzled,
+}
+
+impl RawLayout {
+    fn name(self) -> &'static str {
+        match self {
+            RawLayout::Swizzled => "swizzled",
+            RawLayout::Deswizzled => "deswizzled",
+        }
+    }
+}
+
+/// Header fields written to the metadata sidecar, in order. `mip_offsets` and `unk4` are lists.
+pub(crate) const FIELDS: &[&str] = &[
+    "flags", "dim", "tile_mode", "swizzle", "mips_count", "num_multi_sample", "format", "unk2",
+    "width", "height", "depth", "array_len", "size_range", "image_size", "align", "comp_sel", "ty",
+];
+
+impl Texture {
+    /// Writes the texture's data to `data` without decoding it, and its header to `metadata` as
+    /// lines of `key = value` text, so the data can be edited by another tool and brought back
+    /// with [`import_raw`](Texture::import_raw). This works for formats this crate can't
+    /// decode, so they can still be repacked.
+    pub fn export_raw<W: Write, M: Write>(
+        &self,
+        data: &mut W,
+        metadata: &mut M,
+        layout: RawLayout,
+    ) -> Result<(), Error> {
+        match layout {
+            RawLayout::Swizzled => data.write_all(self.raw_data())?,
+            RawLayout::Deswizzled => data.write_all(self.to_surface()?.data())?,
+        }
+
+        writeln!(metadata, "name = {}", self.name())?;
+        writeln!(metadata, "layout = {}", layout.name())?;
+        for (field, value) in FIELDS.iter().zip(self.raw_fields()) {
+            writeln!(metadata, "{} = {:#x}", field, value)?;
+        }
+
+        let list = |values: &mut dyn Iterator<Item = u64>| {
+            values.map(|value| format!("{:#x}", value)).collect::<Vec<_>>().join(", ")
+        };
+        writeln!(metadata, "mip_offsets = {}", list(&mut self.mip_offsets.iter().copied()))?;
+        writeln!(metadata, "unk4 = {}", list(&mut self.unk4.iter().map(|&value| value as u64)))?;
+
+        Ok(())
+    }
+
+    /// Reads a texture written by [`export_raw`](Texture::export_raw). Swizzled data must be
+    /// exactly as long as the header's `image_size` and is kept as it is. Deswizzled data is
+    /// swizzled again with the header's tile mode and block height, and its sizes and offsets
+    /// recomputed.
+    pub fn import_raw<R: Read, M: Read>(data: &mut R, metadata: &mut M) -> Result<Texture, Error> {
+        let mut text = String::new();
+        metadata.read_to_string(&mut text)?;
+
+        let fields: HashMap<_, _> = text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (key, value) = line.split_once('=').ok_or_else(|| {
+                    Error::InvalidMetadata(format!("expected `key = value`, found {:?}", line))
+                })?;
+                Ok((key.trim(), value.trim()))
+            })
+            .collect::<Result<_, Error>>()?;
+
+        let field = |key: &str| fields.get(key)
+            .copied()
+            .ok_or_else(|| Error::InvalidMetadata(format!("missing {}", key)));
+        let mut texture = Texture::header_from_fields(field)?;
+
+        let mut bytes = Vec::new();
+        data.read_to_end(&mut bytes)?;
+
+        match field("layout")? {
+            "swizzled" => texture.set_raw_data(bytes)?,
+            "deswizzled" => {
+                let (format, layers) = (texture.format, texture.array_len.max(1));
+                let surface = Surface::new(
+                    format, texture.width, texture.height, texture.mip_levels(), layers, bytes
+                )?;
+
+                // laid out for the header's tile mode, block height and swizzle, like the original
+                let (params, mip_offsets, layer_size) =
+                    texture.computed_layout(format, texture.size_range)?;
+                let tables = texture.address_tables(&params);
+                let mut data = vec![0; layer_size * layers as usize];
+                for layer in 0..layers {
+                    for (mip, (&offset, table)) in (0..).zip(mip_offsets.iter().zip(&tables)) {
+                        let start = layer as usize * layer_size + offset as usize;
+                        table.swizzle_into(surface.mip(layer, mip).unwrap(), &mut data[start..]);
+                    }
+                }
+
+                texture.size_range = params.block_height.log2();
+                texture.image_size = data.len() as u32;
+                texture.mip_offsets = mip_offsets;
+                texture.set_image_data(data);
+            }
+            layout => return Err(Error::InvalidMetadata(format!("unknown layout {:?}", layout))),
+        }
+
+        Ok(texture)
+    }
+
+    /// A texture with no data, made from the header fields written by
+    /// [`export_raw`](Texture::export_raw) and a `name`, each looked up with `field`
+    pub(crate) fn header_from_fields<'a>(
+        field: impl Fn(&str) -> Result<&'a str, Error>,
+    ) -> Result<Texture, Error> {
+        let list = |key: &str| -> Result<Vec<u64>, Error> {
+            field(key)?.split(',').map(|value| parse_number(key, value)).collect()
+        };
+        let number = |key: &str| parse_number(key, field(key)?);
+
+        let mut unk4 = [0; 6];
+        for (value, parsed) in unk4.iter_mut().zip(list("unk4")?) {
+            *value = parsed as u32;
+        }
+
+        let texture = Texture {
+            flags: number("flags")? as u8,
+            dim: number("dim")? as u8,
+            tile_mode: number("tile_mode")? as u16,
+            swizzle: number("swizzle")? as u16,
+            mips_count: number("mips_count")? as u16,
+            num_multi_sample: number("num_multi_sample")? as u32,
+            format: SurfaceFormat::from_id(number("format")? as u32),
+            unk2: number("unk2")? as u32,
+            width: number("width")? as u32,
+            height: number("height")? as u32,
+            depth: number("depth")? as u32,
+            array_len: number("array_len")? as u32,
+            size_range: number("size_range")? as i32,
+            unk4,
+            image_size: number("image_size")? as u32,
+            align: crate::read_alignment(number("align")? as u32)?,
+            comp_sel: number("comp_sel")? as u32,
+            kind: TextureKind::from_id(number("ty")? as u32),
+            name: BntxStr::try_new(field("name")?)?,
+            mip_offsets: list("mip_offsets")?,
+            texture: ImageData(Vec::new()),
+            source: None,
+            pitch_alignment: crate::LINEAR_PITCH_ALIGN,
+        };
+        texture.kind.check(texture.dim, texture.depth, texture.array_len)?;
+
+        Ok(texture.with_pitch_alignment())
+    }
+
+    /// The values of [`FIELDS`], in the same order
+    pub(crate) fn raw_fields(&self) -> [u64; 17] {
+        [
+            self.flags as u64,
+            self.dim as u64,
+            self.tile_mode as u64,
+            self.swizzle as u64,
+            self.mips_count as u64,
+            self.num_multi_sample as u64,
+            self.format.id() as u64,
+            self.unk2 as u64,
+            self.width as u64,
+            self.height as u64,
+            self.depth as u64,
+            self.array_len as u64,
+            self.size_range as u32 as u64,
+            self.image_size as u64,
+            self.align as u64,
+            self.comp_sel as u64,
+            self.kind.id() as u64,
+        ]
+    }
+}
+
+/// Parses a decimal or `0x` prefixed hexadecimal number
+pub(crate) fn parse_number(key: &str, value: &str) -> Result<u64, Error> {
+    let value = value.trim();
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+
+    parsed.map_err(|_| Error::InvalidMetadata(format!("invalid {} {:?}", key, value)))
+}
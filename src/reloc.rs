@@ -0,0 +1,224 @@
+//! The relocation table (`_RLT` section) at the end of a BNTX file, which lists the position of
+//! every pointer in the file so the game can turn them from offsets into addresses when loading.
+//!
+//! [`BntxFile`](crate::BntxFile) regenerates the table whenever it's written, so this is only
+//! needed to inspect a file's table, or to repair one written by another tool with
+//! [`RelocationTable::from_pointers`].
+
+use std::convert::{TryFrom, TryInto};
+#[cfg(feature = "write")]
+use std::mem::size_of;
+
+use binrw::prelude::*;
+use binrw::io::{Read, Seek, SeekFrom};
+use binrw::Endian;
+
+use crate::DATA_PTR_SIZE;
+
+/// Offset of the relocation table's position in the BNTX header
+const RELOC_TABLE_PTR_OFFSET: u64 = 0x18;
+
+/// Offset of the byte order mark in the BNTX header
+const BOM_OFFSET: u64 = 0xC;
+
+/// A region of the file that pointers point into, along with the entries listing those
+/// pointers
+#[derive(BinRead, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "write", derive(BinWrite))]
+pub struct RelocationSection {
+    /// Where the region is loaded at runtime, always 0 in the file
+    pub pointer: u64,
+    /// Offset of the region from the start of the file
+    pub position: u32,
+    pub size: u32,
+    /// Index of the section's first entry in [`RelocationTable::entries`]
+    pub index: u32,
+    /// Number of entries belonging to the section
+    pub count: u32,
+}
+
+#[cfg(feature = "write")]
+const SIZE_OF_RELOC_SECTION: usize = size_of::<u64>() + (size_of::<u32>() * 4);
+
+/// A run of pointers: `struct_count` groups of `offset_count` consecutive pointers, each group
+/// followed by `padding_count` pointer-sized gaps
+#[derive(BinRead, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "write", derive(BinWrite))]
+pub struct RelocationEntry {
+    /// Offset of the first pointer from the start of the file
+    pub position: u32,
+    pub struct_count: u16,
+    pub offset_count: u8,
+    pub padding_count: u8,
+}
+
+#[cfg(feature = "write")]
+const SIZE_OF_RELOC_ENTRY: usize = size_of::<u32>() + size_of::<u16>() + (size_of::<u8>() * 2);
+
+/// The `_RLT` section. Written with the position it's written at, which the section stores.
+#[cfg_attr(feature = "write", binrw::binrw)]
+#[cfg_attr(not(feature = "write"), binrw::binread)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[brw(magic = b"_RLT")]
+#[bw(import(position: u32))]
+pub struct RelocationTable {
+    #[br(temp)]
+    #[bw(calc = position)]
+    rlt_section_pos: u32,
+
+    #[br(temp)]
+    #[bw(calc = sections.len() as u32)]
+    count: u32,
+
+    #[brw(pad_before = 4)]
+    #[br(count = count)]
+    pub sections: Vec<RelocationSection>,
+
+    #[br(count = sections.iter().map(|x| x.count).sum::<u32>())]
+    pub entries: Vec<RelocationEntry>,
+}
+
+impl RelocationTable {
+    /// Reads the relocation table of a BNTX file, finding it through the file's header
+    pub fn from_file<R: Read + Seek>(reader: &mut R) -> BinResult<Self> {
+        reader.seek(SeekFrom::Start(BOM_OFFSET))?;
+        let endian = match reader.read_be::<u16>()? {
+            0xFEFF => Endian::Big,
+            _ => Endian::Little,
+        };
+
+        reader.seek(SeekFrom::Start(RELOC_TABLE_PTR_OFFSET))?;
+        let offset: u32 = reader.read_type(endian)?;
+        reader.seek(SeekFrom::Start(offset as u64))?;
+
+        reader.read_type(endian)
+    }
+
+    /// Builds a relocation table from the positions of every pointer in the file, grouped by
+    /// the region (position, size) of the file the pointers point into
+    pub fn from_pointers(regions: &[(usize, usize, Vec<usize>)]) -> Self {
+        let mut sections = Vec::with_capacity(regions.len());
+        let mut entries = Vec::new();
+
+        for (position, size, pointers) in regions {
+            let index = entries.len();
+            entries.extend(RelocationEntry::from_pointers(pointers.clone()));
+
+            sections.push(RelocationSection {
+                pointer: 0,
+                position: *position as u32,
+                size: *size as u32,
+                index: index as u32,
+                count: (entries.len() - index) as u32,
+            });
+        }
+
+        RelocationTable { sections, entries }
+    }
+
+    /// The entries belonging to a section, or an empty slice if the section's range is invalid
+    pub fn section_entries(&self, section: &RelocationSection) -> &[RelocationEntry] {
+        let start = section.index as usize;
+
+        self.entries.get(start..start + section.count as usize).unwrap_or_default()
+    }
+
+    /// The position of every pointer the table lists, section by section
+    pub fn pointers(&self) -> impl Iterator<Item = usize> + '_ {
+        self.entries.iter().flat_map(RelocationEntry::pointers)
+    }
+
+    /// Pairs the position of every pointer the table lists with the offset it points to, read
+    /// from `data`, the little endian file the table belongs to. Pointers past the end of the
+    /// data are skipped.
+    pub fn resolve(&self, data: &[u8]) -> Vec<(usize, u64)> {
+        self.pointers()
+            .filter_map(|position| {
+                let bytes = data.get(position..position + DATA_PTR_SIZE)?;
+
+                Some((position, u64::from_le_bytes(bytes.try_into().unwrap())))
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "write")]
+    pub(crate) fn get_size(&self) -> usize {
+        b"_RLT".len() +
+        size_of::<u32>() +
+        size_of::<u32>() +
+        size_of::<u32>() +
+        (self.sections.len() * SIZE_OF_RELOC_SECTION) +
+        (self.entries.len() * SIZE_OF_RELOC_ENTRY)
+    }
+}
+
+impl RelocationEntry {
+    /// The position of every pointer the entry covers
+    pub fn pointers(&self) -> impl Iterator<Item = usize> {
+        let (start, offset_count) = (self.position as usize, self.offset_count as usize);
+        let stride = (offset_count + self.padding_count as usize) * DATA_PTR_SIZE;
+
+        (0..self.struct_count as usize).flat_map(move |i| {
+            (0..offset_count).map(move |j| start + (i * stride) + (j * DATA_PTR_SIZE))
+        })
+    }
+
+    /// Packs a list of pointer positions into entries, each entry covering `struct_count` runs
+    /// of `offset_count` consecutive pointers spaced `padding_count` pointers apart
+    pub fn from_pointers(mut pointers: Vec<usize>) -> Vec<Self> {
+        pointers.sort_unstable();
+        pointers.dedup();
+
+        let mut entries = Vec::new();
+        let mut i = 0;
+        while i < pointers.len() {
+            let start = pointers[i];
+
+            let mut offset_count = 1;
+            while i + offset_count < pointers.len()
+                && offset_count < u8::MAX as usize
+                && pointers[i + offset_count] == start + offset_count * DATA_PTR_SIZE
+            {
+                offset_count += 1;
+            }
+
+            let mut next = i + offset_count;
+            let mut struct_count = 1;
+            let mut padding_count = 0;
+
+            if let Some(&next_start) = pointers.get(next) {
+                let run_end = start + offset_count * DATA_PTR_SIZE;
+                let padding = (next_start - run_end) / DATA_PTR_SIZE;
+
+                if (next_start - run_end).is_multiple_of(DATA_PTR_SIZE) && padding <= u8::MAX as usize {
+                    let stride = (offset_count + padding) * DATA_PTR_SIZE;
+
+                    while struct_count < u16::MAX as usize
+                        && next + offset_count <= pointers.len()
+                        && (0..offset_count).all(|j| {
+                            pointers[next + j] == start + (stride * struct_count) + (j * DATA_PTR_SIZE)
+                        })
+                    {
+                        struct_count += 1;
+                        next += offset_count;
+                    }
+
+                    if struct_count > 1 {
+                        padding_count = padding;
+                    }
+                }
+            }
+
+            entries.push(RelocationEntry {
+                position: start as u32,
+                struct_count: struct_count as u16,
+                offset_count: offset_count as u8,
+                padding_count: padding_count as u8,
+            });
+
+            i = next;
+        }
+
+        entries
+    }
+}
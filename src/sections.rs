@@ -0,0 +1,278 @@
+use std::convert::TryInto;
+use std::ops::Range;
+
+use crate::{BNTX_HEADER_SIZE, HEADER_SIZE, NX_HEADER_SIZE};
+
+/// Offsets of the fields of the BNTX header that lead to the other sections
+const BOM_OFFSET: usize = 0xC;
+const FIRST_BLOCK_OFFSET: usize = 0x16;
+const RELOC_TABLE_OFFSET: usize = 0x18;
+
+/// Offsets of the fields of the NX header that lead to the textures and other sections
+const TEXTURE_COUNT_OFFSET: usize = 0x4;
+const INFO_PTR_ARRAY_OFFSET: usize = 0x8;
+const DATA_BLOCK_PTR_OFFSET: usize = 0x10;
+const DICT_PTR_OFFSET: usize = 0x18;
+
+/// Offsets of the fields of a BRTI that lead to the rest of the texture
+const BRTI_MIP_COUNT_OFFSET: usize = 0x16;
+const BRTI_IMAGE_SIZE_OFFSET: usize = 0x50;
+const BRTI_MIP_TABLE_OFFSET: usize = 0x70;
+const BRTI_GFX_OBJECT_OFFSETS: [usize; 2] = [0x80, 0x88];
+const BRTI_SIZE: usize = 0xA0;
+const GFX_OBJECT_SIZE: usize = 0x100;
+const BRTD_HEADER_SIZE: usize = 0x10;
+const DATA_PTR_SIZE: usize = 8;
+
+const DICT_HEADER_SIZE: usize = 8;
+const DICT_NODE_SIZE: usize = 0x10;
+const RELOC_HEADER_SIZE: usize = 0x10;
+const RELOC_SECTION_SIZE: usize = 0x18;
+const RELOC_ENTRY_SIZE: usize = 8;
+
+/// A section of a BNTX file found by [`SectionIter`], without its contents interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawSection {
+    /// The section's magic, such as `BRTI` or `_STR`. Sections this crate doesn't know are
+    /// still found if they're linked to the others, and have whatever magic they were given.
+    pub magic: [u8; 4],
+    pub offset: usize,
+    pub size: usize,
+}
+
+impl RawSection {
+    /// The magic as text, with any bytes that aren't printable ASCII replaced
+    pub fn name(&self) -> String {
+        self.magic.iter()
+            .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+            .collect()
+    }
+}
+
+/// Walks the sections of a BNTX file in order of their offsets, following the header's pointers
+/// and the chain of blocks linked from the string pool, without parsing the sections
+/// themselves. Useful for exploring files that don't parse, or that have sections this crate
+/// doesn't know.
+///
+/// Pointers that lead outside the data, or blocks that don't link forward, end the walk early
+/// rather than failing, so truncated or damaged files yield as many sections as can be found.
+#[derive(Debug, Clone)]
+pub struct SectionIter {
+    sections: std::vec::IntoIter<RawSection>,
+}
+
+impl SectionIter {
+    pub fn new(data: &[u8]) -> Self {
+        let reader = SectionReader::new(data);
+
+        let mut sections = reader.sections();
+        sections.sort_by_key(|section| section.offset);
+
+        SectionIter { sections: sections.into_iter() }
+    }
+}
+
+impl Iterator for SectionIter {
+    type Item = RawSection;
+
+    fn next(&mut self) -> Option<RawSection> {
+        self.sections.next()
+    }
+}
+
+/// Byte ranges of `data`, a whole BNTX file, that no known section or texture covers, with the
+/// zeros around them trimmed so alignment padding isn't reported
+pub(crate) fn unparsed_ranges(data: &[u8]) -> Vec<Range<usize>> {
+    let reader = SectionReader::new(data);
+
+    let mut known = reader.known_ranges();
+    known.sort_by_key(|range| range.start);
+
+    let mut gaps = Vec::new();
+    let mut pos = 0;
+    for range in known.into_iter().chain(std::iter::once(data.len()..data.len())) {
+        let start = range.start.min(data.len());
+        if start > pos {
+            gaps.push(pos..start);
+        }
+        pos = pos.max(range.end.min(data.len()));
+    }
+
+    gaps.into_iter()
+        .filter_map(|gap| {
+            let bytes = &data[gap.clone()];
+            let start = bytes.iter().position(|&byte| byte != 0)?;
+            let end = bytes.iter().rposition(|&byte| byte != 0)? + 1;
+
+            Some(gap.start + start..gap.start + end)
+        })
+        .collect()
+}
+
+/// Offset of the data of the texture at `index` in `data`, a whole BNTX file
+#[cfg(feature = "write")]
+pub(crate) fn texture_data_offset(data: &[u8], index: usize) -> Option<usize> {
+    let reader = SectionReader::new(data);
+
+    let info_ptrs = reader.u64(BNTX_HEADER_SIZE + INFO_PTR_ARRAY_OFFSET)? as usize;
+    let brti = reader.u64(info_ptrs.checked_add(index.checked_mul(DATA_PTR_SIZE)?)?)? as usize;
+    let mip_table = reader.u64(brti.checked_add(BRTI_MIP_TABLE_OFFSET)?)? as usize;
+
+    reader.u64(mip_table).map(|ptr| ptr as usize)
+}
+
+/// `len` bytes from `start`, clamped rather than overflowing for pointers from damaged files
+fn span(start: usize, len: usize) -> Range<usize> {
+    start..start.saturating_add(len)
+}
+
+struct SectionReader<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> SectionReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        SectionReader {
+            data,
+            big_endian: data.get(BOM_OFFSET..BOM_OFFSET + 2) == Some(&[0xfe, 0xff]),
+        }
+    }
+
+    fn sections(&self) -> Vec<RawSection> {
+        let mut sections = Vec::new();
+        if self.data.get(..4) != Some(b"BNTX") {
+            return sections;
+        }
+
+        sections.push(RawSection { magic: *b"BNTX", offset: 0, size: BNTX_HEADER_SIZE });
+        if let Some(magic) = self.magic(BNTX_HEADER_SIZE) {
+            sections.push(RawSection { magic, offset: BNTX_HEADER_SIZE, size: NX_HEADER_SIZE });
+        }
+
+        // every block starts with its magic, the offset of the next block relative to its own
+        // start (0 for the last), and its size
+        let mut offset = self.u16(FIRST_BLOCK_OFFSET).map(usize::from);
+        while let Some(block) = offset.filter(|&offset| offset != 0) {
+            let (Some(magic), Some(next), Some(size)) =
+                (self.magic(block), self.u32(block + 4), self.u32(block + 8))
+            else {
+                break;
+            };
+
+            sections.push(RawSection { magic, offset: block, size: size as usize });
+            offset = (next != 0).then(|| block + next as usize);
+        }
+
+        let dict = self.u64(BNTX_HEADER_SIZE + DICT_PTR_OFFSET).map(|ptr| ptr as usize);
+        if let Some((dict, count)) = dict.and_then(|dict| Some((dict, self.u32(dict + 4)?))) {
+            if self.magic(dict) == Some(*b"_DIC") {
+                // the root node isn't counted
+                let size = DICT_HEADER_SIZE + (count as usize + 1) * DICT_NODE_SIZE;
+                sections.push(RawSection { magic: *b"_DIC", offset: dict, size });
+            }
+        }
+
+        let reloc = self.u32(RELOC_TABLE_OFFSET).filter(|&ptr| ptr != 0).map(|ptr| ptr as usize);
+        if let Some(reloc) = reloc {
+            if let (Some(magic), Some(count)) = (self.magic(reloc), self.u32(reloc + 8)) {
+                let table = reloc + RELOC_HEADER_SIZE;
+                // stops at the end of the data, so a corrupt count doesn't loop billions of times
+                let entries: usize = (0..count as usize)
+                    .map_while(|i| self.u32(table + i * RELOC_SECTION_SIZE + 0x14))
+                    .map(|count| count as usize)
+                    .sum();
+
+                let size = RELOC_HEADER_SIZE
+                    + count as usize * RELOC_SECTION_SIZE
+                    + entries * RELOC_ENTRY_SIZE;
+                sections.push(RawSection { magic, offset: reloc, size });
+            }
+        }
+
+        sections
+    }
+
+    /// Every range of the file something is read from: the sections, the memory pool, and each
+    /// texture's header, mip table, runtime objects and data. The data block only counts its
+    /// header, since the space between textures' data is padding.
+    fn known_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<_> = self.sections()
+            .into_iter()
+            .filter(|section| &section.magic != b"BRTI" && &section.magic != b"BRTD")
+            .map(|section| span(section.offset, section.size))
+            .collect();
+
+        let (Some(count), Some(info_ptrs)) = (
+            self.u32(BNTX_HEADER_SIZE + TEXTURE_COUNT_OFFSET),
+            self.u64(BNTX_HEADER_SIZE + INFO_PTR_ARRAY_OFFSET),
+        ) else {
+            return ranges;
+        };
+        let (count, info_ptrs) = (count as usize, info_ptrs as usize);
+
+        // the memory pool fills the space between the headers and the texture pointers
+        let other_sections = [DATA_BLOCK_PTR_OFFSET, DICT_PTR_OFFSET]
+            .map(|offset| self.u64(BNTX_HEADER_SIZE + offset).unwrap_or(0) as usize);
+        if info_ptrs >= HEADER_SIZE && other_sections.iter().all(|&ptr| ptr == 0 || ptr > info_ptrs) {
+            ranges.push(HEADER_SIZE..info_ptrs);
+        }
+        ranges.push(span(info_ptrs, count.saturating_mul(DATA_PTR_SIZE)));
+
+        if let Some(brtd) = self.u64(BNTX_HEADER_SIZE + DATA_BLOCK_PTR_OFFSET).filter(|&ptr| ptr != 0) {
+            ranges.push(span(brtd as usize, BRTD_HEADER_SIZE));
+        }
+
+        for i in 0..count {
+            let Some(brti) = self.u64(info_ptrs + i * DATA_PTR_SIZE).map(|ptr| ptr as usize) else {
+                break;
+            };
+            ranges.push(span(brti, BRTI_SIZE));
+
+            for offset in BRTI_GFX_OBJECT_OFFSETS {
+                if let Some(ptr) = self.u64(brti + offset).filter(|&ptr| ptr != 0) {
+                    ranges.push(span(ptr as usize, GFX_OBJECT_SIZE));
+                }
+            }
+
+            let (Some(mips), Some(mip_table), Some(image_size)) = (
+                self.u16(brti + BRTI_MIP_COUNT_OFFSET),
+                self.u64(brti + BRTI_MIP_TABLE_OFFSET),
+                self.u32(brti + BRTI_IMAGE_SIZE_OFFSET),
+            ) else {
+                continue;
+            };
+
+            let mip_table = mip_table as usize;
+            ranges.push(span(mip_table, usize::from(mips.max(1)) * DATA_PTR_SIZE));
+            if let Some(data) = self.u64(mip_table) {
+                ranges.push(span(data as usize, image_size as usize));
+            }
+        }
+
+        ranges
+    }
+
+    fn bytes<const N: usize>(&self, offset: usize) -> Option<[u8; N]> {
+        self.data.get(offset..offset.checked_add(N)?)?.try_into().ok()
+    }
+
+    fn magic(&self, offset: usize) -> Option<[u8; 4]> {
+        self.bytes(offset)
+    }
+
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes = self.bytes(offset)?;
+        Some(if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.bytes(offset)?;
+        Some(if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+
+    fn u64(&self, offset: usize) -> Option<u64> {
+        let bytes = self.bytes(offset)?;
+        Some(if self.big_endian { u64::from_be_bytes(bytes) } else { u64::from_le_bytes(bytes) })
+    }
+}
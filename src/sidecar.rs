@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use crate::raw::{parse_number, FIELDS};
+use crate::{BntxFile, Error, PngColorSpace, Target, Texture};
+
+impl BntxFile {
+    /// Writes everything about a texture except its pixels to `writer` as TOML, usually saved
+    /// as a `.bntx.toml` file next to the extracted image: its format, mip layout, swizzle,
+    /// channel mapping, alignment and the rest of its header, along with the file's name and
+    /// version. [`from_image_with_sidecar`](BntxFile::from_image_with_sidecar) rebuilds the
+    /// file from an edited copy of the image with exactly the same parameters.
+    pub fn write_sidecar<W: Write>(&self, name: &str, writer: &mut W) -> Result<(), Error> {
+        let texture = self.texture(name).ok_or_else(|| Error::MissingTexture(name.to_owned()))?;
+        let target = self.target();
+        let list = |values: &mut dyn Iterator<Item = u64>| {
+            values.map(|value| format!("{:#x}", value)).collect::<Vec<_>>().join(", ")
+        };
+
+        let file_name = String::from(self.header.inner.file_name.clone());
+        writeln!(writer, "file_name = {}", toml_string(&file_name))?;
+        writeln!(writer, "version = [{:#x}, {:#x}]", target.version.0, target.version.1)?;
+        writeln!(writer, "revision = {:#x}", target.revision)?;
+        writeln!(writer)?;
+
+        writeln!(writer, "name = {}", toml_string(texture.name()))?;
+        for (field, value) in FIELDS.iter().zip(texture.raw_fields()) {
+            writeln!(writer, "{} = {:#x}", field, value)?;
+        }
+        writeln!(writer, "mip_offsets = [{}]", list(&mut texture.mip_offsets.iter().copied()))?;
+        writeln!(writer, "unk4 = [{}]", list(&mut texture.unk4.iter().map(|&value| value as u64)))?;
+
+        Ok(())
+    }
+
+    /// Creates a file with a single texture from an image and a sidecar written by
+    /// [`write_sidecar`](BntxFile::write_sidecar), keeping every parameter the sidecar lists.
+    /// The image must be the size the sidecar gives, and replaces every mip level of the first
+    /// array layer as in [`Texture::replace_image`]. Any other layers are left blank.
+    pub fn from_image_with_sidecar<R: Read>(img: image::DynamicImage, sidecar: &mut R) -> Result<Self, Error> {
+        Self::from_png_with_sidecar(img, sidecar, PngColorSpace::Untagged)
+    }
+
+    /// Like [`from_image_with_sidecar`](BntxFile::from_image_with_sidecar), for an image from a
+    /// PNG written with `color_space`
+    pub(crate) fn from_png_with_sidecar<R: Read>(
+        img: image::DynamicImage,
+        sidecar: &mut R,
+        color_space: PngColorSpace,
+    ) -> Result<Self, Error> {
+        let mut text = String::new();
+        sidecar.read_to_string(&mut text)?;
+
+        let fields: HashMap<_, _> = text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (key, value) = line.split_once('=').ok_or_else(|| {
+                    Error::InvalidMetadata(format!("expected `key = value`, found {:?}", line))
+                })?;
+                Ok((key.trim(), toml_value(value.trim())?))
+            })
+            .collect::<Result<_, Error>>()?;
+
+        let field = |key: &str| fields.get(key)
+            .map(String::as_str)
+            .ok_or_else(|| Error::InvalidMetadata(format!("missing {}", key)));
+
+        let version = field("version")?
+            .split(',')
+            .map(|value| parse_number("version", value).map(|value| value as u16))
+            .collect::<Result<Vec<_>, _>>()?;
+        let target = match version[..] {
+            [minor, major] => Target {
+                version: (minor, major),
+                revision: parse_number("revision", field("revision")?)? as u16,
+            },
+            _ => return Err(Error::InvalidMetadata(format!("invalid version {:?}", field("version")?))),
+        };
+
+        let mut texture = Texture::header_from_fields(field)?;
+        texture.set_image_data(vec![0; texture.image_size as usize]);
+        texture.replace_image(color_space.decode(img, texture.format))?;
+
+        let mut file = BntxFile::with_target(field("file_name")?, target);
+        file.push_texture(texture)?;
+
+        Ok(file)
+    }
+}
+
+/// Quotes a string as a TOML basic string
+fn toml_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The contents of a TOML string, with its escapes undone, or the items of an array separated
+/// by commas, or any other value as it is
+fn toml_value(value: &str) -> Result<String, Error> {
+    if let Some(quoted) = value.strip_prefix('"') {
+        let quoted = quoted.strip_suffix('"')
+            .ok_or_else(|| Error::InvalidMetadata(format!("unterminated string {:?}", value)))?;
+
+        let mut unescaped = String::new();
+        let mut chars = quoted.chars();
+        while let Some(c) = chars.next() {
+            unescaped.push(if c == '\\' { chars.next().unwrap_or('\\') } else { c });
+        }
+
+        Ok(unescaped)
+    } else if let Some(items) = value.strip_prefix('[').and_then(|value| value.strip_suffix(']')) {
+        Ok(items.trim().trim_end_matches(',').to_owned())
+    } else {
+        Ok(value.to_owned())
+    }
+}
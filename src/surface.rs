@@ -0,0 +1,185 @@
+use std::ops::Range;
+
+use crate::progress::NO_PROGRESS;
+use crate::tegra_swizzle::Backend;
+use crate::{Error, Progress, SurfaceFormat, Texture};
+
+/// A texture's data without any of the BNTX file's layout: every mip level of every array layer,
+/// deswizzled and tightly packed, layer by layer with the largest mip first. This is the same
+/// order DDS files use.
+///
+/// Surfaces can be edited freely and turned back into a [`Texture`] with
+/// [`Texture::from_surface`], which takes care of swizzling and the texture's offsets and sizes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Surface {
+    format: SurfaceFormat,
+    width: u32,
+    height: u32,
+    mips: u32,
+    layers: u32,
+    data: Vec<u8>,
+}
+
+impl Surface {
+    /// Creates a surface from already deswizzled data, which must be exactly as long as the
+    /// format, size, mip count and layer count require
+    pub fn new(
+        format: SurfaceFormat,
+        width: u32,
+        height: u32,
+        mips: u32,
+        layers: u32,
+        data: Vec<u8>,
+    ) -> Result<Self, Error> {
+        let mut surface = Surface::zeroed(format, width, height, mips, layers)?;
+        if data.len() != surface.data.len() {
+            return Err(Error::MismatchedDataSize { expected: surface.data.len(), found: data.len() });
+        }
+
+        surface.data = data;
+
+        Ok(surface)
+    }
+
+    /// Creates a surface with every byte set to zero
+    pub fn zeroed(format: SurfaceFormat, width: u32, height: u32, mips: u32, layers: u32) -> Result<Self, Error> {
+        format.bytes_per_block().ok_or(Error::UnsupportedFormat(format))?;
+
+        let mut surface = Surface {
+            format,
+            width,
+            height,
+            mips: mips.max(1),
+            layers: layers.max(1),
+            data: Vec::new(),
+        };
+        surface.data = vec![0; surface.layer_size() * surface.layers as usize];
+
+        Ok(surface)
+    }
+
+    pub fn format(&self) -> SurfaceFormat {
+        self.format
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn mip_count(&self) -> u32 {
+        self.mips
+    }
+
+    pub fn layer_count(&self) -> u32 {
+        self.layers
+    }
+
+    /// Every mip level of every layer, as described in [`Surface`]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Mutable access to the data, which can be changed but not resized
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// The data of a single mip level of a single array layer, or `None` if it doesn't exist
+    pub fn mip(&self, layer: u32, mip: u32) -> Option<&[u8]> {
+        self.mip_range(layer, mip).map(|range| &self.data[range])
+    }
+
+    pub fn mip_mut(&mut self, layer: u32, mip: u32) -> Option<&mut [u8]> {
+        self.mip_range(layer, mip).map(move |range| &mut self.data[range])
+    }
+
+    fn mip_dimensions(&self, mip: u32) -> (u32, u32) {
+        ((self.width >> mip).max(1), (self.height >> mip).max(1))
+    }
+
+    fn mip_size(&self, mip: u32) -> usize {
+        let (width, height) = self.mip_dimensions(mip);
+        let (block_width, block_height) = self.format.block_dimensions();
+        let bytes_per_block = self.format.bytes_per_block().unwrap_or(0);
+
+        width.div_ceil(block_width) as usize
+            * height.div_ceil(block_height) as usize
+            * bytes_per_block as usize
+    }
+
+    fn layer_size(&self) -> usize {
+        (0..self.mips).map(|mip| self.mip_size(mip)).sum()
+    }
+
+    fn mip_range(&self, layer: u32, mip: u32) -> Option<Range<usize>> {
+        if layer >= self.layers || mip >= self.mips {
+            return None;
+        }
+
+        let start = layer as usize * self.layer_size()
+            + (0..mip).map(|mip| self.mip_size(mip)).sum::<usize>();
+
+        Some(start..start + self.mip_size(mip))
+    }
+}
+
+impl Texture {
+    /// Deswizzles every mip level and array layer of the texture into a [`Surface`]
+    pub fn to_surface(&self) -> Result<Surface, Error> {
+        self.to_surface_with_progress(NO_PROGRESS)
+    }
+
+    /// Like [`to_surface`](Texture::to_surface), reporting progress after each mip level of
+    /// each array layer
+    pub fn to_surface_with_progress(&self, progress: &dyn Progress) -> Result<Surface, Error> {
+        self.to_surface_with_backend(&Backend::Cpu, progress)
+    }
+
+    /// Like [`to_surface_with_progress`](Texture::to_surface_with_progress), deswizzling with
+    /// the given backend
+    pub fn to_surface_with_backend(&self, backend: &Backend, progress: &dyn Progress) -> Result<Surface, Error> {
+        let params = self.surface_params(self.format, self.size_range)?;
+        let total = (self.array_len.max(1) * self.mip_levels()) as u64;
+
+        let tables = self.address_tables(&params);
+
+        let mut data = Vec::with_capacity(self.texture.0.len());
+        for layer in 0..self.array_len.max(1) {
+            for (mip, table) in (0..).zip(&tables) {
+                let surface = self.swizzled_surface(layer, mip, table.params());
+                data.extend(backend.deswizzle_with_table(table, &surface));
+                progress.update((layer * self.mip_levels() + mip + 1) as u64, total);
+            }
+        }
+
+        Surface::new(self.format, self.width, self.height, self.mip_levels(), self.array_len, data)
+    }
+
+    /// Creates a 2D texture, or texture array if the surface has more than one layer, laying out
+    /// the surface's data the way the hardware expects
+    pub fn from_surface(name: &str, surface: &Surface) -> Result<Texture, Error> {
+        let mut texture = Texture::blank(
+            name, surface.format, surface.width, surface.height, surface.mips, surface.layers
+        )?;
+
+        let params = texture.surface_params(surface.format, texture.size_range)?;
+        let layer_size = texture.layer_size();
+        let tables = texture.address_tables(&params);
+        for layer in 0..surface.layers {
+            for (mip, (&offset, table)) in (0..).zip(texture.mip_offsets.iter().zip(&tables)) {
+                let start = layer as usize * layer_size + offset as usize;
+                table.swizzle_into(surface.mip(layer, mip).unwrap(), &mut texture.texture.0[start..]);
+            }
+        }
+
+        Ok(texture)
+    }
+}
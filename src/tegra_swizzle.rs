@@ -1,156 +1,700 @@
-pub fn deswizzle(
-    width: u32,
-    height: u32,
-    _depth: u32,
-    blk_width: u32,
-    blk_height: u32,
-    _blk_depth: u32,
-    round_pitch: bool,
-    bpp: u32,
-    tile_mode: u32,
-    size_range: i32,
-    data: &[u8],
-) -> Vec<u8> {
-    _swizzle(
-        width,
-        height,
-        _depth,
-        blk_width,
-        blk_height,
-        _blk_depth,
-        round_pitch,
-        bpp,
-        tile_mode,
-        size_range,
-        data,
-        false,
-    )
-}
-
-pub fn swizzle(
-    width: u32,
-    height: u32,
-    _depth: u32,
-    blk_width: u32,
-    blk_height: u32,
-    _blk_depth: u32,
-    round_pitch: bool,
-    bpp: u32,
-    tile_mode: u32,
-    size_range: i32,
+//! Swizzling for the Tegra X1's block linear surface layout, used by textures on the Switch.
+//!
+//! A surface is described by [`SurfaceParams`], and can be converted between the swizzled
+//! layout and plain rows of blocks with [`swizzle`] and [`deswizzle`] (or their `_into`
+//! variants, which reuse a buffer). To convert many surfaces with the same parameters, such as
+//! every layer of a texture array, compute an [`AddressTable`] once and use its methods instead.
+//! How blocks are copied can be tuned for the target hardware with [`SwizzleOptions`].
+
+use crate::{Error, SurfaceFormat};
+
+#[cfg(feature = "wgpu")]
+pub use crate::gpu::GpuDeswizzler;
+
+/// Where surfaces are deswizzled
+#[derive(Debug, Clone, Default)]
+pub enum Backend {
+    /// On the current thread, with [`deswizzle`]
+    #[default]
+    Cpu,
+    /// On the current thread, copying blocks as the options say
+    CpuWith(SwizzleOptions),
+    /// With a compute shader, for large batches of textures. Surfaces the shader doesn't
+    /// support are deswizzled on the CPU.
+    #[cfg(feature = "wgpu")]
+    Gpu(std::sync::Arc<GpuDeswizzler>),
+}
+
+impl Backend {
+    /// Deswizzles a surface like [`deswizzle`], using this backend
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` is smaller than [`swizzled_surface_size`].
+    pub fn deswizzle(&self, params: &SurfaceParams, data: &[u8]) -> Vec<u8> {
+        match self {
+            Backend::Cpu => deswizzle(params, data),
+            Backend::CpuWith(options) => AddressTable::new(params).deswizzle_with(data, options),
+            #[cfg(feature = "wgpu")]
+            Backend::Gpu(gpu) => gpu.deswizzle(params, data),
+        }
+    }
+
+    /// Like [`deswizzle`](Backend::deswizzle), reusing a table of block offsets when
+    /// deswizzling on the CPU
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` is smaller than [`swizzled_surface_size`].
+    pub fn deswizzle_with_table(&self, table: &AddressTable, data: &[u8]) -> Vec<u8> {
+        match self {
+            Backend::Cpu => table.deswizzle(data),
+            Backend::CpuWith(options) => table.deswizzle_with(data, options),
+            #[cfg(feature = "wgpu")]
+            Backend::Gpu(gpu) => gpu.deswizzle(table.params(), data),
+        }
+    }
+}
+
+/// Knobs for how blocks are copied when swizzling and deswizzling on the CPU, to tune
+/// throughput for the target hardware. The result is the same whatever the options; the
+/// `swizzle` benchmarks compare them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SwizzleOptions {
+    /// Rows of GOBs (8 rows of blocks each) copied together, a column of GOBs at a time, so
+    /// each GOB is filled in while it's still in cache. 0 copies the surface a row at a time.
+    pub gob_rows: u32,
+    /// Checks every block's offset against the buffers once up front and then copies without
+    /// bounds checks. Surfaces with blocks past the end of the swizzled data are still checked
+    /// block by block.
+    pub unchecked: bool,
+}
+
+/// The height, in GOBs, of each block of a block linear surface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockHeight {
+    One = 1,
+    Two = 2,
+    Four = 4,
+    Eight = 8,
+    Sixteen = 16,
+    ThirtyTwo = 32,
+}
+
+impl BlockHeight {
+    /// Converts a base 2 logarithm, as stored in a texture's header, to a block height
+    pub fn from_log2(log2: i32) -> Option<Self> {
+        Some(match log2 {
+            0 => BlockHeight::One,
+            1 => BlockHeight::Two,
+            2 => BlockHeight::Four,
+            3 => BlockHeight::Eight,
+            4 => BlockHeight::Sixteen,
+            5 => BlockHeight::ThirtyTwo,
+            _ => return None,
+        })
+    }
+
+    /// The base 2 logarithm of the block height, as stored in a texture's header
+    pub fn log2(self) -> i32 {
+        (self as u32).trailing_zeros() as i32
+    }
+}
+
+/// Describes the layout of a single surface (one mip level of one array layer) to swizzle or
+/// deswizzle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SurfaceParams {
+    /// Width of the surface in pixels
+    pub width: u32,
+    /// Height of the surface in pixels
+    pub height: u32,
+    /// Width and height in pixels of each block of the format, `(4, 4)` for BCn and `(1, 1)` for
+    /// uncompressed formats
+    pub block_dimensions: (u32, u32),
+    /// Size of each block (or pixel for uncompressed formats) in bytes
+    pub bytes_per_block: u32,
+    /// 0 for block linear surfaces, 1 for pitch linear ones
+    pub tile_mode: u32,
+    /// Height in GOBs of each block of a block linear surface
+    pub block_height: BlockHeight,
+    /// The texture's swizzle value, which offsets the GOB rows within each block
+    pub swizzle: u32,
+    /// What each row of a pitch linear surface is padded to a multiple of, in bytes, with 1 for
+    /// tightly packed rows. Ignored for block linear surfaces.
+    pub pitch_alignment: u32,
+}
+
+impl SurfaceParams {
+    /// Parameters for a block linear surface of an uncompressed format, using the block height
+    /// the hardware would pick for it
+    pub fn new(width: u32, height: u32, bytes_per_block: u32) -> Self {
+        SurfaceParams {
+            width,
+            height,
+            block_dimensions: (1, 1),
+            bytes_per_block,
+            tile_mode: 0,
+            block_height: block_height(height),
+            swizzle: 0,
+            pitch_alignment: 1,
+        }
+    }
+
+    /// Parameters for mip `level` of a surface whose first mip is described by `self`, with
+    /// the dimensions halved and the block height shrunk to fit
+    pub fn mip(&self, level: u32) -> Self {
+        let height = (self.height >> level).max(1);
+        let block_height = if level == 0 {
+            self.block_height
+        } else {
+            mip_block_height(div_round_up(height, self.block_dimensions.1), self.block_height)
+        };
+
+        SurfaceParams {
+            width: (self.width >> level).max(1),
+            height,
+            block_height,
+            ..*self
+        }
+    }
+
+    /// Distance in bytes between the start of each row of blocks. Rows of block linear
+    /// surfaces are padded to whole GOBs, and rows of pitch linear ones to the pitch alignment.
+    pub fn pitch(&self) -> u32 {
+        surface_layout(self).0
+    }
+
+    /// The surface's size in blocks
+    fn blocks(&self) -> (u32, u32) {
+        (
+            div_round_up(self.width, self.block_dimensions.0),
+            div_round_up(self.height, self.block_dimensions.1),
+        )
+    }
+}
+
+/// Reorders a swizzled surface into rows of blocks. The returned buffer is
+/// [`deswizzled_mip_size`] bytes long.
+///
+/// # Panics
+///
+/// Panics if `data` is smaller than [`swizzled_surface_size`].
+pub fn deswizzle(params: &SurfaceParams, data: &[u8]) -> Vec<u8> {
+    let mut result = vec![0; deswizzled_mip_size(params)];
+    deswizzle_into(params, data, &mut result);
+
+    result
+}
+
+/// Swizzles a surface stored as rows of blocks. The returned buffer is
+/// [`swizzled_surface_size`] bytes long, with any padding zeroed.
+///
+/// # Panics
+///
+/// Panics if `data` is smaller than [`deswizzled_mip_size`].
+pub fn swizzle(params: &SurfaceParams, data: &[u8]) -> Vec<u8> {
+    let mut result = vec![0; swizzled_surface_size(params)];
+    swizzle_into(params, data, &mut result);
+
+    result
+}
+
+/// Like [`deswizzle`], but writes into `out` instead of allocating, so one buffer can be reused
+/// across textures and mips. Bytes of `out` past the deswizzled surface are left untouched.
+///
+/// # Panics
+///
+/// Panics if `out` is smaller than [`deswizzled_mip_size`] or `data` is smaller than
+/// [`swizzled_surface_size`].
+pub fn deswizzle_into(params: &SurfaceParams, data: &[u8], out: &mut [u8]) {
+    let size = deswizzled_mip_size(params);
+    assert!(
+        out.len() >= size,
+        "deswizzle buffer is {} bytes but the surface needs {}", out.len(), size
+    );
+
+    AddressTable::new(params).copy_blocks(data, out, false, &SwizzleOptions::default())
+}
+
+/// Like [`swizzle`], but writes into `out` instead of allocating, so one buffer can be reused
+/// across textures and mips. Padding within the swizzled surface isn't cleared.
+///
+/// # Panics
+///
+/// Panics if `out` is smaller than [`swizzled_surface_size`] or `data` is smaller than
+/// [`deswizzled_mip_size`].
+pub fn swizzle_into(params: &SurfaceParams, data: &[u8], out: &mut [u8]) {
+    let size = swizzled_surface_size(params);
+    assert!(
+        out.len() >= size,
+        "swizzle buffer is {} bytes but the surface needs {}", out.len(), size
+    );
+
+    AddressTable::new(params).copy_blocks(data, out, true, &SwizzleOptions::default())
+}
+
+/// Like [`deswizzle`], but only for a rectangle of the surface, given in blocks (pixels, for
+/// uncompressed formats). Only the GOBs overlapping the rectangle are read. The returned buffer
+/// holds the rectangle's rows of blocks, `width * height * bytes_per_block` bytes long.
+///
+/// # Panics
+///
+/// Panics if the rectangle extends past the surface, or `data` is smaller than
+/// [`swizzled_surface_size`].
+pub fn deswizzle_region(
+    params: &SurfaceParams,
     data: &[u8],
-) -> Vec<u8> {
-    _swizzle(
-        width,
-        height,
-        _depth,
-        blk_width,
-        blk_height,
-        _blk_depth,
-        round_pitch,
-        bpp,
-        tile_mode,
-        size_range,
-        data,
-        true,
-    )
-}
-
-// Ported from https://github.com/KillzXGaming/Switch-Toolbox/blob/f7d674fe1896decf5234329c01ca2c868e88d96f/Switch_Toolbox_Library/Texture%20Decoding/Switch/TegraX1Swizzle.cs
-fn _swizzle(
+    x: u32,
+    y: u32,
     width: u32,
     height: u32,
-    _depth: u32,
-    blk_width: u32,
-    blk_height: u32,
-    _blk_depth: u32,
-    round_pitch: bool,
-    bpp: u32,
-    tile_mode: u32,
-    block_height_log_2: i32,
-    data: &[u8],
-    to_swizzle: bool,
 ) -> Vec<u8> {
-    let block_height = 1 << block_height_log_2;
+    trace_span!("deswizzle_region", x, y, width, height);
+
+    let (surface_width, surface_height) = params.blocks();
+    assert!(
+        x + width <= surface_width && y + height <= surface_height,
+        "region {}x{} at ({}, {}) extends past the {}x{} surface",
+        width, height, x, y, surface_width, surface_height
+    );
+
+    let bpp = params.bytes_per_block as usize;
+    let table = AddressTable::new(params);
+
+    let mut result = vec![0; width as usize * height as usize * bpp];
+    for (row, block_y) in (y as usize..(y + height) as usize).enumerate() {
+        for (column, block_x) in (x as usize..(x + width) as usize).enumerate() {
+            let pos_ = (row * width as usize + column) * bpp;
+
+            if let Some(pos) = table.offset(block_x, block_y) {
+                result[pos_..pos_ + bpp].copy_from_slice(&data[pos..pos + bpp]);
+            }
+        }
+    }
 
-    //Console.WriteLine($"Swizzle {width} {height} {blk_width} {blk_height} {round_pitch} {bpp} {tile_mode} {block_height_log_2} {data.Length} {to_swizzle}");
+    result
+}
 
-    let width = div_round_up(width, blk_width);
-    let height = div_round_up(height, blk_height);
+/// Converts a swizzled surface to another layout, such as a different block height or tile
+/// mode. Blocks are copied as they are, so compressed data stays bit-exact.
+///
+/// # Panics
+///
+/// Panics if the surfaces differ in size (in blocks) or bytes per block, or `data` is smaller
+/// than `src`'s [`swizzled_surface_size`].
+pub fn reswizzle(src: &SurfaceParams, dst: &SurfaceParams, data: &[u8]) -> Vec<u8> {
+    AddressTable::new(src).reswizzle(&AddressTable::new(dst), data)
+}
 
-    let pitch;
-    let surf_size;
-    if tile_mode == 1 {
-        if round_pitch {
-            pitch = round_up(width * bpp, 32);
+/// The offset of every block of a swizzled surface, computed once so surfaces with the same
+/// parameters, such as each layer of a texture array at a given mip, can be swizzled and
+/// deswizzled without recomputing addresses.
+///
+/// The address of a block is the sum of a part depending only on its column and a part
+/// depending only on its row, so the table only stores one offset per column and one per row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressTable {
+    params: SurfaceParams,
+    size: usize,
+    x_offsets: Vec<usize>,
+    y_offsets: Vec<usize>,
+}
+
+impl AddressTable {
+    /// Computes the block offsets of surfaces described by `params`
+    pub fn new(params: &SurfaceParams) -> Self {
+        let bpp = params.bytes_per_block;
+        let (width, height) = params.blocks();
+        let (pitch, size) = surface_layout(params);
+
+        let (x_offsets, y_offsets) = if params.tile_mode == 1 {
+            (
+                (0..width).map(|x| (x * bpp) as usize).collect(),
+                (0..height).map(|y| (y * pitch) as usize).collect(),
+            )
         } else {
-            pitch = width * bpp;
+            let block_height = params.block_height as u32;
+            (
+                (0..width).map(|x| block_linear_x(x, bpp, block_height) as usize).collect(),
+                (0..height)
+                    .map(|y| block_linear_y(y, width, bpp, block_height, params.swizzle) as usize)
+                    .collect(),
+            )
+        };
+
+        AddressTable { params: *params, size: size as usize, x_offsets, y_offsets }
+    }
+
+    /// The parameters the table was computed for
+    pub fn params(&self) -> &SurfaceParams {
+        &self.params
+    }
+
+    /// Like [`deswizzle`], using the table's parameters
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` is smaller than [`swizzled_surface_size`].
+    pub fn deswizzle(&self, data: &[u8]) -> Vec<u8> {
+        let mut result = vec![0; deswizzled_mip_size(&self.params)];
+        self.deswizzle_into(data, &mut result);
+
+        result
+    }
+
+    /// Like [`swizzle`], using the table's parameters
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` is smaller than [`deswizzled_mip_size`].
+    pub fn swizzle(&self, data: &[u8]) -> Vec<u8> {
+        let mut result = vec![0; self.size];
+        self.swizzle_into(data, &mut result);
+
+        result
+    }
+
+    /// Like [`deswizzle_into`], using the table's parameters
+    ///
+    /// # Panics
+    ///
+    /// Panics if `out` is smaller than [`deswizzled_mip_size`] or `data` is smaller than
+    /// [`swizzled_surface_size`].
+    pub fn deswizzle_into(&self, data: &[u8], out: &mut [u8]) {
+        self.deswizzle_into_with(data, out, &SwizzleOptions::default())
+    }
+
+    /// Like [`deswizzle`](AddressTable::deswizzle), copying blocks as `options` say
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` is smaller than [`swizzled_surface_size`].
+    pub fn deswizzle_with(&self, data: &[u8], options: &SwizzleOptions) -> Vec<u8> {
+        let mut result = vec![0; deswizzled_mip_size(&self.params)];
+        self.deswizzle_into_with(data, &mut result, options);
+
+        result
+    }
+
+    /// Like [`deswizzle_into`](AddressTable::deswizzle_into), copying blocks as `options` say
+    ///
+    /// # Panics
+    ///
+    /// Panics if `out` is smaller than [`deswizzled_mip_size`] or `data` is smaller than
+    /// [`swizzled_surface_size`].
+    pub fn deswizzle_into_with(&self, data: &[u8], out: &mut [u8], options: &SwizzleOptions) {
+        let size = deswizzled_mip_size(&self.params);
+        assert!(
+            out.len() >= size,
+            "deswizzle buffer is {} bytes but the surface needs {}", out.len(), size
+        );
+
+        self.copy_blocks(data, out, false, options)
+    }
+
+    /// Like [`swizzle_into`], using the table's parameters
+    ///
+    /// # Panics
+    ///
+    /// Panics if `out` is smaller than [`swizzled_surface_size`] or `data` is smaller than
+    /// [`deswizzled_mip_size`].
+    pub fn swizzle_into(&self, data: &[u8], out: &mut [u8]) {
+        self.swizzle_into_with(data, out, &SwizzleOptions::default())
+    }
+
+    /// Like [`swizzle_into`](AddressTable::swizzle_into), copying blocks as `options` say
+    ///
+    /// # Panics
+    ///
+    /// Panics if `out` is smaller than [`swizzled_surface_size`] or `data` is smaller than
+    /// [`deswizzled_mip_size`].
+    pub fn swizzle_into_with(&self, data: &[u8], out: &mut [u8], options: &SwizzleOptions) {
+        assert!(
+            out.len() >= self.size,
+            "swizzle buffer is {} bytes but the surface needs {}", out.len(), self.size
+        );
+
+        self.copy_blocks(data, out, true, options)
+    }
+
+    /// Like [`reswizzle`], from the table's parameters to `dst`'s
+    ///
+    /// # Panics
+    ///
+    /// Panics if the surfaces differ in size (in blocks) or bytes per block, or `data` is smaller
+    /// than the table's [`swizzled_surface_size`].
+    pub fn reswizzle(&self, dst: &AddressTable, data: &[u8]) -> Vec<u8> {
+        let (src, dst_params) = (&self.params, &dst.params);
+        trace_span!("reswizzle", src.width, src.height, src.bytes_per_block);
+
+        assert!(
+            src.blocks() == dst_params.blocks() && src.bytes_per_block == dst_params.bytes_per_block,
+            "surfaces must have the same size and bytes per block to be reswizzled"
+        );
+
+        let bpp = src.bytes_per_block as usize;
+        let mut result = vec![0; dst.size];
+        for y in 0..self.y_offsets.len() {
+            for x in 0..self.x_offsets.len() {
+                if let (Some(src_pos), Some(dst_pos)) = (self.offset(x, y), dst.offset(x, y)) {
+                    result[dst_pos..dst_pos + bpp].copy_from_slice(&data[src_pos..src_pos + bpp]);
+                }
+            }
         }
 
-        surf_size = pitch * height;
-    } else {
-        pitch = round_up(width * bpp, 64);
-        surf_size = pitch * round_up(height, block_height * 8);
+        result
+    }
+
+    /// Offset in bytes of the block at `x`, `y` within the swizzled surface, or `None` if the
+    /// block lies past the end of the surface
+    fn offset(&self, x: usize, y: usize) -> Option<usize> {
+        let pos = self.x_offsets[x] + self.y_offsets[y];
+
+        Some(pos).filter(|pos| pos + self.params.bytes_per_block as usize <= self.size)
+    }
+
+    /// Runs of columns whose blocks are next to each other in the swizzled surface, as the
+    /// first column and the number of columns, so each run of a row is copied at once. Runs
+    /// are 16 bytes within a GOB, or a whole row of a linear surface.
+    fn runs(&self) -> Vec<(usize, usize)> {
+        let bpp = self.params.bytes_per_block as usize;
+
+        let mut runs: Vec<(usize, usize)> = Vec::new();
+        for (x, &offset) in self.x_offsets.iter().enumerate() {
+            match runs.last_mut() {
+                Some((start, len)) if self.x_offsets[*start] + *len * bpp == offset => *len += 1,
+                _ => runs.push((x, 1)),
+            }
+        }
+
+        runs
     }
 
-    let mut result = vec![0; surf_size as usize];
+    /// Whether every block lies within the swizzled surface and both buffers are big enough
+    /// for it, so blocks can be copied without bounds checks
+    fn fits(&self, swizzled: &[u8], deswizzled: &[u8]) -> bool {
+        let bpp = self.params.bytes_per_block as usize;
+        let end = match (self.x_offsets.iter().max(), self.y_offsets.iter().max()) {
+            (Some(x), Some(y)) => x + y + bpp,
+            _ => 0,
+        };
+
+        end <= self.size
+            && swizzled.len() >= self.size
+            && deswizzled.len() >= self.x_offsets.len() * self.y_offsets.len() * bpp
+    }
+
+    // Ported from https://github.com/KillzXGaming/Switch-Toolbox/blob/f7d674fe1896decf5234329c01ca2c868e88d96f/Switch_Toolbox_Library/Texture%20Decoding/Switch/TegraX1Swizzle.cs
+    fn copy_blocks(&self, data: &[u8], result: &mut [u8], to_swizzle: bool, options: &SwizzleOptions) {
+        let params = &self.params;
+        trace_span!("swizzle", params.width, params.height, params.bytes_per_block, to_swizzle);
+
+        let bpp = params.bytes_per_block as usize;
+        let (width, height) = (self.x_offsets.len(), self.y_offsets.len());
+        let runs = self.runs();
+
+        let unchecked = options.unchecked && if to_swizzle {
+            self.fits(result, data)
+        } else {
+            self.fits(data, result)
+        };
+        let batch_rows = match options.gob_rows {
+            0 => 1,
+            gob_rows => gob_rows as usize * 8,
+        };
 
-    for y in 0..height {
-        for x in 0..width {
-            let pos = if tile_mode == 1 {
-                y * pitch + x * bpp
-            } else {
-                get_addr_block_linear(x, y, width, bpp, 0, block_height)
-            } as usize;
+        for batch in (0..height).step_by(batch_rows) {
+            for &(x, len) in &runs {
+                for y in batch..(batch + batch_rows).min(height) {
+                    let pos_ = (y * width + x) * bpp;
+                    let pos = self.x_offsets[x] + self.y_offsets[y];
 
-            let pos_ = ((y * width + x) * bpp) as usize;
-            let bpp = bpp as usize;
+                    // Blocks past the end of the surface are skipped, and the offsets within a
+                    // run only increase, so only the end of a run can be cut off
+                    let len = if unchecked {
+                        len * bpp
+                    } else {
+                        len.min(self.size.saturating_sub(pos) / bpp) * bpp
+                    };
+                    if len == 0 {
+                        continue;
+                    }
+                    let (src, dst) = if to_swizzle { (pos_, pos) } else { (pos, pos_) };
 
-            if pos + bpp <= surf_size as usize {
-                if to_swizzle {
-                    (&mut result[pos..pos + bpp]).copy_from_slice(&data[pos_..pos_ + bpp]);
-                } else {
-                    (&mut result[pos_..pos_ + bpp]).copy_from_slice(&data[pos..pos + bpp]);
+                    if unchecked {
+                        // SAFETY: `fits` checked every block lies within both buffers
+                        unsafe {
+                            std::ptr::copy_nonoverlapping(
+                                data.as_ptr().add(src),
+                                result.as_mut_ptr().add(dst),
+                                len,
+                            );
+                        }
+                    } else {
+                        result[dst..dst + len].copy_from_slice(&data[src..src + len]);
+                    }
                 }
             }
         }
     }
+}
 
-    result
+/// Pitch and total size in bytes of a swizzled surface
+fn surface_layout(params: &SurfaceParams) -> (u32, u32) {
+    let (width, height) = params.blocks();
+    let bpp = params.bytes_per_block;
+
+    if params.tile_mode == 1 {
+        let pitch = (width * bpp).next_multiple_of(params.pitch_alignment.max(1));
+
+        (pitch, pitch * height)
+    } else {
+        let pitch = round_up(width * bpp, 64);
+
+        (pitch, pitch * round_up(height, params.block_height as u32 * 8))
+    }
+}
+
+/// Size in bytes of a swizzled surface, including the padding out to whole GOBs and blocks
+pub fn swizzled_surface_size(params: &SurfaceParams) -> usize {
+    surface_layout(params).1 as usize
+}
+
+/// Size in bytes of a deswizzled surface, stored as tightly packed rows of blocks
+pub fn deswizzled_mip_size(params: &SurfaceParams) -> usize {
+    let (width, height) = params.blocks();
+
+    (width * height * params.bytes_per_block) as usize
+}
+
+/// Size in bytes of a texture's data stored as tightly packed rows of blocks, with each mip
+/// level following the one before it, for a single array layer
+pub fn deswizzled_size(
+    format: SurfaceFormat,
+    width: u32,
+    height: u32,
+    depth: u32,
+    mips: u32,
+) -> Result<usize, Error> {
+    let bytes_per_block = format.bytes_per_block().ok_or(Error::UnsupportedFormat(format))?;
+    let params = SurfaceParams {
+        block_dimensions: format.block_dimensions(),
+        ..SurfaceParams::new(width, height, bytes_per_block)
+    };
+
+    Ok((0..mips.max(1))
+        .map(|mip| deswizzled_mip_size(&params.mip(mip)) * (depth >> mip).max(1) as usize)
+        .sum())
+}
+
+/// Size in bytes of a texture's swizzled data for a single array layer, with every mip level
+/// block linear, using the block height (and for 3D textures, block depth) the hardware picks,
+/// and packed back to back as NintendoWare lays them out
+pub fn swizzled_size(
+    format: SurfaceFormat,
+    width: u32,
+    height: u32,
+    depth: u32,
+    mips: u32,
+) -> Result<usize, Error> {
+    let bytes_per_block = format.bytes_per_block().ok_or(Error::UnsupportedFormat(format))?;
+    let block_dimensions = format.block_dimensions();
+    let params = SurfaceParams {
+        block_dimensions,
+        block_height: block_height(div_round_up(height, block_dimensions.1)),
+        ..SurfaceParams::new(width, height, bytes_per_block)
+    };
+
+    let depth = depth.max(1);
+    let block_depth_mip0 = block_depth(depth);
+
+    Ok((0..mips.max(1))
+        .map(|mip| {
+            let mip_depth = (depth >> mip).max(1);
+            let slices = round_up(mip_depth, mip_block_depth(mip_depth, block_depth_mip0));
+
+            swizzled_surface_size(&params.mip(mip)) * slices as usize
+        })
+        .sum())
+}
+
+/// The number of slices of a 3D surface in each block, which the hardware picks the same way
+/// as the block height
+fn block_depth(depth: u32) -> u32 {
+    match depth.saturating_add(depth / 2) {
+        d if d >= 16 => 16,
+        d if d >= 8 => 8,
+        d if d >= 4 => 4,
+        d if d >= 2 => 2,
+        _ => 1,
+    }
+}
+
+/// The block depth of a smaller mip level, which shrinks until it no longer exceeds the level
+fn mip_block_depth(depth: u32, block_depth_mip0: u32) -> u32 {
+    let mut block_depth = block_depth_mip0;
+    while block_depth > 1 && depth <= block_depth / 2 {
+        block_depth /= 2;
+    }
+
+    block_depth
+}
+
+/// The block height the hardware uses for the first mip level of a surface `height` blocks
+/// tall (pixels, for uncompressed formats)
+pub fn block_height(height: u32) -> BlockHeight {
+    match height.saturating_add(height / 2) {
+        h if h >= 128 => BlockHeight::Sixteen,
+        h if h >= 64 => BlockHeight::Eight,
+        h if h >= 32 => BlockHeight::Four,
+        h if h >= 16 => BlockHeight::Two,
+        _ => BlockHeight::One,
+    }
+}
+
+/// The block height of a smaller mip level, `height` blocks tall, which shrinks from the first
+/// mip's block height until it no longer exceeds the level
+pub fn mip_block_height(height: u32, block_height_mip0: BlockHeight) -> BlockHeight {
+    let mut block_height = block_height_mip0 as u32;
+    while block_height > 1 && height <= (block_height / 2) * 8 {
+        block_height /= 2;
+    }
+
+    BlockHeight::from_log2(block_height.trailing_zeros() as i32).unwrap()
 }
 
 fn div_round_up(n: u32, d: u32) -> u32 {
-    (n + d - 1) / d
+    n.div_ceil(d)
 }
 
 fn round_up(x: u32, y: u32) -> u32 {
     ((x - 1) | (y - 1)) + 1
 }
 
-fn get_addr_block_linear(
-    x: u32,
-    y: u32,
-    width: u32,
-    bytes_per_pixel: u32,
-    base_address: u32,
-    block_height: u32,
-) -> u32 {
-    /*
-    From Tega X1 TRM
-                     */
-    let image_width_in_gobs = div_round_up(width * bytes_per_pixel, 64);
-
-    let gob_address = base_address
-        + (y / (8 * block_height)) * 512 * block_height * image_width_in_gobs
-        + (x * bytes_per_pixel / 64) * 512 * block_height
-        + (y % (8 * block_height) / 8) * 512;
+// From the Tegra X1 TRM. A block's address is the sum of `block_linear_x` and `block_linear_y`.
 
+/// The part of a block linear address that depends on the block's column
+fn block_linear_x(x: u32, bytes_per_pixel: u32, block_height: u32) -> u32 {
     let x = x * bytes_per_pixel;
 
-    gob_address
+    (x / 64) * 512 * block_height
         + ((x % 64) / 32) * 256
-        + ((y % 8) / 2) * 64
         + ((x % 32) / 16) * 32
-        + (y % 2) * 16
         + (x % 16)
 }
+
+/// The part of a block linear address that depends on the block's row.
+///
+/// The texture's swizzle value selects which GOB within a block each row of GOBs is stored in,
+/// by XORing its low bits with the GOB's row in the block. A swizzle of 0 is the plain layout.
+fn block_linear_y(y: u32, width: u32, bytes_per_pixel: u32, block_height: u32, swizzle: u32) -> u32 {
+    let image_width_in_gobs = div_round_up(width * bytes_per_pixel, 64);
+
+    let gob_row_in_block = (y % (8 * block_height) / 8) ^ (swizzle & (block_height - 1));
+
+    (y / (8 * block_height)) * 512 * block_height * image_width_in_gobs
+        + gob_row_in_block * 512
+        + ((y % 8) / 2) * 64
+        + (y % 2) * 16
+}
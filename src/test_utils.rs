@@ -0,0 +1,92 @@
+//! [`proptest`] strategies for generating random valid textures and files, for property tests
+//! such as checking that writing and reading a file gives the same file back
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use crate::{BntxFile, SurfaceFormat, Texture};
+
+/// Every format a texture can be created with
+const FORMATS: &[SurfaceFormat] = &[
+    SurfaceFormat::R8_UNORM,
+    SurfaceFormat::R4G4B4A4_UNORM,
+    SurfaceFormat::R5G5B5A1_UNORM,
+    SurfaceFormat::R5G6B5_UNORM,
+    SurfaceFormat::R8G8B8A8_UNORM,
+    SurfaceFormat::R8G8B8A8_SRGB,
+    SurfaceFormat::B8G8R8A8_UNORM,
+    SurfaceFormat::B8G8R8A8_SRGB,
+    SurfaceFormat::R10G10B10A2_UNORM,
+    SurfaceFormat::R16G16B16A16_FLOAT,
+    SurfaceFormat::R32G32B32A32_FLOAT,
+    SurfaceFormat::BC1_UNORM,
+    SurfaceFormat::BC1_SRGB,
+    SurfaceFormat::BC2_UNORM,
+    SurfaceFormat::BC2_SRGB,
+    SurfaceFormat::BC3_UNORM,
+    SurfaceFormat::BC3_SRGB,
+    SurfaceFormat::BC4_UNORM,
+    SurfaceFormat::BC4_SNORM,
+    SurfaceFormat::BC5_UNORM,
+    SurfaceFormat::BC5_SNORM,
+    SurfaceFormat::BC6H_SF16,
+    SurfaceFormat::BC6H_UF16,
+    SurfaceFormat::BC7_UNORM,
+    SurfaceFormat::BC7_SRGB,
+    SurfaceFormat::EAC_R11_UNORM,
+    SurfaceFormat::EAC_R11_SNORM,
+    SurfaceFormat::EAC_R11G11_UNORM,
+    SurfaceFormat::EAC_R11G11_SNORM,
+    SurfaceFormat::ETC2_RGB8_UNORM,
+    SurfaceFormat::ETC2_RGB8_SRGB,
+    SurfaceFormat::ETC2_RGB8A1_UNORM,
+    SurfaceFormat::ETC2_RGB8A1_SRGB,
+    SurfaceFormat::ETC2_RGBA8_UNORM,
+    SurfaceFormat::ETC2_RGBA8_SRGB,
+];
+
+/// The largest width or height generated, which keeps textures small enough for many cases
+const MAX_SIZE: u32 = 128;
+
+/// A format the crate can create textures in
+pub fn surface_format() -> impl Strategy<Value = SurfaceFormat> {
+    proptest::sample::select(FORMATS)
+}
+
+/// A texture with a random name, format, size, mip count and array length, filled with random
+/// data. Sizes are always a multiple of the format's block size.
+pub fn texture() -> impl Strategy<Value = Texture> {
+    (surface_format(), 1..=MAX_SIZE, 1..=MAX_SIZE, 1..=3u32, "[a-z_][a-z0-9_]{0,15}")
+        .prop_flat_map(|(format, width, height, array_len, name)| {
+            let (block_width, block_height) = format.block_dimensions();
+            let width = width.div_ceil(block_width) * block_width;
+            let height = height.div_ceil(block_height) * block_height;
+            let max_mips = 32 - width.max(height).leading_zeros();
+
+            (1..=max_mips).prop_flat_map(move |mips| {
+                let texture = Texture::blank(&name, format, width, height, mips, array_len)
+                    .expect("generated texture should be valid");
+                let size = texture.raw_data().len();
+
+                vec(any::<u8>(), size).prop_map(move |data| {
+                    let mut texture = texture.clone();
+                    texture.set_raw_data(data).unwrap();
+                    texture
+                })
+            })
+        })
+}
+
+/// A file with up to `max_textures` random textures. Textures that happen to share a name
+/// replace each other, so there may be fewer.
+pub fn bntx_file(max_textures: usize) -> impl Strategy<Value = BntxFile> {
+    ("[a-z_]{1,16}", vec(texture(), 1..=max_textures.max(1)))
+        .prop_map(|(name, textures)| {
+            let mut file = BntxFile::new(&name);
+            for texture in textures {
+                file.push_texture(texture).expect("generated names aren't empty");
+            }
+
+            file
+        })
+}
@@ -0,0 +1,19 @@
+//! Instrumentation for the optional `tracing` feature. Without the feature the macros expand to
+//! nothing, so callers don't need their own `cfg`s.
+
+/// Enters a debug span for the rest of the enclosing block, so subscribers can time it
+macro_rules! trace_span {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!($($arg)*).entered();
+    };
+}
+
+/// Emits a debug event
+#[cfg_attr(not(feature = "write"), allow(unused_macros))]
+macro_rules! trace_event {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
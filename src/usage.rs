@@ -0,0 +1,143 @@
+//! Typed views of the bit fields in a texture's header, which keep any bits this crate doesn't
+//! know about as they are, so changing one flag never disturbs the rest
+
+use std::fmt;
+use std::ops::{BitOr, BitOrAssign};
+
+macro_rules! flags {
+    (
+        $(#[$attr:meta])*
+        pub struct $name:ident($bits:ty) {
+            $($(#[$flag_attr:meta])* const $flag:ident = $value:expr;)*
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+        pub struct $name($bits);
+
+        impl $name {
+            $($(#[$flag_attr])* pub const $flag: Self = $name($value);)*
+
+            /// Every flag this crate knows the meaning of
+            const KNOWN: &'static [(&'static str, $name)] = &[$((stringify!($flag), $name::$flag)),*];
+
+            /// No flags set
+            pub const fn empty() -> Self {
+                $name(0)
+            }
+
+            /// The flags as stored in the header, including unknown bits
+            pub const fn bits(self) -> $bits {
+                self.0
+            }
+
+            /// Flags from the value stored in the header, keeping unknown bits as they are
+            pub const fn from_bits_retain(bits: $bits) -> Self {
+                $name(bits)
+            }
+
+            /// Whether every flag set in `other` is also set in `self`
+            pub const fn contains(self, other: Self) -> bool {
+                self.0 & other.0 == other.0
+            }
+
+            pub fn insert(&mut self, other: Self) {
+                self.0 |= other.0;
+            }
+
+            pub fn remove(&mut self, other: Self) {
+                self.0 &= !other.0;
+            }
+
+            /// Sets or clears the flags in `other`
+            pub fn set(&mut self, other: Self, value: bool) {
+                if value {
+                    self.insert(other);
+                } else {
+                    self.remove(other);
+                }
+            }
+
+            /// The bits set that this crate doesn't know the meaning of
+            pub fn unknown_bits(self) -> $bits {
+                Self::KNOWN.iter().fold(self.0, |bits, (_, flag)| bits & !flag.0)
+            }
+        }
+
+        impl BitOr for $name {
+            type Output = Self;
+
+            fn bitor(self, other: Self) -> Self {
+                $name(self.0 | other.0)
+            }
+        }
+
+        impl BitOrAssign for $name {
+            fn bitor_assign(&mut self, other: Self) {
+                self.0 |= other.0;
+            }
+        }
+
+        /// Lists the known flags by name, such as `TEXTURE | COLOR_BUFFER`, followed by any
+        /// unknown bits in hex
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let mut names: Vec<String> = Self::KNOWN.iter()
+                    .filter(|(_, flag)| flag.0 != 0 && self.contains(*flag))
+                    .map(|(name, _)| name.to_string())
+                    .collect();
+                if self.unknown_bits() != 0 {
+                    names.push(format!("{:#x}", self.unknown_bits()));
+                }
+
+                if names.is_empty() {
+                    write!(f, "{}(empty)", stringify!($name))
+                } else {
+                    write!(f, "{}({})", stringify!($name), names.join(" | "))
+                }
+            }
+        }
+    };
+}
+
+flags! {
+    /// The flags byte at the start of a texture's header
+    pub struct TextureFlags(u8) {
+        /// The texture's layout words, the block height and the word after it, are filled in.
+        /// Set on every texture NintendoWare's tools write.
+        const SPECIFY_TEXTURE_LAYOUT = 1 << 0;
+        /// The texture's memory is bound a page at a time rather than all at once
+        const SPARSE_BINDING = 1 << 1;
+        /// The texture can be only partly resident in memory, which requires sparse binding
+        const SPARSE_RESIDENCY = 1 << 2;
+    }
+}
+
+flags! {
+    /// How the GPU is allowed to access a texture, such as sampling it or rendering to it.
+    /// Textures read from files only need [`TEXTURE`](GpuAccess::TEXTURE), but ones a game
+    /// renders into at runtime also need [`COLOR_BUFFER`](GpuAccess::COLOR_BUFFER) or
+    /// [`DEPTH_STENCIL`](GpuAccess::DEPTH_STENCIL).
+    pub struct GpuAccess(u32) {
+        const READ = 1 << 0;
+        const WRITE = 1 << 1;
+        const VERTEX_BUFFER = 1 << 2;
+        const INDEX_BUFFER = 1 << 3;
+        const CONSTANT_BUFFER = 1 << 4;
+        /// Sampled by shaders
+        const TEXTURE = 1 << 5;
+        const UNORDERED_ACCESS_BUFFER = 1 << 6;
+        /// Rendered to as a color target
+        const COLOR_BUFFER = 1 << 7;
+        /// Rendered to as a depth or stencil target
+        const DEPTH_STENCIL = 1 << 8;
+        const INDIRECT_BUFFER = 1 << 9;
+        /// Presented to the screen
+        const SCAN_BUFFER = 1 << 10;
+        const QUERY_BUFFER = 1 << 11;
+        const DESCRIPTOR = 1 << 12;
+        const SHADER_CODE = 1 << 13;
+        /// Read and written by shaders as a storage image
+        const IMAGE = 1 << 14;
+    }
+}